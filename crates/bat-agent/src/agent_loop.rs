@@ -1,17 +1,40 @@
 use anyhow::Result;
 use std::collections::HashMap;
-use tokio::sync::mpsc::Sender;
+use tokio::sync::mpsc::{Sender, UnboundedReceiver};
 use tracing::{error, info, warn};
 use uuid::Uuid;
 
-use bat_types::message::{ImageAttachment, Message, ToolCall, ToolResult};
-use crate::llm::{AnthropicMessage, ChatRequest, ContentBlock};
-use crate::provider::LlmClient;
+use bat_types::command_policy::CommandDenial;
+use bat_types::message::{ImageAttachment, Message, Role, ToolCall, ToolResult};
+use crate::llm::{AnthropicMessage, ChatRequest, ContentBlock, StreamEvent, ThinkingLevel};
+use crate::provider::ModelChain;
 use crate::tools::ToolRegistry;
 
 const MAX_TOOL_ITERATIONS: usize = 25;
 /// After this many consecutive identical errors, inject a circuit-breaker warning.
 const ERROR_REPEAT_THRESHOLD: usize = 3;
+/// Command-policy denials never change on retry, so the warning comes sooner.
+const DENIAL_REPEAT_THRESHOLD: usize = 2;
+
+/// Control messages relayed from the gateway while a turn is running.
+#[derive(Debug)]
+pub enum Control {
+    /// Extra instruction to add to the conversation.
+    Instruction(String),
+    /// Stop at the next checkpoint until `Resume` arrives.
+    Pause,
+    /// Continue after a pause, optionally with a new instruction.
+    Resume(Option<String>),
+}
+
+/// Live progress of a turn, forwarded to the gateway as it happens.
+#[derive(Debug, Clone)]
+pub enum TurnEvent {
+    /// Text or tool-use input streamed from an LLM call.
+    Stream(StreamEvent),
+    /// A tool finished running.
+    ToolResult(ToolResult),
+}
 
 /// Result of a single conversation turn.
 pub struct TurnResult {
@@ -20,72 +43,108 @@ pub struct TurnResult {
     pub tool_results: Vec<ToolResult>,
     pub total_input_tokens: i64,
     pub total_output_tokens: i64,
+    /// Part of `total_output_tokens` spent on thinking.
+    pub total_thinking_tokens: i64,
+    /// Input tokens served from / written to the prompt cache.
+    pub total_cache_read_tokens: i64,
+    pub total_cache_write_tokens: i64,
 }
 
 /// Run one conversation turn with real-time streaming for the text response.
 ///
-/// The first LLM call streams text deltas through `text_tx`. Subsequent
-/// calls (after tool execution) are non-streaming. Returns once the full
-/// turn completes (stop_reason == "end_turn" or max iterations reached).
+/// Every LLM call streams its text and tool-use input through `events`, and
+/// each tool result is sent there as soon as the tool finishes. Returns once
+/// the full turn completes (stop_reason == "end_turn" or max iterations
+/// reached).
+///
+/// `control_rx` is checked before every LLM call and before every batch of
+/// tool runs: instructions are added to the conversation, and a pause blocks
+/// the turn until it is resumed.
+///
+/// Tool results replayed from `history` are cut to `history_result_chars`.
+/// Thinking blocks produced under `thinking` are sent back with the tool
+/// results that follow them, as the API requires.
 pub async fn run_turn_streaming(
-    client: &LlmClient,
+    client: &ModelChain,
     registry: &ToolRegistry,
     model: &str,
     system_prompt: &str,
     history: &[Message],
+    history_result_chars: usize,
+    thinking: ThinkingLevel,
     user_content: &str,
     user_images: &[ImageAttachment],
     _session_id: Uuid,
-    text_tx: Sender<String>,
+    events: Sender<TurnEvent>,
+    control_rx: &mut UnboundedReceiver<Control>,
 ) -> Result<TurnResult> {
-    let mut messages = history_to_anthropic(history);
-    messages.push(AnthropicMessage {
-        role: "user".to_string(),
-        content: build_user_content(user_content, user_images),
-    });
+    let mut messages = history_to_anthropic(history, history_result_chars);
+    let history_len = messages.len();
+    push_message(&mut messages, "user", build_user_content(user_content, user_images));
+    // The user message may have been merged into the last history message.
+    let stable_prefix = history_len.min(messages.len() - 1);
 
     let tool_defs = registry.definitions();
     let mut all_tool_calls: Vec<ToolCall> = Vec::new();
     let mut all_tool_results: Vec<ToolResult> = Vec::new();
     let mut total_input = 0i64;
     let mut total_output = 0i64;
+    let mut total_thinking = 0i64;
+    let mut total_cache_read = 0i64;
+    let mut total_cache_write = 0i64;
     // Tracks consecutive error counts per (tool_name, error_prefix) signature.
     let mut error_counts: HashMap<String, usize> = HashMap::new();
 
-    // First iteration uses streaming to deliver text deltas in real time.
-    // Subsequent iterations (post-tool-use) use non-streaming.
-    let mut first_call = true;
-
     for iteration in 0..MAX_TOOL_ITERATIONS {
+        let instructions = poll_control(control_rx).await;
+        append_instructions(&mut messages, &instructions);
+
         info!("LLM call iteration {}", iteration + 1);
 
         let request = ChatRequest {
             model: model.to_string(),
-            max_tokens: 8192,
+            // Set from each model's registry entry by the ModelChain.
+            max_tokens: 0,
             system: system_prompt.to_string(),
             messages: messages.clone(),
             tools: tool_defs.clone(),
-            stream: false, // overridden below
+            stream: false, // overridden by chat_streaming
+            thinking,
+            stable_prefix,
         };
 
-        let (response, response_text) = if first_call {
-            first_call = false;
-            let (resp, text) = client.chat_streaming(&request, text_tx.clone()).await?;
-            (resp, text)
-        } else {
-            let resp = client.chat(&request).await?;
-            let text = resp.text();
-            // Send the post-tool-use text (usually empty) via channel too
-            if !text.is_empty() {
-                let _ = text_tx.send(text.clone()).await;
+        let (stream_tx, mut stream_rx) = tokio::sync::mpsc::channel::<StreamEvent>(128);
+        let forward = async {
+            while let Some(event) = stream_rx.recv().await {
+                let _ = events.send(TurnEvent::Stream(event)).await;
             }
-            (resp, text)
         };
+        let (streamed, ()) = tokio::join!(client.chat_streaming(&request, stream_tx), forward);
+        let (response, response_text) = streamed?;
 
         total_input += response.usage.input_tokens;
         total_output += response.usage.output_tokens;
+        total_thinking += response.usage.thinking_tokens;
+        total_cache_read += response.usage.cache_read_tokens;
+        total_cache_write += response.usage.cache_write_tokens;
 
         if !response.wants_tool_use() {
+            // Don't drop instructions that arrived during the final call —
+            // hand them to the model and keep the turn going instead.
+            let late = poll_control(control_rx).await;
+            if !late.is_empty() {
+                messages.push(AnthropicMessage {
+                    role: "assistant".to_string(),
+                    content: serde_json::Value::Array(build_assistant_content(&response.content)),
+                });
+                messages.push(AnthropicMessage {
+                    role: "user".to_string(),
+                    content: serde_json::Value::String(String::new()),
+                });
+                append_instructions(&mut messages, &late);
+                continue;
+            }
+
             info!("Turn complete after {} iteration(s)", iteration + 1);
             return Ok(TurnResult {
                 response_text,
@@ -93,6 +152,9 @@ pub async fn run_turn_streaming(
                 tool_results: all_tool_results,
                 total_input_tokens: total_input,
                 total_output_tokens: total_output,
+                total_thinking_tokens: total_thinking,
+                total_cache_read_tokens: total_cache_read,
+                total_cache_write_tokens: total_cache_write,
             });
         }
 
@@ -103,17 +165,21 @@ pub async fn run_turn_streaming(
             content: serde_json::Value::Array(assistant_content),
         });
 
+        let instructions = poll_control(control_rx).await;
         let tool_result_blocks = execute_tools(
             &response.content,
             registry,
             &mut all_tool_calls,
             &mut all_tool_results,
             &mut error_counts,
-        );
+            &events,
+        )
+        .await;
         messages.push(AnthropicMessage {
             role: "user".to_string(),
             content: serde_json::Value::Array(tool_result_blocks),
         });
+        append_instructions(&mut messages, &instructions);
     }
 
     error!("Max tool iterations ({}) reached", MAX_TOOL_ITERATIONS);
@@ -123,13 +189,16 @@ pub async fn run_turn_streaming(
         tool_results: all_tool_results,
         total_input_tokens: total_input,
         total_output_tokens: total_output,
+        total_thinking_tokens: total_thinking,
+        total_cache_read_tokens: total_cache_read,
+        total_cache_write_tokens: total_cache_write,
     })
 }
 
 /// Non-streaming turn — convenience wrapper used in tests.
 #[allow(dead_code)]
 pub async fn run_turn(
-    client: &LlmClient,
+    client: &ModelChain,
     registry: &ToolRegistry,
     model: &str,
     system_prompt: &str,
@@ -138,14 +207,88 @@ pub async fn run_turn(
     session_id: Uuid,
 ) -> Result<TurnResult> {
     let (tx, _rx) = tokio::sync::mpsc::channel(128);
+    let (_control_tx, mut control_rx) = tokio::sync::mpsc::unbounded_channel();
     run_turn_streaming(
-        client, registry, model, system_prompt, history, user_content, &[], session_id, tx,
+        client,
+        registry,
+        model,
+        system_prompt,
+        history,
+        bat_types::config::default_history_tool_result_max_chars(),
+        ThinkingLevel::Off,
+        user_content,
+        &[],
+        session_id,
+        tx,
+        &mut control_rx,
     )
     .await
 }
 
 // ─── Helpers ──────────────────────────────────────────────────────────────────
 
+/// Drain pending control messages, blocking while paused.
+/// Returns any instructions received, in arrival order.
+async fn poll_control(control_rx: &mut UnboundedReceiver<Control>) -> Vec<String> {
+    let mut instructions = Vec::new();
+    let mut paused = false;
+    loop {
+        let msg = if paused {
+            match control_rx.recv().await {
+                Some(msg) => msg,
+                // Gateway went away while we were paused — nothing will resume us.
+                None => break,
+            }
+        } else {
+            match control_rx.try_recv() {
+                Ok(msg) => msg,
+                Err(_) => break,
+            }
+        };
+        match msg {
+            Control::Instruction(text) => instructions.push(text),
+            Control::Pause => {
+                info!("Paused by gateway");
+                paused = true;
+            }
+            Control::Resume(extra) => {
+                info!("Resumed by gateway");
+                paused = false;
+                instructions.extend(extra);
+            }
+        }
+    }
+    instructions
+}
+
+/// Append orchestrator instructions to the latest (user) message so the model
+/// sees them on its next call.
+fn append_instructions(messages: &mut [AnthropicMessage], instructions: &[String]) {
+    if instructions.is_empty() {
+        return;
+    }
+    let Some(last) = messages.last_mut() else {
+        return;
+    };
+    let text = instructions
+        .iter()
+        .map(|i| format!("[New instruction from orchestrator]\n{i}"))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let block = serde_json::json!({ "type": "text", "text": text });
+    match &mut last.content {
+        serde_json::Value::String(existing) if existing.is_empty() => {
+            last.content = serde_json::Value::String(text);
+        }
+        serde_json::Value::String(existing) => {
+            let first = serde_json::json!({ "type": "text", "text": existing });
+            last.content = serde_json::Value::Array(vec![first, block]);
+        }
+        serde_json::Value::Array(parts) => parts.push(block),
+        _ => {}
+    }
+}
+
 fn build_assistant_content(content: &[ContentBlock]) -> Vec<serde_json::Value> {
     content
         .iter()
@@ -160,26 +303,40 @@ fn build_assistant_content(content: &[ContentBlock]) -> Vec<serde_json::Value> {
                 "name": name,
                 "input": input,
             }),
+            ContentBlock::Thinking { thinking, signature } => serde_json::json!({
+                "type": "thinking",
+                "thinking": thinking,
+                "signature": signature,
+            }),
+            ContentBlock::RedactedThinking { data } => serde_json::json!({
+                "type": "redacted_thinking",
+                "data": data,
+            }),
         })
         .collect()
 }
 
-fn execute_tools(
+async fn execute_tools(
     content: &[ContentBlock],
     registry: &ToolRegistry,
     all_calls: &mut Vec<ToolCall>,
     all_results: &mut Vec<ToolResult>,
     error_counts: &mut HashMap<String, usize>,
+    events: &Sender<TurnEvent>,
 ) -> Vec<serde_json::Value> {
-    let mut blocks = Vec::new();
-    for (id, name, input) in tool_uses(content) {
-        info!("Executing tool: {}", name);
-        let call = ToolCall {
+    let calls: Vec<ToolCall> = tool_uses(content)
+        .into_iter()
+        .map(|(id, name, input)| ToolCall {
             id: id.to_string(),
             name: name.to_string(),
             input: input.clone(),
-        };
-        let result = registry.execute(&call);
+        })
+        .collect();
+    let results = run_tool_calls(registry, &calls, events).await;
+
+    let mut blocks = Vec::new();
+    for (call, result) in calls.into_iter().zip(results) {
+        let (id, name) = (call.id.as_str(), call.name.as_str());
 
         if result.is_error {
             warn!("Tool {} returned error: {}", name, result.content);
@@ -187,26 +344,44 @@ fn execute_tools(
             // Build a signature from the tool name and the first 120 chars of the
             // error message (enough to distinguish different errors, short enough to
             // avoid spurious mismatches from dynamic content like timestamps).
-            let error_prefix: String = result.content.chars().take(120).collect();
-            let sig = format!("{}:{}", name, error_prefix);
+            // Command-policy denials are keyed by rule instead, so variants of a
+            // refused command — through any tool — count as the same error.
+            let denial = CommandDenial::from_tool_error(&result.content);
+            let (sig, threshold) = match &denial {
+                Some(denial) => (format!("command_denied:{}", denial.rule), DENIAL_REPEAT_THRESHOLD),
+                None => {
+                    let error_prefix: String = result.content.chars().take(120).collect();
+                    (format!("{}:{}", name, error_prefix), ERROR_REPEAT_THRESHOLD)
+                }
+            };
             let count = error_counts.entry(sig).or_insert(0);
             *count += 1;
 
-            if *count >= ERROR_REPEAT_THRESHOLD {
+            if *count >= threshold {
                 warn!(
                     "Circuit breaker: tool '{}' has failed with the same error {} time(s). \
                      Injecting stuck-agent hint.",
                     name, count
                 );
                 // Append a synthetic hint into the result content so the LLM sees it.
-                let hint = format!(
-                    "{}\n\n\
-                    ⚠️ [System] You have encountered this exact error {} times in a row. \
-                    Continuing with the same approach is unlikely to succeed. \
-                    Please try a meaningfully different strategy, use a different tool, \
-                    or ask the user for clarification rather than retrying the same call.",
-                    result.content, count
-                );
+                let hint = match &denial {
+                    Some(denial) => format!(
+                        "{}\n\n\
+                        ⚠️ [System] The command policy has now refused {} commands under the rule \
+                        `{}`. It will refuse every variant of them, whichever tool runs them. \
+                        Stop trying to get around it: reach the goal another way, or tell the \
+                        user the rule is in the way.",
+                        result.content, count, denial.rule
+                    ),
+                    None => format!(
+                        "{}\n\n\
+                        ⚠️ [System] You have encountered this exact error {} times in a row. \
+                        Continuing with the same approach is unlikely to succeed. \
+                        Please try a meaningfully different strategy, use a different tool, \
+                        or ask the user for clarification rather than retrying the same call.",
+                        result.content, count
+                    ),
+                };
                 let hint_result = ToolResult {
                     tool_call_id: result.tool_call_id.clone(),
                     content: hint.clone(),
@@ -240,6 +415,41 @@ fn execute_tools(
     blocks
 }
 
+/// Run tool calls in order, letting each run of consecutive read-only calls
+/// execute concurrently. Each result is sent to `events` as soon as its tool
+/// finishes; the returned results are in the same order as `calls`.
+async fn run_tool_calls(
+    registry: &ToolRegistry,
+    calls: &[ToolCall],
+    events: &Sender<TurnEvent>,
+) -> Vec<ToolResult> {
+    let mut results = Vec::with_capacity(calls.len());
+    let mut start = 0;
+    while start < calls.len() {
+        let end = if registry.is_read_only(&calls[start].name) {
+            calls[start..]
+                .iter()
+                .position(|c| !registry.is_read_only(&c.name))
+                .map_or(calls.len(), |n| start + n)
+        } else {
+            start + 1
+        };
+        let batch = &calls[start..end];
+        if batch.len() > 1 {
+            info!("Executing {} read-only tools concurrently", batch.len());
+        }
+        let runs = batch.iter().map(|call| async move {
+            info!("Executing tool: {}", call.name);
+            let result = registry.execute(call).await;
+            let _ = events.send(TurnEvent::ToolResult(result.clone())).await;
+            result
+        });
+        results.extend(futures_util::future::join_all(runs).await);
+        start = end;
+    }
+    results
+}
+
 fn tool_uses(content: &[ContentBlock]) -> Vec<(&str, &str, &serde_json::Value)> {
     content
         .iter()
@@ -277,22 +487,103 @@ fn build_user_content(text: &str, images: &[ImageAttachment]) -> serde_json::Val
     serde_json::Value::Array(parts)
 }
 
-fn history_to_anthropic(history: &[Message]) -> Vec<AnthropicMessage> {
-    history
-        .iter()
-        .filter(|m| m.role != bat_types::message::Role::System)
-        .map(|m| {
-            let role = match m.role {
-                bat_types::message::Role::User => "user",
-                bat_types::message::Role::Assistant => "assistant",
-                bat_types::message::Role::System => unreachable!(),
-            };
-            AnthropicMessage {
-                role: role.to_string(),
-                content: build_user_content(&m.content, &m.images),
+/// Rebuild the conversation from stored messages. An assistant message that
+/// used tools is replayed as its `tool_use` blocks, a user message with the
+/// matching `tool_result` blocks, then the final text — so the model can see
+/// what it read and ran in earlier turns. Results longer than
+/// `max_result_chars` are elided. System messages are the gateway's summaries
+/// of compacted turns and are replayed as user text.
+fn history_to_anthropic(history: &[Message], max_result_chars: usize) -> Vec<AnthropicMessage> {
+    let mut messages = Vec::new();
+    for m in history {
+        match m.role {
+            Role::User => {
+                push_message(&mut messages, "user", build_user_content(&m.content, &m.images));
+                continue;
             }
-        })
-        .collect()
+            Role::System => {
+                push_message(&mut messages, "user", serde_json::Value::String(m.content.clone()));
+                continue;
+            }
+            Role::Assistant => {}
+        }
+
+        if !m.tool_calls.is_empty() {
+            let uses = m
+                .tool_calls
+                .iter()
+                .map(|call| serde_json::json!({
+                    "type": "tool_use",
+                    "id": call.id,
+                    "name": call.name,
+                    "input": call.input,
+                }))
+                .collect();
+            push_message(&mut messages, "assistant", serde_json::Value::Array(uses));
+
+            let results = m
+                .tool_calls
+                .iter()
+                .map(|call| {
+                    let (content, is_error) = match m.tool_results.iter().find(|r| r.tool_call_id == call.id) {
+                        Some(r) => (elide_tool_result(&r.content, max_result_chars), r.is_error),
+                        None => ("[No result recorded]".to_string(), true),
+                    };
+                    serde_json::json!({
+                        "type": "tool_result",
+                        "tool_use_id": call.id,
+                        "content": content,
+                        "is_error": is_error,
+                    })
+                })
+                .collect();
+            push_message(&mut messages, "user", serde_json::Value::Array(results));
+        }
+
+        if !m.content.is_empty() || m.tool_calls.is_empty() {
+            push_message(&mut messages, "assistant", serde_json::Value::String(m.content.clone()));
+        }
+    }
+    messages
+}
+
+/// Append a message, merging it into the previous one if it has the same
+/// role — the API expects user and assistant turns to alternate.
+fn push_message(messages: &mut Vec<AnthropicMessage>, role: &str, content: serde_json::Value) {
+    if let Some(last) = messages.last_mut().filter(|m| m.role == role) {
+        let mut blocks = content_blocks(std::mem::take(&mut last.content));
+        blocks.extend(content_blocks(content));
+        last.content = serde_json::Value::Array(blocks);
+        return;
+    }
+    messages.push(AnthropicMessage {
+        role: role.to_string(),
+        content,
+    });
+}
+
+/// Normalise message content to a list of content blocks.
+fn content_blocks(content: serde_json::Value) -> Vec<serde_json::Value> {
+    match content {
+        serde_json::Value::Array(blocks) => blocks,
+        serde_json::Value::String(text) if text.is_empty() => vec![],
+        serde_json::Value::String(text) => vec![serde_json::json!({ "type": "text", "text": text })],
+        serde_json::Value::Null => vec![],
+        other => vec![other],
+    }
+}
+
+/// Cut a replayed tool result down to `max_chars`, noting how much was dropped.
+fn elide_tool_result(content: &str, max_chars: usize) -> String {
+    let total = content.chars().count();
+    if total <= max_chars {
+        return content.to_string();
+    }
+    let kept: String = content.chars().take(max_chars).collect();
+    format!(
+        "{kept}\n[... {} more characters elided from this earlier tool result]",
+        total - max_chars
+    )
 }
 
 #[cfg(test)]
@@ -345,11 +636,296 @@ mod tests {
                 media_type: "image/webp".to_string(),
             }],
         );
-        let result = history_to_anthropic(&[msg]);
+        let result = history_to_anthropic(&[msg], 4000);
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].role, "user");
         let content = result[0].content.as_array().expect("should be array");
         assert_eq!(content.len(), 2);
         assert_eq!(content[0]["type"], "image");
     }
+
+    fn assistant_with_tools(session_id: Uuid, text: &str, output: &str) -> Message {
+        let mut msg = Message::assistant(session_id, text);
+        msg.tool_calls = vec![ToolCall {
+            id: "toolu_1".to_string(),
+            name: "fs_read".to_string(),
+            input: serde_json::json!({ "path": "/tmp/a.txt" }),
+        }];
+        msg.tool_results = vec![ToolResult {
+            tool_call_id: "toolu_1".to_string(),
+            content: output.to_string(),
+            is_error: false,
+        }];
+        msg
+    }
+
+    #[test]
+    fn test_history_replays_tool_use_and_results() {
+        let session_id = Uuid::new_v4();
+        let history = vec![
+            Message::user(session_id, "read a.txt"),
+            assistant_with_tools(session_id, "It says hello.", "hello"),
+        ];
+        let result = history_to_anthropic(&history, 4000);
+        let roles: Vec<&str> = result.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, vec!["user", "assistant", "user", "assistant"]);
+
+        let uses = result[1].content.as_array().unwrap();
+        assert_eq!(uses[0]["type"], "tool_use");
+        assert_eq!(uses[0]["id"], "toolu_1");
+        assert_eq!(uses[0]["input"]["path"], "/tmp/a.txt");
+
+        let results = result[2].content.as_array().unwrap();
+        assert_eq!(results[0]["type"], "tool_result");
+        assert_eq!(results[0]["tool_use_id"], "toolu_1");
+        assert_eq!(results[0]["content"], "hello");
+
+        assert_eq!(result[3].content, "It says hello.");
+    }
+
+    #[test]
+    fn test_history_merges_results_into_next_user_message() {
+        let session_id = Uuid::new_v4();
+        let history = vec![
+            Message::user(session_id, "read a.txt"),
+            assistant_with_tools(session_id, "", "hello"),
+            Message::user(session_id, "now fix it"),
+        ];
+        let result = history_to_anthropic(&history, 4000);
+        assert_eq!(result.len(), 3);
+        let parts = result[2].content.as_array().unwrap();
+        assert_eq!(parts[0]["type"], "tool_result");
+        assert_eq!(parts[1]["text"], "now fix it");
+    }
+
+    #[test]
+    fn test_history_elides_large_tool_results() {
+        let session_id = Uuid::new_v4();
+        let history = vec![
+            Message::user(session_id, "read it"),
+            assistant_with_tools(session_id, "done", &"é".repeat(50)),
+        ];
+        let result = history_to_anthropic(&history, 10);
+        let content = result[2].content[0]["content"].as_str().unwrap();
+        assert!(content.starts_with(&"é".repeat(10)));
+        assert!(content.contains("40 more characters elided"));
+    }
+
+    #[test]
+    fn test_history_replays_summary_as_user_text() {
+        let session_id = Uuid::new_v4();
+        let history = vec![
+            Message::system(session_id, "Summary of the earlier conversation:\n\n- read a.txt"),
+            Message::user(session_id, "continue"),
+        ];
+        let result = history_to_anthropic(&history, 4000);
+        assert_eq!(result.len(), 1);
+        let parts = result[0].content.as_array().unwrap();
+        assert!(parts[0]["text"].as_str().unwrap().contains("read a.txt"));
+        assert_eq!(parts[1]["text"], "continue");
+    }
+
+    /// Sleeps for `ms` from the input, then echoes it, logging start/end order.
+    struct Delay {
+        name: &'static str,
+        read_only: bool,
+        log: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl crate::tools::ToolExecutor for Delay {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn description(&self) -> &str {
+            "test tool"
+        }
+
+        fn input_schema(&self) -> serde_json::Value {
+            serde_json::json!({ "type": "object" })
+        }
+
+        fn read_only(&self) -> bool {
+            self.read_only
+        }
+
+        async fn execute(&self, input: &serde_json::Value) -> Result<String> {
+            let ms = input["ms"].as_u64().unwrap_or(0);
+            self.log.lock().unwrap().push(format!("start {ms}"));
+            tokio::time::sleep(std::time::Duration::from_millis(ms)).await;
+            self.log.lock().unwrap().push(format!("end {ms}"));
+            Ok(ms.to_string())
+        }
+    }
+
+    fn delay_registry(log: &std::sync::Arc<std::sync::Mutex<Vec<String>>>) -> ToolRegistry {
+        let mut reg = ToolRegistry::new();
+        reg.register(Box::new(Delay { name: "read", read_only: true, log: log.clone() }));
+        reg.register(Box::new(Delay { name: "write", read_only: false, log: log.clone() }));
+        reg
+    }
+
+    fn call(id: &str, name: &str, ms: u64) -> ToolCall {
+        ToolCall {
+            id: id.to_string(),
+            name: name.to_string(),
+            input: serde_json::json!({ "ms": ms }),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_only_tools_run_concurrently_in_order() {
+        let log = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let reg = delay_registry(&log);
+        let calls: Vec<ToolCall> = (0..10).map(|i| call(&format!("t{i}"), "read", 200 - i * 10)).collect();
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        let started = std::time::Instant::now();
+        let results = run_tool_calls(&reg, &calls, &tx).await;
+        assert!(started.elapsed() < std::time::Duration::from_millis(1000));
+
+        let ids: Vec<&str> = results.iter().map(|r| r.tool_call_id.as_str()).collect();
+        assert_eq!(ids, vec!["t0", "t1", "t2", "t3", "t4", "t5", "t6", "t7", "t8", "t9"]);
+        assert_eq!(results[0].content, "200");
+
+        // Results are reported as each tool finishes, shortest first.
+        let mut reported = Vec::new();
+        while let Ok(TurnEvent::ToolResult(result)) = rx.try_recv() {
+            reported.push(result.tool_call_id);
+        }
+        assert_eq!(reported, vec!["t9", "t8", "t7", "t6", "t5", "t4", "t3", "t2", "t1", "t0"]);
+    }
+
+    #[tokio::test]
+    async fn test_other_tools_run_alone_in_order() {
+        let log = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let reg = delay_registry(&log);
+        let calls = vec![call("a", "read", 30), call("b", "write", 20), call("c", "read", 10)];
+
+        let (tx, _rx) = tokio::sync::mpsc::channel(16);
+        run_tool_calls(&reg, &calls, &tx).await;
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec!["start 30", "end 30", "start 20", "end 20", "start 10", "end 10"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_repeated_command_denials_trip_the_breaker_sooner() {
+        let mut reg = ToolRegistry::new();
+        reg.register(Box::new(crate::tools::shell_run::ShellRun::new(
+            vec![],
+            vec![],
+            bat_types::command_policy::CommandPolicy::default(),
+            None,
+        )));
+        let content: Vec<ContentBlock> = ["sudo ls /root", "ls && sudo -i"]
+            .iter()
+            .enumerate()
+            .map(|(i, command)| ContentBlock::ToolUse {
+                id: format!("t{i}"),
+                name: "shell_run".to_string(),
+                input: serde_json::json!({ "command": command }),
+            })
+            .collect();
+
+        let (tx, _rx) = tokio::sync::mpsc::channel(16);
+        let (mut calls, mut results, mut error_counts) = (Vec::new(), Vec::new(), HashMap::new());
+        let blocks = execute_tools(&content, &reg, &mut calls, &mut results, &mut error_counts, &tx).await;
+        let first = blocks[0]["content"].as_str().unwrap();
+        assert_eq!(CommandDenial::from_tool_error(first).unwrap().rule, "sudo");
+        // A different command under the same rule still counts as a repeat.
+        let second = blocks[1]["content"].as_str().unwrap();
+        assert!(second.contains("[System] The command policy has now refused 2 commands under the rule `sudo`"));
+    }
+
+    #[tokio::test]
+    async fn test_poll_control_collects_instructions() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        tx.send(Control::Instruction("use tabs".to_string())).unwrap();
+        tx.send(Control::Instruction("skip tests".to_string())).unwrap();
+        let instructions = poll_control(&mut rx).await;
+        assert_eq!(instructions, vec!["use tabs", "skip tests"]);
+        assert!(poll_control(&mut rx).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_poll_control_blocks_until_resume() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        tx.send(Control::Pause).unwrap();
+        let handle = tokio::spawn(async move { poll_control(&mut rx).await });
+
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!handle.is_finished());
+
+        tx.send(Control::Instruction("queued while paused".to_string())).unwrap();
+        tx.send(Control::Resume(Some("now do it differently".to_string()))).unwrap();
+        let instructions = handle.await.unwrap();
+        assert_eq!(instructions, vec!["queued while paused", "now do it differently"]);
+    }
+
+    #[test]
+    fn test_append_instructions_to_tool_results() {
+        let mut messages = vec![AnthropicMessage {
+            role: "user".to_string(),
+            content: serde_json::json!([{ "type": "tool_result", "tool_use_id": "t1", "content": "ok" }]),
+        }];
+        append_instructions(&mut messages, &["stop after this".to_string()]);
+        let parts = messages[0].content.as_array().unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[1]["type"], "text");
+        assert!(parts[1]["text"].as_str().unwrap().contains("stop after this"));
+    }
+
+    #[test]
+    fn test_append_instructions_to_text_message() {
+        let mut messages = vec![AnthropicMessage {
+            role: "user".to_string(),
+            content: serde_json::Value::String("summarise the repo".to_string()),
+        }];
+        append_instructions(&mut messages, &["only the README".to_string()]);
+        let parts = messages[0].content.as_array().unwrap();
+        assert_eq!(parts[0]["text"], "summarise the repo");
+        assert!(parts[1]["text"].as_str().unwrap().contains("only the README"));
+    }
+
+    #[test]
+    fn test_assistant_content_keeps_thinking_blocks() {
+        let content = vec![
+            ContentBlock::Thinking { thinking: "check the file".to_string(), signature: "sig".to_string() },
+            ContentBlock::RedactedThinking { data: "opaque".to_string() },
+            ContentBlock::ToolUse {
+                id: "toolu_1".to_string(),
+                name: "fs_read".to_string(),
+                input: serde_json::json!({ "path": "/tmp/a.txt" }),
+            },
+        ];
+        let blocks = build_assistant_content(&content);
+        assert_eq!(blocks[0]["type"], "thinking");
+        assert_eq!(blocks[0]["signature"], "sig");
+        assert_eq!(blocks[1]["type"], "redacted_thinking");
+        assert_eq!(blocks[1]["data"], "opaque");
+        assert_eq!(blocks[2]["type"], "tool_use");
+    }
+
+    #[test]
+    fn test_thinking_level_serializes_as_budget() {
+        let mut request = ChatRequest {
+            model: "claude-sonnet-4-6".to_string(),
+            max_tokens: 16384,
+            system: String::new(),
+            messages: vec![],
+            tools: vec![],
+            stream: false,
+            thinking: ThinkingLevel::parse("Medium"),
+            stable_prefix: 0,
+        };
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["thinking"], serde_json::json!({ "type": "enabled", "budget_tokens": 8192 }));
+
+        request.thinking = ThinkingLevel::parse("off");
+        let json = serde_json::to_value(&request).unwrap();
+        assert!(json.get("thinking").is_none());
+    }
 }
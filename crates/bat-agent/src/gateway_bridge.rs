@@ -1,7 +1,7 @@
 //! Bridge for tools to communicate with the gateway via IPC.
 //!
 //! The agent main loop sets up a relay between this bridge and the actual pipe.
-//! Tools call `request()` which blocks until the gateway responds.
+//! Tools call `request()` and await the gateway's response.
 
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
@@ -64,8 +64,7 @@ pub fn create_bridge() -> (GatewayBridge, BridgeReceiver) {
 
 impl GatewayBridge {
     /// Send a process request to the gateway and wait for the response.
-    /// This blocks the current thread (used from sync tool execute methods).
-    pub fn request(&self, action: ProcessAction) -> ProcessResult {
+    pub async fn request(&self, action: ProcessAction) -> ProcessResult {
         let request_id = {
             let mut c = self.counter.lock().unwrap();
             *c += 1;
@@ -83,11 +82,7 @@ impl GatewayBridge {
             }
         }
 
-        // Block waiting for the response.
-        // We use block_in_place because execute() is sync but called from within tokio.
-        match tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(resp_rx)
-        }) {
+        match resp_rx.await {
             Ok(result) => result,
             Err(_) => ProcessResult::Error {
                 message: "Response channel closed".to_string(),
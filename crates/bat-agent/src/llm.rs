@@ -3,6 +3,10 @@ use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc::Sender;
 
+use bat_types::ipc::LlmRelay;
+
+use crate::retry::ApiError;
+
 // ─── Request types ────────────────────────────────────────────────────────────
 
 #[derive(Debug, Clone, Serialize)]
@@ -15,6 +19,68 @@ pub struct ChatRequest {
     pub tools: Vec<serde_json::Value>,
     #[serde(skip_serializing_if = "std::ops::Not::not")]
     pub stream: bool,
+    /// Sent to Anthropic as an extended-thinking budget; OpenAI-compatible
+    /// providers map it to `reasoning_effort`.
+    #[serde(skip_serializing_if = "ThinkingLevel::is_off", serialize_with = "serialize_thinking")]
+    pub thinking: ThinkingLevel,
+    /// Number of leading `messages` that stay the same for the rest of the
+    /// turn (the replayed history). Anthropic caches up to this point.
+    #[serde(skip)]
+    pub stable_prefix: usize,
+}
+
+/// How much the model may reason before answering (`AgentConfig::thinking_level`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ThinkingLevel {
+    #[default]
+    Off,
+    Low,
+    Medium,
+    High,
+}
+
+impl ThinkingLevel {
+    /// Parse a config value. Anything unrecognised turns thinking off.
+    pub fn parse(level: &str) -> Self {
+        match level.trim().to_ascii_lowercase().as_str() {
+            "low" | "minimal" => Self::Low,
+            "medium" => Self::Medium,
+            "high" => Self::High,
+            _ => Self::Off,
+        }
+    }
+
+    pub fn is_off(&self) -> bool {
+        *self == Self::Off
+    }
+
+    /// Anthropic extended-thinking budget. It counts towards `max_tokens`.
+    pub fn budget_tokens(self) -> Option<u32> {
+        match self {
+            Self::Off => None,
+            Self::Low => Some(2048),
+            Self::Medium => Some(8192),
+            Self::High => Some(24576),
+        }
+    }
+
+    /// OpenAI `reasoning_effort`.
+    pub fn reasoning_effort(self) -> Option<&'static str> {
+        match self {
+            Self::Off => None,
+            Self::Low => Some("low"),
+            Self::Medium => Some("medium"),
+            Self::High => Some("high"),
+        }
+    }
+}
+
+fn serialize_thinking<S: serde::Serializer>(level: &ThinkingLevel, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    serde_json::json!({
+        "type": "enabled",
+        "budget_tokens": level.budget_tokens().unwrap_or_default(),
+    })
+    .serialize(serializer)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -45,12 +111,44 @@ pub enum ContentBlock {
         name: String,
         input: serde_json::Value,
     },
+    /// Extended thinking. Must be sent back unchanged with the tool results
+    /// that follow it.
+    #[serde(rename = "thinking")]
+    Thinking { thinking: String, signature: String },
+    #[serde(rename = "redacted_thinking")]
+    RedactedThinking { data: String },
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct Usage {
     pub input_tokens: i64,
     pub output_tokens: i64,
+    /// Part of `output_tokens` spent on thinking or reasoning.
+    #[serde(default)]
+    pub thinking_tokens: i64,
+    /// Input tokens read from the prompt cache. Not part of `input_tokens`.
+    #[serde(default, rename = "cache_read_input_tokens")]
+    pub cache_read_tokens: i64,
+    /// Input tokens written to the prompt cache. Not part of `input_tokens`.
+    #[serde(default, rename = "cache_creation_input_tokens")]
+    pub cache_write_tokens: i64,
+}
+
+/// Incremental output of a streaming chat request.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamEvent {
+    /// A chunk of response text.
+    Text(String),
+    /// A chunk of the model's thinking.
+    Thinking(String),
+    /// The model started a `tool_use` block; its input follows as `ToolUseInput`.
+    ToolUseStart { id: String, name: String },
+    /// A fragment of a tool call's JSON input.
+    ToolUseInput { id: String, partial_json: String },
+    /// The `tool_use` block closed; `input` is its fully assembled input.
+    ToolUseEnd { id: String, name: String, input: serde_json::Value },
+    /// The request failed on `from` and is being retried on `to`.
+    Failover { from: String, to: String, reason: String },
 }
 
 // ─── SSE streaming types ──────────────────────────────────────────────────────
@@ -68,7 +166,7 @@ enum SseEvent {
     #[serde(rename = "content_block_delta")]
     ContentBlockDelta { index: usize, delta: SseDelta },
     #[serde(rename = "content_block_stop")]
-    ContentBlockStop { #[allow(dead_code)] index: usize },
+    ContentBlockStop { index: usize },
     #[serde(rename = "message_delta")]
     MessageDelta {
         delta: SseMessageDelta,
@@ -78,10 +176,41 @@ enum SseEvent {
     MessageStop,
     #[serde(rename = "ping")]
     Ping,
+    /// Failure reported after the stream started, e.g. `overloaded_error`.
+    #[serde(rename = "error")]
+    Error { error: SseError },
     #[serde(other)]
     Unknown,
 }
 
+#[derive(Debug, Deserialize)]
+struct SseError {
+    #[serde(rename = "type")]
+    kind: String,
+    message: String,
+}
+
+impl SseError {
+    /// Report as the HTTP error the same failure would have produced up front.
+    fn into_api_error(self) -> ApiError {
+        let status = match self.kind.as_str() {
+            "overloaded_error" => 529,
+            "rate_limit_error" => 429,
+            "invalid_request_error" => 400,
+            "authentication_error" => 401,
+            "permission_error" => 403,
+            "not_found_error" => 404,
+            _ => 500,
+        };
+        ApiError {
+            provider: "Anthropic",
+            status: reqwest::StatusCode::from_u16(status).unwrap_or(reqwest::StatusCode::INTERNAL_SERVER_ERROR),
+            retry_after: None,
+            body: format!("{}: {}", self.kind, self.message),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct SseMessageStart {
     id: String,
@@ -91,6 +220,10 @@ struct SseMessageStart {
 #[derive(Debug, Deserialize)]
 struct SseStartUsage {
     input_tokens: i64,
+    #[serde(default)]
+    cache_read_input_tokens: i64,
+    #[serde(default)]
+    cache_creation_input_tokens: i64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -100,15 +233,23 @@ enum SseContentBlockStart {
     Text { text: String },
     #[serde(rename = "tool_use")]
     ToolUse { id: String, name: String },
+    #[serde(rename = "thinking")]
+    Thinking { thinking: String },
+    #[serde(rename = "redacted_thinking")]
+    RedactedThinking { data: String },
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type")]
 enum SseDelta {
     #[serde(rename = "text_delta")]
-    TextDelta { text: String },
+    Text { text: String },
     #[serde(rename = "input_json_delta")]
-    InputJsonDelta { partial_json: String },
+    InputJson { partial_json: String },
+    #[serde(rename = "thinking_delta")]
+    Thinking { thinking: String },
+    #[serde(rename = "signature_delta")]
+    Signature { signature: String },
 }
 
 #[derive(Debug, Deserialize)]
@@ -125,6 +266,8 @@ struct SseDeltaUsage {
 enum BlockAccum {
     Text { text: String },
     ToolUse { id: String, name: String, input_json: String },
+    Thinking { thinking: String, signature: String },
+    RedactedThinking { data: String },
 }
 
 // ─── Client ───────────────────────────────────────────────────────────────────
@@ -144,12 +287,17 @@ impl AnthropicClient {
         }
     }
 
-    #[allow(dead_code)]
     pub fn with_base_url(mut self, url: String) -> Self {
         self.base_url = url;
         self
     }
 
+    /// Send requests through the gateway's LLM relay, which adds the key.
+    pub fn via_relay(relay: &LlmRelay) -> Result<Self> {
+        let client = Self::new(relay.token.clone()).with_base_url(format!("{}/anthropic/v1", relay.url));
+        Ok(Self { client: crate::provider::relay_http().build()?, ..client })
+    }
+
     /// Non-streaming chat request.
     pub async fn chat(&self, request: &ChatRequest) -> Result<ChatResponse> {
         let mut req = request.clone();
@@ -162,31 +310,30 @@ impl AnthropicClient {
             .header("x-api-key", &self.api_key)
             .header("anthropic-version", "2023-06-01")
             .header("content-type", "application/json")
-            .json(&req)
+            .json(&with_cache_breakpoints(&req)?)
             .send()
             .await
             .context("Failed to send request to Anthropic API")?;
 
-        let status = response.status();
-        if !status.is_success() {
-            let body = response.text().await.unwrap_or_default();
-            anyhow::bail!("Anthropic API error ({}): {}", status, body);
+        if !response.status().is_success() {
+            return Err(ApiError::from_response("Anthropic", response).await.into());
         }
 
-        let chat_response: ChatResponse = response
+        let mut chat_response: ChatResponse = response
             .json()
             .await
             .context("Failed to parse Anthropic API response")?;
+        chat_response.usage.thinking_tokens = estimate_thinking_tokens(&chat_response.content);
 
         Ok(chat_response)
     }
 
-    /// Streaming chat request. Text chunks are sent via `text_tx`.
+    /// Streaming chat request. Text and tool-use progress are sent via `events`.
     /// Returns the reconstructed ChatResponse plus the full accumulated text.
     pub async fn chat_streaming(
         &self,
         request: &ChatRequest,
-        text_tx: Sender<String>,
+        events: Sender<StreamEvent>,
     ) -> Result<(ChatResponse, String)> {
         let mut req = request.clone();
         req.stream = true;
@@ -198,15 +345,13 @@ impl AnthropicClient {
             .header("x-api-key", &self.api_key)
             .header("anthropic-version", "2023-06-01")
             .header("content-type", "application/json")
-            .json(&req)
+            .json(&with_cache_breakpoints(&req)?)
             .send()
             .await
             .context("Failed to send streaming request to Anthropic API")?;
 
-        let status = response.status();
-        if !status.is_success() {
-            let body = response.text().await.unwrap_or_default();
-            anyhow::bail!("Anthropic API error ({}): {}", status, body);
+        if !response.status().is_success() {
+            return Err(ApiError::from_response("Anthropic", response).await.into());
         }
 
         // Parse SSE stream
@@ -216,6 +361,8 @@ impl AnthropicClient {
         // Accumulate state
         let mut message_id = String::new();
         let mut input_tokens = 0i64;
+        let mut cache_read_tokens = 0i64;
+        let mut cache_write_tokens = 0i64;
         let mut output_tokens = 0i64;
         let mut stop_reason: Option<String> = None;
         let mut blocks: Vec<BlockAccum> = Vec::new();
@@ -236,6 +383,8 @@ impl AnthropicClient {
                             SseEvent::MessageStart { message: msg_start } => {
                                 message_id = msg_start.id;
                                 input_tokens = msg_start.usage.input_tokens;
+                                cache_read_tokens = msg_start.usage.cache_read_input_tokens;
+                                cache_write_tokens = msg_start.usage.cache_creation_input_tokens;
                             }
                             SseEvent::ContentBlockStart {
                                 index,
@@ -250,12 +399,21 @@ impl AnthropicClient {
                                         BlockAccum::Text { text }
                                     }
                                     SseContentBlockStart::ToolUse { id, name } => {
+                                        let _ = events
+                                            .send(StreamEvent::ToolUseStart { id: id.clone(), name: name.clone() })
+                                            .await;
                                         BlockAccum::ToolUse {
                                             id,
                                             name,
                                             input_json: String::new(),
                                         }
                                     }
+                                    SseContentBlockStart::Thinking { thinking } => {
+                                        BlockAccum::Thinking { thinking, signature: String::new() }
+                                    }
+                                    SseContentBlockStart::RedactedThinking { data } => {
+                                        BlockAccum::RedactedThinking { data }
+                                    }
                                 };
                             }
                             SseEvent::ContentBlockDelta { index, delta } => {
@@ -263,28 +421,55 @@ impl AnthropicClient {
                                     match (block, delta) {
                                         (
                                             BlockAccum::Text { text },
-                                            SseDelta::TextDelta { text: chunk_text },
+                                            SseDelta::Text { text: chunk_text },
                                         ) => {
                                             text.push_str(&chunk_text);
                                             full_text.push_str(&chunk_text);
-                                            // Send text delta to caller (best-effort)
-                                            let _ = text_tx.try_send(chunk_text);
+                                            let _ = events.send(StreamEvent::Text(chunk_text)).await;
                                         }
                                         (
-                                            BlockAccum::ToolUse { input_json, .. },
-                                            SseDelta::InputJsonDelta { partial_json },
+                                            BlockAccum::ToolUse { id, input_json, .. },
+                                            SseDelta::InputJson { partial_json },
                                         ) => {
                                             input_json.push_str(&partial_json);
+                                            let _ = events
+                                                .send(StreamEvent::ToolUseInput { id: id.clone(), partial_json })
+                                                .await;
+                                        }
+                                        (
+                                            BlockAccum::Thinking { thinking, .. },
+                                            SseDelta::Thinking { thinking: chunk_thinking },
+                                        ) => {
+                                            thinking.push_str(&chunk_thinking);
+                                            let _ = events.send(StreamEvent::Thinking(chunk_thinking)).await;
+                                        }
+                                        (
+                                            BlockAccum::Thinking { signature, .. },
+                                            SseDelta::Signature { signature: chunk_signature },
+                                        ) => {
+                                            signature.push_str(&chunk_signature);
                                         }
                                         _ => {}
                                     }
                                 }
                             }
+                            SseEvent::ContentBlockStop { index } => {
+                                if let Some(BlockAccum::ToolUse { id, name, input_json }) = blocks.get(index) {
+                                    let _ = events
+                                        .send(StreamEvent::ToolUseEnd {
+                                            id: id.clone(),
+                                            name: name.clone(),
+                                            input: parse_tool_input(input_json),
+                                        })
+                                        .await;
+                                }
+                            }
                             SseEvent::MessageDelta { delta, usage } => {
                                 stop_reason = delta.stop_reason;
                                 output_tokens = usage.output_tokens;
                             }
                             SseEvent::MessageStop => break,
+                            SseEvent::Error { error } => return Err(error.into_api_error().into()),
                             _ => {}
                         }
                     }
@@ -302,22 +487,27 @@ impl AnthropicClient {
                     Some(ContentBlock::Text { text })
                 }
                 BlockAccum::ToolUse { id, name, input_json } => {
-                    let input = serde_json::from_str(&input_json)
-                        .unwrap_or(serde_json::Value::Object(serde_json::Map::new()));
-                    Some(ContentBlock::ToolUse { id, name, input })
+                    Some(ContentBlock::ToolUse { id, name, input: parse_tool_input(&input_json) })
+                }
+                BlockAccum::Thinking { thinking, signature } => {
+                    Some(ContentBlock::Thinking { thinking, signature })
                 }
+                BlockAccum::RedactedThinking { data } => Some(ContentBlock::RedactedThinking { data }),
                 _ => None,
             })
             .collect();
 
         let chat_response = ChatResponse {
             id: message_id,
-            content,
-            stop_reason,
             usage: Usage {
                 input_tokens,
                 output_tokens,
+                thinking_tokens: estimate_thinking_tokens(&content),
+                cache_read_tokens,
+                cache_write_tokens,
             },
+            content,
+            stop_reason,
         };
 
         Ok((chat_response, full_text))
@@ -343,6 +533,73 @@ fn parse_sse_message(message: &str) -> Option<SseEvent> {
     serde_json::from_str(data).ok()
 }
 
+/// A tool call's streamed JSON input. Unparseable or empty input becomes `{}`.
+pub(crate) fn parse_tool_input(json: &str) -> serde_json::Value {
+    serde_json::from_str(json).unwrap_or(serde_json::Value::Object(serde_json::Map::new()))
+}
+
+/// Serialize a request with prompt-cache breakpoints on the system prompt,
+/// the tool definitions, the end of the stable history and the latest
+/// message. Each call in a tool loop then reads the prefix the previous call
+/// wrote instead of paying for it again.
+fn with_cache_breakpoints(request: &ChatRequest) -> Result<serde_json::Value> {
+    let mut body = serde_json::to_value(request).context("Failed to serialize Anthropic request")?;
+    let marker = serde_json::json!({ "type": "ephemeral" });
+
+    if !request.system.is_empty() {
+        body["system"] = serde_json::json!([{
+            "type": "text",
+            "text": request.system,
+            "cache_control": marker,
+        }]);
+    }
+    if let Some(last_tool) = body.get_mut("tools").and_then(|t| t.as_array_mut()).and_then(|t| t.last_mut()) {
+        last_tool["cache_control"] = marker.clone();
+    }
+    if let Some(messages) = body["messages"].as_array_mut() {
+        let mut points = vec![messages.len().checked_sub(1)];
+        if request.stable_prefix < messages.len() {
+            points.push(request.stable_prefix.checked_sub(1));
+        }
+        for i in points.into_iter().flatten() {
+            mark_message(&mut messages[i], &marker);
+        }
+    }
+    Ok(body)
+}
+
+/// Put `cache_control` on the last block of a message.
+fn mark_message(message: &mut serde_json::Value, marker: &serde_json::Value) {
+    let content = &mut message["content"];
+    if let Some(text) = content.as_str() {
+        if text.is_empty() {
+            return;
+        }
+        *content = serde_json::json!([{ "type": "text", "text": text }]);
+    }
+    let Some(last) = content.as_array_mut().and_then(|blocks| blocks.last_mut()) else {
+        return;
+    };
+    // Thinking blocks can't carry a cache breakpoint.
+    if matches!(last["type"].as_str(), Some("thinking" | "redacted_thinking")) {
+        return;
+    }
+    last["cache_control"] = marker.clone();
+}
+
+/// Anthropic counts thinking as ordinary output tokens, so estimate its share
+/// from the visible thinking text (~4 characters per token).
+fn estimate_thinking_tokens(content: &[ContentBlock]) -> i64 {
+    let chars: usize = content
+        .iter()
+        .map(|block| match block {
+            ContentBlock::Thinking { thinking, .. } => thinking.chars().count(),
+            _ => 0,
+        })
+        .sum();
+    chars.div_ceil(4) as i64
+}
+
 // ─── ChatResponse helpers ─────────────────────────────────────────────────────
 
 impl ChatResponse {
@@ -377,3 +634,102 @@ impl ChatResponse {
         self.stop_reason.as_deref() == Some("tool_use")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: &str, content: serde_json::Value) -> AnthropicMessage {
+        AnthropicMessage { role: role.to_string(), content }
+    }
+
+    #[test]
+    fn test_cache_breakpoints() {
+        let request = ChatRequest {
+            model: "claude-sonnet-4-6".to_string(),
+            max_tokens: 1024,
+            system: "You are Aria.".to_string(),
+            messages: vec![
+                message("user", serde_json::json!("earlier question")),
+                message("assistant", serde_json::json!("earlier answer")),
+                message("user", serde_json::json!("new question")),
+                message("assistant", serde_json::json!([
+                    { "type": "tool_use", "id": "toolu_1", "name": "fs_read", "input": {} }
+                ])),
+                message("user", serde_json::json!([
+                    { "type": "tool_result", "tool_use_id": "toolu_1", "content": "ok" }
+                ])),
+            ],
+            tools: vec![serde_json::json!({ "name": "fs_read" }), serde_json::json!({ "name": "fs_write" })],
+            stream: true,
+            thinking: ThinkingLevel::Off,
+            stable_prefix: 2,
+        };
+        let body = with_cache_breakpoints(&request).unwrap();
+        let ephemeral = serde_json::json!({ "type": "ephemeral" });
+
+        assert_eq!(body["system"][0]["text"], "You are Aria.");
+        assert_eq!(body["system"][0]["cache_control"], ephemeral);
+        assert!(body["tools"][0].get("cache_control").is_none());
+        assert_eq!(body["tools"][1]["cache_control"], ephemeral);
+
+        let messages = body["messages"].as_array().unwrap();
+        assert_eq!(messages[1]["content"][0]["text"], "earlier answer");
+        assert_eq!(messages[1]["content"][0]["cache_control"], ephemeral);
+        assert_eq!(messages[2]["content"], "new question");
+        assert_eq!(messages[4]["content"][0]["cache_control"], ephemeral);
+    }
+
+    fn request() -> ChatRequest {
+        ChatRequest {
+            model: "claude-sonnet-4-6".to_string(),
+            max_tokens: 1024,
+            system: String::new(),
+            messages: vec![message("user", serde_json::json!("read a.txt"))],
+            tools: vec![],
+            stream: false,
+            thinking: ThinkingLevel::Off,
+            stable_prefix: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_streamed_tool_use_is_reassembled() {
+        let input = serde_json::json!({ "path": "a.txt", "limit": 10 });
+        let url = crate::test_support::serve(vec![crate::test_support::anthropic_tool_call("toolu_1", "fs_read", &input)]).await;
+        let client = AnthropicClient::new("test-key".to_string()).with_base_url(url);
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        let (response, text) = client.chat_streaming(&request(), tx).await.unwrap();
+        assert!(text.is_empty());
+        assert!(response.wants_tool_use());
+        assert_eq!(response.tool_uses(), vec![("toolu_1", "fs_read", &input)]);
+
+        let mut events = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            events.push(event);
+        }
+        assert_eq!(events.len(), 4, "{events:?}");
+        assert_eq!(events[0], StreamEvent::ToolUseStart { id: "toolu_1".to_string(), name: "fs_read".to_string() });
+        let partial: String = events[1..3]
+            .iter()
+            .map(|event| match event {
+                StreamEvent::ToolUseInput { id, partial_json } if id == "toolu_1" => partial_json.as_str(),
+                other => panic!("expected input for toolu_1, got {other:?}"),
+            })
+            .collect();
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&partial).unwrap(), input);
+        // The assembled input is reported once the block closes.
+        assert_eq!(
+            events[3],
+            StreamEvent::ToolUseEnd { id: "toolu_1".to_string(), name: "fs_read".to_string(), input }
+        );
+    }
+
+    #[test]
+    fn test_unparseable_tool_input_is_empty() {
+        assert_eq!(parse_tool_input(""), serde_json::json!({}));
+        assert_eq!(parse_tool_input(r#"{"path": "a"#), serde_json::json!({}));
+        assert_eq!(parse_tool_input(r#"{"path": "a"}"#), serde_json::json!({ "path": "a" }));
+    }
+}
@@ -1,9 +1,13 @@
 mod agent_loop;
 pub mod gateway_bridge;
 mod llm;
+mod mock_client;
 mod openai_client;
 mod policy;
 mod provider;
+mod retry;
+#[cfg(test)]
+mod test_support;
 mod tools;
 
 use anyhow::{Context, Result};
@@ -11,7 +15,7 @@ use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tracing_subscriber::EnvFilter;
 use uuid::Uuid;
 
-use bat_types::ipc::{AgentToGateway, GatewayToAgent};
+use bat_types::ipc::{AgentInit, AgentToGateway, GatewayToAgent};
 use bat_types::message::Message;
 
 #[tokio::main]
@@ -99,28 +103,24 @@ mod pipe_client {
 
 // ─── Cross-platform pipe wrapper ─────────────────────────────────────────────
 
-struct GatewayPipe {
-    writer: pipe_client::Writer,
-    reader: pipe_client::Reader,
-}
-
-impl GatewayPipe {
-    async fn connect(pipe_name: &str) -> Result<Self> {
-        let (reader, writer) = pipe_client::connect(pipe_name).await?;
-        Ok(Self { writer, reader })
-    }
+struct PipeWriter(pipe_client::Writer);
 
+impl PipeWriter {
     async fn send(&mut self, msg: &AgentToGateway) -> Result<()> {
         let json = serde_json::to_string(msg)?;
-        self.writer.write_all(json.as_bytes()).await?;
-        self.writer.write_all(b"\n").await?;
-        self.writer.flush().await?;
+        self.0.write_all(json.as_bytes()).await?;
+        self.0.write_all(b"\n").await?;
+        self.0.flush().await?;
         Ok(())
     }
+}
 
+struct PipeReader(pipe_client::Reader);
+
+impl PipeReader {
     async fn recv(&mut self) -> Result<Option<GatewayToAgent>> {
         let mut line = String::new();
-        let n = self.reader.read_line(&mut line).await?;
+        let n = self.0.read_line(&mut line).await?;
         if n == 0 {
             return Ok(None);
         }
@@ -134,205 +134,424 @@ impl GatewayPipe {
     }
 }
 
+async fn connect_pipe(pipe_name: &str) -> Result<(PipeReader, PipeWriter)> {
+    let (reader, writer) = pipe_client::connect(pipe_name).await?;
+    Ok((PipeReader(reader), PipeWriter(writer)))
+}
+
+/// A user message from the gateway: content, images and optional replacement history.
+type UserTurn = (String, Vec<bat_types::message::ImageAttachment>, Option<Vec<Message>>);
+
+/// Route gateway messages: user messages start the next turn, process
+/// responses go to the waiting tool, instruct/pause/resume to the agent loop,
+/// and `Cancel` to the main loop so it can abort the turn.
+async fn route_gateway_messages(
+    mut reader: PipeReader,
+    pending: std::sync::Arc<gateway_bridge::BridgePending>,
+    turn_tx: tokio::sync::mpsc::UnboundedSender<UserTurn>,
+    control_tx: tokio::sync::mpsc::UnboundedSender<agent_loop::Control>,
+    cancel: std::sync::Arc<tokio::sync::Notify>,
+) {
+    use agent_loop::Control;
+
+    loop {
+        let msg = match reader.recv().await {
+            Ok(Some(msg)) => msg,
+            Ok(None) => break,
+            Err(e) => {
+                tracing::warn!("Failed to read from gateway pipe: {e}");
+                break;
+            }
+        };
+        match msg {
+            GatewayToAgent::UserMessage { content, images, history } => {
+                let _ = turn_tx.send((content, images, history));
+            }
+            GatewayToAgent::ProcessResponse { request_id, result } => {
+                if !pending.deliver(&request_id, result) {
+                    tracing::warn!("ProcessResponse for unknown request: {request_id}");
+                }
+            }
+            GatewayToAgent::Instruction { instruction_id, content } => {
+                tracing::info!("Received instruction {instruction_id}");
+                let _ = control_tx.send(Control::Instruction(content));
+            }
+            GatewayToAgent::Pause => {
+                let _ = control_tx.send(Control::Pause);
+            }
+            GatewayToAgent::Resume { instructions } => {
+                let _ = control_tx.send(Control::Resume(instructions));
+            }
+            GatewayToAgent::Cancel => {
+                tracing::info!("Received Cancel");
+                cancel.notify_one();
+            }
+            other => {
+                tracing::warn!("Unexpected message from gateway: {:?}", other);
+            }
+        }
+    }
+}
+
+/// Translate live turn progress into the message the gateway expects.
+fn turn_event_message(event: agent_loop::TurnEvent, session_id: Uuid, session_kind: &str) -> AgentToGateway {
+    use agent_loop::TurnEvent;
+    use llm::StreamEvent;
+
+    let session_kind = session_kind.to_string();
+    match event {
+        TurnEvent::Stream(StreamEvent::Text(content)) => AgentToGateway::TextDelta { session_id, session_kind, content },
+        TurnEvent::Stream(StreamEvent::Thinking(content)) => AgentToGateway::ThinkingDelta { session_id, session_kind, content },
+        // The input streams in as ToolCallInputDelta and arrives whole in ToolCallReady.
+        TurnEvent::Stream(StreamEvent::ToolUseStart { id, name }) => AgentToGateway::ToolCallStart {
+            session_id,
+            session_kind,
+            tool_call: bat_types::message::ToolCall { id, name, input: serde_json::json!({}) },
+        },
+        TurnEvent::Stream(StreamEvent::ToolUseInput { id, partial_json }) => AgentToGateway::ToolCallInputDelta {
+            session_id,
+            session_kind,
+            tool_call_id: id,
+            partial_json,
+        },
+        TurnEvent::Stream(StreamEvent::ToolUseEnd { id, name, input }) => AgentToGateway::ToolCallReady {
+            session_id,
+            session_kind,
+            tool_call: bat_types::message::ToolCall { id, name, input },
+        },
+        TurnEvent::Stream(StreamEvent::Failover { from, to, reason }) => AgentToGateway::AuditLog {
+            level: "warn".to_string(),
+            category: "agent".to_string(),
+            event: "model_failover".to_string(),
+            summary: format!("{from} failed — answering with {to}"),
+            detail_json: Some(serde_json::json!({ "from": from, "to": to, "reason": reason }).to_string()),
+        },
+        TurnEvent::ToolResult(result) => AgentToGateway::ToolCallResult { session_id, session_kind, result },
+    }
+}
+
 // ─── Agent logic ──────────────────────────────────────────────────────────────
 
 async fn run_agent(pipe_name: &str) -> Result<()> {
-    let mut pipe = GatewayPipe::connect(pipe_name).await?;
+    let (mut reader, mut pipe) = connect_pipe(pipe_name).await?;
     tracing::info!("Connected to gateway pipe");
 
     // Step 1: receive Init
-    let init = pipe
+    let init = reader
         .recv()
         .await?
         .ok_or_else(|| anyhow::anyhow!("Pipe closed before Init message"))?;
 
-    let (session_id_str, model, system_prompt, history, path_policies, disabled_tools, session_kind) = match init {
-        GatewayToAgent::Init {
-            session_id,
-            model,
-            system_prompt,
-            history,
-            path_policies,
-            disabled_tools,
-            session_kind,
-        } => (session_id, model, system_prompt, history, path_policies, disabled_tools, session_kind),
+    let init = match init {
+        GatewayToAgent::Init(init) => *init,
         other => anyhow::bail!("Expected Init, got: {:?}", other),
     };
+    let AgentInit {
+        session_id: session_id_str,
+        model,
+        system_prompt,
+        mut history,
+        path_policies,
+        disabled_tools,
+        session_kind,
+        history_tool_result_max_chars: history_result_chars,
+        thinking_level,
+        fallback_models,
+        max_retries,
+        model_overrides,
+        shell_env_allowlist,
+        permissions,
+        command_policy,
+        llm_relay,
+    } = init;
 
     let session_id: Uuid = session_id_str
         .parse()
         .context("Invalid session_id in Init message")?;
 
-    // Normalize model name (strip optional provider prefix)
-    let model = model
-        .strip_prefix("anthropic/")
-        .or_else(|| model.strip_prefix("openai/"))
-        .unwrap_or(&model)
-        .to_string();
+    let models = bat_types::models::ModelRegistry::with_overrides(&model_overrides);
+    let model = models.resolve(&model);
 
     tracing::info!(
         "Initialized: session={}, model={}, history={} msgs",
         session_id,
-        model,
+        model.id,
         history.len()
     );
 
-    // Step 2: receive UserMessage
-    let (user_content, user_images) = match pipe.recv().await? {
-        Some(GatewayToAgent::UserMessage { content, images }) => (content, images),
-        Some(GatewayToAgent::Cancel) => {
-            tracing::info!("Received Cancel before UserMessage — exiting");
-            return Ok(());
-        }
-        None => anyhow::bail!("Pipe closed before UserMessage"),
-        other => anyhow::bail!("Expected UserMessage, got: {:?}", other),
-    };
-
-    tracing::info!("Running turn for: {:?}", &user_content[..user_content.len().min(80)]);
-
-    // Step 3: create streaming channel for text deltas
-    let (tx, mut rx) = tokio::sync::mpsc::channel::<String>(128);
-
-    // Build LLM client based on the model's provider
-    let llm_provider = bat_types::config::ApiKeys::provider_for_model(&model);
-    let client = match llm_provider {
-        bat_types::config::LlmProvider::Anthropic => {
-            let api_key = std::env::var("ANTHROPIC_API_KEY")
-                .context("ANTHROPIC_API_KEY not set for Anthropic model")?;
-            provider::LlmClient::Anthropic(llm::AnthropicClient::new(api_key))
-        }
-        bat_types::config::LlmProvider::OpenAI => {
-            let api_key = std::env::var("OPENAI_API_KEY")
-                .context("OPENAI_API_KEY not set for OpenAI model")?;
-            provider::LlmClient::OpenAICompatible(openai_client::OpenAICompatibleClient::openai(api_key))
+    // Build the model chain: the session's model, then its fallbacks.
+    // A fallback whose provider has no credentials is left out.
+    let mut routes = vec![provider::Route { client: provider::LlmClient::for_model(&model, llm_relay.as_ref())?, model: model.clone() }];
+    for fallback in fallback_models.iter().map(|m| models.resolve(m)) {
+        if fallback.id == model.id {
+            continue;
         }
-        bat_types::config::LlmProvider::Ollama | bat_types::config::LlmProvider::LocalLlm => {
-            let endpoint = std::env::var("OLLAMA_ENDPOINT")
-                .unwrap_or_else(|_| "http://localhost:11434".to_string());
-            provider::LlmClient::OpenAICompatible(openai_client::OpenAICompatibleClient::ollama(endpoint))
+        match provider::LlmClient::for_model(&fallback, llm_relay.as_ref()) {
+            Ok(client) => routes.push(provider::Route { model: fallback, client }),
+            Err(e) => tracing::warn!("Skipping fallback model {}: {e:#}", fallback.id),
         }
-    };
+    }
+    tracing::info!("Models: {}", routes.iter().map(|r| r.model.id.as_str()).collect::<Vec<_>>().join(" → "));
+    let client = provider::ModelChain::new(routes, retry::RetryPolicy::new(max_retries));
+    let thinking = llm::ThinkingLevel::parse(&thinking_level);
+    let client = std::sync::Arc::new(client);
     let (bridge, mut bridge_rx) = gateway_bridge::create_bridge();
     let pending = std::sync::Arc::new(gateway_bridge::BridgePending::new());
 
+    // From here on the read half is owned by the router task, so responses and
+    // control messages are picked up even while a tool is blocked on the bridge.
+    let (turn_tx, mut turn_rx) = tokio::sync::mpsc::unbounded_channel();
+    let (control_tx, mut control_rx) = tokio::sync::mpsc::unbounded_channel();
+    let cancel = std::sync::Arc::new(tokio::sync::Notify::new());
+    tokio::spawn(route_gateway_messages(reader, pending.clone(), turn_tx, control_tx, cancel.clone()));
+
     // Choose tool registry based on session kind
     let registry = if session_kind == "main" {
         // Orchestrator/main sessions only get session management tools
-        tools::ToolRegistry::with_orchestrator_tools(bridge, &disabled_tools)
+        tools::ToolRegistry::with_orchestrator_tools(bridge.clone(), &disabled_tools)
     } else {
         // Worker/subagent sessions get all action tools
-        tools::ToolRegistry::with_default_tools(path_policies, &disabled_tools, Some(bridge))
+        tools::ToolRegistry::with_default_tools(path_policies, &disabled_tools, shell_env_allowlist, command_policy, llm_relay.clone(), Some(bridge.clone()))
     };
+    let registry = std::sync::Arc::new(registry.with_permissions(permissions, bridge));
 
-    // Step 4: run agent turn in a separate task, streaming text deltas
-    let turn_handle = tokio::spawn(async move {
-        agent_loop::run_turn_streaming(
-            &client,
-            &registry,
-            &model,
-            &system_prompt,
-            &history,
-            &user_content,
-            &user_images,
-            session_id,
-            tx,
-        )
-        .await
-    });
-
-    // Step 5: multiplex between text deltas, bridge requests, and pipe responses
-    let mut turn_done = false;
+    // Serve turns until the gateway closes the pipe. One-shot agents see the
+    // pipe close right after their first TurnComplete.
     loop {
-        tokio::select! {
-            // Text delta from the agent turn
-            chunk = rx.recv() => {
-                match chunk {
-                    Some(text) => {
-                        pipe.send(&AgentToGateway::TextDelta { session_id, session_kind: session_kind.clone(), content: text }).await?;
-                    }
-                    None => {
-                        // Agent turn's text channel closed — turn is finishing
-                        turn_done = true;
-                    }
+        // Step 2: wait for the next UserMessage
+        let (user_content, user_images, resync) = tokio::select! {
+            msg = turn_rx.recv() => match msg {
+                Some(turn) => turn,
+                None => {
+                    tracing::info!("Gateway closed the pipe — agent exiting");
+                    return Ok(());
                 }
+            },
+            _ = cancel.notified() => {
+                tracing::info!("Received Cancel while idle — agent exiting");
+                return Ok(());
             }
-            // Process request from a tool via the bridge
-            req = bridge_rx.rx.recv() => {
-                if let Some((request_id, action, resp_tx)) = req {
-                    // Register the response waiter
-                    pending.register(request_id.clone(), resp_tx);
-                    // Send the request to the gateway
-                    pipe.send(&AgentToGateway::ProcessRequest {
-                        request_id,
-                        action,
-                    }).await?;
-                    // Now we need to read the response from the pipe
-                    // The gateway will send a ProcessResponse back
-                    if let Some(msg) = pipe.recv().await? {
-                        match msg {
-                            GatewayToAgent::ProcessResponse { request_id, result } => {
-                                pending.deliver(&request_id, result);
-                            }
-                            _ => {
-                                tracing::warn!("Unexpected message while waiting for ProcessResponse: {:?}", msg);
-                            }
+        };
+        if let Some(replacement) = resync {
+            tracing::info!("History resynced by gateway: {} msgs", replacement.len());
+            history = replacement;
+        }
+
+        tracing::info!("Running turn for: {:?}", &user_content[..user_content.len().min(80)]);
+
+        // Step 3: create streaming channel for turn progress
+        let (tx, mut rx) = tokio::sync::mpsc::channel::<agent_loop::TurnEvent>(128);
+
+        // Step 4: run agent turn in a separate task, streaming its progress.
+        // The control receiver is handed back so the next turn can reuse it.
+        let turn_handle = {
+            let client = client.clone();
+            let registry = registry.clone();
+            let model = model.id.clone();
+            let system_prompt = system_prompt.clone();
+            let history = history.clone();
+            let user_content = user_content.clone();
+            let user_images = user_images.clone();
+            tokio::spawn(async move {
+                let result = agent_loop::run_turn_streaming(
+                    &client,
+                    &registry,
+                    &model,
+                    &system_prompt,
+                    &history,
+                    history_result_chars,
+                    thinking,
+                    &user_content,
+                    &user_images,
+                    session_id,
+                    tx,
+                    &mut control_rx,
+                )
+                .await;
+                (result, control_rx)
+            })
+        };
+
+        // Step 5: multiplex between turn progress and bridge requests.
+        // Responses to bridge requests are delivered by the router task.
+        loop {
+            tokio::select! {
+                // Streamed text, tool-use input or tool result from the agent turn
+                event = rx.recv() => {
+                    match event {
+                        Some(event) => {
+                            pipe.send(&turn_event_message(event, session_id, &session_kind)).await?;
                         }
+                        // Agent turn's event channel closed — turn is finishing
+                        None => break,
                     }
                 }
-            }
-        }
-        if turn_done {
-            // Drain any remaining bridge requests
-            while let Ok((request_id, action, resp_tx)) = bridge_rx.rx.try_recv() {
-                pending.register(request_id.clone(), resp_tx);
-                pipe.send(&AgentToGateway::ProcessRequest { request_id, action }).await?;
-                if let Some(msg) = pipe.recv().await? {
-                    if let GatewayToAgent::ProcessResponse { request_id, result } = msg {
-                        pending.deliver(&request_id, result);
+                // Cancel from the gateway — dropping the turn future aborts any
+                // in-flight LLM request. The gateway kills us if a tool is stuck.
+                _ = cancel.notified() => {
+                    turn_handle.abort();
+                    pipe.send(&AgentToGateway::Error { message: "Turn cancelled".to_string() }).await?;
+                    tracing::info!("Turn cancelled — agent exiting");
+                    return Ok(());
+                }
+                // Process request from a tool via the bridge
+                req = bridge_rx.rx.recv() => {
+                    if let Some((request_id, action, resp_tx)) = req {
+                        // Register the response waiter before the gateway can answer
+                        pending.register(request_id.clone(), resp_tx);
+                        pipe.send(&AgentToGateway::ProcessRequest {
+                            request_id,
+                            action,
+                        }).await?;
                     }
                 }
             }
-            break;
         }
-    }
 
-    // Step 6: get the final turn result
-    let turn_result = turn_handle
-        .await
-        .context("Agent turn task panicked")??;
+        // Step 6: get the final turn result
+        let (turn_result, returned_rx) = turn_handle
+            .await
+            .context("Agent turn task panicked")?;
+        control_rx = returned_rx;
+        let turn_result = match turn_result {
+            Ok(result) => result,
+            Err(e) => {
+                pipe.send(&AgentToGateway::Error { message: format!("{e:#}") }).await?;
+                return Err(e);
+            }
+        };
 
-    // Forward tool events (informational — tools already ran in-process)
-    for tc in &turn_result.tool_calls {
-        pipe.send(&AgentToGateway::ToolCallStart {
+        // Build and send TurnComplete
+        let mut assistant_msg = Message::assistant(session_id, turn_result.response_text);
+        assistant_msg.token_input = Some(turn_result.total_input_tokens);
+        assistant_msg.token_output = Some(turn_result.total_output_tokens);
+        assistant_msg.token_thinking = Some(turn_result.total_thinking_tokens);
+        assistant_msg.token_cache_read = Some(turn_result.total_cache_read_tokens);
+        assistant_msg.token_cache_write = Some(turn_result.total_cache_write_tokens);
+        assistant_msg.tool_calls = turn_result.tool_calls;
+        assistant_msg.tool_results = turn_result.tool_results;
+
+        // Keep our copy of the history in step with what the gateway persists
+        let user_msg = if user_images.is_empty() {
+            Message::user(session_id, user_content)
+        } else {
+            Message::user_with_images(session_id, user_content, user_images)
+        };
+        history.push(user_msg);
+        history.push(assistant_msg.clone());
+
+        pipe.send(&AgentToGateway::TurnComplete {
             session_id,
             session_kind: session_kind.clone(),
-            tool_call: tc.clone(),
+            message: assistant_msg,
         })
         .await?;
+
+        tracing::info!("Turn complete — waiting for next message");
     }
-    for tr in &turn_result.tool_results {
-        pipe.send(&AgentToGateway::ToolCallResult {
-            session_id,
-            session_kind: session_kind.clone(),
-            result: tr.clone(),
-        })
-        .await?;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{anthropic_text, anthropic_tool_call, openai_text, openai_tool_call, serve};
+
+    /// Echoes its `text` input.
+    struct Echo;
+
+    #[async_trait::async_trait]
+    impl tools::ToolExecutor for Echo {
+        fn name(&self) -> &str {
+            "echo"
+        }
+
+        fn description(&self) -> &str {
+            "test tool"
+        }
+
+        fn input_schema(&self) -> serde_json::Value {
+            serde_json::json!({ "type": "object" })
+        }
+
+        async fn execute(&self, input: &serde_json::Value) -> Result<String> {
+            Ok(input["text"].as_str().unwrap_or_default().to_string())
+        }
     }
 
-    // Build and send TurnComplete
-    let mut assistant_msg = Message::assistant(session_id, turn_result.response_text);
-    assistant_msg.token_input = Some(turn_result.total_input_tokens);
-    assistant_msg.token_output = Some(turn_result.total_output_tokens);
-    assistant_msg.tool_calls = turn_result.tool_calls;
-    assistant_msg.tool_results = turn_result.tool_results;
+    /// Run one turn against `client` and return what the gateway would receive.
+    async fn turn_messages(client: provider::LlmClient) -> Vec<AgentToGateway> {
+        let route = provider::Route { model: bat_types::models::ModelInfo::local("test-model"), client };
+        let chain = provider::ModelChain::new(vec![route], retry::RetryPolicy::new(0));
+        let mut registry = tools::ToolRegistry::new();
+        registry.register(Box::new(Echo));
+        let (tx, mut rx) = tokio::sync::mpsc::channel(128);
+        let (_control_tx, mut control_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let session_id = Uuid::new_v4();
+        let result = agent_loop::run_turn_streaming(
+            &chain, &registry, "test-model", "", &[], 1000, llm::ThinkingLevel::Off,
+            "say hi", &[], session_id, tx, &mut control_rx,
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.response_text, "Done.");
 
-    pipe.send(&AgentToGateway::TurnComplete {
-        session_id,
-        session_kind,
-        message: assistant_msg,
-    })
-    .await?;
+        let mut messages = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            messages.push(turn_event_message(event, session_id, "main"));
+        }
+        messages
+    }
 
-    tracing::info!("Turn complete — agent exiting");
-    Ok(())
+    /// The tool call is announced, its input streamed and then reported whole
+    /// before the tool runs, and its result follows.
+    fn assert_tool_call_sequence(messages: &[AgentToGateway], id: &str) {
+        let input = serde_json::json!({ "text": "hi" });
+        let AgentToGateway::ToolCallStart { tool_call, .. } = &messages[0] else {
+            panic!("expected ToolCallStart first, got {messages:?}");
+        };
+        assert_eq!((tool_call.id.as_str(), tool_call.name.as_str()), (id, "echo"));
+
+        let mut partial = String::new();
+        let mut rest = messages[1..].iter();
+        let ready = loop {
+            match rest.next() {
+                Some(AgentToGateway::ToolCallInputDelta { tool_call_id, partial_json, .. }) => {
+                    assert_eq!(tool_call_id, id);
+                    partial.push_str(partial_json);
+                }
+                Some(AgentToGateway::ToolCallReady { tool_call, .. }) => break tool_call,
+                other => panic!("expected input or ToolCallReady, got {other:?}"),
+            }
+        };
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&partial).unwrap(), input);
+        assert_eq!((ready.id.as_str(), &ready.input), (id, &input));
+
+        let Some(AgentToGateway::ToolCallResult { result, .. }) = rest.next() else {
+            panic!("expected ToolCallResult after ToolCallReady, got {messages:?}");
+        };
+        assert_eq!((result.tool_call_id.as_str(), result.content.as_str()), (id, "hi"));
+        assert!(matches!(rest.next(), Some(AgentToGateway::TextDelta { content, .. }) if content == "Done."));
+        assert!(rest.next().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_tool_call_events() {
+        let input = serde_json::json!({ "text": "hi" });
+        let url = serve(vec![anthropic_tool_call("toolu_1", "echo", &input), anthropic_text("Done.")]).await;
+        let client = llm::AnthropicClient::new("test-key".to_string()).with_base_url(url);
+
+        let messages = turn_messages(provider::LlmClient::Anthropic(client)).await;
+        assert_tool_call_sequence(&messages, "toolu_1");
+    }
+
+    #[tokio::test]
+    async fn test_openai_tool_call_events() {
+        let input = serde_json::json!({ "text": "hi" });
+        let url = serve(vec![openai_tool_call("call_1", "echo", &input), openai_text("Done.")]).await;
+        let client = openai_client::OpenAICompatibleClient::openai("test-key".to_string()).with_base_url(url);
+
+        let messages = turn_messages(provider::LlmClient::OpenAICompatible(client)).await;
+        assert_tool_call_sequence(&messages, "call_1");
+    }
 }
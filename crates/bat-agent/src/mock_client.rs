@@ -0,0 +1,241 @@
+//! Scripted LLM client for end-to-end tests — no network, no API keys.
+//!
+//! Setting `BAT_MOCK_LLM` to a fixture file makes every model use this
+//! client. The fixture is a JSON array of steps:
+//!
+//! ```json
+//! [
+//!   { "when": "write the report", "tool_calls": [{ "name": "fs_write", "input": { "path": "/tmp/r.md", "content": "hi" } }] },
+//!   { "when": "Wrote", "text": "Report written." },
+//!   { "error": { "status": 529, "body": "overloaded" } }
+//! ]
+//! ```
+//!
+//! Each request is answered by the first unused step whose `when` appears in
+//! the request's last message (a step without `when` matches anything). A
+//! step is used once. Matching on the last message rather than on call order
+//! keeps scripts deterministic when an orchestrator and its subagents run in
+//! separate agent processes at the same time.
+//!
+//! Every request is appended to the record file (`BAT_MOCK_LLM_RECORD`,
+//! default `<fixture>.requests.jsonl`) as `{"step": n, "request": {...}}`.
+//! The record also tells later agent processes which steps are used up.
+
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use tokio::sync::mpsc::Sender;
+
+use crate::llm::{ChatRequest, ChatResponse, ContentBlock, StreamEvent, Usage};
+use crate::retry::ApiError;
+
+/// One scripted response.
+#[derive(Debug, Clone, Deserialize)]
+struct Step {
+    /// Substring that must appear in the request's last message.
+    #[serde(default)]
+    when: Option<String>,
+    #[serde(default)]
+    thinking: Option<String>,
+    #[serde(default)]
+    text: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<ScriptedToolCall>,
+    /// Fail the request instead of answering.
+    #[serde(default)]
+    error: Option<ScriptedError>,
+    #[serde(default)]
+    usage: Option<ScriptedUsage>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ScriptedToolCall {
+    #[serde(default)]
+    id: Option<String>,
+    name: String,
+    #[serde(default)]
+    input: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ScriptedError {
+    status: u16,
+    #[serde(default)]
+    body: String,
+    /// Seconds, reported like a `retry-after` header.
+    #[serde(default)]
+    retry_after: Option<f64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ScriptedUsage {
+    input_tokens: i64,
+    output_tokens: i64,
+}
+
+pub struct MockClient {
+    steps: Vec<Step>,
+    record_path: PathBuf,
+}
+
+impl MockClient {
+    /// The mock client, if `BAT_MOCK_LLM` names a fixture.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Ok(fixture) = std::env::var("BAT_MOCK_LLM") else {
+            return Ok(None);
+        };
+        let record = std::env::var("BAT_MOCK_LLM_RECORD").unwrap_or_else(|_| format!("{fixture}.requests.jsonl"));
+        Self::load(PathBuf::from(fixture), PathBuf::from(record)).map(Some)
+    }
+
+    fn load(fixture: PathBuf, record_path: PathBuf) -> Result<Self> {
+        let raw = std::fs::read_to_string(&fixture)
+            .with_context(|| format!("Failed to read mock LLM fixture {}", fixture.display()))?;
+        let steps = serde_json::from_str(&raw)
+            .with_context(|| format!("Invalid mock LLM fixture {}", fixture.display()))?;
+        Ok(Self { steps, record_path })
+    }
+
+    /// Pick the step for `request` and record it, holding a lock on the
+    /// record file so concurrent agent processes don't take the same step.
+    fn next_step(&self, request: &ChatRequest) -> Result<Option<(usize, Step)>> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&self.record_path)
+            .with_context(|| format!("Failed to open {}", self.record_path.display()))?;
+        file.lock().context("Failed to lock the mock LLM record")?;
+
+        let used: Vec<usize> = BufReader::new(&file)
+            .lines()
+            .map_while(|line| line.ok())
+            .filter_map(|line| serde_json::from_str::<serde_json::Value>(&line).ok())
+            .filter_map(|entry| entry["step"].as_u64().map(|n| n as usize))
+            .collect();
+        let last_message = request
+            .messages
+            .last()
+            .map(|m| match &m.content {
+                serde_json::Value::String(s) => s.clone(),
+                other => other.to_string(),
+            })
+            .unwrap_or_default();
+        let found = self.steps.iter().enumerate().find(|(i, step)| {
+            !used.contains(i) && step.when.as_deref().is_none_or(|w| last_message.contains(w))
+        });
+
+        let entry = serde_json::json!({
+            "step": found.as_ref().map(|(i, _)| *i),
+            "request": request,
+        });
+        writeln!(file, "{entry}")?;
+        file.unlock()?;
+        Ok(found.map(|(i, step)| (i, step.clone())))
+    }
+
+    pub async fn chat_streaming(
+        &self,
+        request: &ChatRequest,
+        events: Sender<StreamEvent>,
+    ) -> Result<(ChatResponse, String)> {
+        let Some((index, step)) = self.next_step(request)? else {
+            anyhow::bail!("Mock LLM has no scripted response left for this request");
+        };
+        if let Some(error) = step.error {
+            return Err(ApiError {
+                provider: "Mock",
+                status: reqwest::StatusCode::from_u16(error.status).context("Invalid scripted status")?,
+                retry_after: error.retry_after.and_then(|secs| std::time::Duration::try_from_secs_f64(secs).ok()),
+                body: error.body,
+            }
+            .into());
+        }
+
+        let mut content = Vec::new();
+        if let Some(thinking) = step.thinking {
+            let _ = events.send(StreamEvent::Thinking(thinking.clone())).await;
+            content.push(ContentBlock::Thinking { thinking, signature: "mock".to_string() });
+        }
+        let text = step.text.unwrap_or_default();
+        if !text.is_empty() {
+            let _ = events.send(StreamEvent::Text(text.clone())).await;
+            content.push(ContentBlock::Text { text: text.clone() });
+        }
+        for (n, call) in step.tool_calls.into_iter().enumerate() {
+            let id = call.id.unwrap_or_else(|| format!("mock_{index}_{n}"));
+            let _ = events.send(StreamEvent::ToolUseStart { id: id.clone(), name: call.name.clone() }).await;
+            let _ = events
+                .send(StreamEvent::ToolUseInput { id: id.clone(), partial_json: call.input.to_string() })
+                .await;
+            let _ = events
+                .send(StreamEvent::ToolUseEnd { id: id.clone(), name: call.name.clone(), input: call.input.clone() })
+                .await;
+            content.push(ContentBlock::ToolUse { id, name: call.name, input: call.input });
+        }
+
+        let has_tools = content.iter().any(|b| matches!(b, ContentBlock::ToolUse { .. }));
+        let usage = match step.usage {
+            Some(u) => Usage { input_tokens: u.input_tokens, output_tokens: u.output_tokens, ..Default::default() },
+            None => Usage { input_tokens: 100, output_tokens: 10, ..Default::default() },
+        };
+        let response = ChatResponse {
+            id: format!("mock_{index}"),
+            content,
+            stop_reason: Some(if has_tools { "tool_use" } else { "end_turn" }.to_string()),
+            usage,
+        };
+        Ok((response, text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::{AnthropicMessage, ThinkingLevel};
+
+    fn request(last: &str) -> ChatRequest {
+        ChatRequest {
+            model: "mock".to_string(),
+            max_tokens: 100,
+            system: String::new(),
+            messages: vec![AnthropicMessage { role: "user".to_string(), content: serde_json::json!(last) }],
+            tools: vec![],
+            stream: false,
+            thinking: ThinkingLevel::Off,
+            stable_prefix: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replays_matching_steps_once() {
+        let dir = std::env::temp_dir().join(format!("bat-mock-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let fixture = dir.join("script.json");
+        std::fs::write(&fixture, serde_json::json!([
+            { "when": "list", "tool_calls": [{ "name": "fs_list", "input": { "path": "/tmp" } }] },
+            { "when": "list", "error": { "status": 529, "body": "overloaded" } },
+            { "text": "anything else" },
+        ]).to_string()).unwrap();
+        let client = MockClient::load(fixture, dir.join("requests.jsonl")).unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        let (response, _) = client.chat_streaming(&request("please list files"), tx.clone()).await.unwrap();
+        assert_eq!(response.stop_reason.as_deref(), Some("tool_use"));
+        assert!(matches!(rx.recv().await, Some(StreamEvent::ToolUseStart { name, .. }) if name == "fs_list"));
+
+        let err = client.chat_streaming(&request("list again"), tx.clone()).await.unwrap_err();
+        assert_eq!(crate::retry::classify(&err), crate::retry::ErrorKind::Overloaded);
+
+        let (_, text) = client.chat_streaming(&request("hello"), tx.clone()).await.unwrap();
+        assert_eq!(text, "anything else");
+        assert!(client.chat_streaming(&request("hello"), tx).await.is_err());
+
+        let recorded = std::fs::read_to_string(dir.join("requests.jsonl")).unwrap();
+        assert_eq!(recorded.lines().count(), 4);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -8,7 +8,9 @@ use anyhow::{Context, Result};
 use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc::Sender;
-use crate::llm::{ChatResponse, ContentBlock, Usage};
+use bat_types::ipc::LlmRelay;
+use crate::llm::{parse_tool_input, ChatResponse, ContentBlock, StreamEvent, Usage};
+use crate::retry::ApiError;
 
 // ─── OpenAI request/response types ───────────────────────────────────────────
 
@@ -16,55 +18,79 @@ use crate::llm::{ChatResponse, ContentBlock, Usage};
 struct OpenAIRequest {
     model: String,
     messages: Vec<OpenAIMessage>,
-    max_tokens: u32,
+    /// The output limit for servers other than OpenAI's…
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    /// …and for OpenAI, whose reasoning models reject `max_tokens`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_completion_tokens: Option<u32>,
     stream: bool,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     tools: Vec<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reasoning_effort: Option<&'static str>,
+    /// Asks for a final chunk with token usage when streaming.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct OpenAIMessage {
     role: String,
     content: serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_calls: Option<Vec<serde_json::Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_call_id: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
-struct OpenAIResponse {
-    id: String,
-    choices: Vec<OpenAIChoice>,
-    usage: Option<OpenAIUsage>,
-}
-
-#[derive(Debug, Deserialize)]
-struct OpenAIChoice {
-    message: OpenAIChoiceMessage,
-    finish_reason: Option<String>,
+impl OpenAIMessage {
+    fn new(role: &str, content: serde_json::Value) -> Self {
+        Self {
+            role: role.to_string(),
+            content,
+            tool_calls: None,
+            tool_call_id: None,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
-struct OpenAIChoiceMessage {
-    #[allow(dead_code)]
-    role: String,
-    content: Option<String>,
-    tool_calls: Option<Vec<OpenAIToolCall>>,
+struct OpenAIUsage {
+    prompt_tokens: i64,
+    completion_tokens: i64,
+    #[serde(default)]
+    completion_tokens_details: Option<CompletionTokensDetails>,
+    #[serde(default)]
+    prompt_tokens_details: Option<PromptTokensDetails>,
 }
 
+/// OpenAI caches long prompt prefixes automatically and reports the hits here.
 #[derive(Debug, Deserialize)]
-struct OpenAIToolCall {
-    id: String,
-    function: OpenAIFunction,
+struct PromptTokensDetails {
+    #[serde(default)]
+    cached_tokens: i64,
 }
 
 #[derive(Debug, Deserialize)]
-struct OpenAIFunction {
-    name: String,
-    arguments: String,
+struct CompletionTokensDetails {
+    #[serde(default)]
+    reasoning_tokens: i64,
 }
 
-#[derive(Debug, Deserialize)]
-struct OpenAIUsage {
-    prompt_tokens: i64,
-    completion_tokens: i64,
+impl From<OpenAIUsage> for Usage {
+    fn from(u: OpenAIUsage) -> Self {
+        // Cached tokens are included in `prompt_tokens`; report them separately
+        // as Anthropic does.
+        let cached = u.prompt_tokens_details.map_or(0, |d| d.cached_tokens);
+        Usage {
+            input_tokens: u.prompt_tokens - cached,
+            output_tokens: u.completion_tokens,
+            thinking_tokens: u.completion_tokens_details.map_or(0, |d| d.reasoning_tokens),
+            cache_read_tokens: cached,
+            cache_write_tokens: 0,
+        }
+    }
 }
 
 // ─── SSE streaming types ─────────────────────────────────────────────────────
@@ -85,6 +111,9 @@ struct StreamChoice {
 #[derive(Debug, Deserialize)]
 struct StreamDelta {
     content: Option<String>,
+    /// Reasoning text streamed by local reasoning models (DeepSeek, Ollama).
+    #[serde(default, alias = "reasoning")]
+    reasoning_content: Option<String>,
     tool_calls: Option<Vec<StreamToolCall>>,
 }
 
@@ -107,6 +136,8 @@ pub struct OpenAICompatibleClient {
     client: reqwest::Client,
     api_key: Option<String>,
     base_url: String,
+    /// Talks to OpenAI itself rather than a compatible server.
+    openai: bool,
 }
 
 impl OpenAICompatibleClient {
@@ -116,33 +147,123 @@ impl OpenAICompatibleClient {
             client: reqwest::Client::new(),
             api_key: None,
             base_url: format!("{}/v1", endpoint.trim_end_matches('/')),
+            openai: false,
         }
     }
 
     /// Create a client for OpenAI.
-    #[allow(dead_code)]
     pub fn openai(api_key: String) -> Self {
         Self {
             client: reqwest::Client::new(),
             api_key: Some(api_key),
             base_url: "https://api.openai.com/v1".to_string(),
+            openai: true,
         }
     }
 
+    /// Create a client for OpenAI that goes through the gateway's LLM relay,
+    /// which adds the key.
+    pub fn via_relay(relay: &LlmRelay) -> Result<Self> {
+        let client = Self::openai(relay.token.clone()).with_base_url(format!("{}/openai/v1", relay.url));
+        Ok(Self { client: crate::provider::relay_http().build()?, ..client })
+    }
+
+    /// Send requests to an OpenAI-compatible API at `url` (including `/v1`).
+    pub fn with_base_url(mut self, url: String) -> Self {
+        self.base_url = url.trim_end_matches('/').to_string();
+        self
+    }
+
     /// Convert Anthropic-style messages + system prompt to OpenAI format.
+    ///
+    /// `tool_use` blocks become assistant `tool_calls`, each `tool_result`
+    /// block becomes a `tool` message, and images become `image_url` parts.
     fn build_openai_messages(
         system: &str,
         messages: &[crate::llm::AnthropicMessage],
     ) -> Vec<OpenAIMessage> {
-        let mut result = vec![OpenAIMessage {
-            role: "system".to_string(),
-            content: serde_json::Value::String(system.to_string()),
-        }];
+        let mut result = vec![OpenAIMessage::new(
+            "system",
+            serde_json::Value::String(system.to_string()),
+        )];
         for msg in messages {
-            result.push(OpenAIMessage {
-                role: msg.role.clone(),
-                content: msg.content.clone(),
-            });
+            let blocks = match &msg.content {
+                serde_json::Value::Array(blocks) => blocks,
+                other => {
+                    result.push(OpenAIMessage::new(&msg.role, other.clone()));
+                    continue;
+                }
+            };
+            if msg.role == "assistant" {
+                result.push(Self::convert_assistant_blocks(blocks));
+            } else {
+                result.extend(Self::convert_user_blocks(&msg.role, blocks));
+            }
+        }
+        result
+    }
+
+    /// Text blocks are joined into `content`; `tool_use` blocks become `tool_calls`.
+    fn convert_assistant_blocks(blocks: &[serde_json::Value]) -> OpenAIMessage {
+        let mut text = String::new();
+        let mut tool_calls = Vec::new();
+        for block in blocks {
+            match block.get("type").and_then(|t| t.as_str()) {
+                Some("text") => text.push_str(block["text"].as_str().unwrap_or_default()),
+                Some("tool_use") => tool_calls.push(serde_json::json!({
+                    "id": block["id"],
+                    "type": "function",
+                    "function": {
+                        "name": block["name"],
+                        "arguments": block["input"].to_string(),
+                    }
+                })),
+                _ => {}
+            }
+        }
+        let content = if text.is_empty() && !tool_calls.is_empty() {
+            serde_json::Value::Null
+        } else {
+            serde_json::Value::String(text)
+        };
+        let mut msg = OpenAIMessage::new("assistant", content);
+        if !tool_calls.is_empty() {
+            msg.tool_calls = Some(tool_calls);
+        }
+        msg
+    }
+
+    /// Each `tool_result` block becomes a `tool` message; the remaining text
+    /// and image blocks follow as one message with the original role.
+    fn convert_user_blocks(role: &str, blocks: &[serde_json::Value]) -> Vec<OpenAIMessage> {
+        let mut result = Vec::new();
+        let mut parts = Vec::new();
+        for block in blocks {
+            match block.get("type").and_then(|t| t.as_str()) {
+                Some("tool_result") => {
+                    let content = match &block["content"] {
+                        serde_json::Value::String(s) => s.clone(),
+                        other => other.to_string(),
+                    };
+                    let mut msg = OpenAIMessage::new("tool", serde_json::Value::String(content));
+                    msg.tool_call_id = block["tool_use_id"].as_str().map(str::to_string);
+                    result.push(msg);
+                }
+                Some("image") => {
+                    let source = &block["source"];
+                    let url = format!(
+                        "data:{};base64,{}",
+                        source["media_type"].as_str().unwrap_or_default(),
+                        source["data"].as_str().unwrap_or_default()
+                    );
+                    parts.push(serde_json::json!({ "type": "image_url", "image_url": { "url": url } }));
+                }
+                Some("text") => parts.push(serde_json::json!({ "type": "text", "text": block["text"] })),
+                _ => {}
+            }
+        }
+        if !parts.is_empty() {
+            result.push(OpenAIMessage::new(role, serde_json::Value::Array(parts)));
         }
         result
     }
@@ -170,61 +291,29 @@ impl OpenAICompatibleClient {
             .collect()
     }
 
-    /// Non-streaming chat request. Returns in Anthropic-compatible format.
-    pub async fn chat(&self, request: &crate::llm::ChatRequest) -> Result<ChatResponse> {
-        let openai_messages = Self::build_openai_messages(&request.system, &request.messages);
-        let openai_tools = Self::convert_tools(&request.tools);
-
-        let openai_req = OpenAIRequest {
+    /// The streaming request body for `request`.
+    fn build_request(&self, request: &crate::llm::ChatRequest) -> OpenAIRequest {
+        let (max_tokens, max_completion_tokens) =
+            if self.openai { (None, Some(request.max_tokens)) } else { (Some(request.max_tokens), None) };
+        OpenAIRequest {
             model: request.model.clone(),
-            messages: openai_messages,
-            max_tokens: request.max_tokens,
-            stream: false,
-            tools: openai_tools,
-        };
-
-        let url = format!("{}/chat/completions", self.base_url);
-        let mut req_builder = self.client.post(&url)
-            .header("content-type", "application/json");
-
-        if let Some(ref key) = self.api_key {
-            req_builder = req_builder.header("authorization", format!("Bearer {}", key));
-        }
-
-        let response = req_builder
-            .json(&openai_req)
-            .send()
-            .await
-            .context("Failed to send request to OpenAI-compatible API")?;
-
-        let status = response.status();
-        if !status.is_success() {
-            let body = response.text().await.unwrap_or_default();
-            anyhow::bail!("OpenAI-compatible API error ({}): {}", status, body);
+            messages: Self::build_openai_messages(&request.system, &request.messages),
+            max_tokens,
+            max_completion_tokens,
+            stream: true,
+            tools: Self::convert_tools(&request.tools),
+            reasoning_effort: request.thinking.reasoning_effort(),
+            stream_options: Some(serde_json::json!({ "include_usage": true })),
         }
-
-        let oai_resp: OpenAIResponse = response.json().await
-            .context("Failed to parse OpenAI-compatible API response")?;
-
-        self.convert_response(oai_resp)
     }
 
-    /// Streaming chat request. Text deltas sent via `text_tx`.
+    /// Streaming chat request. Text and tool-call progress are sent via `events`.
     pub async fn chat_streaming(
         &self,
         request: &crate::llm::ChatRequest,
-        text_tx: Sender<String>,
+        events: Sender<StreamEvent>,
     ) -> Result<(ChatResponse, String)> {
-        let openai_messages = Self::build_openai_messages(&request.system, &request.messages);
-        let openai_tools = Self::convert_tools(&request.tools);
-
-        let openai_req = OpenAIRequest {
-            model: request.model.clone(),
-            messages: openai_messages,
-            max_tokens: request.max_tokens,
-            stream: true,
-            tools: openai_tools,
-        };
+        let openai_req = self.build_request(request);
 
         let url = format!("{}/chat/completions", self.base_url);
         let mut req_builder = self.client.post(&url)
@@ -240,10 +329,8 @@ impl OpenAICompatibleClient {
             .await
             .context("Failed to send streaming request to OpenAI-compatible API")?;
 
-        let status = response.status();
-        if !status.is_success() {
-            let body = response.text().await.unwrap_or_default();
-            anyhow::bail!("OpenAI-compatible API error ({}): {}", status, body);
+        if !response.status().is_success() {
+            return Err(ApiError::from_response("OpenAI-compatible", response).await.into());
         }
 
         let mut stream = response.bytes_stream();
@@ -278,9 +365,12 @@ impl OpenAICompatibleClient {
                                     total_usage = Some(usage);
                                 }
                                 for choice in &chunk.choices {
+                                    if let Some(ref thinking) = choice.delta.reasoning_content {
+                                        let _ = events.send(StreamEvent::Thinking(thinking.clone())).await;
+                                    }
                                     if let Some(ref text) = choice.delta.content {
                                         full_text.push_str(text);
-                                        let _ = text_tx.try_send(text.clone());
+                                        let _ = events.send(StreamEvent::Text(text.clone())).await;
                                     }
                                     if let Some(ref reason) = choice.finish_reason {
                                         finish_reason = Some(reason.clone());
@@ -290,15 +380,24 @@ impl OpenAICompatibleClient {
                                             while tool_accums.len() <= tc.index {
                                                 tool_accums.push((String::new(), String::new(), String::new()));
                                             }
+                                            let accum = &mut tool_accums[tc.index];
                                             if let Some(ref id) = tc.id {
-                                                tool_accums[tc.index].0 = id.clone();
+                                                accum.0 = id.clone();
                                             }
                                             if let Some(ref f) = tc.function {
                                                 if let Some(ref name) = f.name {
-                                                    tool_accums[tc.index].1 = name.clone();
+                                                    accum.1 = name.clone();
+                                                    let _ = events
+                                                        .send(StreamEvent::ToolUseStart { id: accum.0.clone(), name: name.clone() })
+                                                        .await;
                                                 }
                                                 if let Some(ref args) = f.arguments {
-                                                    tool_accums[tc.index].2.push_str(args);
+                                                    accum.2.push_str(args);
+                                                    if !args.is_empty() {
+                                                        let _ = events
+                                                            .send(StreamEvent::ToolUseInput { id: accum.0.clone(), partial_json: args.clone() })
+                                                            .await;
+                                                    }
                                                 }
                                             }
                                         }
@@ -318,10 +417,13 @@ impl OpenAICompatibleClient {
         if !full_text.is_empty() {
             content.push(ContentBlock::Text { text: full_text.clone() });
         }
+        // Tool calls are only complete once the stream ends.
         for (id, name, args) in tool_accums {
             if !name.is_empty() {
-                let input = serde_json::from_str(&args)
-                    .unwrap_or(serde_json::Value::Object(serde_json::Map::new()));
+                let input = parse_tool_input(&args);
+                let _ = events
+                    .send(StreamEvent::ToolUseEnd { id: id.clone(), name: name.clone(), input: input.clone() })
+                    .await;
                 content.push(ContentBlock::ToolUse { id, name, input });
             }
         }
@@ -334,10 +436,8 @@ impl OpenAICompatibleClient {
             other => other.map(|s| s.to_string()),
         };
 
-        let usage = total_usage.map(|u| Usage {
-            input_tokens: u.prompt_tokens,
-            output_tokens: u.completion_tokens,
-        }).unwrap_or(Usage { input_tokens: 0, output_tokens: 0 });
+        let usage = total_usage.map(Usage::from)
+            .unwrap_or_default();
 
         let resp = ChatResponse {
             id: message_id,
@@ -348,49 +448,142 @@ impl OpenAICompatibleClient {
 
         Ok((resp, full_text))
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::AnthropicMessage;
+
+    #[test]
+    fn test_usage_reports_reasoning_tokens() {
+        let usage: OpenAIUsage = serde_json::from_value(serde_json::json!({
+            "prompt_tokens": 100,
+            "completion_tokens": 700,
+            "completion_tokens_details": { "reasoning_tokens": 512 }
+        }))
+        .unwrap();
+        let usage = Usage::from(usage);
+        assert_eq!((usage.input_tokens, usage.output_tokens, usage.thinking_tokens), (100, 700, 512));
+    }
 
-    fn convert_response(&self, oai: OpenAIResponse) -> Result<ChatResponse> {
-        let choice = oai.choices.into_iter().next()
-            .context("No choices in OpenAI response")?;
+    #[test]
+    fn test_tool_blocks_map_to_openai_messages() {
+        let messages = vec![
+            AnthropicMessage {
+                role: "user".to_string(),
+                content: serde_json::json!("read a.txt"),
+            },
+            AnthropicMessage {
+                role: "assistant".to_string(),
+                content: serde_json::json!([
+                    { "type": "tool_use", "id": "call_1", "name": "fs_read", "input": { "path": "a.txt" } }
+                ]),
+            },
+            AnthropicMessage {
+                role: "user".to_string(),
+                content: serde_json::json!([
+                    { "type": "tool_result", "tool_use_id": "call_1", "content": "hello", "is_error": false },
+                    { "type": "text", "text": "now fix it" }
+                ]),
+            },
+        ];
+        let result = OpenAICompatibleClient::build_openai_messages("sys", &messages);
+        let roles: Vec<&str> = result.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, vec!["system", "user", "assistant", "tool", "user"]);
+
+        let call = &result[2].tool_calls.as_ref().unwrap()[0];
+        assert_eq!(call["id"], "call_1");
+        assert_eq!(call["function"]["name"], "fs_read");
+        assert_eq!(call["function"]["arguments"], r#"{"path":"a.txt"}"#);
+        assert!(result[2].content.is_null());
+
+        assert_eq!(result[3].tool_call_id.as_deref(), Some("call_1"));
+        assert_eq!(result[3].content, "hello");
+        assert_eq!(result[4].content[0]["text"], "now fix it");
+    }
 
-        let mut content = Vec::new();
+    #[test]
+    fn test_image_blocks_map_to_data_urls() {
+        let messages = vec![AnthropicMessage {
+            role: "user".to_string(),
+            content: serde_json::json!([
+                { "type": "image", "source": { "type": "base64", "media_type": "image/png", "data": "abc" } },
+                { "type": "text", "text": "what is this?" }
+            ]),
+        }];
+        let result = OpenAICompatibleClient::build_openai_messages("sys", &messages);
+        assert_eq!(result[1].content[0]["image_url"]["url"], "data:image/png;base64,abc");
+        assert_eq!(result[1].content[1]["text"], "what is this?");
+    }
 
-        if let Some(text) = choice.message.content {
-            if !text.is_empty() {
-                content.push(ContentBlock::Text { text });
-            }
-        }
+    #[test]
+    fn test_output_limit_is_named_for_the_server() {
+        let request = crate::llm::ChatRequest {
+            model: "o3-mini".to_string(),
+            max_tokens: 4096,
+            system: "sys".to_string(),
+            messages: vec![AnthropicMessage { role: "user".to_string(), content: serde_json::json!("hi") }],
+            tools: vec![],
+            stream: false,
+            thinking: crate::llm::ThinkingLevel::Medium,
+            stable_prefix: 0,
+        };
 
-        if let Some(tool_calls) = choice.message.tool_calls {
-            for tc in tool_calls {
-                let input = serde_json::from_str(&tc.function.arguments)
-                    .unwrap_or(serde_json::Value::Object(serde_json::Map::new()));
-                content.push(ContentBlock::ToolUse {
-                    id: tc.id,
-                    name: tc.function.name,
-                    input,
-                });
-            }
-        }
+        let openai = OpenAICompatibleClient::openai("key".to_string());
+        let body = serde_json::to_value(openai.build_request(&request)).unwrap();
+        assert_eq!(body["model"], "o3-mini");
+        assert_eq!(body["max_completion_tokens"], 4096);
+        assert!(body.get("max_tokens").is_none(), "{body}");
+        assert_eq!(body["reasoning_effort"], "medium");
+        assert_eq!(body["stream"], true);
+        assert_eq!(body["messages"][0]["role"], "system");
+
+        let ollama = OpenAICompatibleClient::ollama("http://localhost:11434".to_string());
+        let body = serde_json::to_value(ollama.build_request(&request)).unwrap();
+        assert_eq!(body["max_tokens"], 4096);
+        assert!(body.get("max_completion_tokens").is_none(), "{body}");
+    }
 
-        // Map finish_reason to Anthropic-style stop_reason
-        let stop_reason = match choice.finish_reason.as_deref() {
-            Some("tool_calls") => Some("tool_use".to_string()),
-            Some("stop") => Some("end_turn".to_string()),
-            Some("length") => Some("max_tokens".to_string()),
-            other => other.map(|s| s.to_string()),
+    #[tokio::test]
+    async fn test_streamed_tool_call_is_reassembled() {
+        let input = serde_json::json!({ "path": "a.txt", "limit": 10 });
+        let url = crate::test_support::serve(vec![crate::test_support::openai_tool_call("call_1", "fs_read", &input)]).await;
+        let client = OpenAICompatibleClient::openai("test-key".to_string()).with_base_url(url);
+        let request = crate::llm::ChatRequest {
+            model: "gpt-4o".to_string(),
+            max_tokens: 1024,
+            system: String::new(),
+            messages: vec![AnthropicMessage { role: "user".to_string(), content: serde_json::json!("read a.txt") }],
+            tools: vec![],
+            stream: false,
+            thinking: crate::llm::ThinkingLevel::Off,
+            stable_prefix: 0,
         };
 
-        let usage = oai.usage.map(|u| Usage {
-            input_tokens: u.prompt_tokens,
-            output_tokens: u.completion_tokens,
-        }).unwrap_or(Usage { input_tokens: 0, output_tokens: 0 });
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        let (response, _) = client.chat_streaming(&request, tx).await.unwrap();
+        assert!(response.wants_tool_use());
+        assert_eq!(response.tool_uses(), vec![("call_1", "fs_read", &input)]);
 
-        Ok(ChatResponse {
-            id: oai.id,
-            content,
-            stop_reason,
-            usage,
-        })
+        let mut events = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            events.push(event);
+        }
+        // The empty first argument fragment is not reported.
+        assert_eq!(events.len(), 4, "{events:?}");
+        assert_eq!(events[0], StreamEvent::ToolUseStart { id: "call_1".to_string(), name: "fs_read".to_string() });
+        let partial: String = events[1..3]
+            .iter()
+            .map(|event| match event {
+                StreamEvent::ToolUseInput { id, partial_json } if id == "call_1" => partial_json.as_str(),
+                other => panic!("expected input for call_1, got {other:?}"),
+            })
+            .collect();
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&partial).unwrap(), input);
+        assert_eq!(
+            events[3],
+            StreamEvent::ToolUseEnd { id: "call_1".to_string(), name: "fs_read".to_string(), input }
+        );
     }
 }
@@ -1,3 +1,572 @@
-// Re-exports bat_types::policy for agent-side use.
-#[allow(unused_imports)]
-pub use bat_types::policy::{check_access, AccessLevel, PathPolicy};
+//! Policy-checked file access for the fs tools.
+//!
+//! Checking a canonical path and then opening it by name leaves a window in
+//! which a symlink swapped into the path sends the open somewhere the policy
+//! never saw. Here a path is resolved one component at a time relative to
+//! directory handles, following symlinks by hand, and the policy is checked
+//! against that resolved location. On Linux the final open is an `openat`
+//! with `O_NOFOLLOW` on the directory handle that was walked, so a swap after
+//! the check makes the open fail instead of escaping. Directories are only
+//! created once write access has been granted.
+
+use anyhow::{bail, Result};
+use bat_types::policy::{explain, strip_win_prefix, PathPolicy};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+/// How [`open`] opens a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpenMode {
+    /// Read an existing file or directory.
+    Read,
+    /// Read and rewrite an existing file.
+    Modify,
+    /// Create or truncate a file, creating missing parent directories.
+    Replace,
+}
+
+impl OpenMode {
+    fn writes(self) -> bool {
+        self != OpenMode::Read
+    }
+}
+
+/// A file opened through [`open`], with the location the policy allowed.
+#[derive(Debug)]
+pub struct Guarded {
+    file: File,
+    path: PathBuf,
+}
+
+impl Guarded {
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    pub fn into_file(self) -> File {
+        self.file
+    }
+
+    /// Where the file is, with every symlink on the way resolved.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// List an opened directory through its handle rather than its path.
+    pub fn read_dir(&self) -> std::io::Result<std::fs::ReadDir> {
+        #[cfg(target_os = "linux")]
+        {
+            use std::os::fd::AsRawFd;
+            std::fs::read_dir(format!("/proc/self/fd/{}", self.file.as_raw_fd()))
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            std::fs::read_dir(&self.path)
+        }
+    }
+}
+
+/// A directory entry located through [`locate`] or [`locate_new`]. A final
+/// symlink is the entry itself, not what it points at.
+#[derive(Debug)]
+pub struct Entry {
+    walk: sys::Walk,
+}
+
+impl Entry {
+    /// Where the entry is, with the directories above it resolved.
+    pub fn path(&self) -> &Path {
+        &self.walk.path
+    }
+
+    pub fn exists(&self) -> bool {
+        std::fs::symlink_metadata(self.pinned()).is_ok()
+    }
+
+    /// A path to the entry through the directory handle its parent was
+    /// checked with. Calls that don't follow a final symlink (rename,
+    /// remove, `symlink_metadata`, `read_link`) can't be redirected through
+    /// it by swapping a directory above the entry.
+    pub fn pinned(&self) -> PathBuf {
+        self.walk.pinned()
+    }
+
+    /// Create the missing directories above a new entry.
+    pub fn create_parents(&mut self) -> std::io::Result<()> {
+        self.walk.create_parents()
+    }
+
+    /// Everything inside a directory entry, relative to it; empty for
+    /// anything else. Symlinks are listed, not followed.
+    pub fn contents(&self) -> Result<Vec<PathBuf>> {
+        let mut contents = Vec::new();
+        if std::fs::symlink_metadata(self.pinned()).is_ok_and(|m| m.is_dir()) {
+            list_tree(&self.pinned(), Path::new(""), &mut contents).map_err(|e| {
+                anyhow::anyhow!("Failed to list '{}': {}", strip_win_prefix(self.path()).display(), e)
+            })?;
+        }
+        Ok(contents)
+    }
+}
+
+fn list_tree(dir: &Path, prefix: &Path, contents: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let relative = prefix.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            list_tree(&entry.path(), &relative, contents)?;
+        }
+        contents.push(relative);
+    }
+    Ok(())
+}
+
+/// Refuse unless the policies allow reading or writing each of `contents`
+/// (from [`Entry::contents`]) under `root`. A rule granting a directory can
+/// have deny rules beneath it, so operations on a whole tree check every
+/// entry, not just the top.
+pub fn check_contents(policies: &[PathPolicy], root: &Path, contents: &[PathBuf], write: bool) -> Result<()> {
+    for relative in contents {
+        let access = explain(policies, &root.join(relative), write);
+        if !access.allowed {
+            bail!("Access denied: {access}");
+        }
+    }
+    Ok(())
+}
+
+/// Open `path` if the policies allow it for `mode`.
+pub fn open(policies: &[PathPolicy], path: &Path, mode: OpenMode) -> Result<Guarded> {
+    let mut walk = sys::Walk::new(path, true).map_err(|e| resolve_error(path, e))?;
+    if !walk.exists && mode != OpenMode::Replace {
+        return Err(resolve_error(path, std::io::ErrorKind::NotFound.into()));
+    }
+    let access = explain(policies, &walk.path, mode.writes());
+    if !access.allowed {
+        bail!("Access denied: {access}");
+    }
+    let display = strip_win_prefix(&walk.path).display().to_string();
+    if mode == OpenMode::Replace {
+        walk.create_parents()
+            .map_err(|e| anyhow::anyhow!("Failed to create the directories for '{}': {}", display, e))?;
+    }
+    let file = walk.open(mode).map_err(|e| anyhow::anyhow!("Failed to open '{}': {}", display, e))?;
+    sys::verify(&file, &walk.path)?;
+    Ok(Guarded { file, path: walk.path })
+}
+
+/// Locate an existing entry if the policies allow it for reading or writing.
+pub fn locate(policies: &[PathPolicy], path: &Path, write: bool) -> Result<Entry> {
+    let walk = sys::Walk::new(path, false).map_err(|e| resolve_error(path, e))?;
+    if !walk.exists {
+        return Err(resolve_error(path, std::io::ErrorKind::NotFound.into()));
+    }
+    check_entry(policies, walk, write)
+}
+
+/// Locate an entry that may not exist yet if the policies allow writing it.
+/// Nothing is created; see [`Entry::create_parents`].
+pub fn locate_new(policies: &[PathPolicy], path: &Path) -> Result<Entry> {
+    let walk = sys::Walk::new(path, false).map_err(|e| resolve_error(path, e))?;
+    check_entry(policies, walk, true)
+}
+
+fn check_entry(policies: &[PathPolicy], walk: sys::Walk, write: bool) -> Result<Entry> {
+    let access = explain(policies, &walk.path, write);
+    if !access.allowed {
+        bail!("Access denied: {access}");
+    }
+    Ok(Entry { walk })
+}
+
+fn resolve_error(path: &Path, e: std::io::Error) -> anyhow::Error {
+    anyhow::anyhow!("Cannot resolve path '{}': {}", path.display(), e)
+}
+
+fn absolute(path: &Path) -> std::io::Result<PathBuf> {
+    if path.is_absolute() {
+        Ok(path.to_path_buf())
+    } else {
+        Ok(std::env::current_dir()?.join(path))
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use super::OpenMode;
+    use std::collections::VecDeque;
+    use std::ffi::{CString, OsStr, OsString};
+    use std::fs::File;
+    use std::io::{Error, ErrorKind};
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+    use std::path::{Component, Path, PathBuf};
+
+    /// Symlinks followed before giving up, as the kernel does.
+    const MAX_SYMLINKS: usize = 40;
+
+    /// A resolved path: a handle on the deepest existing directory and the
+    /// names below it, of which all but the last are directories still to
+    /// be created.
+    #[derive(Debug)]
+    pub struct Walk {
+        pub path: PathBuf,
+        pub exists: bool,
+        dir: OwnedFd,
+        rest: Vec<OsString>,
+    }
+
+    impl Walk {
+        pub fn new(path: &Path, follow_last: bool) -> std::io::Result<Self> {
+            let path = super::absolute(path)?;
+            let mut pending = names(&path);
+            let mut dir = open_dir(None, OsStr::new("/"))?;
+            let mut real = PathBuf::from("/");
+            let mut links = 0;
+            while let Some(name) = pending.pop_front() {
+                if name == ".." {
+                    if real.pop() {
+                        dir = open_dir(Some(&dir), &name)?;
+                    }
+                    continue;
+                }
+                let last = pending.is_empty();
+                let mode = match lstat(&dir, &name) {
+                    Ok(mode) => mode,
+                    Err(e) if e.kind() == ErrorKind::NotFound => {
+                        let rest: Vec<OsString> = std::iter::once(name).chain(pending).collect();
+                        if rest.iter().any(|n| n == "..") {
+                            return Err(Error::new(ErrorKind::InvalidInput, "'..' is not allowed in new paths"));
+                        }
+                        real.extend(&rest);
+                        return Ok(Self { path: real, exists: false, dir, rest });
+                    }
+                    Err(e) => return Err(e),
+                };
+                match mode & libc::S_IFMT {
+                    libc::S_IFLNK if !last || follow_last => {
+                        links += 1;
+                        if links > MAX_SYMLINKS {
+                            return Err(Error::from_raw_os_error(libc::ELOOP));
+                        }
+                        let target = read_link(&dir, &name)?;
+                        for name in names(&target).into_iter().rev() {
+                            pending.push_front(name);
+                        }
+                        if target.is_absolute() {
+                            dir = open_dir(None, OsStr::new("/"))?;
+                            real = PathBuf::from("/");
+                        }
+                    }
+                    _ if last => {
+                        real.push(&name);
+                        return Ok(Self { path: real, exists: true, dir, rest: vec![name] });
+                    }
+                    libc::S_IFDIR => {
+                        dir = open_dir(Some(&dir), &name)?;
+                        real.push(&name);
+                    }
+                    _ => return Err(Error::from_raw_os_error(libc::ENOTDIR)),
+                }
+            }
+            // The path named a directory by `/` or a trailing `..`.
+            Ok(Self { path: real, exists: true, dir, rest: vec![".".into()] })
+        }
+
+        pub fn pinned(&self) -> PathBuf {
+            let mut pinned = PathBuf::from(format!("/proc/self/fd/{}", self.dir.as_raw_fd()));
+            pinned.extend(&self.rest);
+            pinned
+        }
+
+        pub fn create_parents(&mut self) -> std::io::Result<()> {
+            while self.rest.len() > 1 {
+                let name = self.rest.remove(0);
+                let c_name = c_str(&name)?;
+                // SAFETY: the directory handle is open and the name is NUL-terminated.
+                if unsafe { libc::mkdirat(self.dir.as_raw_fd(), c_name.as_ptr(), 0o777) } != 0 {
+                    let e = Error::last_os_error();
+                    if e.kind() != ErrorKind::AlreadyExists {
+                        return Err(e);
+                    }
+                }
+                // Something planted under the name meanwhile is refused here.
+                self.dir = open_dir(Some(&self.dir), &name)?;
+            }
+            Ok(())
+        }
+
+        pub fn open(&self, mode: OpenMode) -> std::io::Result<File> {
+            let access = match mode {
+                OpenMode::Read => libc::O_RDONLY,
+                OpenMode::Modify => libc::O_RDWR,
+                OpenMode::Replace => libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC,
+            };
+            let [name] = self.rest.as_slice() else {
+                return Err(ErrorKind::NotFound.into());
+            };
+            let fd = open_at(Some(&self.dir), name, access | libc::O_NOFOLLOW)?;
+            Ok(File::from(fd))
+        }
+    }
+
+    /// Refuse a handle that isn't at `path` any more, e.g. because a
+    /// directory above it was renamed while it was being opened.
+    pub fn verify(file: &File, path: &Path) -> anyhow::Result<()> {
+        let actual = std::fs::read_link(format!("/proc/self/fd/{}", file.as_raw_fd()))?;
+        if actual != path {
+            anyhow::bail!("'{}' changed while it was being opened", path.display());
+        }
+        Ok(())
+    }
+
+    fn names(path: &Path) -> VecDeque<OsString> {
+        path.components()
+            .filter_map(|c| match c {
+                Component::Normal(name) => Some(name.to_os_string()),
+                Component::ParentDir => Some("..".into()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn c_str(name: &OsStr) -> std::io::Result<CString> {
+        CString::new(name.as_bytes()).map_err(|_| Error::new(ErrorKind::InvalidInput, "path contains a NUL byte"))
+    }
+
+    fn open_at(dir: Option<&OwnedFd>, name: &OsStr, flags: libc::c_int) -> std::io::Result<OwnedFd> {
+        let c_name = c_str(name)?;
+        let dir = dir.map_or(libc::AT_FDCWD, |d| d.as_raw_fd());
+        // SAFETY: the name is NUL-terminated; a returned descriptor is ours to own.
+        let fd = unsafe { libc::openat(dir, c_name.as_ptr(), flags | libc::O_CLOEXEC, 0o666 as libc::c_uint) };
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+        // SAFETY: `fd` was just opened and nothing else owns it.
+        Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+
+    /// A handle for walking through a directory, refusing symlinks.
+    fn open_dir(dir: Option<&OwnedFd>, name: &OsStr) -> std::io::Result<OwnedFd> {
+        open_at(dir, name, libc::O_PATH | libc::O_DIRECTORY | libc::O_NOFOLLOW)
+    }
+
+    fn lstat(dir: &OwnedFd, name: &OsStr) -> std::io::Result<libc::mode_t> {
+        let c_name = c_str(name)?;
+        // SAFETY: `stat` is plain data and filled in by the call.
+        let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+        // SAFETY: the handle is open, the name NUL-terminated and `stat` writable.
+        if unsafe { libc::fstatat(dir.as_raw_fd(), c_name.as_ptr(), &mut stat, libc::AT_SYMLINK_NOFOLLOW) } != 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(stat.st_mode)
+    }
+
+    fn read_link(dir: &OwnedFd, name: &OsStr) -> std::io::Result<PathBuf> {
+        let c_name = c_str(name)?;
+        let mut buf = vec![0u8; libc::PATH_MAX as usize];
+        // SAFETY: the buffer is `buf.len()` bytes long and the name NUL-terminated.
+        let len = unsafe { libc::readlinkat(dir.as_raw_fd(), c_name.as_ptr(), buf.as_mut_ptr().cast(), buf.len()) };
+        if len < 0 {
+            return Err(Error::last_os_error());
+        }
+        buf.truncate(len as usize);
+        Ok(PathBuf::from(OsString::from_vec(buf)))
+    }
+
+}
+
+/// Without `openat` and `O_NOFOLLOW`, paths are resolved by name and the
+/// opened file is checked to still be at the resolved location afterwards.
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    use super::OpenMode;
+    use std::fs::File;
+    use std::io::{Error, ErrorKind};
+    use std::path::{Component, Path, PathBuf};
+
+    #[derive(Debug)]
+    pub struct Walk {
+        pub path: PathBuf,
+        pub exists: bool,
+    }
+
+    impl Walk {
+        pub fn new(path: &Path, follow_last: bool) -> std::io::Result<Self> {
+            let path = super::absolute(path)?;
+            let mut existing = path.as_path();
+            let mut rest = Vec::new();
+            while std::fs::symlink_metadata(existing).is_err() {
+                match (existing.parent(), existing.file_name()) {
+                    (Some(parent), Some(name)) => {
+                        rest.push(name);
+                        existing = parent;
+                    }
+                    _ => return Err(ErrorKind::NotFound.into()),
+                }
+            }
+            if !rest.is_empty() && path.components().any(|c| c == Component::ParentDir) {
+                return Err(Error::new(ErrorKind::InvalidInput, "'..' is not allowed in new paths"));
+            }
+            let is_link = std::fs::symlink_metadata(existing)?.file_type().is_symlink();
+            let mut resolved = match (existing.parent(), existing.file_name()) {
+                (Some(parent), Some(name)) if is_link && rest.is_empty() && !follow_last => {
+                    parent.canonicalize()?.join(name)
+                }
+                _ => existing.canonicalize()?,
+            };
+            resolved.extend(rest.iter().rev());
+            Ok(Self { path: resolved, exists: rest.is_empty() })
+        }
+
+        pub fn pinned(&self) -> PathBuf {
+            self.path.clone()
+        }
+
+        pub fn create_parents(&mut self) -> std::io::Result<()> {
+            match self.path.parent() {
+                Some(parent) => std::fs::create_dir_all(parent),
+                None => Ok(()),
+            }
+        }
+
+        pub fn open(&self, mode: OpenMode) -> std::io::Result<File> {
+            let mut options = std::fs::OpenOptions::new();
+            match mode {
+                OpenMode::Read => options.read(true),
+                OpenMode::Modify => options.read(true).write(true),
+                OpenMode::Replace => options.write(true).create(true).truncate(true),
+            };
+            options.open(&self.path)
+        }
+    }
+
+    pub fn verify(_file: &File, path: &Path) -> anyhow::Result<()> {
+        if Walk::new(path, true)?.path != path {
+            anyhow::bail!("'{}' changed while it was being opened", super::strip_win_prefix(path).display());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bat_types::policy::AccessLevel;
+
+    fn setup(name: &str) -> (PathBuf, Vec<PathPolicy>) {
+        let dir = std::env::temp_dir().join(format!("bat-{name}-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("inside")).unwrap();
+        std::fs::create_dir_all(dir.join("outside")).unwrap();
+        let dir = dir.canonicalize().unwrap();
+        std::fs::write(dir.join("outside/secret.txt"), "secret").unwrap();
+        let policies = vec![PathPolicy {
+            id: None,
+            path: dir.join("inside"),
+            access: AccessLevel::ReadWrite,
+            recursive: true,
+            description: None,
+            layer: 0,
+        }];
+        (dir, policies)
+    }
+
+    #[test]
+    fn test_directories_are_created_only_after_access_is_granted() {
+        let (dir, policies) = setup("guard-mkdir");
+        let err = open(&policies, &dir.join("outside/new/deep/a.txt"), OpenMode::Replace).unwrap_err();
+        assert!(err.to_string().contains("Access denied"), "{err}");
+        assert!(!dir.join("outside/new").exists());
+
+        let file = open(&policies, &dir.join("inside/new/deep/a.txt"), OpenMode::Replace).unwrap();
+        assert_eq!(file.path(), dir.join("inside/new/deep/a.txt"));
+        assert!(dir.join("inside/new/deep/a.txt").is_file());
+
+        let err = open(&policies, &dir.join("inside/missing/../a.txt"), OpenMode::Replace).unwrap_err();
+        assert!(err.to_string().contains("'..' is not allowed"), "{err}");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks_are_checked_where_they_lead() {
+        let (dir, policies) = setup("guard-links");
+        let inside = dir.join("inside");
+        std::os::unix::fs::symlink(dir.join("outside/secret.txt"), inside.join("file-link")).unwrap();
+        std::os::unix::fs::symlink("../outside", inside.join("dir-link")).unwrap();
+
+        for path in [inside.join("file-link"), inside.join("dir-link/secret.txt")] {
+            let err = open(&policies, &path, OpenMode::Read).unwrap_err();
+            assert!(err.to_string().contains("Access denied"), "{err}");
+        }
+        let err = open(&policies, &inside.join("dir-link/new/a.txt"), OpenMode::Replace).unwrap_err();
+        assert!(err.to_string().contains("Access denied"), "{err}");
+        assert!(!dir.join("outside/new").exists());
+        // Writing through a link to a denied file is refused, not done to the link.
+        let err = open(&policies, &inside.join("file-link"), OpenMode::Replace).unwrap_err();
+        assert!(err.to_string().contains("Access denied"), "{err}");
+        assert_eq!(std::fs::read_to_string(dir.join("outside/secret.txt")).unwrap(), "secret");
+
+        // The link itself is inside, so it can be located (to delete it, say).
+        let link = locate(&policies, &inside.join("file-link"), true).unwrap();
+        assert_eq!(link.path(), inside.join("file-link"));
+        assert!(std::fs::symlink_metadata(link.pinned()).unwrap().file_type().is_symlink());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// A thread keeps exchanging `inside/sub` between a real directory and a
+    /// symlink to `outside` while files under it are read and written. Any
+    /// open may fail, but none may reach `outside`.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_symlink_swap_race_never_escapes() {
+        use std::io::{Read, Write};
+        use std::os::unix::ffi::OsStrExt;
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+
+        let (dir, policies) = setup("guard-race");
+        let inside = dir.join("inside");
+        std::fs::create_dir(inside.join("sub")).unwrap();
+        std::fs::write(inside.join("sub/secret.txt"), "ok").unwrap();
+        std::os::unix::fs::symlink(dir.join("outside"), inside.join("swap")).unwrap();
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let swapper = {
+            let stop = stop.clone();
+            let sub = std::ffi::CString::new(inside.join("sub").as_os_str().as_bytes()).unwrap();
+            let swap = std::ffi::CString::new(inside.join("swap").as_os_str().as_bytes()).unwrap();
+            std::thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    // SAFETY: both paths are NUL-terminated.
+                    let exchanged = unsafe {
+                        libc::renameat2(libc::AT_FDCWD, sub.as_ptr(), libc::AT_FDCWD, swap.as_ptr(), libc::RENAME_EXCHANGE)
+                    };
+                    assert_eq!(exchanged, 0, "{}", std::io::Error::last_os_error());
+                }
+            })
+        };
+
+        let (mut read, mut written) = (0, 0);
+        for i in 0..3000 {
+            if let Ok(file) = open(&policies, &inside.join("sub/secret.txt"), OpenMode::Read) {
+                let mut content = String::new();
+                file.file().read_to_string(&mut content).unwrap();
+                assert_eq!(content, "ok", "read outside the policy through {}", file.path().display());
+                read += 1;
+            }
+            if let Ok(file) = open(&policies, &inside.join(format!("sub/w{i}.txt")), OpenMode::Replace) {
+                file.file().write_all(b"x").unwrap();
+                written += 1;
+            }
+        }
+        stop.store(true, Ordering::Relaxed);
+        swapper.join().unwrap();
+
+        let escaped: Vec<_> = std::fs::read_dir(dir.join("outside")).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(escaped, vec![std::ffi::OsString::from("secret.txt")]);
+        assert_eq!(std::fs::read_to_string(dir.join("outside/secret.txt")).unwrap(), "secret");
+        assert!(read > 0 && written > 0, "the race never let an open through ({read} reads, {written} writes)");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,35 +1,393 @@
 //! Unified LLM provider — routes requests to the correct backend client.
 
-use anyhow::Result;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
 use tokio::sync::mpsc::Sender;
+use tracing::warn;
+
+use bat_types::config::LlmProvider;
+use bat_types::ipc::LlmRelay;
+use bat_types::models::{ModelInfo, IMAGE_TOKENS};
 
-use crate::llm::{AnthropicClient, ChatRequest, ChatResponse};
+use crate::llm::{AnthropicClient, ChatRequest, ChatResponse, StreamEvent, ThinkingLevel};
+use crate::mock_client::MockClient;
 use crate::openai_client::OpenAICompatibleClient;
+use crate::retry::{self, RetryPolicy};
+
+/// A model that just failed over is skipped for this long, so later calls in
+/// the same turn don't each sit through its retries again.
+const FAILOVER_COOLDOWN: Duration = Duration::from_secs(60);
 
-/// A unified LLM client that wraps either Anthropic or OpenAI-compatible backends.
+/// The output limit never goes below this, however full the context is. A
+/// prompt that really leaves less room fails with the provider's own error.
+const MIN_OUTPUT_TOKENS: u32 = 1024;
+
+/// A unified LLM client that wraps either Anthropic or OpenAI-compatible
+/// backends, or the scripted mock used by end-to-end tests.
 pub enum LlmClient {
     Anthropic(AnthropicClient),
     OpenAICompatible(OpenAICompatibleClient),
+    Mock(MockClient),
 }
 
 impl LlmClient {
-    /// Non-streaming chat request.
-    pub async fn chat(&self, request: &ChatRequest) -> Result<ChatResponse> {
-        match self {
-            LlmClient::Anthropic(c) => c.chat(request).await,
-            LlmClient::OpenAICompatible(c) => c.chat(request).await,
+    /// Build the client that serves `model`, from its registry entry.
+    /// Anthropic and OpenAI models go through the gateway's `relay`, which
+    /// holds the keys and picks the endpoint. `BAT_MOCK_LLM` replaces every
+    /// model with the scripted mock (see `mock_client`).
+    pub fn for_model(model: &ModelInfo, relay: Option<&LlmRelay>) -> Result<Self> {
+        if let Some(mock) = MockClient::from_env()? {
+            return Ok(LlmClient::Mock(mock));
         }
+        Ok(match model.provider {
+            LlmProvider::Anthropic => {
+                let relay = relay.filter(|r| r.anthropic).context("No Anthropic API key is configured")?;
+                LlmClient::Anthropic(AnthropicClient::via_relay(relay)?)
+            }
+            LlmProvider::OpenAI => {
+                let relay = relay.filter(|r| r.openai).context("No OpenAI API key is configured")?;
+                LlmClient::OpenAICompatible(OpenAICompatibleClient::via_relay(relay)?)
+            }
+            LlmProvider::Ollama | LlmProvider::LocalLlm => {
+                let endpoint = model.endpoint.clone()
+                    .or_else(|| std::env::var("OLLAMA_ENDPOINT").ok())
+                    .unwrap_or_else(|| "http://localhost:11434".to_string());
+                LlmClient::OpenAICompatible(OpenAICompatibleClient::ollama(endpoint))
+            }
+        })
     }
 
     /// Streaming chat request.
     pub async fn chat_streaming(
         &self,
         request: &ChatRequest,
-        text_tx: Sender<String>,
+        events: Sender<StreamEvent>,
     ) -> Result<(ChatResponse, String)> {
         match self {
-            LlmClient::Anthropic(c) => c.chat_streaming(request, text_tx).await,
-            LlmClient::OpenAICompatible(c) => c.chat_streaming(request, text_tx).await,
+            LlmClient::Anthropic(c) => c.chat_streaming(request, events).await,
+            LlmClient::OpenAICompatible(c) => c.chat_streaming(request, events).await,
+            LlmClient::Mock(c) => c.chat_streaming(request, events).await,
+        }
+    }
+}
+
+/// An HTTP client for the gateway's LLM relay. The relay is on loopback,
+/// so this skips the egress proxy the agent's environment points at.
+pub fn relay_http() -> reqwest::ClientBuilder {
+    reqwest::Client::builder().no_proxy()
+}
+
+/// A model and the client that serves it.
+pub struct Route {
+    pub model: ModelInfo,
+    pub client: LlmClient,
+}
+
+/// `request` adapted to what `model` accepts: its name, its output limit
+/// (less if the prompt leaves less of the context window), and no thinking or
+/// tools if it doesn't support them.
+fn fit_request(request: &ChatRequest, model: &ModelInfo) -> ChatRequest {
+    let mut req = request.clone();
+    req.model = model.id.clone();
+    let room = model.context_window.saturating_sub(estimate_prompt_tokens(request, model));
+    req.max_tokens = u32::try_from(room)
+        .unwrap_or(u32::MAX)
+        .max(MIN_OUTPUT_TOKENS)
+        .min(model.max_output_tokens);
+    // The thinking budget is part of max_tokens and must be below it.
+    let budget = request.thinking.budget_tokens().unwrap_or(0);
+    if !model.thinking || budget >= req.max_tokens {
+        req.thinking = ThinkingLevel::Off;
+    }
+    if !model.tools {
+        req.tools.clear();
+    }
+    req
+}
+
+/// Estimate the tokens `request`'s system prompt, tools and messages take up
+/// for `model`. Images and documents count a flat `IMAGE_TOKENS` rather than
+/// their encoded size.
+fn estimate_prompt_tokens(request: &ChatRequest, model: &ModelInfo) -> usize {
+    fn chars(value: &serde_json::Value, attachments: &mut usize) -> usize {
+        match value {
+            serde_json::Value::String(s) => s.chars().count(),
+            serde_json::Value::Array(items) => items.iter().map(|item| chars(item, attachments)).sum(),
+            serde_json::Value::Object(fields) => {
+                if matches!(fields.get("type").and_then(|t| t.as_str()), Some("image" | "document")) {
+                    *attachments += 1;
+                    return 0;
+                }
+                fields.iter().map(|(key, value)| key.len() + chars(value, attachments)).sum()
+            }
+            other => other.to_string().len(),
+        }
+    }
+    let mut attachments = 0;
+    let mut total = request.system.chars().count();
+    total += request.tools.iter().map(|tool| chars(tool, &mut attachments)).sum::<usize>();
+    for message in &request.messages {
+        total += message.role.len() + chars(&message.content, &mut attachments);
+    }
+    (total as f64 / model.chars_per_token()).ceil() as usize + attachments * IMAGE_TOKENS
+}
+
+/// The configured model followed by its fallbacks, in order.
+///
+/// Each call retries transient errors (rate limits, overload, dropped
+/// connections) with backoff, then moves on to the next model. Every switch
+/// is reported as a `StreamEvent::Failover`. A call that already streamed
+/// output is never retried, since the UI has shown part of its answer.
+pub struct ModelChain {
+    routes: Vec<Route>,
+    policy: RetryPolicy,
+    /// Per route: skip it until this instant after it failed over.
+    cooldowns: Mutex<Vec<Option<Instant>>>,
+}
+
+impl ModelChain {
+    pub fn new(routes: Vec<Route>, policy: RetryPolicy) -> Self {
+        let cooldowns = Mutex::new(vec![None; routes.len()]);
+        Self { routes, policy, cooldowns }
+    }
+
+    /// Streaming chat request against the first model that answers.
+    /// The request is fitted to each route's model in turn (see `fit_request`).
+    pub async fn chat_streaming(
+        &self,
+        request: &ChatRequest,
+        events: Sender<StreamEvent>,
+    ) -> Result<(ChatResponse, String)> {
+        let mut failed: Option<(String, String)> = None;
+        let mut last_err = None;
+        for (i, route) in self.routes.iter().enumerate() {
+            let is_last = i + 1 == self.routes.len();
+            if let Some((from, reason)) = failed.take() {
+                let _ = events
+                    .send(StreamEvent::Failover { from, to: route.model.id.clone(), reason })
+                    .await;
+            }
+            if !is_last && self.cooling_down(i) {
+                failed = Some((route.model.id.clone(), "cooling down after a recent failure".to_string()));
+                continue;
+            }
+
+            let req = fit_request(request, &route.model);
+            match self.call_with_retries(route, &req, &events).await {
+                Ok(response) => {
+                    self.cooldowns.lock().unwrap()[i] = None;
+                    return Ok(response);
+                }
+                Err((err, emitted)) => {
+                    if emitted || is_last || !retry::classify(&err).allows_failover() {
+                        return Err(err);
+                    }
+                    warn!("Model {} failed, falling back: {err:#}", route.model.id);
+                    self.cooldowns.lock().unwrap()[i] = Some(Instant::now() + FAILOVER_COOLDOWN);
+                    failed = Some((route.model.id.clone(), format!("{err:#}")));
+                    last_err = Some(err);
+                }
+            }
         }
+        Err(last_err.unwrap_or_else(|| anyhow::anyhow!("No model configured")))
+    }
+
+    fn cooling_down(&self, route: usize) -> bool {
+        self.cooldowns.lock().unwrap()[route].is_some_and(|until| Instant::now() < until)
+    }
+
+    /// Call one route, retrying transient errors. On failure, also reports
+    /// whether any output was streamed before the error.
+    async fn call_with_retries(
+        &self,
+        route: &Route,
+        request: &ChatRequest,
+        events: &Sender<StreamEvent>,
+    ) -> std::result::Result<(ChatResponse, String), (anyhow::Error, bool)> {
+        let mut attempt = 0;
+        loop {
+            let (tx, mut rx) = tokio::sync::mpsc::channel::<StreamEvent>(128);
+            let forward = async {
+                let mut emitted = false;
+                while let Some(event) = rx.recv().await {
+                    emitted = true;
+                    let _ = events.send(event).await;
+                }
+                emitted
+            };
+            let (result, emitted) = tokio::join!(route.client.chat_streaming(request, tx), forward);
+            let err = match result {
+                Ok(response) => return Ok(response),
+                Err(err) => err,
+            };
+
+            let kind = retry::classify(&err);
+            let delay = match self.policy.delay(attempt, &err) {
+                Some(delay) if !emitted && kind.is_retryable() && attempt < self.policy.max_retries => delay,
+                _ => return Err((err, emitted)),
+            };
+            warn!(
+                "Model {} failed ({kind:?}), retry {}/{} in {:.1}s: {err:#}",
+                route.model.id,
+                attempt + 1,
+                self.policy.max_retries,
+                delay.as_secs_f32()
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{anthropic_text, serve};
+
+    fn overloaded() -> String {
+        let body = r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#;
+        format!(
+            "HTTP/1.1 529 Overloaded\r\nretry-after: 0\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    fn route(model: &str, url: String) -> Route {
+        let client = AnthropicClient::new("test-key".to_string()).with_base_url(url);
+        Route { model: ModelInfo::local(model), client: LlmClient::Anthropic(client) }
+    }
+
+    fn request() -> ChatRequest {
+        ChatRequest {
+            model: "primary".to_string(),
+            max_tokens: 100,
+            system: String::new(),
+            messages: vec![],
+            tools: vec![],
+            stream: false,
+            thinking: ThinkingLevel::Off,
+            stable_prefix: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retries_then_answers() {
+        let url = serve(vec![overloaded(), anthropic_text("hello")]).await;
+        let chain = ModelChain::new(vec![route("primary", url)], RetryPolicy::new(2));
+
+        let (tx, _rx) = tokio::sync::mpsc::channel(16);
+        let (_, text) = chain.chat_streaming(&request(), tx).await.unwrap();
+        assert_eq!(text, "hello");
+    }
+
+    #[tokio::test]
+    async fn test_fails_over_after_retries() {
+        let primary = serve(vec![overloaded(), overloaded()]).await;
+        let fallback = serve(vec![anthropic_text("from fallback")]).await;
+        let chain = ModelChain::new(
+            vec![route("primary", primary), route("fallback", fallback)],
+            RetryPolicy::new(1),
+        );
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        let (_, text) = chain.chat_streaming(&request(), tx).await.unwrap();
+        assert_eq!(text, "from fallback");
+
+        let Some(StreamEvent::Failover { from, to, reason }) = rx.recv().await else {
+            panic!("expected a failover event first");
+        };
+        assert_eq!((from.as_str(), to.as_str()), ("primary", "fallback"));
+        assert!(reason.contains("529"));
+        // The primary is now skipped until its cooldown ends.
+        assert!(chain.cooling_down(0));
+    }
+
+    #[tokio::test]
+    async fn test_skipped_route_reports_failover() {
+        let primary = serve(vec![]).await;
+        let fallback = serve(vec![anthropic_text("from fallback")]).await;
+        let chain = ModelChain::new(
+            vec![route("primary", primary), route("fallback", fallback)],
+            RetryPolicy::new(1),
+        );
+        chain.cooldowns.lock().unwrap()[0] = Some(Instant::now() + FAILOVER_COOLDOWN);
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        let (_, text) = chain.chat_streaming(&request(), tx).await.unwrap();
+        assert_eq!(text, "from fallback");
+
+        let Some(StreamEvent::Failover { from, to, reason }) = rx.recv().await else {
+            panic!("expected a failover event first");
+        };
+        assert_eq!((from.as_str(), to.as_str()), ("primary", "fallback"));
+        assert!(reason.contains("cooling down"), "reason: {reason}");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_request_is_not_retried() {
+        let body = r#"{"type":"error","error":{"type":"invalid_request_error","message":"bad"}}"#;
+        let bad = format!(
+            "HTTP/1.1 400 Bad Request\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        let primary = serve(vec![bad]).await;
+        let fallback = serve(vec![anthropic_text("unused")]).await;
+        let chain = ModelChain::new(
+            vec![route("primary", primary), route("fallback", fallback)],
+            RetryPolicy::new(3),
+        );
+
+        let (tx, _rx) = tokio::sync::mpsc::channel(16);
+        let err = chain.chat_streaming(&request(), tx).await.unwrap_err();
+        assert_eq!(retry::classify(&err), retry::ErrorKind::InvalidRequest);
+    }
+
+    #[test]
+    fn test_fit_request_to_model() {
+        let registry = bat_types::models::ModelRegistry::builtin();
+        let mut req = request();
+        req.thinking = ThinkingLevel::High;
+        req.tools = vec![serde_json::json!({"name": "fs_read"})];
+
+        let sonnet = fit_request(&req, &registry.resolve("claude-sonnet-4-6"));
+        assert_eq!(sonnet.model, "claude-sonnet-4-6");
+        assert_eq!(sonnet.max_tokens, 64_000);
+        assert_eq!(sonnet.thinking, ThinkingLevel::High);
+
+        let old = fit_request(&req, &registry.resolve("claude-3-5-haiku-20241022"));
+        assert_eq!(old.max_tokens, 8_192);
+        assert_eq!(old.thinking, ThinkingLevel::Off);
+
+        let no_tools = ModelInfo { tools: false, ..ModelInfo::local("tiny") };
+        assert!(fit_request(&req, &no_tools).tools.is_empty());
+    }
+
+    #[test]
+    fn test_output_limit_leaves_room_for_the_prompt() {
+        let model = ModelInfo { thinking: true, ..ModelInfo::local("small") };
+        let mut req = request();
+        req.thinking = ThinkingLevel::Low;
+        assert_eq!(fit_request(&req, &model).max_tokens, model.max_output_tokens);
+
+        // About 6,200 of the 8,192 tokens go to the prompt, 1,600 of them the image.
+        req.messages = vec![crate::llm::AnthropicMessage {
+            role: "user".to_string(),
+            content: serde_json::json!([
+                { "type": "text", "text": "word ".repeat(3_700) },
+                { "type": "image", "source": { "type": "base64", "media_type": "image/png", "data": "A".repeat(500_000) } },
+            ]),
+        }];
+        let prompt = estimate_prompt_tokens(&req, &model);
+        assert!((6_200..6_300).contains(&prompt), "{prompt}");
+        let fitted = fit_request(&req, &model);
+        assert_eq!(fitted.max_tokens as usize, model.context_window - prompt);
+        // The Low budget no longer fits under the smaller limit.
+        assert_eq!(fitted.thinking, ThinkingLevel::Off);
+
+        // A prompt that fills the window still leaves the model something to say.
+        req.system = "x".repeat(40_000);
+        assert_eq!(fit_request(&req, &model).max_tokens, MIN_OUTPUT_TOKENS);
     }
 }
@@ -0,0 +1,206 @@
+//! Error classification and backoff for LLM requests.
+//!
+//! The clients in `llm` and `openai_client` report non-success responses as
+//! [`ApiError`], so `classify` can tell a rate limit or an overloaded server
+//! (worth retrying) from a bad request (not worth retrying anywhere).
+
+use std::time::Duration;
+
+/// Non-success response from an LLM API.
+#[derive(Debug, thiserror::Error)]
+#[error("{provider} API error ({status}): {body}")]
+pub struct ApiError {
+    pub provider: &'static str,
+    pub status: reqwest::StatusCode,
+    /// Server-requested delay from the `retry-after` / `retry-after-ms` headers.
+    pub retry_after: Option<Duration>,
+    pub body: String,
+}
+
+impl ApiError {
+    /// Consume an unsuccessful response, keeping its status, body and retry hint.
+    pub async fn from_response(provider: &'static str, response: reqwest::Response) -> Self {
+        let status = response.status();
+        let retry_after = parse_retry_after(response.headers());
+        let body = response.text().await.unwrap_or_default();
+        Self { provider, status, retry_after, body }
+    }
+}
+
+/// The delay the server asked for. Values no `Duration` can hold, such as
+/// `inf` or `1e400`, are ignored.
+fn parse_retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(str::trim);
+    if let Some(ms) = header("retry-after-ms").and_then(|v| v.parse::<f64>().ok()) {
+        return Duration::try_from_secs_f64(ms.max(0.0) / 1000.0).ok();
+    }
+    let value = header("retry-after")?;
+    if let Ok(secs) = value.parse::<f64>() {
+        return Duration::try_from_secs_f64(secs.max(0.0)).ok();
+    }
+    // HTTP-date form
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let wait = at.with_timezone(&chrono::Utc) - chrono::Utc::now();
+    Some(wait.to_std().unwrap_or_default())
+}
+
+/// What went wrong with an LLM request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// 429 — too many requests or tokens.
+    RateLimited,
+    /// 503 / 529 — the provider is overloaded.
+    Overloaded,
+    /// Other 5xx responses.
+    Server,
+    /// Connection refused or reset, timeouts, a dropped stream.
+    Network,
+    /// 401 / 403 — missing or rejected credentials.
+    Auth,
+    /// 404 — usually an unknown model name.
+    NotFound,
+    /// 400 / 413 / 422 — the request itself was rejected.
+    InvalidRequest,
+    Other,
+}
+
+impl ErrorKind {
+    /// Whether the same request may succeed if sent again later.
+    pub fn is_retryable(self) -> bool {
+        matches!(self, Self::RateLimited | Self::Overloaded | Self::Server | Self::Network)
+    }
+
+    /// Whether another model might succeed where this one failed. A request
+    /// rejected as invalid would be rejected everywhere.
+    pub fn allows_failover(self) -> bool {
+        self != Self::InvalidRequest
+    }
+}
+
+/// Classify an error returned by an LLM client.
+pub fn classify(err: &anyhow::Error) -> ErrorKind {
+    for cause in err.chain() {
+        if let Some(api) = cause.downcast_ref::<ApiError>() {
+            return match api.status.as_u16() {
+                429 => ErrorKind::RateLimited,
+                503 | 529 => ErrorKind::Overloaded,
+                408 => ErrorKind::Network,
+                500..=599 => ErrorKind::Server,
+                401 | 403 => ErrorKind::Auth,
+                404 => ErrorKind::NotFound,
+                400 | 413 | 422 => ErrorKind::InvalidRequest,
+                _ => ErrorKind::Other,
+            };
+        }
+        if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+            if e.is_connect() || e.is_timeout() || e.is_request() || e.is_body() {
+                return ErrorKind::Network;
+            }
+        }
+    }
+    ErrorKind::Other
+}
+
+/// How often, and how patiently, a model is retried before falling back.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+
+    /// Delay before retry number `attempt` (0-based): the server's
+    /// `retry-after` if it sent one, otherwise exponential backoff. `None`
+    /// if the server asks for longer than `max_backoff`, in which case the
+    /// chain moves on to the next model rather than wait.
+    pub fn delay(&self, attempt: u32, err: &anyhow::Error) -> Option<Duration> {
+        let hinted = err.chain().find_map(|c| c.downcast_ref::<ApiError>()).and_then(|e| e.retry_after);
+        match hinted {
+            Some(hint) => (hint <= self.max_backoff).then_some(hint),
+            None => Some(
+                self.initial_backoff
+                    .saturating_mul(2u32.saturating_pow(attempt))
+                    .min(self.max_backoff),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api_error(status: u16, retry_after: Option<Duration>) -> anyhow::Error {
+        anyhow::Error::new(ApiError {
+            provider: "Anthropic",
+            status: reqwest::StatusCode::from_u16(status).unwrap(),
+            retry_after,
+            body: String::new(),
+        })
+        .context("LLM call failed")
+    }
+
+    #[test]
+    fn test_classify_statuses() {
+        assert_eq!(classify(&api_error(429, None)), ErrorKind::RateLimited);
+        assert_eq!(classify(&api_error(529, None)), ErrorKind::Overloaded);
+        assert_eq!(classify(&api_error(502, None)), ErrorKind::Server);
+        assert_eq!(classify(&api_error(401, None)), ErrorKind::Auth);
+        assert_eq!(classify(&api_error(400, None)), ErrorKind::InvalidRequest);
+        assert_eq!(classify(&anyhow::anyhow!("something else")), ErrorKind::Other);
+
+        assert!(ErrorKind::RateLimited.is_retryable());
+        assert!(!ErrorKind::Auth.is_retryable());
+        assert!(ErrorKind::Auth.allows_failover());
+        assert!(!ErrorKind::InvalidRequest.allows_failover());
+    }
+
+    #[test]
+    fn test_delay_prefers_retry_after() {
+        let policy = RetryPolicy::new(3);
+        assert_eq!(policy.delay(0, &api_error(429, Some(Duration::from_secs(7)))), Some(Duration::from_secs(7)));
+        assert_eq!(policy.delay(0, &api_error(529, None)), Some(Duration::from_secs(1)));
+        assert_eq!(policy.delay(2, &api_error(529, None)), Some(Duration::from_secs(4)));
+        assert_eq!(policy.delay(10, &api_error(529, None)), Some(Duration::from_secs(30)));
+        // Asked to wait longer than we back off ourselves: fail over instead.
+        assert_eq!(policy.delay(0, &api_error(429, Some(Duration::from_secs(31)))), None);
+        assert_eq!(policy.delay(0, &api_error(429, Some(Duration::MAX))), None);
+    }
+
+    #[test]
+    fn test_parse_retry_after_headers() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("retry-after", "12".parse().unwrap());
+        assert_eq!(parse_retry_after(&headers), Some(Duration::from_secs(12)));
+
+        headers.insert("retry-after-ms", "1500".parse().unwrap());
+        assert_eq!(parse_retry_after(&headers), Some(Duration::from_millis(1500)));
+    }
+
+    #[test]
+    fn test_unrepresentable_retry_after_is_ignored() {
+        for value in ["inf", "1e400"] {
+            for name in ["retry-after", "retry-after-ms"] {
+                let mut headers = reqwest::header::HeaderMap::new();
+                headers.insert(name, value.parse().unwrap());
+                assert_eq!(parse_retry_after(&headers), None, "{name}: {value}");
+            }
+        }
+
+        // Huge but finite: parsed, and then too long to wait for.
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("retry-after", "1e15".parse().unwrap());
+        let hint = parse_retry_after(&headers);
+        assert_eq!(hint, Some(Duration::from_secs(1_000_000_000_000_000)));
+        assert_eq!(RetryPolicy::new(3).delay(0, &api_error(429, hint)), None);
+    }
+}
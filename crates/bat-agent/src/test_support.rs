@@ -0,0 +1,83 @@
+//! Canned HTTP responses for testing the LLM clients without a network.
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// Serve each canned HTTP response to one connection, in order. Returns the
+/// server's base URL.
+pub async fn serve(responses: Vec<String>) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        for response in responses {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 64 * 1024];
+            let _ = socket.read(&mut buf).await;
+            let _ = socket.write_all(response.as_bytes()).await;
+            let _ = socket.shutdown().await;
+        }
+    });
+    url
+}
+
+/// A `200 OK` server-sent event stream with one `data:` message per event.
+pub fn sse(events: &[serde_json::Value]) -> String {
+    let body: String = events.iter().map(|data| format!("data: {data}\n\n")).collect();
+    format!(
+        "HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+/// An Anthropic stream that calls `tool` with `input`, split into two
+/// `input_json_delta` fragments.
+pub fn anthropic_tool_call(id: &str, tool: &str, input: &serde_json::Value) -> String {
+    let json = input.to_string();
+    let (head, tail) = json.split_at(json.len() / 2);
+    sse(&[
+        serde_json::json!({"type":"message_start","message":{"id":"msg_1","usage":{"input_tokens":10}}}),
+        serde_json::json!({"type":"content_block_start","index":0,"content_block":{"type":"tool_use","id":id,"name":tool}}),
+        serde_json::json!({"type":"content_block_delta","index":0,"delta":{"type":"input_json_delta","partial_json":head}}),
+        serde_json::json!({"type":"content_block_delta","index":0,"delta":{"type":"input_json_delta","partial_json":tail}}),
+        serde_json::json!({"type":"content_block_stop","index":0}),
+        serde_json::json!({"type":"message_delta","delta":{"stop_reason":"tool_use"},"usage":{"output_tokens":5}}),
+        serde_json::json!({"type":"message_stop"}),
+    ])
+}
+
+/// An Anthropic stream that answers with `text`.
+pub fn anthropic_text(text: &str) -> String {
+    sse(&[
+        serde_json::json!({"type":"message_start","message":{"id":"msg_2","usage":{"input_tokens":10}}}),
+        serde_json::json!({"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}),
+        serde_json::json!({"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":text}}),
+        serde_json::json!({"type":"content_block_stop","index":0}),
+        serde_json::json!({"type":"message_delta","delta":{"stop_reason":"end_turn"},"usage":{"output_tokens":5}}),
+        serde_json::json!({"type":"message_stop"}),
+    ])
+}
+
+/// An OpenAI stream that calls `tool` with `input`, split into two argument
+/// fragments after the chunk that names the call.
+pub fn openai_tool_call(id: &str, tool: &str, input: &serde_json::Value) -> String {
+    let json = input.to_string();
+    let (head, tail) = json.split_at(json.len() / 2);
+    let delta = |tool_call: serde_json::Value| {
+        serde_json::json!({"id":"chatcmpl_1","choices":[{"delta":{"tool_calls":[tool_call]},"finish_reason":null}]})
+    };
+    sse(&[
+        delta(serde_json::json!({"index":0,"id":id,"function":{"name":tool,"arguments":""}})),
+        delta(serde_json::json!({"index":0,"function":{"arguments":head}})),
+        delta(serde_json::json!({"index":0,"function":{"arguments":tail}})),
+        serde_json::json!({"id":"chatcmpl_1","choices":[{"delta":{},"finish_reason":"tool_calls"}]}),
+        serde_json::json!({"id":"chatcmpl_1","choices":[],"usage":{"prompt_tokens":10,"completion_tokens":5}}),
+    ])
+}
+
+/// An OpenAI stream that answers with `text`.
+pub fn openai_text(text: &str) -> String {
+    sse(&[
+        serde_json::json!({"id":"chatcmpl_2","choices":[{"delta":{"content":text},"finish_reason":null}]}),
+        serde_json::json!({"id":"chatcmpl_2","choices":[{"delta":{},"finish_reason":"stop"}]}),
+        serde_json::json!({"id":"chatcmpl_2","choices":[],"usage":{"prompt_tokens":10,"completion_tokens":5}}),
+    ])
+}
@@ -9,6 +9,7 @@ impl AppOpen {
     }
 }
 
+#[async_trait::async_trait]
 impl super::ToolExecutor for AppOpen {
     fn name(&self) -> &str {
         "app_open"
@@ -32,7 +33,7 @@ impl super::ToolExecutor for AppOpen {
         })
     }
 
-    fn execute(&self, input: &serde_json::Value) -> Result<String> {
+    async fn execute(&self, input: &serde_json::Value) -> Result<String> {
         let target = input["target"]
             .as_str()
             .ok_or_else(|| anyhow::anyhow!("missing 'target' parameter"))?;
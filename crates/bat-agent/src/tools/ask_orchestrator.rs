@@ -13,6 +13,7 @@ impl AskOrchestrator {
     }
 }
 
+#[async_trait::async_trait]
 impl super::ToolExecutor for AskOrchestrator {
     fn name(&self) -> &str { "ask_orchestrator" }
 
@@ -42,7 +43,12 @@ impl super::ToolExecutor for AskOrchestrator {
         })
     }
 
-    fn execute(&self, input: &Value) -> Result<String> {
+    // Outlasts the gateway's 10-minute wait for an answer.
+    fn timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(660)
+    }
+
+    async fn execute(&self, input: &Value) -> Result<String> {
         let question = input.get("question")
             .and_then(|v| v.as_str())
             .ok_or_else(|| anyhow::anyhow!("Missing required 'question' parameter"))?;
@@ -61,7 +67,7 @@ impl super::ToolExecutor for AskOrchestrator {
             blocking,
         };
 
-        match self.bridge.request(action) {
+        match self.bridge.request(action).await {
             ProcessResult::OrchestratorAnswer { answer } => {
                 Ok(json!({
                     "status": "answered",
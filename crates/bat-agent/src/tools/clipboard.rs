@@ -7,6 +7,7 @@ impl Clipboard {
     pub fn new() -> Self { Self }
 }
 
+#[async_trait::async_trait]
 impl super::ToolExecutor for Clipboard {
     fn name(&self) -> &str { "clipboard" }
     fn description(&self) -> &str {
@@ -29,7 +30,7 @@ impl super::ToolExecutor for Clipboard {
             "required": ["action"]
         })
     }
-    fn execute(&self, input: &Value) -> Result<String> {
+    async fn execute(&self, input: &Value) -> Result<String> {
         let action = input.get("action")
             .and_then(|v| v.as_str())
             .ok_or_else(|| anyhow::anyhow!("Missing 'action' parameter"))?;
@@ -13,6 +13,7 @@ impl ExecKill {
     }
 }
 
+#[async_trait::async_trait]
 impl super::ToolExecutor for ExecKill {
     fn name(&self) -> &str {
         "exec_kill"
@@ -35,14 +36,14 @@ impl super::ToolExecutor for ExecKill {
         })
     }
 
-    fn execute(&self, input: &serde_json::Value) -> Result<String> {
+    async fn execute(&self, input: &serde_json::Value) -> Result<String> {
         let session_id = input["session_id"]
             .as_str()
             .ok_or_else(|| anyhow::anyhow!("missing 'session_id' parameter"))?;
 
         let result = self.bridge.request(ProcessAction::Kill {
             session_id: session_id.to_string(),
-        });
+        }).await;
 
         match result {
             ProcessResult::Killed => Ok(format!("Process {session_id} killed")),
@@ -13,6 +13,7 @@ impl ExecList {
     }
 }
 
+#[async_trait::async_trait]
 impl super::ToolExecutor for ExecList {
     fn name(&self) -> &str {
         "exec_list"
@@ -29,8 +30,12 @@ impl super::ToolExecutor for ExecList {
         })
     }
 
-    fn execute(&self, _input: &serde_json::Value) -> Result<String> {
-        let result = self.bridge.request(ProcessAction::List);
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    async fn execute(&self, _input: &serde_json::Value) -> Result<String> {
+        let result = self.bridge.request(ProcessAction::List).await;
 
         match result {
             ProcessResult::ProcessList { processes } => {
@@ -13,6 +13,7 @@ impl ExecOutput {
     }
 }
 
+#[async_trait::async_trait]
 impl super::ToolExecutor for ExecOutput {
     fn name(&self) -> &str {
         "exec_output"
@@ -36,14 +37,18 @@ impl super::ToolExecutor for ExecOutput {
         })
     }
 
-    fn execute(&self, input: &serde_json::Value) -> Result<String> {
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    async fn execute(&self, input: &serde_json::Value) -> Result<String> {
         let session_id = input["session_id"]
             .as_str()
             .ok_or_else(|| anyhow::anyhow!("missing 'session_id' parameter"))?;
 
         let result = self.bridge.request(ProcessAction::GetOutput {
             session_id: session_id.to_string(),
-        });
+        }).await;
 
         match result {
             ProcessResult::Output { stdout, stderr, is_running, exit_code, .. } => {
@@ -13,6 +13,7 @@ impl ExecRun {
     }
 }
 
+#[async_trait::async_trait]
 impl super::ToolExecutor for ExecRun {
     fn name(&self) -> &str {
         "exec_run"
@@ -45,7 +46,12 @@ impl super::ToolExecutor for ExecRun {
         })
     }
 
-    fn execute(&self, input: &serde_json::Value) -> Result<String> {
+    // Outlasts the gateway's 60-second limit on foreground commands.
+    fn timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(90)
+    }
+
+    async fn execute(&self, input: &serde_json::Value) -> Result<String> {
         let command = input["command"]
             .as_str()
             .ok_or_else(|| anyhow::anyhow!("missing 'command' parameter"))?;
@@ -57,7 +63,7 @@ impl super::ToolExecutor for ExecRun {
             command: command.to_string(),
             workdir,
             background,
-        });
+        }).await;
 
         match result {
             ProcessResult::Started { session_id } => {
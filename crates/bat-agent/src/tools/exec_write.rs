@@ -13,6 +13,7 @@ impl ExecWrite {
     }
 }
 
+#[async_trait::async_trait]
 impl super::ToolExecutor for ExecWrite {
     fn name(&self) -> &str {
         "exec_write"
@@ -39,7 +40,7 @@ impl super::ToolExecutor for ExecWrite {
         })
     }
 
-    fn execute(&self, input: &serde_json::Value) -> Result<String> {
+    async fn execute(&self, input: &serde_json::Value) -> Result<String> {
         let session_id = input["session_id"]
             .as_str()
             .ok_or_else(|| anyhow::anyhow!("missing 'session_id' parameter"))?;
@@ -50,7 +51,7 @@ impl super::ToolExecutor for ExecWrite {
         let result = self.bridge.request(ProcessAction::WriteStdin {
             session_id: session_id.to_string(),
             data: data.to_string(),
-        });
+        }).await;
 
         match result {
             ProcessResult::Written => Ok("Data written to stdin".to_string()),
@@ -0,0 +1,119 @@
+use anyhow::Result;
+use bat_types::policy::{strip_win_prefix, PathPolicy};
+use std::path::{Path, PathBuf};
+
+use crate::policy::{check_contents, locate};
+use super::fs_delete::trash_dir;
+use super::fs_move::{prepare_destination, transfer_paths};
+
+pub struct FsCopy {
+    policies: Vec<PathPolicy>,
+    /// Where a replaced destination goes.
+    trash: PathBuf,
+}
+
+impl FsCopy {
+    pub fn new(policies: Vec<PathPolicy>) -> Self {
+        Self { policies, trash: trash_dir() }
+    }
+}
+
+#[async_trait::async_trait]
+impl super::ToolExecutor for FsCopy {
+    fn name(&self) -> &str {
+        "fs_copy"
+    }
+
+    fn description(&self) -> &str {
+        "Copy a file or directory (recursively). If 'destination' is an existing directory, the copy is \
+         placed inside it. Missing parent directories are created. An existing destination is only replaced \
+         when 'overwrite' is true, and the replaced item goes to the trash."
+    }
+
+    fn input_schema(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "source": {
+                    "type": "string",
+                    "description": "Absolute path of the file or directory to copy"
+                },
+                "destination": {
+                    "type": "string",
+                    "description": "Absolute path of the copy, or an existing directory to copy into"
+                },
+                "overwrite": {
+                    "type": "boolean",
+                    "description": "Replace an existing destination (default false)"
+                }
+            },
+            "required": ["source", "destination"]
+        })
+    }
+
+    async fn execute(&self, input: &serde_json::Value) -> Result<String> {
+        let (source, destination) = transfer_paths(input)?;
+        let source = locate(&self.policies, Path::new(source), false)?;
+        let contents = source.contents()?;
+        check_contents(&self.policies, source.path(), &contents, false)?;
+        let target = prepare_destination(
+            &self.policies,
+            &source,
+            &contents,
+            destination,
+            input["overwrite"].as_bool(),
+            &self.trash,
+        )?;
+
+        let bytes = copy_recursive(&source.pinned(), &target.pinned()).map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to copy '{}' to '{}': {}",
+                strip_win_prefix(source.path()).display(),
+                strip_win_prefix(target.path()).display(),
+                e
+            )
+        })?;
+
+        Ok(format!(
+            "Copied {} ({} bytes) to {}",
+            strip_win_prefix(source.path()).display(),
+            bytes,
+            strip_win_prefix(target.path()).display()
+        ))
+    }
+}
+
+/// Copy a file, symlink or directory tree, returning the bytes copied.
+/// Symlinks are recreated rather than followed.
+pub(super) fn copy_recursive(from: &Path, to: &Path) -> std::io::Result<u64> {
+    let file_type = std::fs::symlink_metadata(from)?.file_type();
+    if file_type.is_symlink() {
+        copy_symlink(from, to)?;
+        Ok(0)
+    } else if file_type.is_dir() {
+        std::fs::create_dir(to)?;
+        let mut bytes = 0;
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            bytes += copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(bytes)
+    } else {
+        std::fs::copy(from, to)
+    }
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(std::fs::read_link(from)?, to)
+}
+
+#[cfg(windows)]
+fn copy_symlink(from: &Path, to: &Path) -> std::io::Result<()> {
+    let link = std::fs::read_link(from)?;
+    if std::fs::metadata(from).is_ok_and(|m| m.is_dir()) {
+        std::os::windows::fs::symlink_dir(link, to)
+    } else {
+        std::os::windows::fs::symlink_file(link, to)
+    }
+}
@@ -0,0 +1,157 @@
+use anyhow::Result;
+use bat_types::policy::{strip_win_prefix, PathPolicy};
+use std::path::{Path, PathBuf};
+
+use crate::policy::{check_contents, locate, Entry};
+
+pub struct FsDelete {
+    policies: Vec<PathPolicy>,
+    /// Where deleted items go.
+    trash: PathBuf,
+}
+
+impl FsDelete {
+    pub fn new(policies: Vec<PathPolicy>) -> Self {
+        Self { policies, trash: trash_dir() }
+    }
+}
+
+#[async_trait::async_trait]
+impl super::ToolExecutor for FsDelete {
+    fn name(&self) -> &str {
+        "fs_delete"
+    }
+
+    fn description(&self) -> &str {
+        "Delete a file or directory (with its contents) by moving it to the Batchismo trash \
+         (~/.batchismo/trash), from where the user can recover it. Returns the trash location."
+    }
+
+    fn input_schema(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "Absolute path of the file or directory to delete"
+                }
+            },
+            "required": ["path"]
+        })
+    }
+
+    async fn execute(&self, input: &serde_json::Value) -> Result<String> {
+        let path_str = input["path"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("missing 'path' parameter"))?;
+
+        let entry = locate(&self.policies, Path::new(path_str), true)?;
+        check_contents(&self.policies, entry.path(), &entry.contents()?, true)?;
+
+        let trashed = move_to_trash(&entry, &self.trash)?;
+        Ok(format!(
+            "Moved {} to the trash at {}",
+            strip_win_prefix(entry.path()).display(),
+            strip_win_prefix(&trashed).display()
+        ))
+    }
+}
+
+/// `~/.batchismo/trash/`
+pub(super) fn trash_dir() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".batchismo")
+        .join("trash")
+}
+
+/// Move `entry` into a fresh folder under `trash`, next to an `info.json`
+/// recording where it came from. Returns its new location.
+pub(super) fn move_to_trash(entry: &Entry, trash: &Path) -> Result<PathBuf> {
+    let path = entry.path();
+    let name = path
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("Refusing to delete '{}'", strip_win_prefix(path).display()))?;
+    let now = chrono::Utc::now();
+    let id = uuid::Uuid::new_v4().simple().to_string();
+    let slot = trash.join(format!("{}-{}", now.format("%Y%m%d-%H%M%S"), &id[..8]));
+    std::fs::create_dir_all(&slot)
+        .map_err(|e| anyhow::anyhow!("Failed to create trash folder '{}': {}", slot.display(), e))?;
+
+    let info = serde_json::json!({
+        "original_path": strip_win_prefix(path),
+        "deleted_at": now.to_rfc3339(),
+    });
+    std::fs::write(slot.join("info.json"), serde_json::to_string_pretty(&info)?)?;
+
+    let target = slot.join(name);
+    super::fs_move::rename_or_copy(&entry.pinned(), &target)
+        .map_err(|e| anyhow::anyhow!("Failed to move '{}' to the trash: {}", strip_win_prefix(path).display(), e))?;
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::ToolExecutor;
+    use bat_types::policy::AccessLevel;
+
+    fn policy(path: &Path, access: AccessLevel) -> PathPolicy {
+        PathPolicy { id: None, path: path.to_path_buf(), access, recursive: true, description: None, layer: 0 }
+    }
+
+    /// The single folder `fs_delete` created under `trash`.
+    fn only_slot(trash: &Path) -> PathBuf {
+        let slots: Vec<_> = std::fs::read_dir(trash).unwrap().map(|e| e.unwrap().path()).collect();
+        assert_eq!(slots.len(), 1, "{slots:?}");
+        slots.into_iter().next().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_delete_moves_to_trash() {
+        let dir = std::env::temp_dir().join(format!("bat-fs-delete-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("work/reports")).unwrap();
+        let dir = dir.canonicalize().unwrap();
+        std::fs::write(dir.join("work/reports/q1.md"), "q1").unwrap();
+        let trash = dir.join("trash");
+        let tool = FsDelete { policies: vec![policy(&dir.join("work"), AccessLevel::ReadWrite)], trash: trash.clone() };
+
+        let before = chrono::Utc::now();
+        let out = tool.execute(&serde_json::json!({ "path": dir.join("work/reports") })).await.unwrap();
+        assert!(!dir.join("work/reports").exists());
+
+        // One `<timestamp>-<id>` folder holding the directory and its info.json.
+        let slot = only_slot(&trash);
+        let slot_name = slot.file_name().unwrap().to_string_lossy().into_owned();
+        assert!(chrono::NaiveDateTime::parse_from_str(&slot_name[..15], "%Y%m%d-%H%M%S").is_ok(), "{slot_name}");
+        assert_eq!(slot_name.len(), 15 + 1 + 8, "{slot_name}");
+        assert_eq!(std::fs::read_to_string(slot.join("reports/q1.md")).unwrap(), "q1");
+        assert!(out.ends_with(&format!("at {}", strip_win_prefix(&slot.join("reports")).display())), "{out}");
+
+        let info: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(slot.join("info.json")).unwrap()).unwrap();
+        assert_eq!(info["original_path"], serde_json::json!(strip_win_prefix(&dir.join("work/reports"))));
+        let deleted_at = chrono::DateTime::parse_from_rfc3339(info["deleted_at"].as_str().unwrap()).unwrap();
+        assert!(deleted_at >= before - chrono::Duration::seconds(1) && deleted_at <= chrono::Utc::now());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_delete_needs_write_access() {
+        let dir = std::env::temp_dir().join(format!("bat-fs-delete-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("readonly")).unwrap();
+        let dir = dir.canonicalize().unwrap();
+        std::fs::write(dir.join("readonly/keep.txt"), "keep").unwrap();
+        std::fs::write(dir.join("outside.txt"), "outside").unwrap();
+        let trash = dir.join("trash");
+        let tool = FsDelete { policies: vec![policy(&dir.join("readonly"), AccessLevel::ReadOnly)], trash: trash.clone() };
+
+        for path in [dir.join("readonly/keep.txt"), dir.join("outside.txt")] {
+            let err = tool.execute(&serde_json::json!({ "path": path })).await.unwrap_err();
+            assert!(err.to_string().contains("Access denied"), "{err}");
+            assert!(path.exists());
+        }
+        assert!(!trash.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,387 @@
+use anyhow::{bail, Result};
+use bat_types::memory::{line_diff, DiffKind};
+use bat_types::policy::{strip_win_prefix, PathPolicy};
+use std::io::{Read, Seek, Write};
+use std::path::Path;
+
+use crate::policy::{open, OpenMode};
+
+/// Unchanged lines shown around each change in the returned diff.
+const DIFF_CONTEXT: usize = 2;
+
+pub struct FsEdit {
+    policies: Vec<PathPolicy>,
+}
+
+impl FsEdit {
+    pub fn new(policies: Vec<PathPolicy>) -> Self {
+        Self { policies }
+    }
+}
+
+#[async_trait::async_trait]
+impl super::ToolExecutor for FsEdit {
+    fn name(&self) -> &str {
+        "fs_edit"
+    }
+
+    fn description(&self) -> &str {
+        "Edit an existing file in place, without rewriting all of it. Either give 'edits', a list of exact \
+         search-and-replace pairs applied in order, or 'diff', a unified diff. Each 'old_text' must match \
+         exactly once (include surrounding lines to disambiguate) unless 'replace_all' is set. Returns the diff \
+         of the change."
+    }
+
+    fn input_schema(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "Absolute path of the file to edit"
+                },
+                "edits": {
+                    "type": "array",
+                    "description": "Replacements applied in order, each to the result of the previous one",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "old_text": {
+                                "type": "string",
+                                "description": "Exact text to find, including whitespace and indentation"
+                            },
+                            "new_text": {
+                                "type": "string",
+                                "description": "Text to put in its place"
+                            },
+                            "replace_all": {
+                                "type": "boolean",
+                                "description": "Replace every occurrence instead of requiring exactly one (default false)"
+                            }
+                        },
+                        "required": ["old_text", "new_text"]
+                    }
+                },
+                "diff": {
+                    "type": "string",
+                    "description": "Unified diff to apply instead of 'edits' (hunks starting with @@)"
+                }
+            },
+            "required": ["path"]
+        })
+    }
+
+    async fn execute(&self, input: &serde_json::Value) -> Result<String> {
+        let path_str = input["path"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("missing 'path' parameter"))?;
+
+        let file = open(&self.policies, Path::new(path_str), OpenMode::Modify)?;
+        let path = file.path();
+
+        let mut old = String::new();
+        file.file()
+            .read_to_string(&mut old)
+            .map_err(|e| anyhow::anyhow!("Failed to read '{}': {}", strip_win_prefix(path).display(), e))?;
+
+        let new = match (input.get("edits"), input["diff"].as_str()) {
+            (Some(_), Some(_)) => bail!("Give either 'edits' or 'diff', not both"),
+            (Some(edits), None) => apply_replacements(&old, &parse_replacements(edits)?)?,
+            (None, Some(diff)) => apply_unified_diff(&old, diff)?,
+            (None, None) => bail!("missing 'edits' or 'diff' parameter"),
+        };
+
+        if new == old {
+            return Ok(format!("No changes to {}", strip_win_prefix(path).display()));
+        }
+
+        let mut handle = file.file();
+        handle
+            .set_len(0)
+            .and_then(|_| handle.rewind())
+            .and_then(|_| handle.write_all(new.as_bytes()))
+            .map_err(|e| anyhow::anyhow!("Failed to write '{}': {}", strip_win_prefix(path).display(), e))?;
+
+        Ok(format!(
+            "Successfully edited {}\n\n{}",
+            strip_win_prefix(path).display(),
+            render_diff(&old, &new)
+        ))
+    }
+}
+
+struct Replacement {
+    old_text: String,
+    new_text: String,
+    replace_all: bool,
+}
+
+fn parse_replacements(edits: &serde_json::Value) -> Result<Vec<Replacement>> {
+    let edits = edits
+        .as_array()
+        .ok_or_else(|| anyhow::anyhow!("'edits' must be an array"))?;
+    if edits.is_empty() {
+        bail!("'edits' is empty");
+    }
+    edits
+        .iter()
+        .enumerate()
+        .map(|(i, edit)| {
+            let field = |name: &str| {
+                edit[name]
+                    .as_str()
+                    .map(String::from)
+                    .ok_or_else(|| anyhow::anyhow!("Edit {}: missing '{}'", i + 1, name))
+            };
+            Ok(Replacement {
+                old_text: field("old_text")?,
+                new_text: field("new_text")?,
+                replace_all: edit["replace_all"].as_bool().unwrap_or(false),
+            })
+        })
+        .collect()
+}
+
+/// Apply each replacement to the result of the previous one. Fails without
+/// changing anything if any `old_text` is missing, or matches more than once
+/// without `replace_all`.
+fn apply_replacements(content: &str, edits: &[Replacement]) -> Result<String> {
+    let mut content = content.to_string();
+    for (i, edit) in edits.iter().enumerate() {
+        if edit.old_text.is_empty() {
+            bail!("Edit {}: 'old_text' is empty", i + 1);
+        }
+        let count = content.matches(edit.old_text.as_str()).count();
+        match count {
+            0 => bail!(
+                "Edit {}: 'old_text' not found. It must match the file exactly, including whitespace: {:?}",
+                i + 1,
+                preview(&edit.old_text)
+            ),
+            1 => content = content.replacen(&edit.old_text, &edit.new_text, 1),
+            _ if edit.replace_all => content = content.replace(&edit.old_text, &edit.new_text),
+            _ => bail!(
+                "Edit {}: 'old_text' matches {} places. Include more surrounding lines to pick one, \
+                 or set 'replace_all': {:?}",
+                i + 1,
+                count,
+                preview(&edit.old_text)
+            ),
+        }
+    }
+    Ok(content)
+}
+
+/// One `@@` section of a unified diff.
+struct Hunk {
+    /// 1-based line the hunk claims to start at in the original file.
+    old_start: usize,
+    /// Context and removed lines, as they must appear in the file.
+    old_lines: Vec<String>,
+    /// Context and added lines, as they replace `old_lines`.
+    new_lines: Vec<String>,
+}
+
+fn parse_unified_diff(diff: &str) -> Result<Vec<Hunk>> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    for line in diff.lines() {
+        if let Some(header) = line.strip_prefix("@@") {
+            let old_start = header
+                .split_whitespace()
+                .find_map(|part| part.strip_prefix('-'))
+                .and_then(|range| range.split(',').next())
+                .and_then(|start| start.parse().ok())
+                .ok_or_else(|| anyhow::anyhow!("Invalid hunk header: {line}"))?;
+            hunks.push(Hunk { old_start, old_lines: vec![], new_lines: vec![] });
+            continue;
+        }
+        // File headers and anything else before the first hunk.
+        let Some(hunk) = hunks.last_mut() else { continue };
+        if line.starts_with('\\') {
+            // "\ No newline at end of file"
+            continue;
+        }
+        match line.split_at_checked(1) {
+            Some(("-", rest)) => hunk.old_lines.push(rest.to_string()),
+            Some(("+", rest)) => hunk.new_lines.push(rest.to_string()),
+            Some((" ", rest)) => {
+                hunk.old_lines.push(rest.to_string());
+                hunk.new_lines.push(rest.to_string());
+            }
+            // Some tools strip the space from blank context lines.
+            None if line.is_empty() => {
+                hunk.old_lines.push(String::new());
+                hunk.new_lines.push(String::new());
+            }
+            _ => bail!("Invalid diff line (expected ' ', '-' or '+'): {line}"),
+        }
+    }
+    if hunks.is_empty() {
+        bail!("'diff' has no hunks (sections starting with @@)");
+    }
+    Ok(hunks)
+}
+
+/// Apply a unified diff. Each hunk must match the file exactly; if its lines
+/// occur more than once, the occurrence at the line the header names wins,
+/// otherwise the hunk is ambiguous. Line endings of the file are preserved.
+fn apply_unified_diff(content: &str, diff: &str) -> Result<String> {
+    let hunks = parse_unified_diff(diff)?;
+    let eol = if content.contains("\r\n") { "\r\n" } else { "\n" };
+    let trailing_newline = content.ends_with('\n');
+    let mut lines: Vec<String> = content.lines().map(String::from).collect();
+
+    // Hunks apply in order; `cursor` is where the next one may start and
+    // `shift` how far earlier hunks moved the header line numbers.
+    let mut cursor = 0;
+    let mut shift: isize = 0;
+    for (i, hunk) in hunks.iter().enumerate() {
+        let hint = (hunk.old_start.saturating_sub(1) as isize + shift).max(0) as usize;
+        let at = if hunk.old_lines.is_empty() {
+            hint.min(lines.len())
+        } else {
+            let len = hunk.old_lines.len();
+            let matches: Vec<usize> = (cursor..=lines.len().saturating_sub(len))
+                .filter(|&start| start + len <= lines.len() && lines[start..start + len] == hunk.old_lines[..])
+                .collect();
+            match matches.as_slice() {
+                [] => bail!(
+                    "Hunk {} does not match the file (expected near line {}): {:?}",
+                    i + 1,
+                    hunk.old_start,
+                    preview(&hunk.old_lines.join("\n"))
+                ),
+                [only] => *only,
+                _ if matches.contains(&hint) => hint,
+                _ => bail!(
+                    "Hunk {} matches {} places in the file and none at line {}. Add more context lines",
+                    i + 1,
+                    matches.len(),
+                    hunk.old_start
+                ),
+            }
+        };
+        let removed = hunk.old_lines.len();
+        lines.splice(at..at + removed, hunk.new_lines.iter().cloned());
+        cursor = at + hunk.new_lines.len();
+        shift += hunk.new_lines.len() as isize - removed as isize;
+    }
+
+    let mut out = lines.join(eol);
+    if trailing_newline && !out.is_empty() {
+        out.push_str(eol);
+    }
+    Ok(out)
+}
+
+/// Render the change as `-`/`+` lines with a little context around each
+/// change; runs of unchanged lines in between are elided.
+fn render_diff(old: &str, new: &str) -> String {
+    let diff = line_diff(old, new);
+    let changed: Vec<usize> = diff
+        .iter()
+        .enumerate()
+        .filter(|(_, d)| d.kind != DiffKind::Context)
+        .map(|(i, _)| i)
+        .collect();
+    let near_change = |i: usize| {
+        changed
+            .iter()
+            .any(|&c| i + DIFF_CONTEXT >= c && i <= c + DIFF_CONTEXT)
+    };
+
+    let mut out = Vec::new();
+    let mut elided = false;
+    for (i, line) in diff.iter().enumerate() {
+        if !near_change(i) {
+            elided = true;
+            continue;
+        }
+        if elided && !out.is_empty() {
+            out.push("  ...".to_string());
+        }
+        elided = false;
+        let marker = match line.kind {
+            DiffKind::Added => '+',
+            DiffKind::Removed => '-',
+            DiffKind::Context => ' ',
+        };
+        out.push(format!("{marker} {}", line.content));
+    }
+    if out.is_empty() {
+        // Only line endings or trailing whitespace changed.
+        return "(whitespace-only change)".to_string();
+    }
+    out.join("\n")
+}
+
+/// First line of `text`, shortened, for error messages.
+fn preview(text: &str) -> String {
+    let first = text.lines().next().unwrap_or_default();
+    if first.chars().count() > 80 {
+        format!("{}...", first.chars().take(80).collect::<String>())
+    } else if text.lines().nth(1).is_some() {
+        format!("{first}...")
+    } else {
+        first.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::ToolExecutor;
+    use bat_types::policy::AccessLevel;
+
+    fn replace(old_text: &str, new_text: &str) -> Replacement {
+        Replacement { old_text: old_text.to_string(), new_text: new_text.to_string(), replace_all: false }
+    }
+
+    #[test]
+    fn test_replacements_require_a_unique_match() {
+        let content = "fn a() {}\nfn b() {}\nfn a() {}\n";
+        let err = apply_replacements(content, &[replace("fn c", "fn d")]).unwrap_err();
+        assert!(err.to_string().contains("not found"), "{err}");
+        let err = apply_replacements(content, &[replace("fn a() {}", "fn x() {}")]).unwrap_err();
+        assert!(err.to_string().contains("matches 2 places"), "{err}");
+
+        let all = Replacement { replace_all: true, ..replace("fn a", "fn x") };
+        let out = apply_replacements(content, &[all, replace("fn b", "fn y")]).unwrap();
+        assert_eq!(out, "fn x() {}\nfn y() {}\nfn x() {}\n");
+    }
+
+    #[test]
+    fn test_unified_diff_uses_header_line_to_disambiguate() {
+        let content = "x\r\nend\r\ny\r\nend\r\n";
+        let diff = "--- a/f\n+++ b/f\n@@ -3,2 +3,3 @@\n y\n-end\n+done\n+end\n";
+        assert_eq!(apply_unified_diff(content, diff).unwrap(), "x\r\nend\r\ny\r\ndone\r\nend\r\n");
+
+        let err = apply_unified_diff(content, "@@ -9 +9 @@\n-end\n+done\n").unwrap_err();
+        assert!(err.to_string().contains("matches 2 places"), "{err}");
+        let err = apply_unified_diff(content, "@@ -1 +1 @@\n-z\n+w\n").unwrap_err();
+        assert!(err.to_string().contains("does not match"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_edit_checks_write_access_and_returns_diff() {
+        let dir = std::env::temp_dir().join(format!("bat-fs-edit-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let dir = dir.canonicalize().unwrap();
+        let file = dir.join("notes.md");
+        std::fs::write(&file, "# Notes\n\n- one\n- two\n").unwrap();
+        let input = serde_json::json!({
+            "path": file,
+            "edits": [{ "old_text": "- two", "new_text": "- 2" }],
+        });
+        let policy = |access| PathPolicy { id: None, path: dir.clone(), access, recursive: true, description: None, layer: 0 };
+
+        let read_only = FsEdit::new(vec![policy(AccessLevel::ReadOnly)]);
+        assert!(read_only.execute(&input).await.unwrap_err().to_string().contains("Access denied"));
+
+        let tool = FsEdit::new(vec![policy(AccessLevel::ReadWrite)]);
+        let out = tool.execute(&input).await.unwrap();
+        assert!(out.contains("- - two\n+ - 2"), "{out}");
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "# Notes\n\n- one\n- 2\n");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,5 +1,6 @@
 use anyhow::{bail, Result};
-use bat_types::policy::{check_access, strip_win_prefix, PathPolicy};
+use crate::policy::{open, OpenMode};
+use bat_types::policy::{strip_win_prefix, PathPolicy};
 use std::path::Path;
 
 pub struct FsList {
@@ -12,6 +13,7 @@ impl FsList {
     }
 }
 
+#[async_trait::async_trait]
 impl super::ToolExecutor for FsList {
     fn name(&self) -> &str {
         "fs_list"
@@ -34,28 +36,22 @@ impl super::ToolExecutor for FsList {
         })
     }
 
-    fn execute(&self, input: &serde_json::Value) -> Result<String> {
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    async fn execute(&self, input: &serde_json::Value) -> Result<String> {
         let path_str = input["path"]
             .as_str()
             .ok_or_else(|| anyhow::anyhow!("missing 'path' parameter"))?;
 
-        let path = Path::new(path_str)
-            .canonicalize()
-            .map_err(|e| anyhow::anyhow!("Cannot resolve path '{}': {}", path_str, e))?;
-
-        if !check_access(&self.policies, &path, false) {
-            bail!(
-                "Access denied: '{}' is not in any allowed read policy",
-                strip_win_prefix(&path).display()
-            );
-        }
-
-        if !path.is_dir() {
-            bail!("'{}' is not a directory", strip_win_prefix(&path).display());
+        let dir = open(&self.policies, Path::new(path_str), OpenMode::Read)?;
+        if !dir.file().metadata()?.is_dir() {
+            bail!("'{}' is not a directory", strip_win_prefix(dir.path()).display());
         }
 
         let mut entries = Vec::new();
-        for entry in std::fs::read_dir(&path)? {
+        for entry in dir.read_dir()? {
             let entry = entry?;
             let metadata = entry.metadata()?;
             let modified = metadata
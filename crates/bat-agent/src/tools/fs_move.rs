@@ -0,0 +1,293 @@
+use anyhow::{bail, Result};
+use bat_types::policy::{strip_win_prefix, PathPolicy};
+use std::path::{Path, PathBuf};
+
+use super::fs_delete::{move_to_trash, trash_dir};
+use crate::policy::{check_contents, locate, locate_new, Entry};
+
+pub struct FsMove {
+    policies: Vec<PathPolicy>,
+    /// Where a replaced destination goes.
+    trash: PathBuf,
+}
+
+impl FsMove {
+    pub fn new(policies: Vec<PathPolicy>) -> Self {
+        Self { policies, trash: trash_dir() }
+    }
+}
+
+#[async_trait::async_trait]
+impl super::ToolExecutor for FsMove {
+    fn name(&self) -> &str {
+        "fs_move"
+    }
+
+    fn description(&self) -> &str {
+        "Move or rename a file or directory. If 'destination' is an existing directory, the source is moved \
+         into it. Missing parent directories are created. An existing destination is only replaced when \
+         'overwrite' is true, and the replaced item goes to the trash."
+    }
+
+    fn input_schema(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "source": {
+                    "type": "string",
+                    "description": "Absolute path of the file or directory to move"
+                },
+                "destination": {
+                    "type": "string",
+                    "description": "Absolute path to move it to, or an existing directory to move it into"
+                },
+                "overwrite": {
+                    "type": "boolean",
+                    "description": "Replace an existing destination (default false)"
+                }
+            },
+            "required": ["source", "destination"]
+        })
+    }
+
+    async fn execute(&self, input: &serde_json::Value) -> Result<String> {
+        let (source, destination) = transfer_paths(input)?;
+        // Moving removes the source, so it needs write access, not just read.
+        let source = locate(&self.policies, Path::new(source), true)?;
+        let contents = source.contents()?;
+        check_contents(&self.policies, source.path(), &contents, true)?;
+        let target = prepare_destination(
+            &self.policies,
+            &source,
+            &contents,
+            destination,
+            input["overwrite"].as_bool(),
+            &self.trash,
+        )?;
+
+        rename_or_copy(&source.pinned(), &target.pinned()).map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to move '{}' to '{}': {}",
+                strip_win_prefix(source.path()).display(),
+                strip_win_prefix(target.path()).display(),
+                e
+            )
+        })?;
+
+        Ok(format!(
+            "Moved {} to {}",
+            strip_win_prefix(source.path()).display(),
+            strip_win_prefix(target.path()).display()
+        ))
+    }
+}
+
+/// The `source` and `destination` parameters of a move or copy.
+pub(super) fn transfer_paths(input: &serde_json::Value) -> Result<(&str, &str)> {
+    let source = input["source"]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("missing 'source' parameter"))?;
+    let destination = input["destination"]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("missing 'destination' parameter"))?;
+    Ok((source, destination))
+}
+
+/// Work out where `source` lands for `destination`, check write access
+/// there and to where `contents` (the source's, from `Entry::contents`) will
+/// land, create missing parent directories and clear the way when
+/// overwriting (the replaced item goes to `trash`). Nothing is created
+/// until access has been checked.
+pub(super) fn prepare_destination(
+    policies: &[PathPolicy],
+    source: &Entry,
+    contents: &[PathBuf],
+    destination: &str,
+    overwrite: Option<bool>,
+    trash: &Path,
+) -> Result<Entry> {
+    let source_path = source.path();
+    let mut destination = PathBuf::from(destination);
+    if std::fs::symlink_metadata(&destination).is_ok_and(|m| m.is_dir()) {
+        let name = source_path
+            .file_name()
+            .ok_or_else(|| anyhow::anyhow!("Invalid source '{}'", strip_win_prefix(source_path).display()))?;
+        destination.push(name);
+    }
+    let mut target = locate_new(policies, &destination)?;
+
+    if target.path() == source_path {
+        bail!("Source and destination are the same: '{}'", strip_win_prefix(source_path).display());
+    }
+    if target.path().starts_with(source_path) {
+        bail!(
+            "Cannot put '{}' inside itself ('{}')",
+            strip_win_prefix(source_path).display(),
+            strip_win_prefix(target.path()).display()
+        );
+    }
+
+    check_contents(policies, target.path(), contents, true)?;
+
+    if target.exists() {
+        if !overwrite.unwrap_or(false) {
+            bail!(
+                "'{}' already exists. Set 'overwrite' to replace it (the old one goes to the trash)",
+                strip_win_prefix(target.path()).display()
+            );
+        }
+        check_contents(policies, target.path(), &target.contents()?, true)?;
+        move_to_trash(&target, trash)?;
+    }
+    target.create_parents()?;
+    Ok(target)
+}
+
+/// Rename `from` to `to`, falling back to copy-and-delete when they are on
+/// different filesystems.
+pub(super) fn rename_or_copy(from: &Path, to: &Path) -> std::io::Result<()> {
+    match std::fs::rename(from, to) {
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            super::fs_copy::copy_recursive(from, to)?;
+            if std::fs::symlink_metadata(from)?.is_dir() {
+                std::fs::remove_dir_all(from)
+            } else {
+                std::fs::remove_file(from)
+            }
+        }
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::ToolExecutor;
+    use bat_types::policy::AccessLevel;
+
+    #[tokio::test]
+    async fn test_move_and_copy() {
+        let dir = std::env::temp_dir().join(format!("bat-fs-move-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("inbox")).unwrap();
+        std::fs::create_dir_all(dir.join("readonly")).unwrap();
+        let dir = dir.canonicalize().unwrap();
+        std::fs::write(dir.join("inbox/a.txt"), "a").unwrap();
+        std::fs::write(dir.join("readonly/r.txt"), "r").unwrap();
+        let policies = vec![
+            PathPolicy { id: None, path: dir.join("inbox"), access: AccessLevel::ReadWrite, recursive: true, description: None, layer: 0 },
+            PathPolicy { id: None, path: dir.join("archive"), access: AccessLevel::ReadWrite, recursive: true, description: None, layer: 0 },
+            PathPolicy { id: None, path: dir.join("readonly"), access: AccessLevel::ReadOnly, recursive: true, description: None, layer: 0 },
+        ];
+        let mv = FsMove::new(policies.clone());
+        let cp = super::super::fs_copy::FsCopy::new(policies.clone());
+
+        // Moving into a missing directory creates it.
+        let to = dir.join("archive/2024/a.txt");
+        mv.execute(&serde_json::json!({ "source": dir.join("inbox/a.txt"), "destination": to })).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&to).unwrap(), "a");
+        assert!(!dir.join("inbox/a.txt").exists());
+
+        // Read-only sources can be copied but not moved; nothing leaves the allowed paths.
+        let r = dir.join("readonly/r.txt");
+        let err = mv.execute(&serde_json::json!({ "source": r, "destination": dir.join("inbox") })).await.unwrap_err();
+        assert!(err.to_string().contains("Access denied"), "{err}");
+        cp.execute(&serde_json::json!({ "source": r, "destination": dir.join("inbox") })).await.unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join("inbox/r.txt")).unwrap(), "r");
+        let err = cp.execute(&serde_json::json!({ "source": r, "destination": dir.join("elsewhere/r.txt") })).await.unwrap_err();
+        assert!(err.to_string().contains("Access denied"), "{err}");
+        assert!(!dir.join("elsewhere").exists());
+
+        // Existing destinations are kept unless overwriting.
+        let err = cp.execute(&serde_json::json!({ "source": r, "destination": dir.join("inbox") })).await.unwrap_err();
+        assert!(err.to_string().contains("already exists"), "{err}");
+        let err = mv.execute(&serde_json::json!({ "source": dir.join("archive"), "destination": dir.join("archive/2024") })).await.unwrap_err();
+        assert!(err.to_string().contains("inside itself"), "{err}");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_overwrite_sends_destination_to_trash() {
+        let dir = std::env::temp_dir().join(format!("bat-fs-move-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("work")).unwrap();
+        let dir = dir.canonicalize().unwrap();
+        std::fs::write(dir.join("work/new.txt"), "new").unwrap();
+        std::fs::write(dir.join("work/old.txt"), "old").unwrap();
+        let trash = dir.join("trash");
+        let policies =
+            vec![PathPolicy { id: None, path: dir.join("work"), access: AccessLevel::ReadWrite, recursive: true, description: None, layer: 0 }];
+        let mv = FsMove { policies, trash: trash.clone() };
+
+        let input = serde_json::json!({ "source": dir.join("work/new.txt"), "destination": dir.join("work/old.txt") });
+        mv.execute(&input).await.unwrap_err();
+        assert!(!trash.exists());
+
+        let mut input = input;
+        input["overwrite"] = true.into();
+        mv.execute(&input).await.unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join("work/old.txt")).unwrap(), "new");
+        assert!(!dir.join("work/new.txt").exists());
+
+        let slots: Vec<_> = std::fs::read_dir(&trash).unwrap().map(|e| e.unwrap().path()).collect();
+        assert_eq!(slots.len(), 1, "{slots:?}");
+        assert_eq!(std::fs::read_to_string(slots[0].join("old.txt")).unwrap(), "old");
+        let info: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(slots[0].join("info.json")).unwrap()).unwrap();
+        assert_eq!(info["original_path"], serde_json::json!(strip_win_prefix(&dir.join("work/old.txt"))));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_deny_rules_inside_a_directory_are_honoured() {
+        let dir = std::env::temp_dir().join(format!("bat-fs-move-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("Projects/app/secrets/keys")).unwrap();
+        std::fs::create_dir_all(dir.join("inbox/bundle/secrets")).unwrap();
+        let dir = dir.canonicalize().unwrap();
+        std::fs::write(dir.join("Projects/app/main.rs"), "fn main() {}").unwrap();
+        std::fs::write(dir.join("Projects/app/secrets/keys/id"), "key").unwrap();
+        std::fs::write(dir.join("inbox/bundle/secrets/token"), "token").unwrap();
+        let trash = dir.join("trash");
+        let policies = vec![
+            PathPolicy { id: None, path: dir.join("Projects"), access: AccessLevel::ReadWrite, recursive: true, description: None, layer: 0 },
+            PathPolicy { id: None, path: dir.join("inbox"), access: AccessLevel::ReadWrite, recursive: true, description: None, layer: 0 },
+            PathPolicy { id: None, path: dir.join("backup"), access: AccessLevel::ReadWrite, recursive: true, description: None, layer: 0 },
+            PathPolicy { id: None, path: dir.join("Projects/*/secrets/**"), access: AccessLevel::Deny, recursive: true, description: None, layer: 0 },
+        ];
+        let mv = FsMove { policies: policies.clone(), trash: trash.clone() };
+        let cp = super::super::fs_copy::FsCopy::new(policies.clone());
+        let rm = super::super::fs_delete::FsDelete::new(policies);
+        let app = dir.join("Projects/app");
+
+        // Nothing that would read, move or remove the denied files goes ahead.
+        let attempts = [
+            cp.execute(&serde_json::json!({ "source": app, "destination": dir.join("backup/app") })).await,
+            mv.execute(&serde_json::json!({ "source": app, "destination": dir.join("backup/app") })).await,
+            rm.execute(&serde_json::json!({ "path": app })).await,
+            // Nor can a copy or move land files where they are denied.
+            cp.execute(&serde_json::json!({ "source": dir.join("inbox/bundle"), "destination": dir.join("Projects") })).await,
+            mv.execute(&serde_json::json!({ "source": dir.join("inbox/bundle"), "destination": dir.join("Projects") })).await,
+        ];
+        for attempt in attempts {
+            let err = attempt.unwrap_err();
+            assert!(err.to_string().contains("blocked by the deny rule"), "{err}");
+        }
+        assert_eq!(std::fs::read_to_string(app.join("secrets/keys/id")).unwrap(), "key");
+        assert!(dir.join("inbox/bundle/secrets/token").exists());
+        assert!(!dir.join("backup").exists());
+        assert!(!dir.join("Projects/bundle").exists());
+        assert!(!trash.exists());
+
+        // Replacing a directory with denied contents is refused too.
+        std::fs::write(dir.join("inbox/app"), "not a directory").unwrap();
+        let err = mv
+            .execute(&serde_json::json!({ "source": dir.join("inbox/app"), "destination": dir.join("Projects"), "overwrite": true }))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("blocked by the deny rule"), "{err}");
+        assert!(app.join("main.rs").exists());
+        assert!(!trash.exists());
+
+        // What the rule doesn't cover is unaffected.
+        cp.execute(&serde_json::json!({ "source": app.join("main.rs"), "destination": dir.join("backup/main.rs") })).await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
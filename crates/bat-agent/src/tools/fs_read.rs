@@ -1,7 +1,22 @@
 use anyhow::{bail, Result};
-use bat_types::policy::{check_access, strip_win_prefix, PathPolicy};
+use crate::policy::{open, OpenMode};
+use bat_types::policy::{strip_win_prefix, PathPolicy};
+use encoding_rs::Encoding;
+use std::io::{Read, Seek, SeekFrom};
+use std::fs::File;
 use std::path::Path;
 
+/// Most text returned by one call; longer reads stop at a line boundary and
+/// say where to continue.
+const MAX_OUTPUT_BYTES: usize = 100_000;
+/// Lines returned when no `limit` is given.
+const DEFAULT_LINE_LIMIT: usize = 2000;
+/// Line-based reads scan the file from the start; beyond this size a byte
+/// range is required.
+const MAX_LINE_READ_BYTES: u64 = 64 * 1024 * 1024;
+/// How much of the file is inspected to tell text from binary.
+const SNIFF_BYTES: usize = 8192;
+
 pub struct FsRead {
     policies: Vec<PathPolicy>,
 }
@@ -12,13 +27,17 @@ impl FsRead {
     }
 }
 
+#[async_trait::async_trait]
 impl super::ToolExecutor for FsRead {
     fn name(&self) -> &str {
         "fs_read"
     }
 
     fn description(&self) -> &str {
-        "Read the contents of a file. Returns the file content as text."
+        "Read a text file. Returns numbered lines ('   12\\tcontent'; the number and tab are not part of the \
+         file), up to 2000 lines per call — use 'offset' and 'limit' to page through longer files, or \
+         'byte_offset' and 'byte_limit' for huge files. Non-UTF-8 text is decoded lossily unless 'encoding' \
+         is given. Binary files are rejected with a summary."
     }
 
     fn input_schema(&self) -> serde_json::Value {
@@ -28,40 +47,262 @@ impl super::ToolExecutor for FsRead {
                 "path": {
                     "type": "string",
                     "description": "Absolute path to the file to read"
+                },
+                "offset": {
+                    "type": "integer",
+                    "description": "First line to return, starting at 1 (default 1)"
+                },
+                "limit": {
+                    "type": "integer",
+                    "description": "Maximum number of lines to return (default 2000)"
+                },
+                "byte_offset": {
+                    "type": "integer",
+                    "description": "Read raw text starting at this byte instead of by line"
+                },
+                "byte_limit": {
+                    "type": "integer",
+                    "description": "Maximum number of bytes to read from 'byte_offset' (default and max 100000)"
+                },
+                "encoding": {
+                    "type": "string",
+                    "description": "Text encoding, e.g. 'utf-8', 'utf-16le', 'windows-1252', 'shift_jis'. Detected from a byte-order mark when omitted, otherwise UTF-8"
+                },
+                "line_numbers": {
+                    "type": "boolean",
+                    "description": "Prefix each line with its number (default true)"
                 }
             },
             "required": ["path"]
         })
     }
 
-    fn execute(&self, input: &serde_json::Value) -> Result<String> {
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    async fn execute(&self, input: &serde_json::Value) -> Result<String> {
         let path_str = input["path"]
             .as_str()
             .ok_or_else(|| anyhow::anyhow!("missing 'path' parameter"))?;
 
-        let path = Path::new(path_str)
-            .canonicalize()
-            .map_err(|e| anyhow::anyhow!("Cannot resolve path '{}': {}", path_str, e))?;
+        let file = open(&self.policies, Path::new(path_str), OpenMode::Read)?;
+
+        let encoding = match input["encoding"].as_str() {
+            Some(label) => Some(
+                Encoding::for_label(label.trim().as_bytes())
+                    .ok_or_else(|| anyhow::anyhow!("Unknown encoding '{}'", label))?,
+            ),
+            None => None,
+        };
+        let number = |name: &str| input[name].as_u64().map(|n| n as usize);
+        let request = ReadRequest {
+            offset: number("offset").unwrap_or(1).max(1),
+            limit: number("limit").unwrap_or(DEFAULT_LINE_LIMIT).max(1),
+            byte_range: match (number("byte_offset"), number("byte_limit")) {
+                (None, None) => None,
+                (start, len) => Some((start.unwrap_or(0), len.unwrap_or(MAX_OUTPUT_BYTES).min(MAX_OUTPUT_BYTES))),
+            },
+            encoding,
+            line_numbers: input["line_numbers"].as_bool().unwrap_or(true),
+        };
+
+        let path = strip_win_prefix(file.path()).display().to_string();
+        read_file(file.into_file(), &request).map_err(|e| anyhow::anyhow!("Failed to read '{}': {}", path, e))
+    }
+}
+
+struct ReadRequest {
+    /// 1-based first line.
+    offset: usize,
+    limit: usize,
+    /// `(start, len)` in bytes; replaces the line range when set.
+    byte_range: Option<(usize, usize)>,
+    encoding: Option<&'static Encoding>,
+    line_numbers: bool,
+}
 
-        if !check_access(&self.policies, &path, false) {
-            bail!(
-                "Access denied: '{}' is not in any allowed read policy",
-                strip_win_prefix(&path).display()
-            );
+fn read_file(mut file: File, request: &ReadRequest) -> Result<String> {
+    let size = file.metadata()?.len();
+
+    let mut head = Vec::new();
+    (&mut file).take(SNIFF_BYTES as u64).read_to_end(&mut head)?;
+    let encoding = request
+        .encoding
+        .or_else(|| Encoding::for_bom(&head).map(|(encoding, _)| encoding));
+    // UTF-16 text is full of zero bytes; anything else with one is binary.
+    let utf16 = encoding.is_some_and(|e| e == encoding_rs::UTF_16LE || e == encoding_rs::UTF_16BE);
+    if !utf16 && head.contains(&0) {
+        bail!("{}", describe_binary(&head, size));
+    }
+
+    if let Some((start, len)) = request.byte_range {
+        file.seek(SeekFrom::Start(start as u64))?;
+        let mut bytes = Vec::new();
+        file.take(len as u64).read_to_end(&mut bytes)?;
+        let (text, note) = decode(&bytes, encoding);
+        let end = start + bytes.len();
+        let mut out = text;
+        out.push_str(&format!("\n\n[Bytes {}-{} of {}{}]", start, end, size, note));
+        if (end as u64) < size {
+            out.push_str(&format!(" [Use byte_offset={} to continue]", end));
         }
+        return Ok(out);
+    }
+
+    if size > MAX_LINE_READ_BYTES {
+        bail!(
+            "file is {} bytes, too large to read by line. Use 'byte_offset' and 'byte_limit' to read part of it",
+            size
+        );
+    }
+    file.seek(SeekFrom::Start(0))?;
+    let mut bytes = Vec::with_capacity(size as usize);
+    file.read_to_end(&mut bytes)?;
+    let (text, note) = decode(&bytes, encoding);
+    Ok(format_lines(&text, request, &note))
+}
 
-        let content = std::fs::read_to_string(&path)
-            .map_err(|e| anyhow::anyhow!("Failed to read '{}': {}", strip_win_prefix(&path).display(), e))?;
+/// Decode `bytes` with `encoding`, or as UTF-8 when it's unknown. Returns the
+/// text and a note for the footer when decoding was lossy or not UTF-8.
+fn decode(bytes: &[u8], encoding: Option<&'static Encoding>) -> (String, String) {
+    let encoding = encoding.unwrap_or(encoding_rs::UTF_8);
+    let (text, used, had_errors) = encoding.decode(bytes);
+    let note = if had_errors {
+        format!(
+            "; invalid {} replaced with \u{FFFD} — pass 'encoding' (e.g. 'windows-1252') if the file uses another one",
+            used.name()
+        )
+    } else if used != encoding_rs::UTF_8 {
+        format!("; decoded as {}", used.name())
+    } else {
+        String::new()
+    };
+    (text.into_owned(), note)
+}
 
-        // Truncate to 100KB to avoid blowing up context
-        if content.len() > 100_000 {
-            Ok(format!(
-                "{}\n\n[Truncated: file is {} bytes, showing first 100,000 characters]",
-                &content[..100_000],
-                content.len()
-            ))
+/// The requested line window, numbered, capped at `MAX_OUTPUT_BYTES`, with a
+/// footer whenever the reader hasn't seen the whole file.
+fn format_lines(text: &str, request: &ReadRequest, note: &str) -> String {
+    let total = text.lines().count();
+    let mut out = String::new();
+    let mut last = request.offset - 1;
+    let mut cut_short = false;
+    let mut clipped_line = None;
+    for (i, line) in text.lines().enumerate().skip(request.offset - 1).take(request.limit) {
+        let formatted = if request.line_numbers {
+            format!("{:>6}\t{}\n", i + 1, line)
         } else {
-            Ok(content)
+            format!("{line}\n")
+        };
+        if out.len() + formatted.len() > MAX_OUTPUT_BYTES {
+            if out.is_empty() {
+                // A single enormous line: show what fits of it.
+                out.push_str(super::truncate_str(&formatted, MAX_OUTPUT_BYTES));
+                out.push_str("...\n");
+                last = i + 1;
+                clipped_line = Some(last);
+            }
+            cut_short = true;
+            break;
         }
+        out.push_str(&formatted);
+        last = i + 1;
+    }
+
+    if total == 0 {
+        return format!("[Empty file{note}]");
+    }
+    if request.offset > total {
+        return format!("[Offset {} is past the end of the file ({} lines){}]", request.offset, total, note);
+    }
+    if request.offset > 1 || last < total || !note.is_empty() || clipped_line.is_some() {
+        out.push_str(&format!("\n[Lines {}-{} of {}{}]", request.offset, last, total, note));
+        if let Some(line) = clipped_line {
+            out.push_str(&format!(
+                " [Line {line} is longer than {MAX_OUTPUT_BYTES} bytes and was cut; use byte_offset to read all of it]"
+            ));
+        }
+        if last < total {
+            let why = if cut_short && clipped_line.is_none() { " Output limit reached." } else { "" };
+            out.push_str(&format!("{why} [Use offset={} to continue]", last + 1));
+        }
+    }
+    out
+}
+
+/// Why a file was taken for binary, with what it looks like and how to read it.
+fn describe_binary(head: &[u8], size: u64) -> String {
+    const SIGNATURES: &[(&[u8], &str, &str)] = &[
+        (b"%PDF", "PDF document", " Use fs_read_pdf to extract its text."),
+        (b"\x89PNG", "PNG image", ""),
+        (b"\xFF\xD8\xFF", "JPEG image", ""),
+        (b"GIF8", "GIF image", ""),
+        (b"PK\x03\x04", "ZIP archive (or an Office document)", ""),
+        (b"\x1F\x8B", "gzip archive", ""),
+        (b"\x7FELF", "ELF executable", ""),
+        (b"MZ", "Windows executable", ""),
+        (b"SQLite format 3", "SQLite database", " Query it with sqlite3 through shell_run."),
+    ];
+    let (kind, hint) = SIGNATURES
+        .iter()
+        .find(|(magic, _, _)| head.starts_with(magic))
+        .map(|(_, kind, hint)| (*kind, *hint))
+        .unwrap_or(("binary data", ""));
+    format!("this is a binary file ({kind}, {size} bytes), not text.{hint}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(offset: usize, limit: usize) -> ReadRequest {
+        ReadRequest { offset, limit, byte_range: None, encoding: None, line_numbers: true }
+    }
+
+    #[test]
+    fn test_line_window_and_continuation() {
+        let text = "one\ntwo\nthree\nfour\n";
+        assert_eq!(format_lines(text, &request(1, 10), ""), "     1\tone\n     2\ttwo\n     3\tthree\n     4\tfour\n");
+        assert_eq!(
+            format_lines(text, &request(2, 2), ""),
+            "     2\ttwo\n     3\tthree\n\n[Lines 2-3 of 4] [Use offset=4 to continue]"
+        );
+        assert!(format_lines(text, &request(9, 2), "").contains("past the end"));
+    }
+
+    #[test]
+    fn test_long_multibyte_line_truncates_on_char_boundary() {
+        let text = "é".repeat(MAX_OUTPUT_BYTES);
+        let out = format_lines(&text, &request(1, 10), "");
+        assert!(out.len() < MAX_OUTPUT_BYTES + 300);
+        assert!(out.contains("[Lines 1-1 of 1] [Line 1 is longer"), "{}", &out[out.len() - 200..]);
+    }
+
+    #[test]
+    fn test_decoding_and_binary_detection() {
+        let dir = std::env::temp_dir().join(format!("bat-fs-read-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let latin1 = dir.join("latin1.txt");
+        std::fs::write(&latin1, b"caf\xe9\n").unwrap();
+        let lossy = read_file(File::open(&latin1).unwrap(), &request(1, 10)).unwrap();
+        assert!(lossy.contains("caf\u{FFFD}") && lossy.contains("pass 'encoding'"), "{lossy}");
+        let explicit = ReadRequest { encoding: Encoding::for_label(b"latin1"), ..request(1, 10) };
+        assert!(read_file(File::open(&latin1).unwrap(), &explicit).unwrap().contains("café"));
+
+        let utf16 = dir.join("utf16.txt");
+        std::fs::write(&utf16, b"\xFF\xFEh\0i\0").unwrap();
+        assert!(read_file(File::open(&utf16).unwrap(), &request(1, 10)).unwrap().starts_with("     1\thi"));
+
+        let png = dir.join("image.png");
+        std::fs::write(&png, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        let err = read_file(File::open(&png).unwrap(), &request(1, 10)).unwrap_err().to_string();
+        assert!(err.contains("PNG image"), "{err}");
+
+        let bytes = ReadRequest { byte_range: Some((2, 3)), ..request(1, 10) };
+        let out = read_file(File::open(&latin1).unwrap(), &bytes).unwrap();
+        assert!(out.starts_with("f\u{FFFD}\n") && out.contains("[Bytes 2-5 of 5"), "{out}");
+        let _ = std::fs::remove_dir_all(&dir);
     }
 }
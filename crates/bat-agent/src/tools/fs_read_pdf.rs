@@ -1,20 +1,24 @@
 use anyhow::{bail, Result};
-use bat_types::policy::{check_access, strip_win_prefix, PathPolicy};
+use bat_types::ipc::LlmRelay;
+use bat_types::policy::{strip_win_prefix, PathPolicy};
+use std::io::Read;
 use std::path::Path;
 
-use crate::llm::{AnthropicClient, AnthropicMessage, ChatRequest};
+use crate::policy::{open, OpenMode};
+use crate::llm::{AnthropicClient, AnthropicMessage, ChatRequest, ThinkingLevel};
 
 pub struct FsReadPdf {
     policies: Vec<PathPolicy>,
-    api_key: String,
+    relay: LlmRelay,
 }
 
 impl FsReadPdf {
-    pub fn new(policies: Vec<PathPolicy>, api_key: String) -> Self {
-        Self { policies, api_key }
+    pub fn new(policies: Vec<PathPolicy>, relay: LlmRelay) -> Self {
+        Self { policies, relay }
     }
 }
 
+#[async_trait::async_trait]
 impl super::ToolExecutor for FsReadPdf {
     fn name(&self) -> &str {
         "fs_read_pdf"
@@ -37,25 +41,26 @@ impl super::ToolExecutor for FsReadPdf {
         })
     }
 
-    fn execute(&self, input: &serde_json::Value) -> Result<String> {
+    fn timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(180)
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    async fn execute(&self, input: &serde_json::Value) -> Result<String> {
         let path_str = input["path"]
             .as_str()
             .ok_or_else(|| anyhow::anyhow!("missing 'path' parameter"))?;
 
-        let path = Path::new(path_str)
-            .canonicalize()
-            .map_err(|e| anyhow::anyhow!("Cannot resolve path '{}': {}", path_str, e))?;
-
-        if !check_access(&self.policies, &path, false) {
-            bail!(
-                "Access denied: '{}' is not in any allowed read policy",
-                strip_win_prefix(&path).display()
-            );
-        }
+        let file = open(&self.policies, Path::new(path_str), OpenMode::Read)?;
 
         // Read PDF bytes
-        let bytes = std::fs::read(&path)
-            .map_err(|e| anyhow::anyhow!("Failed to read '{}': {}", strip_win_prefix(&path).display(), e))?;
+        let mut bytes = Vec::new();
+        file.file()
+            .read_to_end(&mut bytes)
+            .map_err(|e| anyhow::anyhow!("Failed to read '{}': {}", strip_win_prefix(file.path()).display(), e))?;
 
         // Enforce a size limit (32MB)
         if bytes.len() > 32 * 1024 * 1024 {
@@ -67,7 +72,7 @@ impl super::ToolExecutor for FsReadPdf {
         let encoded = base64::engine::general_purpose::STANDARD.encode(&bytes);
 
         // Call Anthropic API with document content block
-        let client = AnthropicClient::new(self.api_key.clone());
+        let client = AnthropicClient::via_relay(&self.relay)?;
         let request = ChatRequest {
             model: "claude-haiku-4-5-latest".to_string(),
             max_tokens: 8192,
@@ -91,11 +96,11 @@ impl super::ToolExecutor for FsReadPdf {
             }],
             tools: vec![],
             stream: false,
+            thinking: ThinkingLevel::Off,
+            stable_prefix: 0,
         };
 
-        // Block on async call from sync context
-        let handle = tokio::runtime::Handle::current();
-        let response = handle.block_on(client.chat(&request))?;
+        let response = client.chat(&request).await?;
 
         let text = response.text();
         if text.is_empty() {
@@ -0,0 +1,450 @@
+use anyhow::{bail, Result};
+use bat_types::policy::{check_access, strip_win_prefix, PathPolicy};
+use std::io::Read;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::policy::{open, OpenMode};
+
+const DEFAULT_MAX_RESULTS: usize = 100;
+const MAX_RESULTS: usize = 1000;
+const DEFAULT_MAX_DEPTH: usize = 25;
+const MAX_CONTEXT: usize = 10;
+const SEARCH_TIME_LIMIT: Duration = Duration::from_secs(20);
+/// Larger files are skipped by content search.
+const MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+/// Matched and context lines are cut to this many bytes.
+const MAX_LINE_BYTES: usize = 300;
+
+pub struct FsSearch {
+    policies: Vec<PathPolicy>,
+}
+
+impl FsSearch {
+    pub fn new(policies: Vec<PathPolicy>) -> Self {
+        Self { policies }
+    }
+}
+
+#[async_trait::async_trait]
+impl super::ToolExecutor for FsSearch {
+    fn name(&self) -> &str {
+        "fs_search"
+    }
+
+    fn description(&self) -> &str {
+        "Search a directory tree for files by name (glob) and/or content (regex). Only walks allowed paths \
+         and skips files ignored by .gitignore. Returns matches as JSON with 'path' and, for content \
+         matches, 'line' and the matching text — pass the path straight to fs_read."
+    }
+
+    fn input_schema(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "Absolute path of the directory to search"
+                },
+                "glob": {
+                    "type": "string",
+                    "description": "File name pattern, e.g. '*.rs' or 'report-*.{md,txt}'. Patterns containing '/' match the path relative to 'path', e.g. 'src/**/*.rs'"
+                },
+                "query": {
+                    "type": "string",
+                    "description": "Regular expression to search file contents for. Without it, only names are matched"
+                },
+                "case_insensitive": {
+                    "type": "boolean",
+                    "description": "Match 'query' ignoring case (default false)"
+                },
+                "context": {
+                    "type": "integer",
+                    "description": "Lines of context before and after each content match (default 0, max 10)"
+                },
+                "max_results": {
+                    "type": "integer",
+                    "description": "Stop after this many matches (default 100, max 1000)"
+                },
+                "max_depth": {
+                    "type": "integer",
+                    "description": "How many directory levels below 'path' to descend (default 25)"
+                },
+                "include_hidden": {
+                    "type": "boolean",
+                    "description": "Also search hidden files and directories (default false)"
+                }
+            },
+            "required": ["path"]
+        })
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    async fn execute(&self, input: &serde_json::Value) -> Result<String> {
+        let path_str = input["path"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("missing 'path' parameter"))?;
+
+        let root = Path::new(path_str)
+            .canonicalize()
+            .map_err(|e| anyhow::anyhow!("Cannot resolve path '{}': {}", path_str, e))?;
+
+        if !may_descend(&self.policies, &root) {
+            bail!(
+                "Access denied: '{}' is not in any allowed read policy",
+                strip_win_prefix(&root).display()
+            );
+        }
+        if !root.is_dir() {
+            bail!("'{}' is not a directory", strip_win_prefix(&root).display());
+        }
+
+        let options = SearchOptions::from_input(input)?;
+        if options.glob.is_none() && options.query.is_none() {
+            bail!("Give a 'glob', a 'query', or both");
+        }
+
+        let policies = self.policies.clone();
+        let found = tokio::task::spawn_blocking(move || search(&policies, &root, &options)).await??;
+        Ok(serde_json::to_string_pretty(&found)?)
+    }
+}
+
+struct SearchOptions {
+    glob: Option<globset::GlobMatcher>,
+    /// Whether `glob` matches the relative path rather than the file name.
+    glob_on_path: bool,
+    query: Option<regex::Regex>,
+    context: usize,
+    max_results: usize,
+    max_depth: usize,
+    include_hidden: bool,
+    /// Stop walking after this long and return what was found so far.
+    time_limit: Duration,
+}
+
+impl SearchOptions {
+    fn from_input(input: &serde_json::Value) -> Result<Self> {
+        let glob_str = input["glob"].as_str().filter(|g| !g.is_empty());
+        let glob = glob_str
+            .map(|g| {
+                globset::GlobBuilder::new(g)
+                    .literal_separator(true)
+                    .build()
+                    .map(|g| g.compile_matcher())
+                    .map_err(|e| anyhow::anyhow!("Invalid glob '{}': {}", g, e))
+            })
+            .transpose()?;
+        let query = input["query"]
+            .as_str()
+            .filter(|q| !q.is_empty())
+            .map(|q| {
+                regex::RegexBuilder::new(q)
+                    .case_insensitive(input["case_insensitive"].as_bool().unwrap_or(false))
+                    .build()
+                    .map_err(|e| anyhow::anyhow!("Invalid regex '{}': {}", q, e))
+            })
+            .transpose()?;
+        let number = |name: &str| input[name].as_u64().map(|n| n as usize);
+        Ok(Self {
+            glob,
+            glob_on_path: glob_str.is_some_and(|g| g.contains('/')),
+            query,
+            context: number("context").unwrap_or(0).min(MAX_CONTEXT),
+            max_results: number("max_results").unwrap_or(DEFAULT_MAX_RESULTS).clamp(1, MAX_RESULTS),
+            max_depth: number("max_depth").unwrap_or(DEFAULT_MAX_DEPTH),
+            include_hidden: input["include_hidden"].as_bool().unwrap_or(false),
+            time_limit: SEARCH_TIME_LIMIT,
+        })
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+struct SearchMatch {
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    before: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    after: Vec<String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct SearchResults {
+    matches: Vec<SearchMatch>,
+    files_searched: usize,
+    /// Why the search stopped early, if it did.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    truncated: Option<String>,
+}
+
+/// Whether the walk may enter `dir`: it is readable itself, or an allowed
+/// path lies somewhere beneath it (so searching `~` still reaches `~/Documents`).
+fn may_descend(policies: &[PathPolicy], dir: &Path) -> bool {
+    check_access(policies, dir, false)
+        || policies.iter().any(|p| strip_win_prefix(&p.path).starts_with(strip_win_prefix(dir)))
+}
+
+fn search(policies: &[PathPolicy], root: &Path, options: &SearchOptions) -> Result<SearchResults> {
+    let started = Instant::now();
+    let dir_policies = policies.to_vec();
+    let walker = ignore::WalkBuilder::new(root)
+        .max_depth(Some(options.max_depth))
+        .hidden(!options.include_hidden)
+        .follow_links(false)
+        // Honour .gitignore even outside a git checkout.
+        .require_git(false)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .filter_entry(move |entry| {
+            !entry.file_type().is_some_and(|t| t.is_dir()) || may_descend(&dir_policies, entry.path())
+        })
+        .build();
+
+    let mut results = SearchResults { matches: vec![], files_searched: 0, truncated: None };
+    for entry in walker {
+        if started.elapsed() > options.time_limit {
+            results.truncated = Some(format!("time limit of {}s reached", options.time_limit.as_secs()));
+            break;
+        }
+        if results.matches.len() >= options.max_results {
+            results.truncated = Some(format!("result limit of {} reached", options.max_results));
+            break;
+        }
+        // Unreadable directories and the like are skipped, as `grep -s` would.
+        let Ok(entry) = entry else { continue };
+        if !entry.file_type().is_some_and(|t| t.is_file()) || !check_access(policies, entry.path(), false) {
+            continue;
+        }
+
+        let path = entry.path();
+        if let Some(glob) = &options.glob {
+            let subject = if options.glob_on_path {
+                path.strip_prefix(root).unwrap_or(path)
+            } else {
+                Path::new(entry.file_name())
+            };
+            if !glob.is_match(subject) {
+                continue;
+            }
+        }
+
+        let display = strip_win_prefix(path).display().to_string();
+        let Some(query) = &options.query else {
+            results.files_searched += 1;
+            results.matches.push(SearchMatch { path: display, line: None, text: None, before: vec![], after: vec![] });
+            continue;
+        };
+        let Some(content) = read_text(policies, path) else { continue };
+        results.files_searched += 1;
+
+        let lines: Vec<&str> = content.lines().collect();
+        for (i, line) in lines.iter().enumerate() {
+            if !query.is_match(line) {
+                continue;
+            }
+            if results.matches.len() >= options.max_results {
+                break;
+            }
+            let before = lines[i.saturating_sub(options.context)..i].iter().map(|l| clip(l)).collect();
+            let after = lines[i + 1..(i + 1 + options.context).min(lines.len())]
+                .iter()
+                .map(|l| clip(l))
+                .collect();
+            results.matches.push(SearchMatch {
+                path: display.clone(),
+                line: Some(i + 1),
+                text: Some(clip(line)),
+                before,
+                after,
+            });
+        }
+    }
+    Ok(results)
+}
+
+/// File contents for content search, or `None` for binary, oversized or
+/// unreadable files.
+fn read_text(policies: &[PathPolicy], path: &Path) -> Option<String> {
+    // Opened through the policy again: the walk's own check was by path.
+    let file = open(policies, path, OpenMode::Read).ok()?.into_file();
+    if file.metadata().ok()?.len() > MAX_FILE_SIZE {
+        return None;
+    }
+    let mut bytes = Vec::new();
+    file.take(MAX_FILE_SIZE).read_to_end(&mut bytes).ok()?;
+    if bytes[..bytes.len().min(8192)].contains(&0) {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn clip(line: &str) -> String {
+    let line = line.trim_end();
+    let clipped = super::truncate_str(line, MAX_LINE_BYTES);
+    if clipped.len() < line.len() {
+        format!("{clipped}...")
+    } else {
+        line.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::ToolExecutor;
+    use bat_types::policy::AccessLevel;
+
+    fn policy(path: &Path) -> PathPolicy {
+        PathPolicy { id: None, path: path.to_path_buf(), access: AccessLevel::ReadOnly, recursive: true, description: None, layer: 0 }
+    }
+
+    /// A scratch directory holding `files` (relative path, contents).
+    fn tree(files: &[(&str, &str)]) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("bat-fs-search-{}", uuid::Uuid::new_v4()));
+        for (path, contents) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+        dir.canonicalize().unwrap()
+    }
+
+    async fn run(dir: &Path, input: serde_json::Value) -> serde_json::Value {
+        let mut input = input;
+        input["path"] = serde_json::json!(dir);
+        let out = FsSearch::new(vec![policy(dir)]).execute(&input).await.unwrap();
+        serde_json::from_str(&out).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_search_respects_policy_and_gitignore() {
+        let dir = std::env::temp_dir().join(format!("bat-fs-search-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("allowed/src")).unwrap();
+        std::fs::create_dir_all(dir.join("secret")).unwrap();
+        let dir = dir.canonicalize().unwrap();
+        std::fs::write(dir.join("allowed/src/main.rs"), "fn main() {\n    todo!()\n}\n").unwrap();
+        std::fs::write(dir.join("allowed/.gitignore"), "build/\n").unwrap();
+        std::fs::create_dir_all(dir.join("allowed/build")).unwrap();
+        std::fs::write(dir.join("allowed/build/out.rs"), "todo!()\n").unwrap();
+        std::fs::write(dir.join("secret/keys.rs"), "todo!()\n").unwrap();
+
+        // Searching from the parent only reaches the allowed subdirectory.
+        let tool = FsSearch::new(vec![policy(&dir.join("allowed"))]);
+        let out = tool
+            .execute(&serde_json::json!({ "path": dir, "glob": "*.rs", "query": "TODO", "case_insensitive": true, "context": 1 }))
+            .await
+            .unwrap();
+        let out: serde_json::Value = serde_json::from_str(&out).unwrap();
+        let matches = out["matches"].as_array().unwrap();
+        assert_eq!(matches.len(), 1, "{out}");
+        assert!(matches[0]["path"].as_str().unwrap().ends_with("main.rs"));
+        assert_eq!(matches[0]["line"], 2);
+        assert_eq!(matches[0]["before"], serde_json::json!(["fn main() {"]));
+
+        let err = tool.execute(&serde_json::json!({ "path": dir.join("secret"), "glob": "*" })).await.unwrap_err();
+        assert!(err.to_string().contains("Access denied"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_result_limit_truncates() {
+        let dir = tree(&[("a.txt", "x\nx\nx\n"), ("b.txt", "x\n")]);
+        let out = run(&dir, serde_json::json!({ "query": "x", "max_results": 2 })).await;
+        assert_eq!(out["matches"].as_array().unwrap().len(), 2, "{out}");
+        assert_eq!(out["truncated"], "result limit of 2 reached");
+
+        let out = run(&dir, serde_json::json!({ "query": "x" })).await;
+        assert_eq!(out["matches"].as_array().unwrap().len(), 4, "{out}");
+        assert!(out.get("truncated").is_none(), "{out}");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_depth_limit() {
+        let dir = tree(&[("top.txt", ""), ("one/mid.txt", ""), ("one/two/deep.txt", "")]);
+        let names = |out: serde_json::Value| -> Vec<String> {
+            out["matches"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|m| Path::new(m["path"].as_str().unwrap()).file_name().unwrap().to_string_lossy().into_owned())
+                .collect()
+        };
+        assert_eq!(names(run(&dir, serde_json::json!({ "glob": "*.txt", "max_depth": 1 })).await), ["top.txt"]);
+        assert_eq!(
+            names(run(&dir, serde_json::json!({ "glob": "*.txt", "max_depth": 2 })).await),
+            ["mid.txt", "top.txt"]
+        );
+        assert_eq!(names(run(&dir, serde_json::json!({ "glob": "*.txt" })).await).len(), 3);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_time_limit_truncates() {
+        let dir = tree(&[("a.txt", "x\n")]);
+        let mut options = SearchOptions::from_input(&serde_json::json!({ "query": "x" })).unwrap();
+        assert_eq!(options.time_limit, SEARCH_TIME_LIMIT);
+        options.time_limit = Duration::ZERO;
+        let results = search(&[policy(&dir)], &dir, &options).unwrap();
+        assert!(results.matches.is_empty());
+        assert_eq!(results.truncated.as_deref(), Some("time limit of 0s reached"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_context_lines() {
+        let dir = tree(&[("a.txt", "one\ntwo\nthree\nMATCH\nfour\nfive\n"), ("b.txt", "MATCH\n")]);
+        let out = run(&dir, serde_json::json!({ "query": "MATCH", "context": 2 })).await;
+        let matches = out["matches"].as_array().unwrap();
+        assert_eq!(matches[0]["before"], serde_json::json!(["two", "three"]));
+        assert_eq!(matches[0]["after"], serde_json::json!(["four", "five"]));
+        // Clipped at the file's edges, and omitted when empty.
+        assert!(matches[1].get("before").is_none() && matches[1].get("after").is_none(), "{out}");
+
+        // Capped at MAX_CONTEXT lines.
+        let long: String = (0..30).map(|i| format!("{i}\n")).chain(["MATCH\n".to_string()]).collect();
+        let dir2 = tree(&[("long.txt", &long)]);
+        let out = run(&dir2, serde_json::json!({ "query": "MATCH", "context": 50 })).await;
+        assert_eq!(out["matches"][0]["before"].as_array().unwrap().len(), MAX_CONTEXT);
+        let _ = std::fs::remove_dir_all(&dir);
+        let _ = std::fs::remove_dir_all(&dir2);
+    }
+
+    #[tokio::test]
+    async fn test_structured_output() {
+        let dir = tree(&[("notes.md", "alpha\nbeta\n"), ("other.md", "gamma\n")]);
+        let out = run(&dir, serde_json::json!({ "query": "beta" })).await;
+        let expected = dir.join("notes.md");
+        assert_eq!(
+            out,
+            serde_json::json!({
+                "matches": [{ "path": strip_win_prefix(&expected), "line": 2, "text": "beta" }],
+                "files_searched": 2,
+            })
+        );
+
+        // Name-only matches carry just the path, ready for fs_read.
+        let out = run(&dir, serde_json::json!({ "glob": "notes.*" })).await;
+        assert_eq!(out["matches"], serde_json::json!([{ "path": strip_win_prefix(&expected) }]));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_patterns_are_reported() {
+        let dir = tree(&[("a.txt", "x\n")]);
+        let tool = FsSearch::new(vec![policy(&dir)]);
+        let err = tool.execute(&serde_json::json!({ "path": dir, "query": "(unclosed" })).await.unwrap_err();
+        assert!(err.to_string().starts_with("Invalid regex '(unclosed'"), "{err}");
+        let err = tool.execute(&serde_json::json!({ "path": dir, "glob": "[z-a]" })).await.unwrap_err();
+        assert!(err.to_string().starts_with("Invalid glob '[z-a]'"), "{err}");
+        let err = tool.execute(&serde_json::json!({ "path": dir })).await.unwrap_err();
+        assert!(err.to_string().contains("Give a 'glob', a 'query', or both"), "{err}");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,146 @@
+use anyhow::Result;
+use bat_types::policy::{strip_win_prefix, PathPolicy};
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::policy::locate;
+
+pub struct FsStat {
+    policies: Vec<PathPolicy>,
+}
+
+impl FsStat {
+    pub fn new(policies: Vec<PathPolicy>) -> Self {
+        Self { policies }
+    }
+}
+
+#[async_trait::async_trait]
+impl super::ToolExecutor for FsStat {
+    fn name(&self) -> &str {
+        "fs_stat"
+    }
+
+    fn description(&self) -> &str {
+        "Get metadata for a file or directory: type, size, timestamps, permissions, and for directories the \
+         number of entries. Cheaper than reading the file."
+    }
+
+    fn input_schema(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "Absolute path of the file or directory"
+                }
+            },
+            "required": ["path"]
+        })
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    async fn execute(&self, input: &serde_json::Value) -> Result<String> {
+        let path_str = input["path"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("missing 'path' parameter"))?;
+
+        let entry = locate(&self.policies, Path::new(path_str), false)?;
+        let path = entry.path();
+        let pinned = entry.pinned();
+
+        let metadata = std::fs::symlink_metadata(&pinned)
+            .map_err(|e| anyhow::anyhow!("Failed to stat '{}': {}", strip_win_prefix(path).display(), e))?;
+        let file_type = metadata.file_type();
+        let kind = if file_type.is_symlink() {
+            "symlink"
+        } else if file_type.is_dir() {
+            "directory"
+        } else if file_type.is_file() {
+            "file"
+        } else {
+            "other"
+        };
+        let timestamp = |t: std::io::Result<SystemTime>| {
+            t.ok().map(|t| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339())
+        };
+
+        let mut stat = serde_json::json!({
+            "path": strip_win_prefix(path),
+            "type": kind,
+            "size": metadata.len(),
+            "modified": timestamp(metadata.modified()),
+            "created": timestamp(metadata.created()),
+            "accessed": timestamp(metadata.accessed()),
+            "readonly": metadata.permissions().readonly(),
+        });
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            stat["mode"] = format!("{:o}", metadata.permissions().mode() & 0o7777).into();
+        }
+        if file_type.is_symlink() {
+            stat["target"] = std::fs::read_link(&pinned).ok().map(|t| t.display().to_string()).into();
+        }
+        if file_type.is_dir() {
+            stat["entries"] = std::fs::read_dir(&pinned).map(|d| d.count()).ok().into();
+        }
+
+        Ok(serde_json::to_string_pretty(&stat)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::ToolExecutor;
+    use bat_types::policy::AccessLevel;
+
+    #[tokio::test]
+    async fn test_stat_reports_metadata() {
+        let dir = std::env::temp_dir().join(format!("bat-fs-stat-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("docs")).unwrap();
+        let dir = dir.canonicalize().unwrap();
+        std::fs::write(dir.join("docs/a.txt"), "hello").unwrap();
+        std::fs::write(dir.join("docs/b.txt"), "").unwrap();
+        let policies =
+            vec![PathPolicy { id: None, path: dir.join("docs"), access: AccessLevel::ReadOnly, recursive: true, description: None, layer: 0 }];
+        let tool = FsStat::new(policies);
+        let stat = |path: std::path::PathBuf| {
+            let tool = &tool;
+            async move {
+                let out = tool.execute(&serde_json::json!({ "path": path })).await.unwrap();
+                serde_json::from_str::<serde_json::Value>(&out).unwrap()
+            }
+        };
+
+        let file = stat(dir.join("docs/a.txt")).await;
+        assert_eq!(file["path"], serde_json::json!(strip_win_prefix(&dir.join("docs/a.txt"))));
+        assert_eq!(file["type"], "file");
+        assert_eq!(file["size"], 5);
+        assert_eq!(file["readonly"], false);
+        assert!(chrono::DateTime::parse_from_rfc3339(file["modified"].as_str().unwrap()).is_ok(), "{file}");
+        assert!(file.get("entries").is_none(), "{file}");
+        #[cfg(unix)]
+        assert_eq!(file["mode"].as_str().unwrap().len(), 3, "{file}");
+
+        let directory = stat(dir.join("docs")).await;
+        assert_eq!(directory["type"], "directory");
+        assert_eq!(directory["entries"], 2);
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("a.txt", dir.join("docs/link")).unwrap();
+            let link = stat(dir.join("docs/link")).await;
+            assert_eq!(link["type"], "symlink");
+            assert_eq!(link["target"], "a.txt");
+        }
+
+        let err = tool.execute(&serde_json::json!({ "path": dir })).await.unwrap_err();
+        assert!(err.to_string().contains("Access denied"), "{err}");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
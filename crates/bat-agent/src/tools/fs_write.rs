@@ -1,5 +1,7 @@
-use anyhow::{bail, Result};
-use bat_types::policy::{check_access, strip_win_prefix, PathPolicy};
+use anyhow::Result;
+use crate::policy::{open, OpenMode};
+use bat_types::policy::{strip_win_prefix, PathPolicy};
+use std::io::Write;
 use std::path::Path;
 
 pub struct FsWrite {
@@ -12,6 +14,7 @@ impl FsWrite {
     }
 }
 
+#[async_trait::async_trait]
 impl super::ToolExecutor for FsWrite {
     fn name(&self) -> &str {
         "fs_write"
@@ -38,7 +41,7 @@ impl super::ToolExecutor for FsWrite {
         })
     }
 
-    fn execute(&self, input: &serde_json::Value) -> Result<String> {
+    async fn execute(&self, input: &serde_json::Value) -> Result<String> {
         let path_str = input["path"]
             .as_str()
             .ok_or_else(|| anyhow::anyhow!("missing 'path' parameter"))?;
@@ -46,35 +49,13 @@ impl super::ToolExecutor for FsWrite {
             .as_str()
             .ok_or_else(|| anyhow::anyhow!("missing 'content' parameter"))?;
 
-        let path = Path::new(path_str);
+        // Missing parent directories are only created once the write is allowed.
+        let file = open(&self.policies, Path::new(path_str), OpenMode::Replace)?;
+        let path = strip_win_prefix(file.path()).display().to_string();
+        file.file()
+            .write_all(content.as_bytes())
+            .map_err(|e| anyhow::anyhow!("Failed to write '{}': {}", path, e))?;
 
-        // For new files, we can't canonicalize yet — check parent dir
-        let check_path = if path.exists() {
-            path.canonicalize()?
-        } else {
-            let parent = path
-                .parent()
-                .ok_or_else(|| anyhow::anyhow!("Invalid path: no parent directory"))?;
-            if !parent.exists() {
-                std::fs::create_dir_all(parent)?;
-            }
-            parent.canonicalize()?.join(path.file_name().unwrap())
-        };
-
-        if !check_access(&self.policies, &check_path, true) {
-            bail!(
-                "Access denied: '{}' is not in any allowed write policy",
-                strip_win_prefix(&check_path).display()
-            );
-        }
-
-        std::fs::write(&check_path, content)
-            .map_err(|e| anyhow::anyhow!("Failed to write '{}': {}", strip_win_prefix(&check_path).display(), e))?;
-
-        Ok(format!(
-            "Successfully wrote {} bytes to {}",
-            content.len(),
-            strip_win_prefix(&check_path).display()
-        ))
+        Ok(format!("Successfully wrote {} bytes to {}", content.len(), path))
     }
 }
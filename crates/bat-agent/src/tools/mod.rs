@@ -1,7 +1,13 @@
 pub mod fs_read;
 pub mod fs_read_pdf;
 pub mod fs_write;
+pub mod fs_edit;
 pub mod fs_list;
+pub mod fs_search;
+pub mod fs_move;
+pub mod fs_copy;
+pub mod fs_delete;
+pub mod fs_stat;
 pub mod web_fetch;
 pub mod web_search;
 pub mod shell_run;
@@ -23,28 +29,73 @@ pub mod session_resume;
 pub mod session_instruct;
 pub mod session_cancel;
 
-use anyhow::Result;
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use bat_types::approval::{ApprovalDecision, PermissionMode};
+use bat_types::command_policy::CommandPolicy;
+use bat_types::config::PermissionsConfig;
+use bat_types::ipc::{LlmRelay, ProcessAction, ProcessResult};
 use bat_types::message::{ToolCall, ToolResult};
 use bat_types::policy::PathPolicy;
 
 use crate::gateway_bridge::GatewayBridge;
 
+/// How long a tool may run unless it overrides `timeout`.
+pub const DEFAULT_TOOL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The longest prefix of `s` that is at most `max_bytes` long and ends on a
+/// character boundary, so multi-byte characters are never split.
+pub fn truncate_str(s: &str, max_bytes: usize) -> &str {
+    if s.len() <= max_bytes {
+        return s;
+    }
+    let mut end = max_bytes;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
 /// Tool executor trait — each tool implements this.
+#[async_trait::async_trait]
 pub trait ToolExecutor: Send + Sync {
     fn name(&self) -> &str;
     fn description(&self) -> &str;
     fn input_schema(&self) -> serde_json::Value;
-    fn execute(&self, input: &serde_json::Value) -> Result<String>;
+    async fn execute(&self, input: &serde_json::Value) -> Result<String>;
+
+    /// Maximum run time; the call is abandoned and reported as an error after this.
+    fn timeout(&self) -> Duration {
+        DEFAULT_TOOL_TIMEOUT
+    }
+
+    /// Whether the tool only reads state. Read-only calls from the same model
+    /// response run concurrently; any other call runs on its own, in order.
+    fn read_only(&self) -> bool {
+        false
+    }
 }
 
 /// Registry of available tools.
 pub struct ToolRegistry {
     tools: Vec<Box<dyn ToolExecutor>>,
+    permissions: PermissionsConfig,
+    /// Where "ask" mode calls are sent for approval. Without one they are refused.
+    approver: Option<GatewayBridge>,
 }
 
 impl ToolRegistry {
     pub fn new() -> Self {
-        Self { tools: vec![] }
+        Self { tools: vec![], permissions: PermissionsConfig::allow_all(), approver: None }
+    }
+
+    /// Check every call against `permissions`, asking the user through
+    /// `approver` for tools in "ask" mode.
+    pub fn with_permissions(mut self, permissions: PermissionsConfig, approver: GatewayBridge) -> Self {
+        self.permissions = permissions;
+        self.approver = Some(approver);
+        self
     }
 
     /// Create a registry with only orchestrator (session management) tools.
@@ -78,7 +129,16 @@ impl ToolRegistry {
     }
 
     /// Create a registry with all default tools, skipping any in `disabled`.
-    pub fn with_default_tools(policies: Vec<PathPolicy>, disabled: &[String], bridge: Option<GatewayBridge>) -> Self {
+    /// `shell_env_allowlist` and `command_policy` are passed to `shell_run`;
+    /// `fs_read_pdf` and `web_search` need a `relay` with the provider's key.
+    pub fn with_default_tools(
+        policies: Vec<PathPolicy>,
+        disabled: &[String],
+        shell_env_allowlist: Vec<String>,
+        command_policy: CommandPolicy,
+        relay: Option<LlmRelay>,
+        bridge: Option<GatewayBridge>,
+    ) -> Self {
         let mut reg = Self::new();
         if !disabled.contains(&"fs_read".to_string()) {
             reg.register(Box::new(fs_read::FsRead::new(policies.clone())));
@@ -86,24 +146,47 @@ impl ToolRegistry {
         if !disabled.contains(&"fs_write".to_string()) {
             reg.register(Box::new(fs_write::FsWrite::new(policies.clone())));
         }
+        if !disabled.contains(&"fs_edit".to_string()) {
+            reg.register(Box::new(fs_edit::FsEdit::new(policies.clone())));
+        }
         if !disabled.contains(&"fs_list".to_string()) {
             reg.register(Box::new(fs_list::FsList::new(policies.clone())));
         }
+        if !disabled.contains(&"fs_search".to_string()) {
+            reg.register(Box::new(fs_search::FsSearch::new(policies.clone())));
+        }
+        if !disabled.contains(&"fs_move".to_string()) {
+            reg.register(Box::new(fs_move::FsMove::new(policies.clone())));
+        }
+        if !disabled.contains(&"fs_copy".to_string()) {
+            reg.register(Box::new(fs_copy::FsCopy::new(policies.clone())));
+        }
+        if !disabled.contains(&"fs_delete".to_string()) {
+            reg.register(Box::new(fs_delete::FsDelete::new(policies.clone())));
+        }
+        if !disabled.contains(&"fs_stat".to_string()) {
+            reg.register(Box::new(fs_stat::FsStat::new(policies.clone())));
+        }
         if !disabled.contains(&"fs_read_pdf".to_string()) {
-            if let Ok(api_key) = std::env::var("ANTHROPIC_API_KEY") {
-                reg.register(Box::new(fs_read_pdf::FsReadPdf::new(policies, api_key)));
+            if let Some(relay) = relay.clone().filter(|r| r.anthropic) {
+                reg.register(Box::new(fs_read_pdf::FsReadPdf::new(policies.clone(), relay)));
             }
         }
         if !disabled.contains(&"web_fetch".to_string()) {
             reg.register(Box::new(web_fetch::WebFetch::new()));
         }
         if !disabled.contains(&"web_search".to_string()) {
-            if let Ok(api_key) = std::env::var("OPENAI_API_KEY") {
-                reg.register(Box::new(web_search::WebSearch::new(api_key)));
+            if let Some(relay) = relay.filter(|r| r.openai) {
+                reg.register(Box::new(web_search::WebSearch::new(relay)));
             }
         }
         if !disabled.contains(&"shell_run".to_string()) {
-            reg.register(Box::new(shell_run::ShellRun::new()));
+            reg.register(Box::new(shell_run::ShellRun::new(
+                policies,
+                shell_env_allowlist,
+                command_policy,
+                bridge.clone(),
+            )));
         }
         if !disabled.contains(&"app_open".to_string()) {
             reg.register(Box::new(app_open::AppOpen::new()));
@@ -155,12 +238,30 @@ impl ToolRegistry {
         self.tools.iter().find(|t| t.name() == name).map(|t| t.as_ref())
     }
 
+    /// Whether a call may run alongside other read-only calls.
+    pub fn is_read_only(&self, name: &str) -> bool {
+        self.get(name).is_some_and(|t| t.read_only())
+    }
+
     /// Execute a tool call, returning a ToolResult.
-    pub fn execute(&self, call: &ToolCall) -> ToolResult {
-        let result = self
-            .get(&call.name)
-            .map(|t| t.execute(&call.input))
-            .unwrap_or_else(|| Err(anyhow::anyhow!("Unknown tool: {}", call.name)));
+    pub async fn execute(&self, call: &ToolCall) -> ToolResult {
+        let result = match self.get(&call.name) {
+            Some(tool) => match self.authorize(call).await {
+                Ok(()) => {
+                    let timeout = tool.timeout();
+                    match tokio::time::timeout(timeout, tool.execute(&call.input)).await {
+                        Ok(result) => result,
+                        Err(_) => Err(anyhow::anyhow!(
+                            "Tool '{}' timed out after {} seconds",
+                            call.name,
+                            timeout.as_secs()
+                        )),
+                    }
+                }
+                Err(e) => Err(e),
+            },
+            None => Err(anyhow::anyhow!("Unknown tool: {}", call.name)),
+        };
 
         match result {
             Ok(output) => ToolResult {
@@ -176,6 +277,30 @@ impl ToolRegistry {
         }
     }
 
+    /// Apply the tool's permission mode. The approval wait is not part of the
+    /// tool's timeout.
+    async fn authorize(&self, call: &ToolCall) -> Result<()> {
+        match self.permissions.mode_for(&call.name) {
+            PermissionMode::Allow => Ok(()),
+            PermissionMode::Deny => bail!("Tool '{}' is blocked by the user's permission settings", call.name),
+            PermissionMode::Ask => {
+                let Some(approver) = &self.approver else {
+                    bail!("Tool '{}' needs the user's approval, which is not available here", call.name);
+                };
+                let action = ProcessAction::RequestApproval { tool: call.name.clone(), input: call.input.clone() };
+                match approver.request(action).await {
+                    ProcessResult::Approval { decision: ApprovalDecision::Deny { reason } } => match reason {
+                        Some(reason) => bail!("The user denied this call to '{}': {}", call.name, reason),
+                        None => bail!("The user denied this call to '{}'", call.name),
+                    },
+                    ProcessResult::Approval { .. } => Ok(()),
+                    ProcessResult::Error { message } => bail!("Approval for '{}' failed: {}", call.name, message),
+                    other => bail!("Unexpected approval response: {:?}", other),
+                }
+            }
+        }
+    }
+
     /// Returns Anthropic-format tool definitions for the API.
     pub fn definitions(&self) -> Vec<serde_json::Value> {
         self.tools
@@ -190,3 +315,97 @@ impl ToolRegistry {
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Sleepy;
+
+    #[async_trait::async_trait]
+    impl ToolExecutor for Sleepy {
+        fn name(&self) -> &str {
+            "sleepy"
+        }
+
+        fn description(&self) -> &str {
+            "Sleeps for a while"
+        }
+
+        fn input_schema(&self) -> serde_json::Value {
+            serde_json::json!({ "type": "object" })
+        }
+
+        fn timeout(&self) -> Duration {
+            Duration::from_millis(50)
+        }
+
+        async fn execute(&self, _input: &serde_json::Value) -> Result<String> {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok("done".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execute_times_out() {
+        let mut reg = ToolRegistry::new();
+        reg.register(Box::new(Sleepy));
+        let call = ToolCall {
+            id: "t1".to_string(),
+            name: "sleepy".to_string(),
+            input: serde_json::json!({}),
+        };
+        let result = reg.execute(&call).await;
+        assert!(result.is_error);
+        assert!(result.content.contains("timed out"));
+    }
+
+    #[test]
+    fn test_truncate_str_keeps_whole_characters() {
+        assert_eq!(truncate_str("héllo", 2), "h");
+        assert_eq!(truncate_str("héllo", 3), "hé");
+        assert_eq!(truncate_str("héllo", 100), "héllo");
+    }
+
+    #[tokio::test]
+    async fn test_execute_respects_permission_modes() {
+        let (bridge, _rx) = crate::gateway_bridge::create_bridge();
+        let mut permissions = PermissionsConfig::allow_all();
+        permissions.tools.insert("sleepy".to_string(), PermissionMode::Deny);
+        let mut reg = ToolRegistry::new();
+        reg.register(Box::new(Sleepy));
+        let reg = reg.with_permissions(permissions, bridge);
+        let call = ToolCall {
+            id: "t1".to_string(),
+            name: "sleepy".to_string(),
+            input: serde_json::json!({}),
+        };
+        let result = reg.execute(&call).await;
+        assert!(result.is_error);
+        assert!(result.content.contains("blocked by the user's permission settings"));
+
+        // "ask" with the gateway gone fails rather than running the tool.
+        let mut permissions = PermissionsConfig::allow_all();
+        permissions.default = PermissionMode::Ask;
+        let (bridge, rx) = crate::gateway_bridge::create_bridge();
+        drop(rx);
+        let mut reg = ToolRegistry::new();
+        reg.register(Box::new(Sleepy));
+        let result = reg.with_permissions(permissions, bridge).execute(&call).await;
+        assert!(result.is_error);
+        assert!(result.content.contains("Approval for 'sleepy' failed"), "{}", result.content);
+    }
+
+    #[tokio::test]
+    async fn test_execute_unknown_tool() {
+        let reg = ToolRegistry::new();
+        let call = ToolCall {
+            id: "t1".to_string(),
+            name: "nope".to_string(),
+            input: serde_json::json!({}),
+        };
+        let result = reg.execute(&call).await;
+        assert!(result.is_error);
+        assert_eq!(result.content, "Unknown tool: nope");
+    }
+}
@@ -17,6 +17,7 @@ impl Screenshot {
     }
 }
 
+#[async_trait::async_trait]
 impl super::ToolExecutor for Screenshot {
     fn name(&self) -> &str { "screenshot" }
     fn description(&self) -> &str {
@@ -34,7 +35,7 @@ impl super::ToolExecutor for Screenshot {
             "required": []
         })
     }
-    fn execute(&self, input: &Value) -> Result<String> {
+    async fn execute(&self, input: &Value) -> Result<String> {
         let filename = input.get("filename")
             .and_then(|v| v.as_str())
             .map(|s| s.to_string())
@@ -13,6 +13,7 @@ impl SessionAnswer {
     }
 }
 
+#[async_trait::async_trait]
 impl super::ToolExecutor for SessionAnswer {
     fn name(&self) -> &str { "session_answer" }
 
@@ -37,7 +38,7 @@ impl super::ToolExecutor for SessionAnswer {
         })
     }
 
-    fn execute(&self, input: &Value) -> Result<String> {
+    async fn execute(&self, input: &Value) -> Result<String> {
         let session_key = input.get("session_key")
             .and_then(|v| v.as_str())
             .ok_or_else(|| anyhow::anyhow!("Missing required 'session_key' parameter"))?;
@@ -13,6 +13,7 @@ impl SessionCancel {
     }
 }
 
+#[async_trait::async_trait]
 impl super::ToolExecutor for SessionCancel {
     fn name(&self) -> &str { "session_cancel" }
 
@@ -33,7 +34,7 @@ impl super::ToolExecutor for SessionCancel {
         })
     }
 
-    fn execute(&self, input: &Value) -> Result<String> {
+    async fn execute(&self, input: &Value) -> Result<String> {
         let session_key = input.get("session_key")
             .and_then(|v| v.as_str())
             .ok_or_else(|| anyhow::anyhow!("Missing required 'session_key' parameter"))?;
@@ -42,7 +43,7 @@ impl super::ToolExecutor for SessionCancel {
             session_key: session_key.to_string(),
         };
 
-        match self.bridge.request(action) {
+        match self.bridge.request(action).await {
             ProcessResult::SubagentCancelled => {
                 Ok(json!({
                     "status": "cancelled",
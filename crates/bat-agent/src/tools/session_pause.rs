@@ -13,6 +13,7 @@ impl SessionPause {
     }
 }
 
+#[async_trait::async_trait]
 impl super::ToolExecutor for SessionPause {
     fn name(&self) -> &str { "session_pause" }
 
@@ -33,7 +34,7 @@ impl super::ToolExecutor for SessionPause {
         })
     }
 
-    fn execute(&self, input: &Value) -> Result<String> {
+    async fn execute(&self, input: &Value) -> Result<String> {
         let session_key = input.get("session_key")
             .and_then(|v| v.as_str())
             .ok_or_else(|| anyhow::anyhow!("Missing required 'session_key' parameter"))?;
@@ -42,12 +43,12 @@ impl super::ToolExecutor for SessionPause {
             session_key: session_key.to_string(),
         };
 
-        match self.bridge.request(action) {
+        match self.bridge.request(action).await {
             ProcessResult::SubagentPaused => {
                 Ok(json!({
                     "status": "paused",
                     "session_key": session_key,
-                    "message": "Sub-agent has been paused"
+                    "message": "Sub-agent will pause before its next step"
                 }).to_string())
             }
             ProcessResult::Error { message } => {
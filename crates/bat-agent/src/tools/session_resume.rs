@@ -13,6 +13,7 @@ impl SessionResume {
     }
 }
 
+#[async_trait::async_trait]
 impl super::ToolExecutor for SessionResume {
     fn name(&self) -> &str { "session_resume" }
 
@@ -37,7 +38,7 @@ impl super::ToolExecutor for SessionResume {
         })
     }
 
-    fn execute(&self, input: &Value) -> Result<String> {
+    async fn execute(&self, input: &Value) -> Result<String> {
         let session_key = input.get("session_key")
             .and_then(|v| v.as_str())
             .ok_or_else(|| anyhow::anyhow!("Missing required 'session_key' parameter"))?;
@@ -51,7 +52,7 @@ impl super::ToolExecutor for SessionResume {
             instructions,
         };
 
-        match self.bridge.request(action) {
+        match self.bridge.request(action).await {
             ProcessResult::SubagentResumed => {
                 Ok(json!({
                     "status": "resumed",
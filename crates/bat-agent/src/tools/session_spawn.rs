@@ -2,6 +2,7 @@ use anyhow::Result;
 use serde_json::{Value, json};
 use crate::gateway_bridge::GatewayBridge;
 use bat_types::ipc::{ProcessAction, ProcessResult};
+use bat_types::policy::PathGrant;
 
 pub struct SessionSpawn {
     bridge: GatewayBridge,
@@ -13,10 +14,20 @@ impl SessionSpawn {
     }
 }
 
+fn host_list(input: &Value, key: &str) -> Result<Option<Vec<String>>> {
+    match input.get(key) {
+        Some(hosts) => serde_json::from_value::<Vec<String>>(hosts.clone())
+            .map(Some)
+            .map_err(|e| anyhow::anyhow!("Invalid '{key}': {e}")),
+        None => Ok(None),
+    }
+}
+
+#[async_trait::async_trait]
 impl super::ToolExecutor for SessionSpawn {
     fn name(&self) -> &str { "session_spawn" }
     fn description(&self) -> &str {
-        "Spawn a background subagent to handle a task concurrently. Returns immediately with a session key. The subagent runs independently and announces results when done."
+        "Spawn a background subagent to handle a task concurrently. Returns immediately with a session key. The subagent runs independently and announces results when done. By default it gets your paths, tools, model and memory limit; pass 'paths', 'tools', 'model', 'memory_limit_mb', 'hosts' or 'blocked_hosts' to give it less."
     }
     fn input_schema(&self) -> Value {
         json!({
@@ -29,19 +40,82 @@ impl super::ToolExecutor for SessionSpawn {
                 "label": {
                     "type": "string",
                     "description": "Short label for this subagent (shown in UI). Defaults to first 40 chars of task."
+                },
+                "paths": {
+                    "type": "array",
+                    "description": "Paths the subagent may access, each within your own access. Defaults to all of yours.",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "path": { "type": "string" },
+                            "access": { "type": "string", "enum": ["read-only", "read-write", "write-only"] }
+                        },
+                        "required": ["path", "access"]
+                    }
+                },
+                "tools": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Tools the subagent may use, e.g. [\"fs_read\", \"fs_list\"]. Defaults to all worker tools."
+                },
+                "model": {
+                    "type": "string",
+                    "description": "Model for the subagent, from the enabled models. Defaults to the configured model."
+                },
+                "memory_limit_mb": {
+                    "type": "integer",
+                    "description": "Memory limit for the subagent's process, no higher than your own."
+                },
+                "hosts": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Hosts the subagent may connect to, e.g. [\"docs.rs\", \"*.github.com\"]. Defaults to any the network policy allows."
+                },
+                "blocked_hosts": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Hosts the subagent may not connect to."
                 }
             },
             "required": ["task"]
         })
     }
-    fn execute(&self, input: &Value) -> Result<String> {
+    async fn execute(&self, input: &Value) -> Result<String> {
         let task = input.get("task")
             .and_then(|v| v.as_str())
             .ok_or_else(|| anyhow::anyhow!("Missing required 'task' parameter"))?;
         let label = input.get("label").and_then(|v| v.as_str()).map(|s| s.to_string());
 
-        let action = ProcessAction::SpawnSubagent { task: task.to_string(), label: label.clone() };
-        match self.bridge.request(action) {
+        let paths = match input.get("paths") {
+            Some(paths) => Some(
+                serde_json::from_value::<Vec<PathGrant>>(paths.clone())
+                    .map_err(|e| anyhow::anyhow!("Invalid 'paths': {e}"))?,
+            ),
+            None => None,
+        };
+        let tools = match input.get("tools") {
+            Some(tools) => Some(
+                serde_json::from_value::<Vec<String>>(tools.clone())
+                    .map_err(|e| anyhow::anyhow!("Invalid 'tools': {e}"))?,
+            ),
+            None => None,
+        };
+        let hosts = host_list(input, "hosts")?;
+        let blocked_hosts = host_list(input, "blocked_hosts")?;
+        let model = input.get("model").and_then(|v| v.as_str()).map(|s| s.to_string());
+        let memory_limit_mb = input.get("memory_limit_mb").and_then(|v| v.as_u64());
+
+        let action = ProcessAction::SpawnSubagent {
+            task: task.to_string(),
+            label: label.clone(),
+            paths,
+            tools,
+            model,
+            memory_limit_mb,
+            hosts,
+            blocked_hosts,
+        };
+        match self.bridge.request(action).await {
             ProcessResult::SubagentSpawned { session_key, session_id } => {
                 Ok(json!({
                     "status": "spawned",
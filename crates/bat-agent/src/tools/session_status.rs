@@ -13,6 +13,7 @@ impl SessionStatus {
     }
 }
 
+#[async_trait::async_trait]
 impl super::ToolExecutor for SessionStatus {
     fn name(&self) -> &str { "session_status" }
     fn description(&self) -> &str {
@@ -25,9 +26,13 @@ impl super::ToolExecutor for SessionStatus {
             "required": []
         })
     }
-    fn execute(&self, _input: &Value) -> Result<String> {
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    async fn execute(&self, _input: &Value) -> Result<String> {
         let action = ProcessAction::ListSubagents;
-        match self.bridge.request(action) {
+        match self.bridge.request(action).await {
             ProcessResult::SubagentList { subagents } => {
                 if subagents.is_empty() {
                     Ok("No subagents have been spawned.".to_string())
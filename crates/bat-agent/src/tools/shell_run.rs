@@ -1,21 +1,73 @@
-use anyhow::Result;
-use std::process::Command;
+use crate::gateway_bridge::GatewayBridge;
+use anyhow::{bail, Result};
+use bat_types::command_policy::CommandPolicy;
+use bat_types::ipc::{ProcessAction, ProcessResult};
+use bat_types::policy::{check_access, strip_win_prefix, PathPolicy};
+use bat_types::process::kill_process_tree;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::Command;
 
-pub struct ShellRun;
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+const MAX_TIMEOUT_SECS: u64 = 600;
+/// Each of stdout and stderr is cut to this many bytes.
+const MAX_STREAM_BYTES: usize = 25_000;
+
+/// Environment variables commands inherit from the agent. Everything else —
+/// notably provider API keys — is dropped unless the user allowlists it.
+const INHERITED_ENV: &[&str] = &[
+    "PATH", "HOME", "USER", "LOGNAME", "SHELL", "LANG", "LANGUAGE", "TERM", "TZ", "TMPDIR", "TMP", "TEMP",
+    "DISPLAY", "WAYLAND_DISPLAY", "XDG_RUNTIME_DIR",
+    // The gateway's egress proxy
+    "HTTP_PROXY", "HTTPS_PROXY", "ALL_PROXY",
+    // Windows
+    "SYSTEMROOT", "SYSTEMDRIVE", "WINDIR", "COMSPEC", "PATHEXT", "USERPROFILE", "USERNAME", "HOMEDRIVE",
+    "HOMEPATH", "APPDATA", "LOCALAPPDATA", "PROGRAMDATA", "PROGRAMFILES", "PROGRAMFILES(X86)",
+    "COMMONPROGRAMFILES", "COMPUTERNAME", "NUMBER_OF_PROCESSORS", "PROCESSOR_ARCHITECTURE", "OS",
+];
+
+pub struct ShellRun {
+    policies: Vec<PathPolicy>,
+    /// `AgentConfig::shell_env_allowlist` — extra variables to pass through.
+    env_allowlist: Vec<String>,
+    /// `BatConfig::commands` — which commands may run at all.
+    commands: CommandPolicy,
+    /// Tells the gateway about running commands so it can kill them if it
+    /// has to kill the agent.
+    bridge: Option<GatewayBridge>,
+}
 
 impl ShellRun {
-    pub fn new() -> Self {
-        Self
+    pub fn new(
+        policies: Vec<PathPolicy>,
+        env_allowlist: Vec<String>,
+        commands: CommandPolicy,
+        bridge: Option<GatewayBridge>,
+    ) -> Self {
+        Self { policies, env_allowlist, commands, bridge }
+    }
+
+    fn inherits(&self, name: &str) -> bool {
+        let upper = name.to_ascii_uppercase();
+        INHERITED_ENV.contains(&upper.as_str())
+            || upper.starts_with("LC_")
+            || self.env_allowlist.iter().any(|allowed| allowed.eq_ignore_ascii_case(name))
     }
 }
 
+#[async_trait::async_trait]
 impl super::ToolExecutor for ShellRun {
     fn name(&self) -> &str {
         "shell_run"
     }
 
     fn description(&self) -> &str {
-        "Execute a shell command and return its output. The command runs in the system shell (cmd on Windows, sh on Unix). Times out after 30 seconds."
+        "Execute a shell command and return its exit code, stdout and stderr as JSON. The command runs in the \
+         system shell (cmd on Windows, sh on Unix) with a minimal environment. It is killed, with everything \
+         it started, after 'timeout' seconds (default 30). Background processes do not outlive the command; \
+         use exec_run for long-running ones."
     }
 
     fn input_schema(&self) -> serde_json::Value {
@@ -25,58 +77,268 @@ impl super::ToolExecutor for ShellRun {
                 "command": {
                     "type": "string",
                     "description": "The shell command to execute"
+                },
+                "cwd": {
+                    "type": "string",
+                    "description": "Absolute path of the directory to run in; must be an allowed path"
+                },
+                "timeout": {
+                    "type": "integer",
+                    "description": "Seconds before the command is killed (default 30, max 600)"
+                },
+                "env": {
+                    "type": "object",
+                    "description": "Extra environment variables to set, e.g. {\"RUST_LOG\": \"debug\"}",
+                    "additionalProperties": { "type": "string" }
                 }
             },
             "required": ["command"]
         })
     }
 
-    fn execute(&self, input: &serde_json::Value) -> Result<String> {
+    /// The command enforces its own `timeout`; this is only a backstop.
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(MAX_TIMEOUT_SECS + 10)
+    }
+
+    async fn execute(&self, input: &serde_json::Value) -> Result<String> {
         let command = input["command"]
             .as_str()
             .ok_or_else(|| anyhow::anyhow!("missing 'command' parameter"))?;
+        let timeout = Duration::from_secs(
+            input["timeout"].as_u64().unwrap_or(DEFAULT_TIMEOUT_SECS).clamp(1, MAX_TIMEOUT_SECS),
+        );
 
-        let output = if cfg!(target_os = "windows") {
-            Command::new("cmd")
-                .args(["/C", command])
-                .output()
+        let mut cmd = if cfg!(target_os = "windows") {
+            let mut c = Command::new("cmd");
+            c.args(["/C", command]);
+            c
         } else {
-            Command::new("sh")
-                .args(["-c", command])
-                .output()
+            let mut c = Command::new("sh");
+            c.args(["-c", command]);
+            c
+        };
+
+        let cwd = match input["cwd"].as_str() {
+            Some(cwd) => Some(Path::new(cwd)
+                .canonicalize()
+                .map_err(|e| anyhow::anyhow!("Cannot resolve cwd '{}': {}", cwd, e))?),
+            None => None,
+        };
+        if let Some(cwd) = &cwd {
+            if !cwd.is_dir() {
+                bail!("cwd '{}' is not a directory", strip_win_prefix(cwd).display());
+            }
+            if !check_access(&self.policies, cwd, false) {
+                bail!(
+                    "Access denied: cwd '{}' is not in any allowed policy",
+                    strip_win_prefix(cwd).display()
+                );
+            }
+            cmd.current_dir(cwd);
+        }
+        self.commands
+            .check(command, cwd.as_deref())
+            .map_err(|denial| anyhow::anyhow!(denial.to_tool_error()))?;
+
+        cmd.env_clear();
+        cmd.envs(std::env::vars_os().filter(|(name, _)| name.to_str().is_some_and(|n| self.inherits(n))));
+        if let Some(env) = input["env"].as_object() {
+            for (name, value) in env {
+                let value = value
+                    .as_str()
+                    .ok_or_else(|| anyhow::anyhow!("env value for '{}' must be a string", name))?;
+                cmd.env(name, value);
+            }
         }
-        .map_err(|e| anyhow::anyhow!("Failed to execute command: {}", e))?;
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        let code = output.status.code().unwrap_or(-1);
+        // Run in a new process group so a timeout or cancellation takes down
+        // everything the command started, not just the shell.
+        #[cfg(unix)]
+        cmd.process_group(0);
 
-        let mut result = String::new();
-        if !stdout.is_empty() {
-            result.push_str(&stdout);
+        let mut child = cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("Failed to execute command: {}", e))?;
+        let mut group = child.id().map(|pgid| ProcessGroup::new(pgid, self.bridge.clone()));
+        if let Some(group) = &group {
+            group.track().await;
         }
-        if !stderr.is_empty() {
-            if !result.is_empty() {
-                result.push('\n');
+        let stdout = tokio::spawn(read_capped(child.stdout.take()));
+        let stderr = tokio::spawn(read_capped(child.stderr.take()));
+
+        let (exit_code, timed_out) = match tokio::time::timeout(timeout, child.wait()).await {
+            Ok(status) => (status?.code(), false),
+            Err(_) => {
+                drop(group.take());
+                let _ = child.kill().await;
+                (None, true)
             }
-            result.push_str("[stderr] ");
-            result.push_str(&stderr);
+        };
+        // Take down anything the command left running in the background.
+        drop(group);
+        // A background process that left the group may still hold the pipes
+        // open; don't wait for it.
+        let collect = |task: tokio::task::JoinHandle<Captured>| async move {
+            match tokio::time::timeout(Duration::from_secs(2), task).await {
+                Ok(Ok(captured)) => captured,
+                _ => Captured::default(),
+            }
+        };
+        let (stdout, stderr) = (collect(stdout).await, collect(stderr).await);
+
+        let mut result = serde_json::json!({
+            "exit_code": exit_code,
+            "stdout": stdout.text,
+            "stderr": stderr.text,
+        });
+        if timed_out {
+            result["timed_out"] = format!("killed after {} seconds", timeout.as_secs()).into();
         }
-        if result.is_empty() {
-            result = format!("(no output, exit code {})", code);
-        } else if code != 0 {
-            result.push_str(&format!("\n(exit code {})", code));
+        for (field, captured) in [("stdout_truncated_bytes", &stdout), ("stderr_truncated_bytes", &stderr)] {
+            if captured.dropped > 0 {
+                result[field] = captured.dropped.into();
+            }
         }
+        Ok(serde_json::to_string_pretty(&result)?)
+    }
+}
 
-        // Truncate
-        if result.len() > 50_000 {
-            Ok(format!(
-                "{}\n\n[Truncated: output is {} bytes]",
-                &result[..50_000],
-                result.len()
-            ))
-        } else {
-            Ok(result)
+#[derive(Default)]
+struct Captured {
+    text: String,
+    /// Bytes read past `MAX_STREAM_BYTES` and thrown away.
+    dropped: usize,
+}
+
+/// Read a stream to the end, keeping the first `MAX_STREAM_BYTES`.
+async fn read_capped(stream: Option<impl AsyncRead + Unpin>) -> Captured {
+    let Some(mut stream) = stream else {
+        return Captured::default();
+    };
+    let mut kept = Vec::new();
+    let mut dropped = 0;
+    let mut buf = [0u8; 8192];
+    while let Ok(n) = stream.read(&mut buf).await {
+        if n == 0 {
+            break;
         }
+        let room = MAX_STREAM_BYTES.saturating_sub(kept.len()).min(n);
+        kept.extend_from_slice(&buf[..room]);
+        dropped += n - room;
+    }
+    let text = String::from_utf8_lossy(&kept);
+    let text = if dropped > 0 {
+        // The cut may have split a character; drop the partial one.
+        text.trim_end_matches('\u{FFFD}').to_string()
+    } else {
+        text.into_owned()
+    };
+    Captured { text, dropped }
+}
+
+/// The process group a command's shell leads (see `execute`). Dropping it
+/// kills the group, so a command can't outlive its call — not even when the
+/// call is cancelled.
+struct ProcessGroup {
+    pgid: u32,
+    bridge: Option<GatewayBridge>,
+}
+
+impl ProcessGroup {
+    fn new(pgid: u32, bridge: Option<GatewayBridge>) -> Self {
+        Self { pgid, bridge }
+    }
+
+    /// Have the gateway kill the group along with the agent, which would
+    /// otherwise die without dropping it.
+    async fn track(&self) {
+        if let Some(bridge) = &self.bridge {
+            if let ProcessResult::Error { message } =
+                bridge.request(ProcessAction::TrackProcessGroup { pgid: self.pgid }).await
+            {
+                tracing::warn!("Gateway won't track process group {}: {message}", self.pgid);
+            }
+        }
+    }
+}
+
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        kill_process_tree(self.pgid);
+        if let (Some(bridge), Ok(runtime)) = (self.bridge.take(), tokio::runtime::Handle::try_current()) {
+            let pgid = self.pgid;
+            runtime.spawn(async move {
+                bridge.request(ProcessAction::UntrackProcessGroup { pgid }).await;
+            });
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::tools::ToolExecutor;
+
+    #[tokio::test]
+    async fn test_separate_streams_and_scrubbed_env() {
+        std::env::set_var("BAT_TEST_SHELL_SECRET", "hunter2");
+        let tool = ShellRun::new(vec![], vec![], CommandPolicy::default(), None);
+        let out = tool
+            .execute(&serde_json::json!({
+                "command": "echo \"out:$BAT_TEST_SHELL_SECRET:$EXTRA\"; echo err >&2; exit 3",
+                "env": { "EXTRA": "x" },
+            }))
+            .await
+            .unwrap();
+        let out: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(out["exit_code"], 3);
+        assert_eq!(out["stdout"], "out::x\n");
+        assert_eq!(out["stderr"], "err\n");
+
+        let allowed = ShellRun::new(vec![], vec!["BAT_TEST_SHELL_SECRET".to_string()], CommandPolicy::default(), None);
+        let out = allowed.execute(&serde_json::json!({ "command": "echo $BAT_TEST_SHELL_SECRET" })).await.unwrap();
+        assert!(out.contains("hunter2"));
+    }
+
+    #[tokio::test]
+    async fn test_timeout_kills_process_group() {
+        let tool = ShellRun::new(vec![], vec![], CommandPolicy::default(), None);
+        let started = std::time::Instant::now();
+        let out = tool
+            .execute(&serde_json::json!({ "command": "echo started; sleep 30 & sleep 30", "timeout": 1 }))
+            .await
+            .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(10));
+        let out: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(out["stdout"], "started\n");
+        assert!(out["timed_out"].is_string());
+        assert!(out["exit_code"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_cwd_must_be_allowed() {
+        let err = ShellRun::new(vec![], vec![], CommandPolicy::default(), None)
+            .execute(&serde_json::json!({ "command": "pwd", "cwd": "/" }))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Access denied"));
+    }
+
+    #[tokio::test]
+    async fn test_denied_command_does_not_run() {
+        let marker = std::env::temp_dir().join(format!("bat-shell-denied-{}", uuid::Uuid::new_v4()));
+        let policy = CommandPolicy { deny: vec!["touch".to_string()], ..CommandPolicy::default() };
+        let err = ShellRun::new(vec![], vec![], policy, None)
+            .execute(&serde_json::json!({ "command": format!("echo hi && touch {}", marker.display()) }))
+            .await
+            .unwrap_err();
+        let denial = bat_types::command_policy::CommandDenial::from_tool_error(&err.to_string()).unwrap();
+        assert_eq!(denial.rule, "touch");
+        assert!(!marker.exists());
     }
 }
@@ -8,6 +8,7 @@ impl SystemInfo {
     }
 }
 
+#[async_trait::async_trait]
 impl super::ToolExecutor for SystemInfo {
     fn name(&self) -> &str {
         "system_info"
@@ -24,7 +25,11 @@ impl super::ToolExecutor for SystemInfo {
         })
     }
 
-    fn execute(&self, _input: &serde_json::Value) -> Result<String> {
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    async fn execute(&self, _input: &serde_json::Value) -> Result<String> {
         let mut info = String::new();
 
         // OS info
@@ -8,6 +8,7 @@ impl WebFetch {
     }
 }
 
+#[async_trait::async_trait]
 impl super::ToolExecutor for WebFetch {
     fn name(&self) -> &str {
         "web_fetch"
@@ -30,7 +31,15 @@ impl super::ToolExecutor for WebFetch {
         })
     }
 
-    fn execute(&self, input: &serde_json::Value) -> Result<String> {
+    fn timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(30)
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    async fn execute(&self, input: &serde_json::Value) -> Result<String> {
         let url = input["url"]
             .as_str()
             .ok_or_else(|| anyhow::anyhow!("missing 'url' parameter"))?;
@@ -39,32 +48,25 @@ impl super::ToolExecutor for WebFetch {
             anyhow::bail!("URL must start with http:// or https://");
         }
 
-        // Use tokio's current runtime to make the async request
-        let handle = tokio::runtime::Handle::current();
-        let url_owned = url.to_string();
-
-        let result = handle.block_on(async {
-            let client = reqwest::Client::builder()
-                .timeout(std::time::Duration::from_secs(15))
-                .user_agent("Batchismo/0.1")
-                .build()?;
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(15))
+            .user_agent("Batchismo/0.1")
+            .build()?;
 
-            let resp = client.get(&url_owned).send().await?;
-            let status = resp.status();
+        let resp = client.get(url).send().await?;
+        let status = resp.status();
 
-            if !status.is_success() {
-                anyhow::bail!("HTTP {} {}", status.as_u16(), status.canonical_reason().unwrap_or(""));
-            }
+        if !status.is_success() {
+            anyhow::bail!("HTTP {} {}", status.as_u16(), status.canonical_reason().unwrap_or(""));
+        }
 
-            let body = resp.text().await?;
-            Ok::<String, anyhow::Error>(body)
-        })?;
+        let result = resp.text().await?;
 
         // Truncate to 50KB
         if result.len() > 50_000 {
             Ok(format!(
-                "{}\n\n[Truncated: response is {} bytes, showing first 50,000 characters]",
-                &result[..50_000],
+                "{}\n\n[Truncated: response is {} bytes, showing the first 50,000]",
+                super::truncate_str(&result, 50_000),
                 result.len()
             ))
         } else {
@@ -1,16 +1,19 @@
 use anyhow::Result;
+use bat_types::ipc::LlmRelay;
 
-/// Web search tool that uses OpenAI's Responses API with the `web_search` built-in tool.
+/// Web search tool that uses OpenAI's Responses API with the `web_search`
+/// built-in tool, through the gateway's LLM relay.
 pub struct WebSearch {
-    api_key: String,
+    relay: LlmRelay,
 }
 
 impl WebSearch {
-    pub fn new(api_key: String) -> Self {
-        Self { api_key }
+    pub fn new(relay: LlmRelay) -> Self {
+        Self { relay }
     }
 }
 
+#[async_trait::async_trait]
 impl super::ToolExecutor for WebSearch {
     fn name(&self) -> &str {
         "web_search"
@@ -33,80 +36,78 @@ impl super::ToolExecutor for WebSearch {
         })
     }
 
-    fn execute(&self, input: &serde_json::Value) -> Result<String> {
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    async fn execute(&self, input: &serde_json::Value) -> Result<String> {
         let query = input["query"]
             .as_str()
             .ok_or_else(|| anyhow::anyhow!("missing 'query' parameter"))?;
 
-        let handle = tokio::runtime::Handle::current();
-        let api_key = self.api_key.clone();
-        let query = query.to_string();
-
-        handle.block_on(async move {
-            let client = reqwest::Client::builder()
-                .timeout(std::time::Duration::from_secs(30))
-                .build()?;
-
-            let body = serde_json::json!({
-                "model": "gpt-4.1-mini",
-                "tools": [{ "type": "web_search" }],
-                "input": query,
-            });
-
-            let resp = client
-                .post("https://api.openai.com/v1/responses")
-                .header("Authorization", format!("Bearer {}", api_key))
-                .header("Content-Type", "application/json")
-                .json(&body)
-                .send()
-                .await?;
-
-            let status = resp.status();
-            let resp_text = resp.text().await?;
-
-            if !status.is_success() {
-                anyhow::bail!("OpenAI API error ({}): {}", status.as_u16(), resp_text);
-            }
-
-            // Parse the response and extract the text output
-            let parsed: serde_json::Value = serde_json::from_str(&resp_text)?;
-
-            // The Responses API returns output items; find the message output
-            let mut result_parts: Vec<String> = Vec::new();
-
-            if let Some(output) = parsed["output"].as_array() {
-                for item in output {
-                    match item["type"].as_str() {
-                        Some("message") => {
-                            if let Some(content) = item["content"].as_array() {
-                                for part in content {
-                                    if let Some(text) = part["text"].as_str() {
-                                        result_parts.push(text.to_string());
-                                    }
-                                    // Include annotations (citations) if present
-                                    if let Some(annotations) = part["annotations"].as_array() {
-                                        for ann in annotations {
-                                            if let (Some(title), Some(url)) = (
-                                                ann["title"].as_str(),
-                                                ann["url"].as_str(),
-                                            ) {
-                                                result_parts.push(format!("  - [{}]({})", title, url));
-                                            }
+        let client = crate::provider::relay_http()
+            .timeout(std::time::Duration::from_secs(30))
+            .build()?;
+
+        let body = serde_json::json!({
+            "model": "gpt-4.1-mini",
+            "tools": [{ "type": "web_search" }],
+            "input": query,
+        });
+
+        let resp = client
+            .post(format!("{}/openai/v1/responses", self.relay.url))
+            .header("Authorization", format!("Bearer {}", self.relay.token))
+            .header("Content-Type", "application/json")
+            .json(&body)
+            .send()
+            .await?;
+
+        let status = resp.status();
+        let resp_text = resp.text().await?;
+
+        if !status.is_success() {
+            anyhow::bail!("OpenAI API error ({}): {}", status.as_u16(), resp_text);
+        }
+
+        // Parse the response and extract the text output
+        let parsed: serde_json::Value = serde_json::from_str(&resp_text)?;
+
+        // The Responses API returns output items; find the message output
+        let mut result_parts: Vec<String> = Vec::new();
+
+        if let Some(output) = parsed["output"].as_array() {
+            for item in output {
+                match item["type"].as_str() {
+                    Some("message") => {
+                        if let Some(content) = item["content"].as_array() {
+                            for part in content {
+                                if let Some(text) = part["text"].as_str() {
+                                    result_parts.push(text.to_string());
+                                }
+                                // Include annotations (citations) if present
+                                if let Some(annotations) = part["annotations"].as_array() {
+                                    for ann in annotations {
+                                        if let (Some(title), Some(url)) = (
+                                            ann["title"].as_str(),
+                                            ann["url"].as_str(),
+                                        ) {
+                                            result_parts.push(format!("  - [{}]({})", title, url));
                                         }
                                     }
                                 }
                             }
                         }
-                        _ => {}
                     }
+                    _ => {}
                 }
             }
+        }
 
-            if result_parts.is_empty() {
-                Ok("No search results found.".to_string())
-            } else {
-                Ok(result_parts.join("\n"))
-            }
-        })
+        if result_parts.is_empty() {
+            Ok("No search results found.".to_string())
+        } else {
+            Ok(result_parts.join("\n"))
+        }
     }
 }
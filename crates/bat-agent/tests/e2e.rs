@@ -0,0 +1,500 @@
+//! End-to-end tests: a real gateway spawns the real `bat-agent` binary, talks
+//! to it over the IPC pipe, and the agent answers from a scripted mock LLM
+//! (`BAT_MOCK_LLM`, see `src/mock_client.rs`). No API keys or network needed.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use tokio::sync::broadcast;
+
+use bat_gateway::db::Database;
+use bat_gateway::Gateway;
+use bat_types::approval::ApprovalDecision;
+use bat_types::audit::{AuditEntry, AuditFilter};
+use bat_types::config::BatConfig;
+use bat_types::ipc::AgentToGateway;
+use bat_types::models::ModelInfo;
+use bat_types::session::SubagentStatus;
+
+const MODEL: &str = "mock-model";
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Agents inherit the test process's environment, so tests that set
+/// `BAT_MOCK_LLM` run one at a time.
+static ENV_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+struct Harness {
+    gateway: Gateway,
+    db: Arc<Database>,
+    events: broadcast::Receiver<AgentToGateway>,
+    dir: PathBuf,
+    record: PathBuf,
+}
+
+impl Harness {
+    /// A gateway with an in-memory database, `~` inside a scratch directory,
+    /// read-write access to that directory, and the LLM script `script`
+    /// builds for that directory.
+    async fn new(script: impl FnOnce(&Path) -> Value) -> Self {
+        Self::with_config(script, |_| {}).await
+    }
+
+    /// Like `new`, with `configure` applied to the gateway's config.
+    async fn with_config(script: impl FnOnce(&Path) -> Value, configure: impl FnOnce(&mut BatConfig)) -> Self {
+        let dir = std::env::temp_dir().join(format!("bat-e2e-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let dir = dir.canonicalize().unwrap();
+        let fixture = dir.join("script.json");
+        let record = dir.join("requests.jsonl");
+        std::fs::write(&fixture, script(&dir).to_string()).unwrap();
+
+        std::env::set_var("HOME", &dir);
+        std::env::set_var("BAT_AGENT_BIN", env!("CARGO_BIN_EXE_bat-agent"));
+        std::env::set_var("BAT_MOCK_LLM", &fixture);
+        std::env::set_var("BAT_MOCK_LLM_RECORD", &record);
+
+        let mut config = bat_gateway::config::load_config().unwrap();
+        config.agent.model = MODEL.to_string();
+        config.models = vec![ModelInfo { context_window: 200_000, ..ModelInfo::local(MODEL) }];
+        configure(&mut config);
+        let db = Arc::new(Database::open_in_memory().unwrap());
+        let gateway = Gateway::new(config, db.clone()).unwrap();
+        gateway.add_path_policy(dir.to_str().unwrap(), "read-write", true).await.unwrap();
+        let events = gateway.subscribe_events();
+        Self { gateway, db, events, dir, record }
+    }
+
+    /// Wait for the first event `pick` accepts.
+    async fn wait_for<T>(&mut self, mut pick: impl FnMut(&AgentToGateway) -> Option<T>) -> T {
+        let wait = async {
+            loop {
+                match self.events.recv().await {
+                    Ok(event) => {
+                        if let Some(found) = pick(&event) {
+                            return found;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(e) => panic!("event bus closed: {e}"),
+                }
+            }
+        };
+        tokio::time::timeout(TIMEOUT, wait).await.expect("timed out waiting for the agent")
+    }
+
+    /// Text of the next completed turn in the main session.
+    async fn main_turn_text(&mut self) -> String {
+        self.wait_for(|event| match event {
+            AgentToGateway::TurnComplete { session_kind, message, .. } if session_kind == "main" => {
+                Some(message.content.clone())
+            }
+            _ => None,
+        })
+        .await
+    }
+
+    /// Audit entries whose summary contains `search`, oldest first.
+    fn audits(&self, search: &str) -> Vec<AuditEntry> {
+        let mut entries = self
+            .gateway
+            .query_audit_log(&AuditFilter { search: Some(search.to_string()), ..Default::default() })
+            .unwrap();
+        entries.reverse();
+        entries
+    }
+
+    /// The requests the mock LLM received, in order.
+    fn requests(&self) -> Vec<Value> {
+        read_lines(&self.record)
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn read_lines(path: &Path) -> Vec<Value> {
+    std::fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+fn tool_names(request: &Value) -> Vec<String> {
+    request["request"]["tools"]
+        .as_array()
+        .map(|tools| tools.iter().filter_map(|t| t["name"].as_str().map(String::from)).collect())
+        .unwrap_or_default()
+}
+
+#[tokio::test]
+async fn test_orchestrator_delegates_to_subagent() {
+    let _env = ENV_LOCK.lock().await;
+    let mut h = Harness::new(|dir| {
+        let summary = dir.join("summary.md");
+        json!([
+            { "when": "Summarise my notes", "tool_calls": [{
+                "name": "session_spawn",
+                "input": { "task": format!("Write a summary to {}", summary.display()), "label": "summary" }
+            }]},
+            { "when": "Subagent spawned and running", "text": "A subagent is on it." },
+            { "when": "Write a summary to", "tool_calls": [{
+                "name": "fs_write",
+                "input": { "path": summary, "content": "Notes, summarised." }
+            }]},
+            { "when": "Successfully wrote", "text": "Summary written." },
+            { "when": "[Subagent complete", "text": "Your summary is ready." },
+        ])
+    })
+    .await;
+    let summary_path = h.dir.join("summary.md");
+
+    h.gateway.send_user_message("Summarise my notes", vec![]).await.unwrap();
+    // The completion notice starts a second orchestrator turn, which may
+    // overtake the first.
+    let mut replies = vec![h.main_turn_text().await, h.main_turn_text().await];
+    replies.sort();
+    assert_eq!(replies, ["A subagent is on it.", "Your summary is ready."]);
+
+    assert_eq!(std::fs::read_to_string(&summary_path).unwrap(), "Notes, summarised.");
+    let subagents = h.gateway.get_subagents().await.unwrap();
+    assert_eq!(subagents.len(), 1);
+    assert_eq!(subagents[0].status, SubagentStatus::Completed);
+    assert_eq!(subagents[0].summary.as_deref(), Some("Summary written."));
+
+    // Every step was used exactly once, and the subagent couldn't spawn more subagents.
+    let requests = h.requests();
+    let mut steps: Vec<u64> = requests.iter().filter_map(|r| r["step"].as_u64()).collect();
+    steps.sort();
+    assert_eq!(steps, vec![0, 1, 2, 3, 4]);
+    let subagent_request = requests.iter().find(|r| r["step"] == 2).unwrap();
+    assert!(tool_names(subagent_request).contains(&"fs_write".to_string()));
+    assert!(!tool_names(subagent_request).contains(&"session_spawn".to_string()));
+    let orchestrator_request = requests.iter().find(|r| r["step"] == 0).unwrap();
+    assert!(tool_names(orchestrator_request).contains(&"session_spawn".to_string()));
+}
+
+#[tokio::test]
+async fn test_transient_error_is_retried() {
+    let _env = ENV_LOCK.lock().await;
+    let mut h = Harness::new(|_| json!([
+        { "error": { "status": 529, "body": "overloaded", "retry_after": 0 } },
+        { "text": "Recovered." },
+    ]))
+    .await;
+
+    h.gateway.send_user_message("Hello", vec![]).await.unwrap();
+    assert_eq!(h.main_turn_text().await, "Recovered.");
+    assert_eq!(h.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn test_rejected_request_reports_error() {
+    let _env = ENV_LOCK.lock().await;
+    let mut h = Harness::new(|_| json!([
+        { "error": { "status": 400, "body": "prompt is too long" } },
+    ]))
+    .await;
+
+    h.gateway.send_user_message("Hello", vec![]).await.unwrap();
+    let message = h
+        .wait_for(|event| match event {
+            AgentToGateway::Error { message } => Some(message.clone()),
+            _ => None,
+        })
+        .await;
+    assert!(message.contains("prompt is too long"), "{message}");
+    // A bad request is not worth retrying.
+    assert_eq!(h.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn test_denied_approval_reaches_the_agent() {
+    let _env = ENV_LOCK.lock().await;
+    let mut h = Harness::new(|dir| {
+        json!([
+            { "when": "Tidy up", "tool_calls": [{
+                "name": "session_spawn",
+                "input": { "task": "Delete the build directory", "label": "tidy" }
+            }]},
+            { "when": "Subagent spawned and running", "text": "On it." },
+            { "when": "Delete the build directory", "tool_calls": [{
+                "name": "shell_run",
+                "input": { "command": format!("touch {}", dir.join("ran").display()) }
+            }]},
+            { "when": "not today", "text": "The user declined." },
+            { "when": "[Subagent complete", "text": "Nothing was deleted." },
+        ])
+    })
+    .await;
+
+    h.gateway.send_user_message("Tidy up", vec![]).await.unwrap();
+    // shell_run asks by default.
+    let request = h
+        .wait_for(|event| match event {
+            AgentToGateway::ApprovalRequested { request } => Some(request.clone()),
+            _ => None,
+        })
+        .await;
+    assert_eq!(request.tool, "shell_run");
+    assert_eq!(h.gateway.pending_approvals().len(), 1);
+    // The command is on record before it runs (or is refused).
+    let starts = h
+        .gateway
+        .query_audit_log(&AuditFilter { search: Some("Tool call: shell_run".to_string()), ..Default::default() })
+        .unwrap();
+    assert_eq!(starts.len(), 1);
+    assert_eq!(starts[0].event, "tool_call_start");
+    assert!(starts[0].detail_json.as_deref().unwrap_or_default().contains("touch"), "{:?}", starts[0]);
+    h.gateway
+        .resolve_approval(&request.id, ApprovalDecision::Deny { reason: Some("not today".to_string()) })
+        .unwrap();
+
+    let result = h
+        .wait_for(|event| match event {
+            AgentToGateway::ToolCallResult { result, .. } => Some(result.clone()),
+            _ => None,
+        })
+        .await;
+    assert!(result.is_error);
+    assert!(result.content.contains("not today"), "{}", result.content);
+    assert!(!h.dir.join("ran").exists());
+    assert!(h.gateway.pending_approvals().is_empty());
+
+    let denials = h
+        .gateway
+        .query_audit_log(&AuditFilter { search: Some("Denied: shell_run".to_string()), ..Default::default() })
+        .unwrap();
+    assert_eq!(denials.len(), 1);
+}
+
+/// PIDs of the agents spawned so far, in order.
+fn spawned_pids(h: &Harness) -> Vec<u32> {
+    h.audits("Agent spawned (pid: ")
+        .iter()
+        .map(|entry| {
+            let rest = entry.summary.split("pid: ").nth(1).unwrap();
+            rest[..rest.find(',').unwrap()].parse().unwrap()
+        })
+        .collect()
+}
+
+fn persistent(keep_alive_secs: u64) -> impl FnOnce(&mut BatConfig) {
+    move |config| {
+        config.sandbox.persistent_agents = true;
+        config.sandbox.agent_keep_alive_secs = keep_alive_secs;
+    }
+}
+
+/// Let a finished turn park its agent before the next turn looks for it.
+async fn settle() {
+    tokio::time::sleep(Duration::from_millis(300)).await;
+}
+
+fn two_turn_script(_: &Path) -> Value {
+    json!([
+        { "when": "First", "text": "One." },
+        { "when": "Second", "text": "Two." },
+    ])
+}
+
+#[tokio::test]
+async fn test_persistent_agent_is_reused() {
+    let _env = ENV_LOCK.lock().await;
+    let mut h = Harness::with_config(two_turn_script, persistent(300)).await;
+
+    h.gateway.send_user_message("First", vec![]).await.unwrap();
+    assert_eq!(h.main_turn_text().await, "One.");
+    settle().await;
+    h.gateway.send_user_message("Second", vec![]).await.unwrap();
+    assert_eq!(h.main_turn_text().await, "Two.");
+
+    assert_eq!(spawned_pids(&h).len(), 1);
+    assert_eq!(h.audits("Reusing idle agent").len(), 1);
+    // The agent kept the first turn in its own history.
+    let second = &h.requests()[1]["request"]["messages"];
+    assert!(second.to_string().contains("One."), "{second}");
+}
+
+#[tokio::test]
+async fn test_changed_settings_replace_idle_agent() {
+    let _env = ENV_LOCK.lock().await;
+    let mut h = Harness::with_config(two_turn_script, persistent(300)).await;
+
+    h.gateway.send_user_message("First", vec![]).await.unwrap();
+    assert_eq!(h.main_turn_text().await, "One.");
+    settle().await;
+    let mut config = h.gateway.get_config();
+    config.agent.thinking_level = "high".to_string();
+    h.gateway.update_config(config).unwrap();
+    h.gateway.send_user_message("Second", vec![]).await.unwrap();
+    assert_eq!(h.main_turn_text().await, "Two.");
+
+    assert_eq!(spawned_pids(&h).len(), 2);
+    assert!(h.audits("Reusing idle agent").is_empty());
+}
+
+#[tokio::test]
+async fn test_idle_agent_exits_after_keep_alive() {
+    let _env = ENV_LOCK.lock().await;
+    let mut h = Harness::with_config(two_turn_script, persistent(1)).await;
+
+    h.gateway.send_user_message("First", vec![]).await.unwrap();
+    assert_eq!(h.main_turn_text().await, "One.");
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    h.gateway.send_user_message("Second", vec![]).await.unwrap();
+    assert_eq!(h.main_turn_text().await, "Two.");
+
+    let pids = spawned_pids(&h);
+    assert_eq!(pids.len(), 2);
+    assert!(h.audits("Reusing idle agent").is_empty());
+    #[cfg(unix)]
+    assert!(!process_alive(pids[0]), "idle agent {} outlived its keep-alive", pids[0]);
+}
+
+#[tokio::test]
+async fn test_changed_history_is_resent_to_idle_agent() {
+    let _env = ENV_LOCK.lock().await;
+    let mut h = Harness::with_config(two_turn_script, persistent(300)).await;
+
+    h.gateway.send_user_message("First", vec![]).await.unwrap();
+    assert_eq!(h.main_turn_text().await, "One.");
+    settle().await;
+    // Another client adds to the session while the agent is idle.
+    let session = h.gateway.get_main_session().await.unwrap();
+    let note = bat_types::message::Message::assistant(session.id, "Noted from another device.".to_string());
+    h.db.append_message(&note).unwrap();
+    h.gateway.send_user_message("Second", vec![]).await.unwrap();
+    assert_eq!(h.main_turn_text().await, "Two.");
+
+    assert_eq!(spawned_pids(&h).len(), 1);
+    assert_eq!(h.audits("Reusing idle agent").len(), 1);
+    let second = &h.requests()[1]["request"]["messages"];
+    assert!(second.to_string().contains("Noted from another device."), "{second}");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_dead_idle_agent_is_replaced() {
+    let _env = ENV_LOCK.lock().await;
+    let mut h = Harness::with_config(two_turn_script, persistent(300)).await;
+
+    h.gateway.send_user_message("First", vec![]).await.unwrap();
+    assert_eq!(h.main_turn_text().await, "One.");
+    settle().await;
+    let pid = spawned_pids(&h)[0];
+    std::process::Command::new("kill").args(["-9", &pid.to_string()]).status().unwrap();
+    settle().await;
+    h.gateway.send_user_message("Second", vec![]).await.unwrap();
+    assert_eq!(h.main_turn_text().await, "Two.");
+
+    assert_eq!(spawned_pids(&h).len(), 2);
+    assert!(h.audits("Reusing idle agent").is_empty());
+}
+
+/// How `assert_subagent_commands_die` stops the subagent.
+#[cfg(unix)]
+enum Stop {
+    Cancel,
+    /// An agent that dies mid-command can't clean up; the gateway has to.
+    Kill,
+}
+
+/// Start a subagent running `sleep 30 & sleep 30`, stop it, and check that
+/// neither sleep survives.
+#[cfg(unix)]
+async fn assert_subagent_commands_die(stop: Stop) {
+    let _env = ENV_LOCK.lock().await;
+    let h = Harness::with_config(
+        |dir| {
+            let pids = dir.join("pids");
+            json!([
+                { "when": "Start the servers", "tool_calls": [{
+                    "name": "session_spawn",
+                    "input": { "task": "Run the servers", "label": "servers" }
+                }]},
+                { "when": "Subagent spawned and running", "text": "On it." },
+                { "when": "Run the servers", "tool_calls": [{
+                    "name": "shell_run",
+                    "input": {
+                        "command": format!(
+                            "sleep 30 & echo $! >> {p}; sleep 30 & echo $! >> {p}; wait",
+                            p = pids.display()
+                        ),
+                        "timeout": 60,
+                    }
+                }]},
+            ])
+        },
+        |config| config.permissions = bat_types::config::PermissionsConfig::allow_all(),
+    )
+    .await;
+    let pids_path = h.dir.join("pids");
+
+    h.gateway.send_user_message("Start the servers", vec![]).await.unwrap();
+    let pids = wait_until(TIMEOUT, || {
+        let pids: Vec<u32> = std::fs::read_to_string(&pids_path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| line.parse().ok())
+            .collect();
+        (pids.len() == 2).then_some(pids)
+    })
+    .await;
+    assert!(pids.iter().all(|&pid| process_alive(pid)));
+
+    let subagent = h.gateway.get_subagents().await.unwrap().remove(0);
+    match stop {
+        Stop::Cancel => h.gateway.cancel_subagent(subagent.session_id).await.unwrap(),
+        Stop::Kill => {
+            let pid = *spawned_pids(&h).last().unwrap();
+            std::process::Command::new("kill").args(["-9", &pid.to_string()]).status().unwrap();
+        }
+    }
+    // Well before the sleeps would end on their own.
+    wait_until(Duration::from_secs(10), || pids.iter().all(|&pid| !process_alive(pid)).then_some(())).await;
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_cancelled_subagent_leaves_no_commands_behind() {
+    assert_subagent_commands_die(Stop::Cancel).await;
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_killed_subagent_leaves_no_commands_behind() {
+    assert_subagent_commands_die(Stop::Kill).await;
+}
+
+/// Poll `check` until it returns something, for at most `timeout`.
+async fn wait_until<T>(timeout: Duration, mut check: impl FnMut() -> Option<T>) -> T {
+    let wait = async {
+        loop {
+            if let Some(found) = check() {
+                return found;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    };
+    tokio::time::timeout(timeout, wait).await.expect("timed out waiting")
+}
+
+/// Whether `pid` is running. A zombie waiting to be reaped counts as dead.
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    if let Ok(stat) = std::fs::read_to_string(format!("/proc/{pid}/stat")) {
+        // The state follows the parenthesised command name.
+        return stat.rsplit(')').next().and_then(|rest| rest.trim_start().chars().next()) != Some('Z');
+    }
+    std::process::Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
@@ -0,0 +1,260 @@
+//! Pending tool-call approvals.
+//!
+//! An agent asks before running a tool in "ask" mode. The request is
+//! broadcast on the event bus so every front end (TUI, desktop, Telegram)
+//! can show it; whichever answers first wins.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::oneshot;
+use tracing::info;
+use uuid::Uuid;
+
+use bat_types::approval::{describe_tool_call, ApprovalDecision, ApprovalRequest};
+use bat_types::audit::{AuditCategory, AuditLevel};
+use bat_types::ipc::AgentToGateway;
+
+use crate::db::Database;
+use crate::events::EventBus;
+
+#[derive(Default)]
+struct State {
+    pending: HashMap<String, (ApprovalRequest, oneshot::Sender<ApprovalDecision>)>,
+    /// Tools the user approved for the rest of a session.
+    session_grants: HashMap<Uuid, HashSet<String>>,
+}
+
+/// Shared registry of approvals waiting for an answer.
+#[derive(Clone, Default)]
+pub struct Approvals {
+    state: Arc<Mutex<State>>,
+}
+
+impl Approvals {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask the user whether `tool` may run with `input`, waiting up to
+    /// `timeout` for an answer. No answer counts as a denial.
+    pub async fn request(
+        &self,
+        db: &Database,
+        event_bus: &EventBus,
+        session_id: Uuid,
+        tool: &str,
+        input: serde_json::Value,
+        timeout: Duration,
+    ) -> ApprovalDecision {
+        let sid = session_id.to_string();
+        let summary = describe_tool_call(tool, &input);
+        let granted = self
+            .state
+            .lock()
+            .unwrap()
+            .session_grants
+            .get(&session_id)
+            .is_some_and(|tools| tools.contains(tool));
+        if granted {
+            crate::audit(db, event_bus, AuditLevel::Info, AuditCategory::Tool, "approval_granted",
+                &format!("Approved for this session: {summary}"), Some(&sid),
+                Some(&serde_json::json!({ "tool": tool, "input": input, "decision": "session_grant" }).to_string()));
+            return ApprovalDecision::ApproveOnce;
+        }
+
+        let request = ApprovalRequest {
+            id: Uuid::new_v4().to_string(),
+            session_id,
+            tool: tool.to_string(),
+            input,
+            summary,
+            requested_at: chrono::Utc::now(),
+        };
+        let (tx, rx) = oneshot::channel();
+        self.state.lock().unwrap().pending.insert(request.id.clone(), (request.clone(), tx));
+        crate::audit(db, event_bus, AuditLevel::Info, AuditCategory::Tool, "approval_requested",
+            &format!("Approval requested: {}", request.summary), Some(&sid),
+            Some(&serde_json::json!({ "approval_id": request.id, "tool": tool, "input": request.input }).to_string()));
+        let id = request.id.clone();
+        event_bus.send(AgentToGateway::ApprovalRequested { request });
+
+        match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(decision)) => decision,
+            // Withdrawn: the turn ended while waiting.
+            Ok(Err(_)) => ApprovalDecision::Deny { reason: Some("The request was withdrawn".to_string()) },
+            Err(_) => {
+                let decision = ApprovalDecision::Deny {
+                    reason: Some(format!("No answer within {} seconds", timeout.as_secs())),
+                };
+                if let Some((request, _)) = self.state.lock().unwrap().pending.remove(&id) {
+                    crate::audit(db, event_bus, AuditLevel::Warn, AuditCategory::Tool, "approval_expired",
+                        &format!("Approval expired: {}", request.summary), Some(&sid),
+                        Some(&serde_json::json!({ "approval_id": id, "tool": request.tool }).to_string()));
+                    event_bus.send(AgentToGateway::ApprovalResolved { approval_id: id, decision: decision.clone() });
+                }
+                decision
+            }
+        }
+    }
+
+    /// Answer a pending request. Fails if it was already answered or expired.
+    pub fn resolve(&self, db: &Database, event_bus: &EventBus, id: &str, decision: ApprovalDecision) -> anyhow::Result<()> {
+        let (request, tx) = {
+            let mut state = self.state.lock().unwrap();
+            let (request, tx) = state
+                .pending
+                .remove(id)
+                .ok_or_else(|| anyhow::anyhow!("No pending approval with id '{id}'"))?;
+            if decision == ApprovalDecision::ApproveSession {
+                state.session_grants.entry(request.session_id).or_default().insert(request.tool.clone());
+            }
+            (request, tx)
+        };
+        info!("Approval {id} for {}: {:?}", request.tool, decision);
+
+        let (level, event, summary) = match &decision {
+            ApprovalDecision::ApproveOnce => (AuditLevel::Info, "approval_granted", format!("Approved: {}", request.summary)),
+            ApprovalDecision::ApproveSession => (AuditLevel::Info, "approval_granted",
+                format!("Approved for this session: {}", request.summary)),
+            ApprovalDecision::Deny { reason: Some(reason) } => (AuditLevel::Warn, "approval_denied",
+                format!("Denied: {} ({reason})", request.summary)),
+            ApprovalDecision::Deny { reason: None } => (AuditLevel::Warn, "approval_denied", format!("Denied: {}", request.summary)),
+        };
+        let detail = serde_json::json!({ "approval_id": id, "tool": request.tool, "input": request.input, "decision": decision });
+        crate::audit(db, event_bus, level, AuditCategory::Tool, event, &summary,
+            Some(&request.session_id.to_string()), Some(&detail.to_string()));
+
+        let _ = tx.send(decision.clone());
+        event_bus.send(AgentToGateway::ApprovalResolved { approval_id: id.to_string(), decision });
+        Ok(())
+    }
+
+    /// Requests still waiting for an answer, oldest first.
+    pub fn pending(&self) -> Vec<ApprovalRequest> {
+        let mut pending: Vec<_> = self.state.lock().unwrap().pending.values().map(|(r, _)| r.clone()).collect();
+        pending.sort_by_key(|r| r.requested_at);
+        pending
+    }
+
+    /// The pending request whose id starts with `prefix`, or the only one
+    /// pending when no prefix is given. Used by text front ends like Telegram.
+    pub fn find(&self, prefix: Option<&str>) -> anyhow::Result<ApprovalRequest> {
+        let pending = self.pending();
+        let matches: Vec<_> = match prefix {
+            Some(prefix) => pending.into_iter().filter(|r| r.id.starts_with(prefix)).collect(),
+            None => pending,
+        };
+        match matches.len() {
+            0 => anyhow::bail!("No matching approval is pending"),
+            1 => Ok(matches.into_iter().next().unwrap()),
+            n => anyhow::bail!("{n} approvals are pending — give the id of the one you mean"),
+        }
+    }
+
+    /// Drop the session's unanswered requests, e.g. when its turn ends.
+    /// Their waiters see the request as withdrawn.
+    pub fn withdraw_session(&self, event_bus: &EventBus, session_id: Uuid) {
+        let withdrawn: Vec<String> = {
+            let mut state = self.state.lock().unwrap();
+            let ids: Vec<String> = state
+                .pending
+                .iter()
+                .filter(|(_, (r, _))| r.session_id == session_id)
+                .map(|(id, _)| id.clone())
+                .collect();
+            ids.into_iter().filter(|id| state.pending.remove(id).is_some()).collect()
+        };
+        for approval_id in withdrawn {
+            event_bus.send(AgentToGateway::ApprovalResolved {
+                approval_id,
+                decision: ApprovalDecision::Deny { reason: Some("The request was withdrawn".to_string()) },
+            });
+        }
+    }
+}
+
+/// Parse a chat command answering an approval: `/approve [id]`,
+/// `/approve_session [id]` or `/deny [id] [reason]`. Returns the id prefix,
+/// if one was given, and the decision.
+pub fn parse_command(text: &str) -> Option<(Option<String>, ApprovalDecision)> {
+    let mut words = text.split_whitespace();
+    // Telegram appends the bot's name to commands in group chats.
+    let command = words.next()?.split('@').next()?;
+    let mut rest: Vec<&str> = words.collect();
+    let id = match rest.first() {
+        Some(word) if word.len() >= 4 && word.chars().all(|c| c.is_ascii_hexdigit() || c == '-') => {
+            Some(rest.remove(0).to_string())
+        }
+        _ => None,
+    };
+    let decision = match command {
+        "/approve" => ApprovalDecision::ApproveOnce,
+        "/approve_session" => ApprovalDecision::ApproveSession,
+        "/deny" => {
+            let reason = rest.join(" ");
+            ApprovalDecision::Deny { reason: (!reason.is_empty()).then_some(reason) }
+        }
+        _ => return None,
+    };
+    Some((id, decision))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(parse_command("/approve"), Some((None, ApprovalDecision::ApproveOnce)));
+        assert_eq!(
+            parse_command("/approve_session@bat_bot 1f2e3d4c"),
+            Some((Some("1f2e3d4c".to_string()), ApprovalDecision::ApproveSession))
+        );
+        assert_eq!(
+            parse_command("/deny 1f2e3d4c not on main"),
+            Some((Some("1f2e3d4c".to_string()), ApprovalDecision::Deny { reason: Some("not on main".to_string()) }))
+        );
+        assert_eq!(
+            parse_command("/deny too risky"),
+            Some((None, ApprovalDecision::Deny { reason: Some("too risky".to_string()) }))
+        );
+        assert_eq!(parse_command("approve it"), None);
+    }
+
+    #[tokio::test]
+    async fn test_session_grant_skips_later_prompts() {
+        let db = Arc::new(Database::open_in_memory().unwrap());
+        let event_bus = EventBus::new();
+        let approvals = Approvals::new();
+        let session_id = Uuid::new_v4();
+        let input = serde_json::json!({ "command": "ls" });
+
+        let waiting = {
+            let (approvals, db, event_bus, input) = (approvals.clone(), db.clone(), event_bus.clone(), input.clone());
+            tokio::spawn(async move {
+                approvals.request(&db, &event_bus, session_id, "shell_run", input, Duration::from_secs(5)).await
+            })
+        };
+        let request = loop {
+            if let Ok(request) = approvals.find(None) {
+                break request;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        };
+        assert_eq!(request.summary, "shell_run: ls");
+        approvals.resolve(&db, &event_bus, &request.id, ApprovalDecision::ApproveSession).unwrap();
+        assert_eq!(waiting.await.unwrap(), ApprovalDecision::ApproveSession);
+        assert!(approvals.resolve(&db, &event_bus, &request.id, ApprovalDecision::ApproveOnce).is_err());
+
+        // Same tool, same session: no prompt. Another tool still asks and times out.
+        let decision = approvals.request(&db, &event_bus, session_id, "shell_run", input, Duration::from_secs(5)).await;
+        assert!(decision.is_approved());
+        let decision = approvals
+            .request(&db, &event_bus, session_id, "app_open", serde_json::json!({}), Duration::from_millis(20))
+            .await;
+        assert!(!decision.is_approved());
+        assert!(approvals.pending().is_empty());
+    }
+}
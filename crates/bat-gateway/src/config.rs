@@ -52,6 +52,9 @@ pub fn load_config() -> Result<BatConfig> {
     let mut config: BatConfig = toml::from_str(&contents)
         .with_context(|| format!("Failed to parse config at {}", path.display()))?;
     config.migrate_legacy_keys();
+    for model in &config.models {
+        model.validate().with_context(|| format!("Invalid [[models]] entry in {}", path.display()))?;
+    }
     Ok(config)
 }
 
@@ -109,4 +112,14 @@ mod tests {
         let parsed: BatConfig = toml::from_str(&toml_str).unwrap();
         assert_eq!(parsed.agent.name, "Aria");
     }
+
+    #[test]
+    fn sandbox_without_persistent_fields_uses_defaults() {
+        let sandbox: bat_types::config::SandboxConfig = toml::from_str(
+            "memory_limit_mb = 512\ncpu_shares = 512\nmax_concurrent_subagents = 5\n",
+        )
+        .unwrap();
+        assert!(!sandbox.persistent_agents);
+        assert_eq!(sandbox.agent_keep_alive_secs, 300);
+    }
 }
@@ -0,0 +1,393 @@
+//! Context-window management — keeps the history sent to an agent under the
+//! model's context limit by folding older turns into a rolling summary.
+//!
+//! A summary is persisted as a `Role::System` message timestamped between the
+//! last compacted message and the first one kept, so `get_history` returns it
+//! in place. Messages before the latest summary stay in the database for
+//! display but are no longer sent to the agent.
+
+use anyhow::{Context, Result};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use bat_types::audit::{AuditCategory, AuditLevel};
+use bat_types::config::{ContextConfig, LlmProvider};
+use bat_types::message::{Message, Role};
+use bat_types::models::{ModelInfo, IMAGE_TOKENS};
+
+use crate::db::Database;
+use crate::events::EventBus;
+
+/// First line of every persisted summary message.
+const SUMMARY_HEADER: &str = "Summary of the earlier conversation:";
+
+/// Per-message framing overhead (role markers, block separators).
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+
+/// Each message is cut to this many characters in the summariser's transcript.
+const TRANSCRIPT_MESSAGE_CHARS: usize = 4000;
+
+/// Tool results are cut to this many characters in the summariser's transcript.
+const TRANSCRIPT_RESULT_CHARS: usize = 500;
+
+/// Estimate the token count of a piece of text.
+pub fn estimate_text_tokens(model: &ModelInfo, text: &str) -> usize {
+    (text.chars().count() as f64 / model.chars_per_token()).ceil() as usize
+}
+
+/// Estimate the tokens a stored message takes up when replayed. Tool results
+/// count only up to `result_cap` characters, matching what the agent replays.
+pub fn estimate_message_tokens(model: &ModelInfo, msg: &Message, result_cap: usize) -> usize {
+    let mut chars = msg.content.chars().count();
+    for call in &msg.tool_calls {
+        chars += call.name.len() + call.input.to_string().len();
+    }
+    for result in &msg.tool_results {
+        chars += result.content.chars().count().min(result_cap);
+    }
+    (chars as f64 / model.chars_per_token()).ceil() as usize
+        + msg.images.len() * IMAGE_TOKENS
+        + MESSAGE_OVERHEAD_TOKENS
+}
+
+fn estimate_history_tokens(model: &ModelInfo, history: &[Message], result_cap: usize) -> usize {
+    history.iter().map(|m| estimate_message_tokens(model, m, result_cap)).sum()
+}
+
+/// The part of a session's history the agent should see: the latest summary
+/// and everything after it.
+pub fn active_history(history: &[Message]) -> &[Message] {
+    let start = history.iter().rposition(|m| m.role == Role::System).unwrap_or(0);
+    &history[start..]
+}
+
+/// Folds older turns into summaries: where they are persisted and audited,
+/// when compaction kicks in, and the key used to summarise with an LLM.
+pub struct Compactor<'a> {
+    pub db: &'a Database,
+    pub event_bus: &'a EventBus,
+    pub cfg: &'a ContextConfig,
+    pub anthropic_key: Option<&'a str>,
+}
+
+impl Compactor<'_> {
+    /// Compact the session if its history would push the prompt past the
+    /// configured share of the model's context window. Returns the history
+    /// to send to the agent.
+    ///
+    /// `reserved_tokens` covers everything sent alongside the history (system
+    /// prompt, new user message).
+    pub async fn prepare_history(
+        &self,
+        model: &ModelInfo,
+        session_id: Uuid,
+        history: Vec<Message>,
+        reserved_tokens: usize,
+        result_cap: usize,
+    ) -> Result<Vec<Message>> {
+        let active = active_history(&history).to_vec();
+        if !self.cfg.auto_compact {
+            return Ok(active);
+        }
+        let budget = model.context_window * self.cfg.compact_at_percent.min(100) as usize / 100;
+        self.compact(model, session_id, active, reserved_tokens, result_cap, budget).await
+    }
+
+    async fn compact(
+        &self,
+        model: &ModelInfo,
+        session_id: Uuid,
+        active: Vec<Message>,
+        reserved_tokens: usize,
+        result_cap: usize,
+        budget: usize,
+    ) -> Result<Vec<Message>> {
+        let Compactor { db, event_bus, cfg, anthropic_key } = self;
+        let tokens_before = reserved_tokens + estimate_history_tokens(model, &active, result_cap);
+        if tokens_before <= budget {
+            return Ok(active);
+        }
+
+        let Some(split) = split_index(model, &active, cfg.keep_recent_turns, budget.saturating_sub(reserved_tokens), result_cap) else {
+            warn!("Session {session_id} is over its context budget (~{tokens_before} tokens) but has no older turns to compact");
+            return Ok(active);
+        };
+        let (compacted, kept) = active.split_at(split);
+
+        let summary = match anthropic_key.filter(|k| !k.is_empty() && model.provider == LlmProvider::Anthropic) {
+            Some(key) => match summarise_with_llm(key, &model.id, compacted).await {
+                Ok(text) => text,
+                Err(e) => {
+                    warn!("LLM summary failed, falling back to extractive summary: {e}");
+                    summarise_extractive(compacted)
+                }
+            },
+            None => summarise_extractive(compacted),
+        };
+
+        let mut summary_msg = Message::system(session_id, format!("{SUMMARY_HEADER}\n\n{summary}"));
+        // Slot the summary between the compacted and kept messages.
+        let (last_compacted, first_kept) = (compacted[compacted.len() - 1].created_at, kept[0].created_at);
+        summary_msg.created_at = last_compacted + (first_kept - last_compacted) / 2;
+        db.append_message(&summary_msg).context("Failed to persist context summary")?;
+
+        let mut result = Vec::with_capacity(kept.len() + 1);
+        result.push(summary_msg);
+        result.extend_from_slice(kept);
+
+        let tokens_after = reserved_tokens + estimate_history_tokens(model, &result, result_cap);
+        info!("Compacted {} messages for session {session_id}: ~{tokens_before} → ~{tokens_after} tokens", compacted.len());
+        let detail = serde_json::json!({
+            "model": model.id,
+            "compacted_messages": compacted.len(),
+            "kept_messages": kept.len(),
+            "tokens_before": tokens_before,
+            "tokens_after": tokens_after,
+            "budget": budget,
+        })
+        .to_string();
+        crate::audit(db, event_bus, AuditLevel::Info, AuditCategory::Agent, "context_compacted",
+            &format!("Compacted {} older messages into a summary (~{tokens_before} → ~{tokens_after} tokens)", compacted.len()),
+            Some(&session_id.to_string()), Some(&detail));
+
+        Ok(result)
+    }
+}
+
+/// Where to split `active` so that at most `keep_turns` recent user turns
+/// (at least one) stay verbatim and, if possible, fit in `budget` tokens.
+/// Returns `None` if nothing before the kept turns can be compacted.
+fn split_index(model: &ModelInfo, active: &[Message], keep_turns: usize, budget: usize, result_cap: usize) -> Option<usize> {
+    // Turn boundaries: every user message starts a turn.
+    let turn_starts: Vec<usize> = active
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| m.role == Role::User)
+        .map(|(i, _)| i)
+        .collect();
+
+    let keep = keep_turns.clamp(1, turn_starts.len().max(1));
+    let mut candidates = turn_starts.iter().rev().take(keep).rev().copied();
+    // Prefer keeping as many turns as fit, dropping the oldest kept turns first.
+    let split = candidates
+        .by_ref()
+        .find(|&start| estimate_history_tokens(model, &active[start..], result_cap) <= budget)
+        .or_else(|| turn_starts.last().copied())?;
+
+    // Something other than a previous summary must be compacted.
+    let compactable = active[..split].iter().any(|m| m.role != Role::System);
+    compactable.then_some(split)
+}
+
+/// Render messages as a plain-text transcript for the summariser.
+fn transcript(messages: &[Message]) -> String {
+    let mut out = String::new();
+    for m in messages {
+        let label = match m.role {
+            Role::User => "USER",
+            Role::Assistant => "ASSISTANT",
+            Role::System => "PREVIOUS SUMMARY",
+        };
+        out.push_str(&format!("{label}: {}\n", truncate_chars(&m.content, TRANSCRIPT_MESSAGE_CHARS)));
+        for call in &m.tool_calls {
+            let result = m.tool_results.iter().find(|r| r.tool_call_id == call.id);
+            out.push_str(&format!("  [tool {} {}]", call.name, truncate_chars(&call.input.to_string(), TRANSCRIPT_RESULT_CHARS)));
+            if let Some(r) = result {
+                let status = if r.is_error { "error" } else { "result" };
+                out.push_str(&format!(" {status}: {}", truncate_chars(&r.content, TRANSCRIPT_RESULT_CHARS)));
+            }
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Ask the model for a summary of the compacted turns.
+async fn summarise_with_llm(api_key: &str, model: &str, messages: &[Message]) -> Result<String> {
+    let prompt = format!(
+        r#"The conversation below is being compacted to save context space. Write a summary that lets the assistant continue the conversation without the original messages.
+
+Keep:
+- The user's goals and any open requests
+- Decisions made and preferences stated
+- Files, paths and commands that were used, and what they showed
+- Errors hit and how they were resolved
+- Work still in progress
+
+If the transcript starts with a PREVIOUS SUMMARY, fold it into the new one.
+Respond with the summary only, as concise bullet points.
+
+---
+{}"#,
+        transcript(messages)
+    );
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post("https://api.anthropic.com/v1/messages")
+        .header("x-api-key", api_key)
+        .header("anthropic-version", "2023-06-01")
+        .header("content-type", "application/json")
+        .json(&serde_json::json!({
+            "model": model,
+            "max_tokens": 2048,
+            "messages": [{"role": "user", "content": prompt}]
+        }))
+        .send()
+        .await
+        .context("Summary API request failed")?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!("Summary API error ({status}): {body}");
+    }
+
+    let body: serde_json::Value = response.json().await.context("Failed to parse summary response")?;
+    let text = body["content"][0]["text"].as_str().unwrap_or("").trim();
+    if text.is_empty() {
+        anyhow::bail!("Summary API returned no text");
+    }
+    Ok(text.to_string())
+}
+
+/// Summary built without an LLM: the previous summary plus one line per message.
+fn summarise_extractive(messages: &[Message]) -> String {
+    let mut lines = Vec::new();
+    for m in messages {
+        let line = match m.role {
+            Role::System => {
+                let previous = m.content.strip_prefix(SUMMARY_HEADER).unwrap_or(&m.content).trim();
+                lines.push(previous.to_string());
+                continue;
+            }
+            Role::User => format!("- User: {}", truncate_chars(&m.content, 200)),
+            Role::Assistant => format!("- Assistant: {}", truncate_chars(&m.content, 200)),
+        };
+        lines.push(line);
+        if !m.tool_calls.is_empty() {
+            let names: Vec<&str> = m.tool_calls.iter().map(|c| c.name.as_str()).collect();
+            lines.push(format!("  (used tools: {})", names.join(", ")));
+        }
+    }
+    lines.join("\n")
+}
+
+fn truncate_chars(s: &str, max: usize) -> String {
+    let mut chars = s.chars();
+    let head: String = chars.by_ref().take(max).collect();
+    if chars.next().is_some() {
+        format!("{head}…")
+    } else {
+        head
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bat_types::message::{ToolCall, ToolResult};
+    use bat_types::models::ModelRegistry;
+
+    fn model(name: &str) -> ModelInfo {
+        ModelRegistry::builtin().resolve(name)
+    }
+
+    fn turn(session_id: Uuid, n: usize, size: usize) -> Vec<Message> {
+        vec![
+            Message::user(session_id, format!("question {n} {}", "x".repeat(size))),
+            Message::assistant(session_id, format!("answer {n} {}", "y".repeat(size))),
+        ]
+    }
+
+    #[test]
+    fn test_context_window_by_model() {
+        assert_eq!(model("claude-sonnet-4-6").context_window, 200_000);
+        assert_eq!(model("anthropic/claude-opus-4").context_window, 200_000);
+        assert_eq!(model("gpt-4o-mini").context_window, 128_000);
+        assert_eq!(model("llama3").context_window, 8_192);
+    }
+
+    #[test]
+    fn test_estimate_caps_tool_results() {
+        let sid = Uuid::new_v4();
+        let mut msg = Message::assistant(sid, "");
+        msg.tool_calls = vec![ToolCall { id: "t1".into(), name: "fs_read".into(), input: serde_json::json!({}) }];
+        msg.tool_results = vec![ToolResult { tool_call_id: "t1".into(), content: "z".repeat(100_000), is_error: false }];
+        let capped = estimate_message_tokens(&model("gpt-4o"), &msg, 4000);
+        let uncapped = estimate_message_tokens(&model("gpt-4o"), &msg, usize::MAX);
+        assert!(capped < 1100);
+        assert!(uncapped > 25_000);
+    }
+
+    #[test]
+    fn test_active_history_starts_at_latest_summary() {
+        let sid = Uuid::new_v4();
+        let mut history = turn(sid, 1, 0);
+        history.push(Message::system(sid, "summary"));
+        history.extend(turn(sid, 2, 0));
+        let active = active_history(&history);
+        assert_eq!(active.len(), 3);
+        assert_eq!(active[0].role, Role::System);
+        assert_eq!(active_history(&history[..2]).len(), 2);
+    }
+
+    #[test]
+    fn test_split_keeps_recent_turns() {
+        let sid = Uuid::new_v4();
+        let history: Vec<Message> = (0..6).flat_map(|n| turn(sid, n, 10)).collect();
+        assert_eq!(split_index(&model("gpt-4o"), &history, 2, usize::MAX, 4000), Some(8));
+        // Too tight for two turns — keep only the last one.
+        assert_eq!(split_index(&model("gpt-4o"), &history, 2, 15, 4000), Some(10));
+        // Nothing older than the kept turns.
+        assert_eq!(split_index(&model("gpt-4o"), &history[..4], 2, usize::MAX, 4000), None);
+    }
+
+    #[tokio::test]
+    async fn test_compaction_persists_summary_in_place() {
+        let db = Database::open_in_memory().unwrap();
+        let event_bus = EventBus::new();
+        let session = db.create_session("test", "llama3").unwrap();
+        let cfg = ContextConfig { keep_recent_turns: 1, ..Default::default() };
+
+        for n in 0..4 {
+            for msg in turn(session.id, n, 400) {
+                db.append_message(&msg).unwrap();
+                tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+            }
+        }
+        let history = db.get_history(session.id).unwrap();
+
+        let compactor = Compactor { db: &db, event_bus: &event_bus, cfg: &cfg, anthropic_key: None };
+        let active = compactor.compact(&model("llama3"), session.id, history, 0, 4000, 300)
+            .await
+            .unwrap();
+        assert_eq!(active.len(), 3);
+        assert_eq!(active[0].role, Role::System);
+        assert!(active[0].content.starts_with(SUMMARY_HEADER));
+        assert!(active[0].content.contains("question 0"));
+
+        // The stored history now has the summary just before the kept turn.
+        let stored = db.get_history(session.id).unwrap();
+        assert_eq!(stored.len(), 9);
+        assert_eq!(stored[6].role, Role::System);
+        assert_eq!(active_history(&stored).len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_no_compaction_under_budget() {
+        let db = Database::open_in_memory().unwrap();
+        let event_bus = EventBus::new();
+        let session = db.create_session("test", "llama3").unwrap();
+        let history: Vec<Message> = (0..3).flat_map(|n| turn(session.id, n, 10)).collect();
+
+        let cfg = ContextConfig::default();
+        let compactor = Compactor { db: &db, event_bus: &event_bus, cfg: &cfg, anthropic_key: None };
+        let active = compactor.compact(&model("llama3"), session.id, history, 0, 4000, 10_000)
+            .await
+            .unwrap();
+        assert_eq!(active.len(), 6);
+        assert!(db.get_history(session.id).unwrap().is_empty());
+    }
+}
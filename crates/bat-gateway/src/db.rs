@@ -8,8 +8,9 @@ use chrono::Utc;
 use bat_types::audit::{AuditCategory, AuditEntry, AuditFilter, AuditLevel, AuditStats, AuditLevelCounts, AuditCategoryCounts};
 use bat_types::memory::{Observation, ObservationFilter, ObservationKind, ObservationSummary};
 use bat_types::message::Message;
-use bat_types::session::{SessionKind, SessionMeta, SessionStatus, SubagentInfo, SubagentStatus};
-use bat_types::usage::{UsageStats, SessionUsage, ModelUsage, estimate_cost};
+use bat_types::session::{SessionKind, SessionMeta, SessionStatus, SubagentInfo, SubagentScope, SubagentStatus};
+use bat_types::models::ModelRegistry;
+use bat_types::usage::{UsageStats, SessionUsage, ModelUsage};
 use bat_types::policy::{PathPolicy, AccessLevel};
 
 pub struct Database {
@@ -112,6 +113,9 @@ impl Database {
 
         // Migration: add images_json column to messages (safe if it already exists)
         let _ = conn.execute("ALTER TABLE messages ADD COLUMN images_json TEXT NOT NULL DEFAULT '[]'", []);
+        let _ = conn.execute("ALTER TABLE messages ADD COLUMN token_thinking INTEGER", []);
+        let _ = conn.execute("ALTER TABLE messages ADD COLUMN token_cache_read INTEGER", []);
+        let _ = conn.execute("ALTER TABLE messages ADD COLUMN token_cache_write INTEGER", []);
 
         // Metadata key-value store for tracking things like last consolidation
         conn.execute_batch(
@@ -128,6 +132,9 @@ impl Database {
         let _ = conn.execute("ALTER TABLE sessions ADD COLUMN task TEXT", []);
         let _ = conn.execute("ALTER TABLE sessions ADD COLUMN subagent_status TEXT", []);
         let _ = conn.execute("ALTER TABLE sessions ADD COLUMN summary TEXT", []);
+        let _ = conn.execute("ALTER TABLE sessions ADD COLUMN token_cache_read INTEGER NOT NULL DEFAULT 0", []);
+        let _ = conn.execute("ALTER TABLE sessions ADD COLUMN token_cache_write INTEGER NOT NULL DEFAULT 0", []);
+        let _ = conn.execute("ALTER TABLE sessions ADD COLUMN scope_json TEXT", []);
 
         Ok(())
     }
@@ -249,13 +256,14 @@ impl Database {
     }
 
     /// Get token usage statistics across all sessions.
-    pub fn get_usage_stats(&self) -> Result<UsageStats> {
+    pub fn get_usage_stats(&self, models: &ModelRegistry) -> Result<UsageStats> {
         let conn = self.conn.lock().unwrap();
 
         // Per-session usage
         let mut stmt = conn.prepare(
             "SELECT s.key, s.model, s.token_input, s.token_output, s.updated_at,
-                    (SELECT COUNT(*) FROM messages WHERE session_id = s.id) as msg_count
+                    (SELECT COUNT(*) FROM messages WHERE session_id = s.id) as msg_count,
+                    s.token_cache_read, s.token_cache_write
              FROM sessions s WHERE s.kind = 'main' ORDER BY s.updated_at DESC"
         )?;
         let sessions: Vec<SessionUsage> = stmt.query_map([], |row| {
@@ -266,12 +274,14 @@ impl Database {
                 token_output: row.get(3)?,
                 last_active: row.get(4)?,
                 message_count: row.get(5)?,
+                token_cache_read: row.get(6)?,
+                token_cache_write: row.get(7)?,
             })
         })?.filter_map(|r| r.ok()).collect();
 
         // Per-model usage
         let mut model_stmt = conn.prepare(
-            "SELECT model, SUM(token_input), SUM(token_output), COUNT(*)
+            "SELECT model, SUM(token_input), SUM(token_output), COUNT(*), SUM(token_cache_read), SUM(token_cache_write)
              FROM sessions GROUP BY model"
         )?;
         let by_model: Vec<ModelUsage> = model_stmt.query_map([], |row| {
@@ -280,24 +290,34 @@ impl Database {
                 token_input: row.get::<_, i64>(1)?,
                 token_output: row.get::<_, i64>(2)?,
                 session_count: row.get(3)?,
+                token_cache_read: row.get::<_, i64>(4)?,
+                token_cache_write: row.get::<_, i64>(5)?,
             })
         })?.filter_map(|r| r.ok()).collect();
 
         // Totals
         let total_input: i64 = sessions.iter().map(|s| s.token_input).sum();
         let total_output: i64 = sessions.iter().map(|s| s.token_output).sum();
+        let total_cache_read: i64 = sessions.iter().map(|s| s.token_cache_read).sum();
+        let total_cache_write: i64 = sessions.iter().map(|s| s.token_cache_write).sum();
 
         // Estimated cost
         let estimated_cost_usd: f64 = by_model.iter()
-            .map(|m| estimate_cost(&m.model, m.token_input, m.token_output))
+            .map(|m| models.resolve(&m.model).estimate_cost(m.token_input, m.token_output, m.token_cache_read, m.token_cache_write))
+            .sum();
+        let cache_savings_usd: f64 = by_model.iter()
+            .map(|m| models.resolve(&m.model).cache_savings(m.token_cache_read, m.token_cache_write))
             .sum();
 
         Ok(UsageStats {
             total_input,
             total_output,
+            total_cache_read,
+            total_cache_write,
             sessions,
             by_model,
             estimated_cost_usd,
+            cache_savings_usd,
         })
     }
 
@@ -311,6 +331,16 @@ impl Database {
         Ok(())
     }
 
+    pub fn update_cache_usage(&self, session_id: Uuid, cache_read: i64, cache_write: i64) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE sessions SET token_cache_read = token_cache_read + ?1, token_cache_write = token_cache_write + ?2
+             WHERE id = ?3",
+            params![cache_read, cache_write, session_id.to_string()],
+        )?;
+        Ok(())
+    }
+
     // --- Messages ---
 
     pub fn append_message(&self, msg: &Message) -> Result<()> {
@@ -319,8 +349,8 @@ impl Database {
         let tool_results_json = serde_json::to_string(&msg.tool_results)?;
         let images_json = serde_json::to_string(&msg.images)?;
         conn.execute(
-            "INSERT INTO messages (id, session_id, role, content, tool_calls_json, tool_results_json, images_json, token_input, token_output, created_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            "INSERT INTO messages (id, session_id, role, content, tool_calls_json, tool_results_json, images_json, token_input, token_output, token_thinking, token_cache_read, token_cache_write, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
             params![
                 msg.id.to_string(),
                 msg.session_id.to_string(),
@@ -331,6 +361,9 @@ impl Database {
                 images_json,
                 msg.token_input,
                 msg.token_output,
+                msg.token_thinking,
+                msg.token_cache_read,
+                msg.token_cache_write,
                 msg.created_at.to_rfc3339(),
             ],
         )?;
@@ -340,7 +373,7 @@ impl Database {
     pub fn get_history(&self, session_id: Uuid) -> Result<Vec<Message>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, session_id, role, content, tool_calls_json, tool_results_json, token_input, token_output, created_at, images_json
+            "SELECT id, session_id, role, content, tool_calls_json, tool_results_json, token_input, token_output, created_at, images_json, token_thinking, token_cache_read, token_cache_write
              FROM messages WHERE session_id = ?1 ORDER BY created_at ASC"
         )?;
         let rows = stmt.query_map(params![session_id.to_string()], |row| {
@@ -355,6 +388,9 @@ impl Database {
                 token_output: row.get(7)?,
                 created_at: row.get(8)?,
                 images_json: row.get::<_, Option<String>>(9)?.unwrap_or_else(|| "[]".to_string()),
+                token_thinking: row.get(10)?,
+                token_cache_read: row.get(11)?,
+                token_cache_write: row.get(12)?,
             })
         })?;
         let mut messages = Vec::new();
@@ -370,6 +406,9 @@ impl Database {
                 tool_results: serde_json::from_str(&r.tool_results_json)?,
                 token_input: r.token_input,
                 token_output: r.token_output,
+                token_thinking: r.token_thinking,
+                token_cache_read: r.token_cache_read,
+                token_cache_write: r.token_cache_write,
                 created_at: chrono::DateTime::parse_from_rfc3339(&r.created_at)?.with_timezone(&Utc),
             });
         }
@@ -396,6 +435,7 @@ impl Database {
                 "read-only" => AccessLevel::ReadOnly,
                 "read-write" => AccessLevel::ReadWrite,
                 "write-only" => AccessLevel::WriteOnly,
+                "deny" => AccessLevel::Deny,
                 _ => continue,
             };
             policies.push(PathPolicy {
@@ -404,6 +444,7 @@ impl Database {
                 access,
                 recursive,
                 description: desc,
+                layer: 0,
             });
         }
         Ok(policies)
@@ -415,6 +456,7 @@ impl Database {
             AccessLevel::ReadOnly => "read-only",
             AccessLevel::ReadWrite => "read-write",
             AccessLevel::WriteOnly => "write-only",
+            AccessLevel::Deny => "deny",
         };
         conn.execute(
             "INSERT INTO path_policies (path, access, recursive, description, created_at)
@@ -814,27 +856,32 @@ struct MessageRow {
     images_json: String,
     token_input: Option<i64>,
     token_output: Option<i64>,
+    token_thinking: Option<i64>,
+    token_cache_read: Option<i64>,
+    token_cache_write: Option<i64>,
     created_at: String,
 }
 
 impl Database {
-    /// Create a subagent session.
+    /// Create a subagent session, recording what it was narrowed to.
     pub fn create_subagent_session(
         &self,
         parent_id: Uuid,
         model: &str,
         label: &str,
         task: &str,
+        scope: &SubagentScope,
     ) -> Result<SessionMeta> {
         let conn = self.conn.lock().unwrap();
         let id = Uuid::new_v4();
         let key = format!("subagent:{}", &id.to_string()[..8]);
         let now = Utc::now();
         let now_str = now.to_rfc3339();
+        let scope_json = if scope.is_empty() { None } else { Some(serde_json::to_string(scope)?) };
         conn.execute(
-            "INSERT INTO sessions (id, key, model, status, token_input, token_output, created_at, updated_at, kind, parent_id, label, task, subagent_status)
-             VALUES (?1, ?2, ?3, 'active', 0, 0, ?4, ?4, 'subagent', ?5, ?6, ?7, 'running')",
-            params![id.to_string(), key, model, now_str, parent_id.to_string(), label, task],
+            "INSERT INTO sessions (id, key, model, status, token_input, token_output, created_at, updated_at, kind, parent_id, label, task, subagent_status, scope_json)
+             VALUES (?1, ?2, ?3, 'active', 0, 0, ?4, ?4, 'subagent', ?5, ?6, ?7, 'running', ?8)",
+            params![id.to_string(), key, model, now_str, parent_id.to_string(), label, task, scope_json],
         )?;
         Ok(SessionMeta {
             id,
@@ -857,7 +904,7 @@ impl Database {
     pub fn get_subagents(&self, parent_id: Uuid) -> Result<Vec<SubagentInfo>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, key, label, task, subagent_status, created_at, updated_at, summary, token_input, token_output
+            "SELECT id, key, label, task, subagent_status, created_at, updated_at, summary, token_input, token_output, scope_json
              FROM sessions WHERE parent_id = ?1 ORDER BY created_at DESC"
         )?;
         let rows = stmt.query_map(params![parent_id.to_string()], |row| {
@@ -872,12 +919,13 @@ impl Database {
                 row.get::<_, Option<String>>(7)?,
                 row.get::<_, i64>(8)?,
                 row.get::<_, i64>(9)?,
+                row.get::<_, Option<String>>(10)?,
             ))
         })?;
 
         let mut result = Vec::new();
         for row in rows {
-            let (id_str, key, label, task, status_str, created, _updated, summary, tok_in, tok_out) = row?;
+            let (id_str, key, label, task, status_str, created, _updated, summary, tok_in, tok_out, scope_json) = row?;
             let status = match status_str.as_deref() {
                 Some("completed") => SubagentStatus::Completed,
                 Some("failed") => SubagentStatus::Failed,
@@ -899,28 +947,30 @@ impl Database {
                 summary,
                 token_input: tok_in,
                 token_output: tok_out,
+                scope: scope_json.and_then(|s| serde_json::from_str(&s).ok()),
             });
         }
         Ok(result)
     }
 
-    /// Count currently running subagents (across all parent sessions).
+    /// Count live subagents (across all parent sessions). Paused subagents
+    /// still hold a process, so they count towards the limit.
     pub fn count_running_subagents(&self) -> Result<i64> {
         let conn = self.conn.lock().unwrap();
         let count: i64 = conn.query_row(
-            "SELECT COUNT(*) FROM sessions WHERE kind = 'subagent' AND subagent_status = 'running'",
+            "SELECT COUNT(*) FROM sessions WHERE kind = 'subagent' AND subagent_status IN ('running', 'paused')",
             [],
             |row| row.get(0),
         )?;
         Ok(count)
     }
 
-    /// Get running subagent sessions older than the given duration.
+    /// Get running or paused subagent sessions older than the given duration.
     pub fn get_timed_out_subagents(&self, timeout_minutes: u32) -> Result<Vec<(Uuid, String)>> {
         let conn = self.conn.lock().unwrap();
         let cutoff = (Utc::now() - chrono::Duration::minutes(timeout_minutes as i64)).to_rfc3339();
         let mut stmt = conn.prepare(
-            "SELECT id, key FROM sessions WHERE kind = 'subagent' AND subagent_status = 'running' AND created_at < ?1"
+            "SELECT id, key FROM sessions WHERE kind = 'subagent' AND subagent_status IN ('running', 'paused') AND created_at < ?1"
         )?;
         let rows = stmt.query_map(params![cutoff], |row| {
             Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
@@ -948,6 +998,20 @@ impl Database {
         Ok(result)
     }
 
+    /// Get the current status of a subagent session.
+    pub fn get_subagent_status(&self, session_id: Uuid) -> Result<Option<SubagentStatus>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT subagent_status FROM sessions WHERE id = ?1")?;
+        let mut rows = stmt.query(params![session_id.to_string()])?;
+        match rows.next()? {
+            Some(row) => {
+                let status: Option<String> = row.get(0)?;
+                Ok(status.and_then(|s| s.parse().ok()))
+            }
+            None => Ok(None),
+        }
+    }
+
     /// Update subagent status and optionally set summary.
     pub fn update_subagent_status(
         &self,
@@ -1062,6 +1126,19 @@ mod tests {
         assert_eq!(updated.token_output, 275);
     }
 
+    #[test]
+    fn test_cache_usage_in_stats() {
+        let db = Database::open_in_memory().unwrap();
+        let session = db.create_session("test", "claude-sonnet-4-6").unwrap();
+        db.update_token_usage(session.id, 1_000, 500).unwrap();
+        db.update_cache_usage(session.id, 1_000_000, 0).unwrap();
+
+        let stats = db.get_usage_stats(&ModelRegistry::builtin()).unwrap();
+        assert_eq!(stats.total_cache_read, 1_000_000);
+        assert_eq!(stats.by_model[0].token_cache_read, 1_000_000);
+        assert!((stats.cache_savings_usd - 2.7).abs() < 1e-9);
+    }
+
     #[test]
     fn test_path_policies() {
         let db = Database::open_in_memory().unwrap();
@@ -1071,6 +1148,7 @@ mod tests {
             access: AccessLevel::ReadWrite,
             recursive: true,
             description: Some("Test folder".to_string()),
+            layer: 0,
         };
         db.add_path_policy(&policy).unwrap();
 
@@ -1159,4 +1237,43 @@ mod tests {
         assert_eq!(summary.total_sessions, 1);
         assert_eq!(summary.top_tools.len(), 2);
     }
+
+    #[test]
+    fn test_paused_subagents_count_as_running() {
+        let db = Database::open_in_memory().unwrap();
+        let main = db.get_or_create_main("claude-opus").unwrap();
+        let a = db.create_subagent_session(main.id, "claude-opus", "a", "task a", &SubagentScope::default()).unwrap();
+        let b = db.create_subagent_session(main.id, "claude-opus", "b", "task b", &SubagentScope::default()).unwrap();
+        assert_eq!(db.count_running_subagents().unwrap(), 2);
+
+        db.update_subagent_status(a.id, SubagentStatus::Paused, None).unwrap();
+        assert_eq!(db.count_running_subagents().unwrap(), 2);
+
+        db.update_subagent_status(b.id, SubagentStatus::Completed, Some("done")).unwrap();
+        assert_eq!(db.count_running_subagents().unwrap(), 1);
+
+        let subs = db.get_subagents(main.id).unwrap();
+        let paused = subs.iter().find(|s| s.session_id == a.id).unwrap();
+        assert_eq!(paused.status, SubagentStatus::Paused);
+        assert_eq!(db.get_subagent_status(a.id).unwrap(), Some(SubagentStatus::Paused));
+        assert_eq!(db.get_subagent_status(main.id).unwrap(), None);
+    }
+
+    #[test]
+    fn test_subagent_scope_is_recorded() {
+        let db = Database::open_in_memory().unwrap();
+        let main = db.get_or_create_main("claude-opus").unwrap();
+        let scope = SubagentScope {
+            paths: Some(vec![bat_types::policy::PathGrant { path: "/home/u/Docs/Acme".into(), access: AccessLevel::ReadOnly }]),
+            tools: Some(vec!["fs_read".to_string(), "fs_list".to_string()]),
+            ..Default::default()
+        };
+        let narrowed = db.create_subagent_session(main.id, "claude-opus", "a", "task a", &scope).unwrap();
+        db.create_subagent_session(main.id, "claude-opus", "b", "task b", &SubagentScope::default()).unwrap();
+
+        let subs = db.get_subagents(main.id).unwrap();
+        let scopes: Vec<_> = subs.iter().map(|s| (s.session_id == narrowed.id, s.scope.clone())).collect();
+        assert!(scopes.contains(&(true, Some(scope))));
+        assert!(scopes.contains(&(false, None)));
+    }
 }
@@ -12,6 +12,7 @@ use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use uuid::Uuid;
 
 use bat_types::ipc::{AgentToGateway, GatewayToAgent};
+pub use bat_types::process::kill_process_tree;
 
 // ─── Platform-specific transport ──────────────────────────────────────────────
 
@@ -92,6 +93,8 @@ mod platform {
 pub struct AgentPipe {
     writer: platform::Writer,
     reader: platform::Reader,
+    /// Bytes of a partially received line, kept across `recv` calls.
+    buf: Vec<u8>,
 }
 
 impl AgentPipe {
@@ -105,9 +108,13 @@ impl AgentPipe {
     }
 
     /// Receive the next message from the agent. Returns None if the pipe closed.
+    ///
+    /// Cancel-safe: a partially read line stays buffered, so this can be used
+    /// as a `tokio::select!` branch.
     pub async fn recv(&mut self) -> Result<Option<AgentToGateway>> {
-        let mut line = String::new();
-        let n = self.reader.read_line(&mut line).await?;
+        let n = self.reader.read_until(b'\n', &mut self.buf).await?;
+        let line = String::from_utf8_lossy(&self.buf).into_owned();
+        self.buf.clear();
         if n == 0 {
             return Ok(None);
         }
@@ -130,10 +137,11 @@ pub fn create_pipe_server(session_id: Uuid) -> Result<(platform::PipeServer, Str
 /// Wait for the agent to connect, then return a bidirectional channel.
 pub async fn wait_for_agent(server: platform::PipeServer) -> Result<AgentPipe> {
     let (reader, writer) = platform::wait_for_connection(server).await?;
-    Ok(AgentPipe { writer, reader })
+    Ok(AgentPipe { writer, reader, buf: Vec::new() })
 }
 
-/// Environment variables to pass to the agent for LLM provider configuration.
+/// LLM provider configuration for a session. The keys stay in the gateway:
+/// the agent reaches the providers through the relay in its `Init`.
 pub struct AgentEnv {
     pub anthropic_key: Option<String>,
     pub openai_key: Option<String>,
@@ -141,26 +149,41 @@ pub struct AgentEnv {
 }
 
 /// Spawn the bat-agent child process, pointed at the given pipe/socket.
-/// Provider credentials are passed via environment variables.
-pub fn spawn_agent(pipe_name: &str, env: &AgentEnv) -> Result<tokio::process::Child> {
+/// API keys in the gateway's environment aren't passed on, network
+/// traffic goes through `proxy` if set, and the spawn-time part of the
+/// sandbox is hooked in from `sandbox`.
+pub fn spawn_agent(
+    pipe_name: &str,
+    env: &AgentEnv,
+    proxy: Option<&crate::egress::ProxyRoute>,
+    sandbox: &mut crate::sandbox::PreSpawnConfig,
+) -> Result<tokio::process::Child> {
     let agent_exe = find_agent_binary()?;
     let mut cmd = tokio::process::Command::new(&agent_exe);
     cmd.arg("--pipe")
         .arg(pipe_name);
 
-    if let Some(ref key) = env.anthropic_key {
-        cmd.env("ANTHROPIC_API_KEY", key);
-    }
-    if let Some(ref key) = env.openai_key {
-        cmd.env("OPENAI_API_KEY", key);
+    for var in bat_types::config::ApiKeys::ENV_VARS {
+        cmd.env_remove(var);
     }
     if let Some(ref endpoint) = env.local_llm_endpoint {
         cmd.env("OLLAMA_ENDPOINT", endpoint);
     }
+    if let Some(proxy) = proxy {
+        proxy.apply_to(&mut cmd);
+    }
 
     // Capture stderr so we can log agent errors
     cmd.stderr(std::process::Stdio::piped());
 
+    // Give the agent its own process group so cancellation can take down
+    // everything it started in one go. Commands that lead groups of their
+    // own (`shell_run`) are tracked separately; see `AgentProcess::kill`.
+    #[cfg(unix)]
+    cmd.process_group(0);
+
+    sandbox.apply_to(&mut cmd, &agent_exe);
+
     // On Windows, prevent the agent from flashing a console window.
     #[cfg(target_os = "windows")]
     {
@@ -175,12 +198,37 @@ pub fn spawn_agent(pipe_name: &str, env: &AgentEnv) -> Result<tokio::process::Ch
     Ok(child)
 }
 
+/// Whether `pid` is a child of `parent`, i.e. whether an agent may have the
+/// gateway kill its group. Only Linux can tell; elsewhere this trusts the
+/// agent.
+pub fn is_child_of(pid: u32, parent: u32) -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        let Ok(stat) = std::fs::read_to_string(format!("/proc/{pid}/stat")) else {
+            return false;
+        };
+        // The parent PID is the second field after the parenthesised command name.
+        stat.rsplit(')').next().and_then(|rest| rest.split_whitespace().nth(1)) == Some(parent.to_string().as_str())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (pid, parent);
+        true
+    }
+}
+
 /// Find the bat-agent binary. Checks:
+/// 0. The `BAT_AGENT_BIN` environment variable (integration tests)
 /// 1. Next to the current executable (dev/release builds)
 /// 2. Tauri externalBin sidecar (with target-triple suffix, in same dir)
 /// 3. Tauri resource directory (installed via MSI/NSIS)
 /// 4. macOS app bundle Resources
 fn find_agent_binary() -> Result<PathBuf> {
+    if let Some(path) = std::env::var_os("BAT_AGENT_BIN") {
+        return Ok(PathBuf::from(path));
+    }
+
     let exe = std::env::current_exe().context("Cannot determine current exe path")?;
     let dir = exe
         .parent()
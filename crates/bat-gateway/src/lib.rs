@@ -1,21 +1,28 @@
+pub mod approvals;
 pub mod channels;
 pub mod config;
 pub mod consolidation;
+pub mod context;
 pub mod correction;
 pub mod db;
+pub mod egress;
 pub mod events;
 pub mod ipc;
 pub mod memory;
 pub mod process_manager;
 pub mod sandbox;
 pub mod session;
+pub mod subagent_scope;
 pub mod stt;
 pub mod reflection;
+pub mod relay;
 pub mod system_prompt;
 pub mod tts;
+pub mod workers;
 
 pub use events::EventBus;
 
+use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 
 use anyhow::{Context, Result};
@@ -25,13 +32,16 @@ use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
 use bat_types::{
+    approval::{ApprovalDecision, ApprovalRequest},
     audit::{AuditCategory, AuditEntry, AuditFilter, AuditLevel, AuditStats},
+    command_policy::CommandPolicy,
     config::BatConfig,
     ipc::{AgentToGateway, GatewayToAgent},
     memory::{MemoryFileInfo, Observation, ObservationFilter, ObservationSummary, ObservationKind},
     message::Message,
+    models::ModelInfo,
     policy::{AccessLevel, PathPolicy},
-    session::SessionMeta,
+    session::{SessionMeta, SubagentScope},
 };
 
 use db::Database;
@@ -76,7 +86,7 @@ pub struct LocalLlmModel {
 pub type OllamaModel = LocalLlmModel;
 
 /// Shared state for routing agent questions through the active Telegram channel.
-/// Created once per `start_channels()` call and threaded through `run_agent_turn`.
+/// Created once per `start_channels()` call and carried to agent turns in `GatewayHandles`.
 struct TelegramState {
     /// Channel for sending messages out to Telegram.
     outbound: tokio::sync::mpsc::UnboundedSender<channels::telegram::OutboundMessage>,
@@ -86,6 +96,20 @@ struct TelegramState {
     pending_question: Arc<std::sync::Mutex<Option<tokio::sync::oneshot::Sender<String>>>>,
 }
 
+/// Shared gateway state handed to agent turns and the subagent actions they
+/// trigger. Every field is a shared handle, so clones are cheap.
+#[derive(Clone)]
+struct GatewayHandles {
+    db: Arc<Database>,
+    event_bus: EventBus,
+    proc_mgr: process_manager::ProcessManager,
+    workers: workers::WorkerRegistry,
+    approvals: approvals::Approvals,
+    config: Arc<RwLock<BatConfig>>,
+    /// Set for turns started from Telegram, so subagent questions are asked there.
+    telegram_state: Option<Arc<TelegramState>>,
+}
+
 /// The central gateway — owns the database, session state, and event bus.
 /// The Tauri shell holds an `Arc<Gateway>` in `AppState`.
 pub struct Gateway {
@@ -94,10 +118,14 @@ pub struct Gateway {
     config: Arc<RwLock<BatConfig>>,
     event_bus: EventBus,
     process_manager: process_manager::ProcessManager,
+    /// Control channels to agents that are currently mid-turn.
+    workers: workers::WorkerRegistry,
     /// Key of the currently active session.
     active_session_key: Arc<RwLock<String>>,
     /// Last consolidation diffs (for diff view in UI).
     last_consolidation_diffs: Arc<RwLock<Vec<consolidation::FileDiff>>>,
+    /// Tool calls waiting for the user's approval.
+    approvals: approvals::Approvals,
 }
 
 impl Gateway {
@@ -107,18 +135,36 @@ impl Gateway {
         let session_manager =
             Arc::new(SessionManager::new(Arc::clone(&db), default_model));
         let event_bus = EventBus::new();
+        let config = Arc::new(RwLock::new(config));
+        let egress = egress::EgressProxy::new(config.clone(), db.clone(), event_bus.clone());
+        let workers = workers::WorkerRegistry::new(egress.clone());
 
         Ok(Self {
             session_manager,
             db,
-            config: Arc::new(RwLock::new(config)),
+            config,
             event_bus,
-            process_manager: process_manager::ProcessManager::new(),
+            process_manager: process_manager::ProcessManager::new(egress),
+            workers,
             active_session_key: Arc::new(RwLock::new("main".to_string())),
             last_consolidation_diffs: Arc::new(RwLock::new(Vec::new())),
+            approvals: approvals::Approvals::new(),
         })
     }
 
+    /// Handles for an agent turn that didn't come from Telegram.
+    fn handles(&self) -> GatewayHandles {
+        GatewayHandles {
+            db: Arc::clone(&self.db),
+            event_bus: self.event_bus.clone(),
+            proc_mgr: self.process_manager.clone(),
+            workers: self.workers.clone(),
+            approvals: self.approvals.clone(),
+            config: Arc::clone(&self.config),
+            telegram_state: None,
+        }
+    }
+
     /// Start channel adapters (Telegram, etc.) based on config.
     pub fn start_channels(&self) {
         let cfg = self.config.read().unwrap().clone();
@@ -142,7 +188,7 @@ impl Gateway {
                 let db = Arc::clone(&self.db);
                 let session_manager = Arc::clone(&self.session_manager);
                 let config = Arc::clone(&self.config);
-                let proc_mgr = self.process_manager.clone();
+                let approvals = self.approvals.clone();
                 let outbound = outbound_tx.clone();
                 let typing_client = reqwest::Client::new();
 
@@ -156,6 +202,44 @@ impl Gateway {
                     active_chat_id: Arc::clone(&active_chat_id_shared),
                     pending_question: Arc::clone(&pending_question_shared),
                 });
+                let handles = GatewayHandles { telegram_state: Some(telegram_state), ..self.handles() };
+
+                // Show approval requests in the active chat
+                {
+                    let mut events = self.event_bus.subscribe();
+                    let outbound = outbound_tx.clone();
+                    let active_chat_id = Arc::clone(&active_chat_id_shared);
+                    tokio::spawn(async move {
+                        loop {
+                            let request = match events.recv().await {
+                                Ok(AgentToGateway::ApprovalRequested { request }) => request,
+                                Ok(_) => continue,
+                                Err(broadcast::error::RecvError::Lagged(n)) => {
+                                    warn!("Telegram approval listener lagged by {n} events");
+                                    continue;
+                                }
+                                Err(broadcast::error::RecvError::Closed) => break,
+                            };
+                            let chat_id = *active_chat_id.lock().unwrap();
+                            if chat_id == 0 {
+                                continue;
+                            }
+                            let short_id = &request.id[..8];
+                            let text = format!(
+                                "🔐 *Approval needed*\n\n{}\n\nReply /approve {short_id} to run it once, \
+                                 /approve_session {short_id} to allow {} for the rest of the session, \
+                                 or /deny {short_id} followed by a reason.",
+                                request.summary, request.tool,
+                            );
+                            let _ = outbound.send(channels::telegram::OutboundMessage {
+                                chat_id,
+                                text,
+                                reply_to: None,
+                                voice_data: None,
+                            });
+                        }
+                    });
+                }
 
                 tokio::spawn(async move {
                     // Track which chat_id to respond to (shared with TelegramState)
@@ -178,6 +262,29 @@ impl Gateway {
                             continue; // Skip empty messages
                         }
 
+                        // Answers to approval requests never reach the agent
+                        if let Some((id, decision)) = approvals::parse_command(&msg.text) {
+                            let verb = match decision {
+                                bat_types::approval::ApprovalDecision::ApproveOnce => "Approved",
+                                bat_types::approval::ApprovalDecision::ApproveSession => "Approved for this session",
+                                bat_types::approval::ApprovalDecision::Deny { .. } => "Denied",
+                            };
+                            let text = match approvals.find(id.as_deref()) {
+                                Ok(request) => match approvals.resolve(&db, &event_bus, &request.id, decision) {
+                                    Ok(()) => format!("{verb}: {}", request.summary),
+                                    Err(e) => format!("⚠️ {e}"),
+                                },
+                                Err(e) => format!("⚠️ {e}"),
+                            };
+                            let _ = outbound.send(channels::telegram::OutboundMessage {
+                                chat_id: msg.chat_id,
+                                text,
+                                reply_to: None,
+                                voice_data: None,
+                            });
+                            continue;
+                        }
+
                         // If a subagent question is pending, this reply is the answer —
                         // don't start a new agent turn.
                         {
@@ -220,12 +327,8 @@ impl Gateway {
                         };
                         let path_policies = db.get_path_policies().unwrap_or_default();
 
-                        let eb = event_bus.clone();
                         let sm = Arc::new(SessionManager::new(Arc::clone(&db), cfg_model.clone()));
-                        let db2 = Arc::clone(&db);
-                        let pm = proc_mgr.clone();
-                        let cfg2 = Arc::clone(&config);
-                        let tg_state = Arc::clone(&telegram_state);
+                        let turn_handles = handles.clone();
 
                         // Start typing indicator (repeats every 4s until cancelled)
                         let typing_cancel = channels::telegram::spawn_typing_loop(
@@ -258,9 +361,9 @@ impl Gateway {
                                 session.id, cfg_model, system_prompt, history, msg.text,
                                 vec![],  // Telegram messages don't carry images yet
                                 path_policies, disabled_tools, agent_env,
-                                eb, sm, db2, pm, cfg2,
+                                sm, turn_handles,
                                 "main".to_string(),  // Telegram sessions are main/orchestrator
-                                Some(tg_state),
+                                None,
                                 Some(turn_tx),
                             ).await;
                             // Cancel typing indicator
@@ -331,6 +434,7 @@ impl Gateway {
                     bat_types::session::SubagentStatus::TimedOut,
                     Some("Subagent exceeded timeout limit"),
                 );
+                stop_agent(*id, &self.workers, &self.process_manager);
                 self.event_bus.send(AgentToGateway::AuditLog {
                     level: "warn".into(),
                     category: "agent".into(),
@@ -387,17 +491,18 @@ impl Gateway {
             .context("Failed to get path policies")?;
 
         // Read config once under lock
-        let (model, disabled_tools, agent_env) = {
+        let (model, model_info, disabled_tools, agent_env) = {
             let cfg = self.config.read().unwrap();
             (
                 cfg.agent.model.clone(),
+                cfg.model_registry().resolve(&cfg.agent.model),
                 cfg.agent.disabled_tools.clone(),
                 build_agent_env(&cfg),
             )
         };
 
-        // Validate that the required API key is available for the chosen model's provider
-        validate_provider_key(&model, &agent_env)?;
+        // Validate that the chosen model can take this message and that its provider has an API key
+        validate_model(&model_info, &agent_env, !images.is_empty())?;
 
         let system_prompt = {
             let cfg = self.config.read().unwrap();
@@ -430,8 +535,8 @@ impl Gateway {
         );
 
         let db = Arc::clone(&self.db);
-        let proc_mgr = self.process_manager.clone();
         let gw_config = Arc::clone(&self.config);
+        let handles = self.handles();
 
         // Spawn the agent turn in a background task
         tokio::spawn(async move {
@@ -470,13 +575,10 @@ impl Gateway {
                 path_policies,
                 disabled_tools,
                 agent_env,
-                event_bus.clone(),
                 session_manager.clone(),
-                db,
-                proc_mgr,
-                gw_config,
+                handles,
                 session_kind,
-                None, // No Telegram state for UI-originated turns
+                None,
                 None, // No dedicated Telegram reply channel
             )
             .await
@@ -592,6 +694,7 @@ impl Gateway {
         let session = self.db.get_session_by_key(key)?
             .ok_or_else(|| anyhow::anyhow!("Session not found: {key}"))?;
         self.db.delete_session(session.id)?;
+        self.process_manager.egress().end_session(session.id);
         // If we deleted the active session, switch back to main
         if *self.active_session_key.read().unwrap() == key {
             *self.active_session_key.write().unwrap() = "main".to_string();
@@ -615,19 +718,40 @@ impl Gateway {
 
     /// Get token usage statistics.
     pub fn get_usage_stats(&self) -> Result<bat_types::usage::UsageStats> {
-        self.db.get_usage_stats()
+        let models = self.config.read().unwrap().model_registry();
+        self.db.get_usage_stats(&models)
     }
 
-    /// Cancel a running subagent by session ID.
+    /// Cancel a running subagent by session ID, stopping its agent process
+    /// and any background processes it started.
     pub async fn cancel_subagent(&self, session_id: uuid::Uuid) -> Result<()> {
         self.db.update_subagent_status(
             session_id,
             bat_types::session::SubagentStatus::Cancelled,
             Some("Cancelled by user"),
         )?;
+        stop_agent(session_id, &self.workers, &self.process_manager);
+        self.log_event(
+            AuditLevel::Info,
+            AuditCategory::Agent,
+            "subagent_cancelled",
+            "Subagent cancelled by user",
+            Some(&session_id.to_string()),
+            None,
+        );
         Ok(())
     }
 
+    /// Tool calls waiting for the user's approval, oldest first.
+    pub fn pending_approvals(&self) -> Vec<ApprovalRequest> {
+        self.approvals.pending()
+    }
+
+    /// Answer a pending approval request.
+    pub fn resolve_approval(&self, approval_id: &str, decision: ApprovalDecision) -> Result<()> {
+        self.approvals.resolve(&self.db, &self.event_bus, approval_id, decision)
+    }
+
     /// Get all subagent sessions for the main session.
     pub async fn get_subagents(&self) -> Result<Vec<bat_types::session::SubagentInfo>> {
         let session = self.session_manager.get_or_create_main()?;
@@ -650,6 +774,7 @@ impl Gateway {
             "read-only" => AccessLevel::ReadOnly,
             "read-write" => AccessLevel::ReadWrite,
             "write-only" => AccessLevel::WriteOnly,
+            "deny" => AccessLevel::Deny,
             other => anyhow::bail!("Unknown access level: {other}"),
         };
         let policy = PathPolicy {
@@ -658,6 +783,7 @@ impl Gateway {
             access: access_level,
             recursive,
             description: None,
+            layer: 0,
         };
         self.db.add_path_policy(&policy)
     }
@@ -675,6 +801,9 @@ impl Gateway {
     /// Update config in-memory and persist to disk.
     /// Also writes personality_prompt to IDENTITY.md if set.
     pub fn update_config(&self, new_config: BatConfig) -> Result<()> {
+        for model in &new_config.models {
+            model.validate()?;
+        }
         config::save_config(&new_config)?;
 
         // Sync personality prompt to IDENTITY.md
@@ -707,6 +836,13 @@ impl Gateway {
                 icon: "✏️".to_string(),
                 enabled: !disabled.contains(&"fs_write".to_string()),
             },
+            ToolInfo {
+                name: "fs_edit".to_string(),
+                display_name: "Edit File".to_string(),
+                description: "Make targeted search-and-replace or diff edits to a file.".to_string(),
+                icon: "📝".to_string(),
+                enabled: !disabled.contains(&"fs_edit".to_string()),
+            },
             ToolInfo {
                 name: "fs_list".to_string(),
                 display_name: "List Directory".to_string(),
@@ -714,6 +850,41 @@ impl Gateway {
                 icon: "📁".to_string(),
                 enabled: !disabled.contains(&"fs_list".to_string()),
             },
+            ToolInfo {
+                name: "fs_search".to_string(),
+                display_name: "Search Files".to_string(),
+                description: "Find files by name or content within allowed folders.".to_string(),
+                icon: "🔍".to_string(),
+                enabled: !disabled.contains(&"fs_search".to_string()),
+            },
+            ToolInfo {
+                name: "fs_move".to_string(),
+                display_name: "Move File".to_string(),
+                description: "Move or rename files and folders.".to_string(),
+                icon: "📦".to_string(),
+                enabled: !disabled.contains(&"fs_move".to_string()),
+            },
+            ToolInfo {
+                name: "fs_copy".to_string(),
+                display_name: "Copy File".to_string(),
+                description: "Copy files and folders.".to_string(),
+                icon: "📑".to_string(),
+                enabled: !disabled.contains(&"fs_copy".to_string()),
+            },
+            ToolInfo {
+                name: "fs_delete".to_string(),
+                display_name: "Delete File".to_string(),
+                description: "Move files and folders to the recoverable Batchismo trash.".to_string(),
+                icon: "🗑️".to_string(),
+                enabled: !disabled.contains(&"fs_delete".to_string()),
+            },
+            ToolInfo {
+                name: "fs_stat".to_string(),
+                display_name: "File Info".to_string(),
+                description: "Get size, type and timestamps of a file or folder.".to_string(),
+                icon: "ℹ️".to_string(),
+                enabled: !disabled.contains(&"fs_stat".to_string()),
+            },
             ToolInfo {
                 name: "web_fetch".to_string(),
                 display_name: "Fetch URL".to_string(),
@@ -1266,6 +1437,20 @@ impl Gateway {
 
 // ─── Agent turn runner ────────────────────────────────────────────────────────
 
+/// One-line audit summary for tools that change or inspect files outside a
+/// plain read/write, so each move, copy or delete stands out in the log.
+fn file_operation_summary(tool: &str, input: &serde_json::Value, failed: bool) -> Option<String> {
+    let arg = |key: &str| input.get(key).and_then(|v| v.as_str()).unwrap_or("?");
+    let summary = match tool {
+        "fs_move" => format!("Move {} -> {}", arg("source"), arg("destination")),
+        "fs_copy" => format!("Copy {} -> {}", arg("source"), arg("destination")),
+        "fs_delete" => format!("Delete {} (to trash)", arg("path")),
+        "fs_stat" => format!("Stat {}", arg("path")),
+        _ => return None,
+    };
+    Some(if failed { format!("{summary} failed") } else { summary })
+}
+
 /// Helper to log an audit event from the agent turn (fire-and-forget to DB + event bus).
 fn audit(
     db: &Database,
@@ -1292,17 +1477,15 @@ fn audit(
 fn handle_subagent_action(
     action: bat_types::ipc::ProcessAction,
     session_id: Uuid,
-    db: Arc<Database>,
-    event_bus: EventBus,
-    proc_mgr: process_manager::ProcessManager,
-    config: Arc<RwLock<BatConfig>>,
-    telegram_state: Option<Arc<TelegramState>>,
+    handles: GatewayHandles,
 ) -> bat_types::ipc::ProcessResult {
     use bat_types::ipc::{ProcessAction, ProcessResult};
     use bat_types::session::SubagentStatus;
 
+    let GatewayHandles { db, event_bus, proc_mgr, workers, config, telegram_state, .. } = &handles;
+
     match action {
-        ProcessAction::SpawnSubagent { task, label } => {
+        ProcessAction::SpawnSubagent { task, label, paths, tools, model, memory_limit_mb, hosts, blocked_hosts } => {
             // Enforce max concurrent subagents limit
             let max_concurrent = {
                 let cfg = config.read().unwrap();
@@ -1325,40 +1508,39 @@ fn handle_subagent_action(
             }
 
             let label = label.unwrap_or_else(|| task.chars().take(40).collect::<String>());
-            let (model, sub_agent_env, disabled_tools_base) = {
+            // The subagent inherits the parent's paths, tools, model, memory limit and hosts unless narrowed.
+            let scope = SubagentScope { paths, tools, model, memory_limit_mb, hosts, blocked_hosts };
+            let narrowed = {
                 let cfg = config.read().unwrap();
-                (
-                    cfg.agent.model.clone(),
-                    build_agent_env(&cfg),
-                    cfg.agent.disabled_tools.clone(),
-                )
+                subagent_scope::narrow_scope(&cfg, db.get_path_policies().unwrap_or_default(), &scope)
+                    .map(|narrowed| (narrowed, build_agent_env(&cfg)))
+            };
+            let (narrowed, sub_agent_env) = match narrowed {
+                Ok(narrowed) => narrowed,
+                Err(message) => return ProcessResult::Error { message },
             };
-            match db.create_subagent_session(session_id, &model, &label, &task) {
+            let subagent_scope::Narrowed { path_policies, grants, disabled_tools, model, memory_limit_mb, hosts } = narrowed;
+            match db.create_subagent_session(session_id, &model, &label, &task, &scope) {
                 Ok(sub_session) => {
                     let sub_key = sub_session.key.clone();
                     let sub_key2 = sub_key.clone();
                     let sub_id = sub_session.id;
-                    let path_policies = db.get_path_policies().unwrap_or_default();
+                    proc_mgr.egress().set_session_hosts(sub_id, hosts);
                     let sub_prompt = {
                         let cfg = config.read().unwrap();
-                        crate::system_prompt::build_worker_prompt(&cfg, &path_policies, &task)
+                        crate::system_prompt::build_worker_prompt(&cfg, grants.as_deref().unwrap_or(&path_policies), &task)
                             .unwrap_or_else(|e| {
                                 tracing::warn!("Failed to build worker prompt: {e}");
                                 format!("You are a subagent. Complete this task: {task}")
                             })
                     };
-                    let mut disabled_tools = disabled_tools_base;
-                    disabled_tools.push("session_spawn".to_string());
 
                     let eb = event_bus.clone();
                     let db2 = db.clone();
                     let sm = Arc::new(session::SessionManager::new(db.clone(), model.clone()));
                     let sm2 = sm.clone();
-                    let pm = proc_mgr.clone();
-                    let pm_notify = proc_mgr.clone();
-                    let cfg2 = config.clone();
-                    let cfg_notify = config.clone();
-                    let tg_state = telegram_state.clone();
+                    let sub_handles = handles.clone();
+                    let notify_handles = handles.clone();
 
                     tokio::spawn(async move {
                         info!("Subagent starting: key={sub_key}, task={}", &task[..task.len().min(60)]);
@@ -1366,11 +1548,19 @@ fn handle_subagent_action(
                             sub_id, model, sub_prompt, vec![], task.clone(),
                             vec![],  // Subagents don't receive images
                             path_policies, disabled_tools, sub_agent_env,
-                            eb.clone(), sm, db2.clone(), pm, cfg2,
+                            sm, sub_handles,
                             "subagent".to_string(),  // This is a subagent/worker session
-                            tg_state,
+                            memory_limit_mb,
                             None, // Subagents don't have dedicated Telegram reply channels
                         ).await;
+                        // A cancelled or timed-out subagent keeps that status.
+                        if matches!(
+                            db2.get_subagent_status(sub_id),
+                            Ok(Some(SubagentStatus::Cancelled | SubagentStatus::TimedOut))
+                        ) {
+                            info!("Subagent stopped: key={sub_key}");
+                            return;
+                        }
                         match result {
                             Ok(()) => {
                                 let summary = sm2.get_history(sub_id)
@@ -1399,17 +1589,13 @@ fn handle_subagent_action(
                                 });
                                 info!("Subagent completed: key={sub_key}");
                                 // Notify the orchestrator so it can react without user input
-                                let db3 = db2.clone();
-                                let eb2 = eb.clone();
                                 let label2 = label.clone();
                                 let summary2 = summary.clone();
                                 tokio::spawn(async move {
                                     let notification = format!(
                                         "[Subagent complete: {label2}]\n\nSummary: {summary2}"
                                     );
-                                    if let Err(e) = inject_orchestrator_message(
-                                        &notification, &db3, &cfg_notify, &pm_notify, eb2,
-                                    ).await {
+                                    if let Err(e) = inject_orchestrator_message(&notification, &notify_handles).await {
                                         warn!("Failed to notify orchestrator of subagent completion: {e}");
                                     }
                                 });
@@ -1437,7 +1623,22 @@ fn handle_subagent_action(
                 Err(e) => ProcessResult::Error { message: e.to_string() },
             }
         }
-        ProcessAction::CancelSubagent { .. } => ProcessResult::SubagentCancelled,
+        ProcessAction::CancelSubagent { session_key } => {
+            let sub = match find_subagent(db, session_id, &session_key) {
+                Ok(sub) => sub,
+                Err(message) => return ProcessResult::Error { message },
+            };
+            if !matches!(sub.status, SubagentStatus::Running | SubagentStatus::Paused | SubagentStatus::WaitingForAnswer) {
+                return ProcessResult::Error {
+                    message: format!("Subagent '{session_key}' is {} and cannot be cancelled", sub.status),
+                };
+            }
+            let _ = db.update_subagent_status(sub.session_id, SubagentStatus::Cancelled, Some("Cancelled by orchestrator"));
+            stop_agent(sub.session_id, workers, proc_mgr);
+            audit(db, event_bus, AuditLevel::Info, AuditCategory::Agent, "subagent_cancelled",
+                &format!("[Subagent: {} — cancelled]", sub.label), Some(&sub.session_id.to_string()), None);
+            ProcessResult::SubagentCancelled
+        }
         ProcessAction::AskOrchestrator { question, context, blocking } => {
             if let Some(ref ts) = telegram_state {
                 let chat_id = *ts.active_chat_id.lock().unwrap();
@@ -1495,14 +1696,10 @@ fn handle_subagent_action(
             }
             // No Telegram active — inject the question into the orchestrator session
             // and run a real orchestrator turn so it can answer autonomously.
-            let db2 = db.clone();
-            let config2 = config.clone();
-            let proc_mgr2 = proc_mgr.clone();
-            let eb2 = event_bus.clone();
             let msg = format!("[Subagent question]\n\nContext: {context}\n\nQuestion: {question}");
             let answer = tokio::task::block_in_place(|| {
-                tokio::runtime::Handle::current().block_on(async move {
-                    inject_orchestrator_message(&msg, &db2, &config2, &proc_mgr2, eb2)
+                tokio::runtime::Handle::current().block_on(async {
+                    inject_orchestrator_message(&msg, &handles)
                         .await
                         .unwrap_or_else(|e| {
                             warn!("Orchestrator wake-up failed: {e}");
@@ -1513,24 +1710,106 @@ fn handle_subagent_action(
             ProcessResult::OrchestratorAnswer { answer }
         }
         ProcessAction::PauseSubagent { session_key } => {
-            // TODO: Implement actual pause logic when mid-turn message injection is ready
-            tracing::info!("Pausing subagent: {}", session_key);
+            let sub = match find_subagent(db, session_id, &session_key) {
+                Ok(sub) => sub,
+                Err(message) => return ProcessResult::Error { message },
+            };
+            if sub.status != SubagentStatus::Running {
+                return ProcessResult::Error {
+                    message: format!("Subagent '{session_key}' is {} and cannot be paused", sub.status),
+                };
+            }
+            if let Err(e) = workers.send(sub.session_id, GatewayToAgent::Pause) {
+                return ProcessResult::Error { message: e.to_string() };
+            }
+            let _ = db.update_subagent_status(sub.session_id, SubagentStatus::Paused, None);
+            audit(db, event_bus, AuditLevel::Info, AuditCategory::Agent, "subagent_paused",
+                &format!("[Subagent: {} — paused]", sub.label), Some(&sub.session_id.to_string()), None);
             ProcessResult::SubagentPaused
         }
         ProcessAction::ResumeSubagent { session_key, instructions } => {
-            // TODO: Implement actual resume logic when mid-turn message injection is ready
-            tracing::info!("Resuming subagent: {} with instructions: {:?}", session_key, instructions);
+            let sub = match find_subagent(db, session_id, &session_key) {
+                Ok(sub) => sub,
+                Err(message) => return ProcessResult::Error { message },
+            };
+            if sub.status != SubagentStatus::Paused {
+                return ProcessResult::Error {
+                    message: format!("Subagent '{session_key}' is {} and cannot be resumed", sub.status),
+                };
+            }
+            let detail = instructions
+                .as_ref()
+                .map(|i| serde_json::json!({ "instructions": i }).to_string());
+            if let Err(e) = workers.send(sub.session_id, GatewayToAgent::Resume { instructions }) {
+                return ProcessResult::Error { message: e.to_string() };
+            }
+            let _ = db.update_subagent_status(sub.session_id, SubagentStatus::Running, None);
+            audit(db, event_bus, AuditLevel::Info, AuditCategory::Agent, "subagent_resumed",
+                &format!("[Subagent: {} — resumed]", sub.label), Some(&sub.session_id.to_string()), detail.as_deref());
             ProcessResult::SubagentResumed
         }
         ProcessAction::InstructSubagent { session_key, instruction } => {
-            // TODO: Implement actual instruction sending when mid-turn message injection is ready
-            tracing::info!("Instructing subagent: {} with: {}", session_key, instruction);
+            let sub = match find_subagent(db, session_id, &session_key) {
+                Ok(sub) => sub,
+                Err(message) => return ProcessResult::Error { message },
+            };
+            // Instructions sent while paused are queued and applied on resume.
+            if !matches!(sub.status, SubagentStatus::Running | SubagentStatus::Paused) {
+                return ProcessResult::Error {
+                    message: format!("Subagent '{session_key}' is {} and cannot receive instructions", sub.status),
+                };
+            }
+            let detail = serde_json::json!({ "instruction": instruction }).to_string();
+            let msg = GatewayToAgent::Instruction {
+                instruction_id: Uuid::new_v4().to_string(),
+                content: instruction,
+            };
+            if let Err(e) = workers.send(sub.session_id, msg) {
+                return ProcessResult::Error { message: e.to_string() };
+            }
+            audit(db, event_bus, AuditLevel::Info, AuditCategory::Agent, "subagent_instructed",
+                &format!("[Subagent: {} — new instruction]", sub.label), Some(&sub.session_id.to_string()), Some(&detail));
             ProcessResult::SubagentInstructed
         }
         _ => ProcessResult::Error { message: "Not a subagent action".into() },
     }
 }
 
+/// Stop a running agent: ask it to cancel, kill its process tree if it hasn't
+/// exited after the grace period, and kill any background processes it started.
+fn stop_agent(
+    session_id: Uuid,
+    workers: &workers::WorkerRegistry,
+    proc_mgr: &process_manager::ProcessManager,
+) {
+    if let Err(e) = workers.cancel(session_id, workers::CANCEL_GRACE_PERIOD) {
+        debug!("No live agent to cancel: {e}");
+    }
+    workers.retire_idle(session_id);
+    let proc_mgr = proc_mgr.clone();
+    tokio::spawn(async move {
+        let killed = proc_mgr.kill_owned_by(session_id).await;
+        if killed > 0 {
+            info!("Killed {killed} background process(es) for session {session_id}");
+        }
+    });
+}
+
+/// Look up a subagent by key, restricted to children of `parent_id`.
+fn find_subagent(
+    db: &Database,
+    parent_id: Uuid,
+    session_key: &str,
+) -> std::result::Result<bat_types::session::SubagentInfo, String> {
+    let subagents = db
+        .get_subagents(parent_id)
+        .map_err(|e| format!("Failed to look up subagents: {e}"))?;
+    subagents
+        .into_iter()
+        .find(|s| s.session_key == session_key)
+        .ok_or_else(|| format!("No subagent with key '{session_key}'"))
+}
+
 /// Inject a message into the orchestrator (main) session and run a fresh orchestrator turn.
 ///
 /// Used by `ask_orchestrator` (subagent asks a question) and subagent completion notifications.
@@ -1538,13 +1817,8 @@ fn handle_subagent_action(
 /// orchestrator's response stream in real time.
 ///
 /// Returns the orchestrator's final text response so `ask_orchestrator` can relay it back.
-async fn inject_orchestrator_message(
-    content: &str,
-    db: &Arc<Database>,
-    config: &Arc<RwLock<BatConfig>>,
-    proc_mgr: &process_manager::ProcessManager,
-    event_bus: EventBus,
-) -> anyhow::Result<String> {
+async fn inject_orchestrator_message(content: &str, handles: &GatewayHandles) -> anyhow::Result<String> {
+    let GatewayHandles { db, config, .. } = handles;
     let (model, disabled_tools, agent_env) = {
         let cfg = config.read().unwrap();
         (cfg.agent.model.clone(), cfg.agent.disabled_tools.clone(), build_agent_env(&cfg))
@@ -1575,11 +1849,9 @@ async fn inject_orchestrator_message(
         path_policies,
         disabled_tools,
         agent_env,
-        event_bus,
         session_manager.clone(),
-        db.clone(),
-        proc_mgr.clone(),
-        config.clone(),
+        // The orchestrator's own questions aren't routed to Telegram.
+        GatewayHandles { telegram_state: None, ..handles.clone() },
         "main".to_string(),
         None,
         None,
@@ -1603,18 +1875,20 @@ async fn inject_orchestrator_message(
 async fn handle_process_request(
     proc_mgr: process_manager::ProcessManager,
     action: bat_types::ipc::ProcessAction,
+    session_id: Uuid,
+    command_policy: &CommandPolicy,
 ) -> bat_types::ipc::ProcessResult {
     use bat_types::ipc::{ProcessAction, ProcessResult};
 
     match action {
         ProcessAction::Start { command, workdir, background } => {
             if background {
-                match proc_mgr.spawn(&command, workdir.as_deref()).await {
+                match proc_mgr.spawn(&command, workdir.as_deref(), Some(session_id), command_policy).await {
                     Ok(session_id) => ProcessResult::Started { session_id },
                     Err(e) => ProcessResult::Error { message: e.to_string() },
                 }
             } else {
-                match proc_mgr.run_foreground(&command, workdir.as_deref()).await {
+                match proc_mgr.run_foreground(&command, workdir.as_deref(), Some(session_id), command_policy).await {
                     Ok((stdout, stderr, exit_code)) => ProcessResult::Output {
                         session_id: String::new(),
                         stdout,
@@ -1660,6 +1934,9 @@ async fn handle_process_request(
         ProcessAction::SpawnSubagent { .. } | ProcessAction::ListSubagents | ProcessAction::CancelSubagent { .. } | ProcessAction::AskOrchestrator { .. } | ProcessAction::PauseSubagent { .. } | ProcessAction::ResumeSubagent { .. } | ProcessAction::InstructSubagent { .. } => {
             ProcessResult::Error { message: "Subagent actions must be handled by the gateway directly".to_string() }
         }
+        ProcessAction::RequestApproval { .. } | ProcessAction::TrackProcessGroup { .. } | ProcessAction::UntrackProcessGroup { .. } => {
+            ProcessResult::Error { message: "Approval and process group requests must be handled by the gateway directly".to_string() }
+        }
     }
 }
 
@@ -1749,16 +2026,14 @@ async fn run_agent_turn(
     path_policies: Vec<PathPolicy>,
     disabled_tools: Vec<String>,
     agent_env: ipc::AgentEnv,
-    event_bus: EventBus,
     session_manager: Arc<SessionManager>,
-    db: Arc<Database>,
-    proc_mgr: process_manager::ProcessManager,
-    gw_config: Arc<RwLock<BatConfig>>,
+    handles: GatewayHandles,
     session_kind: String,  // "main" or "subagent"
-    telegram_state: Option<Arc<TelegramState>>,
+    memory_limit_mb: Option<u64>,  // overrides `sandbox.memory_limit_mb`
     telegram_reply_tx: Option<tokio::sync::mpsc::UnboundedSender<AgentToGateway>>,
 ) -> Result<()> {
     let sid = session_id.to_string();
+    let GatewayHandles { db, event_bus, proc_mgr, workers, approvals, config: gw_config, .. } = handles.clone();
 
     // Detect user corrections/preferences in the message
     let detections = correction::detect(&user_content);
@@ -1767,76 +2042,115 @@ async fn run_agent_turn(
         info!("Detected {:?}: {} = {}", det.kind, det.key, det.value);
     }
 
-    // 1. Create named pipe server
-    let (server, pipe_name) = ipc::create_pipe_server(session_id)
-        .context("Failed to create agent pipe")?;
+    // Register for mid-turn control messages (instruct/pause/resume). Anything
+    // queued before the agent connects is forwarded once the turn is running.
+    let (worker_guard, mut control_rx) = workers.register(session_id);
 
-    info!("Created pipe: {}", pipe_name);
-
-    // 2. Spawn the agent child process
-    let child = ipc::spawn_agent(&pipe_name, &agent_env)
-        .context("Failed to spawn bat-agent")?;
-
-    let pid = child.id().unwrap_or(0);
-    info!("Spawned bat-agent (pid: {})", pid);
-    audit(&db, &event_bus, AuditLevel::Info, AuditCategory::Agent, "agent_spawn",
-        &format!("Agent spawned (pid: {pid}, model: {model})"), Some(&sid), None);
-
-    // Apply OS-native sandbox
-    let sandbox_cfg = {
+    let (persistent, keep_alive, history_tool_result_max_chars, thinking_level, fallback_models, max_retries, context_cfg, model_overrides, model_info, shell_env_allowlist) = {
         let cfg = gw_config.read().unwrap();
-        sandbox::SandboxConfig {
-            memory_limit_mb: cfg.sandbox.memory_limit_mb as u64,
-            ..Default::default()
-        }
+        (cfg.sandbox.persistent_agents, cfg.sandbox.agent_keep_alive_secs, cfg.agent.history_tool_result_max_chars,
+            cfg.agent.thinking_level.clone(), cfg.agent.enabled_fallbacks(), cfg.agent.llm_max_retries, cfg.context.clone(),
+            cfg.models.clone(), cfg.model_registry().resolve(&model), cfg.agent.shell_env_allowlist.clone())
     };
-    let _sandbox_handle = match sandbox::apply_sandbox(pid, &sandbox_cfg) {
-        Ok(handle) => {
-            audit(&db, &event_bus, AuditLevel::Info, AuditCategory::Agent, "sandbox_applied",
-                &format!("Sandbox applied (pid: {pid}, mem: {}MB)", sandbox_cfg.memory_limit_mb), Some(&sid), None);
-            Some(handle)
-        }
-        Err(e) => {
-            warn!("Failed to apply sandbox: {e}");
-            audit(&db, &event_bus, AuditLevel::Warn, AuditCategory::Agent, "sandbox_failed",
-                &format!("Sandbox failed: {e}"), Some(&sid), None);
-            None
-        }
+    let (permissions, command_policy, deny_sensitive_paths) = {
+        let cfg = gw_config.read().unwrap();
+        (cfg.permissions.clone(), cfg.commands.clone(), cfg.sandbox.deny_sensitive_paths)
     };
-
-    // 3. Wait for agent to connect
-    let mut pipe = ipc::wait_for_agent(server)
-        .await
-        .context("Failed while waiting for agent connection")?;
-
-    info!("Agent connected");
-    audit(&db, &event_bus, AuditLevel::Debug, AuditCategory::Ipc, "pipe_connected",
-        "Agent connected to IPC pipe", Some(&sid), None);
-
-    // 4. Send Init
-    pipe.send(&GatewayToAgent::Init {
-        session_id: session_id.to_string(),
+    let mut path_policies = path_policies;
+    if deny_sensitive_paths {
+        path_policies.extend(bat_types::policy::sensitive_path_denies());
+    }
+    let approval_timeout = std::time::Duration::from_secs(permissions.approval_timeout_secs);
+
+    // Keep the history within the model's context window, folding older turns
+    // into a summary if needed.
+    let reserved_tokens = context::estimate_text_tokens(&model_info, &system_prompt)
+        + context::estimate_text_tokens(&model_info, &user_content);
+    let compactor = context::Compactor {
+        db: &db,
+        event_bus: &event_bus,
+        cfg: &context_cfg,
+        anthropic_key: agent_env.anthropic_key.as_deref(),
+    };
+    let history = compactor
+        .prepare_history(&model_info, session_id, history, reserved_tokens, history_tool_result_max_chars)
+        .await?;
+    // Subagents run a single task, so only main sessions keep their agent.
+    let reusable = persistent && session_kind == "main";
+    let init = workers::AgentInit {
         model,
         system_prompt,
-        history,
         path_policies,
         disabled_tools,
         session_kind,
-    })
-    .await
-    .context("Failed to send Init to agent")?;
+        history_tool_result_max_chars,
+        thinking_level,
+        fallback_models,
+        max_retries,
+        model_overrides,
+        shell_env_allowlist,
+        permissions,
+        command_policy: command_policy.clone(),
+        memory_limit_mb,
+    };
+    let marker = history_marker(&history);
+    let history_len = history.len();
+
+    // 1–4. Reuse this session's idle agent, or spawn, sandbox, connect and Init a new one
+    let reused = if reusable { workers.take_idle(session_id, &init) } else { None };
+    let (mut agent, resync) = match reused {
+        Some(agent) => {
+            info!("Reusing idle agent (pid: {})", agent.pid);
+            audit(&db, &event_bus, AuditLevel::Debug, AuditCategory::Agent, "agent_reuse",
+                &format!("Reusing idle agent (pid: {})", agent.pid), Some(&sid), None);
+            // Resend the history only if the session changed since the agent's last turn
+            let resync = (agent.history_marker != marker).then_some(history);
+            (agent, resync)
+        }
+        None => (start_agent(session_id, init, history, &agent_env, &handles).await?, None),
+    };
+    worker_guard.set_process(&agent);
 
     // 5. Send UserMessage
-    pipe.send(&GatewayToAgent::UserMessage {
+    agent.pipe.send(&GatewayToAgent::UserMessage {
         content: user_content,
         images: user_images,
+        history: resync,
     })
     .await
     .context("Failed to send UserMessage to agent")?;
 
-    // 6. Read events until TurnComplete or Error
+    // 6. Read events until TurnComplete or Error, forwarding control messages as they arrive
+    let mut cancelled = false;
+    let mut completed: Option<Uuid> = None;
+    // Tool calls whose input has arrived but whose result hasn't: id → (name, input)
+    let mut pending_calls: HashMap<String, (String, serde_json::Value)> = HashMap::new();
+    // Approval requests wait for the user in their own task, so control
+    // messages (notably Cancel) still get through; answers come back here.
+    let (approval_tx, mut approval_rx) = tokio::sync::mpsc::unbounded_channel::<(String, bat_types::ipc::ProcessResult)>();
     loop {
-        match pipe.recv().await? {
+        let next = tokio::select! {
+            event = agent.pipe.recv() => event?,
+            Some((request_id, result)) = approval_rx.recv() => {
+                agent.pipe.send(&GatewayToAgent::ProcessResponse { request_id, result })
+                    .await
+                    .context("Failed to send approval to agent")?;
+                continue;
+            }
+            Some(ctrl) = control_rx.recv() => {
+                debug!("Forwarding control message to agent: {:?}", ctrl);
+                if matches!(ctrl, GatewayToAgent::Cancel) {
+                    cancelled = true;
+                    audit(&db, &event_bus, AuditLevel::Info, AuditCategory::Agent, "agent_cancel",
+                        "Cancel sent to agent", Some(&sid), None);
+                }
+                agent.pipe.send(&ctrl)
+                    .await
+                    .context("Failed to forward control message to agent")?;
+                continue;
+            }
+        };
+        match next {
             Some(event) => {
                 let is_terminal = matches!(
                     event,
@@ -1845,33 +2159,48 @@ async fn run_agent_turn(
 
                 // Audit tool call events + record observations
                 match &event {
-                    AgentToGateway::ToolCallStart { tool_call, .. } => {
+                    AgentToGateway::ToolCallReady { tool_call, .. } => {
+                        // Audited before the tool runs, so the call is on record even if it never finishes.
                         audit(&db, &event_bus, AuditLevel::Info, AuditCategory::Tool, "tool_call_start",
                             &format!("Tool call: {}", tool_call.name), Some(&sid),
-                            Some(&serde_json::to_string(&tool_call.input).unwrap_or_default()));
+                            Some(&tool_call.input.to_string()));
 
                         // Record tool use observation
                         let _ = db.record_observation(
                             ObservationKind::ToolUse, &tool_call.name, None, Some(&sid),
                         );
-
-                        // Record path access for fs tools
-                        if let Some(path) = tool_call.input.get("path").and_then(|v| v.as_str()) {
-                            let _ = db.record_observation(
-                                ObservationKind::PathAccess, path, Some(&tool_call.name), Some(&sid),
-                            );
-                        }
+                        pending_calls.insert(tool_call.id.clone(), (tool_call.name.clone(), tool_call.input.clone()));
                     }
                     AgentToGateway::ToolCallResult { result, .. } => {
+                        let (name, input) = pending_calls.remove(&result.tool_call_id).unwrap_or_default();
+
+                        // Record path access for fs tools (both ends of a move or copy)
+                        for key in ["path", "source", "destination"] {
+                            if let Some(path) = input.get(key).and_then(|v| v.as_str()) {
+                                let _ = db.record_observation(
+                                    ObservationKind::PathAccess, path, Some(&name), Some(&sid),
+                                );
+                            }
+                        }
+                        if let Some(summary) = file_operation_summary(&name, &input, result.is_error) {
+                            let level = if result.is_error { AuditLevel::Warn } else { AuditLevel::Info };
+                            let detail = serde_json::json!({ "input": input, "result": result.content });
+                            audit(&db, &event_bus, level, AuditCategory::Tool, &name,
+                                &summary, Some(&sid), Some(&detail.to_string()));
+                        }
+
                         let status = if result.is_error { "error" } else { "success" };
                         let summary = format!("Tool result ({}): {} chars", status, result.content.len());
                         audit(&db, &event_bus, AuditLevel::Info, AuditCategory::Tool, "tool_call_result",
-                            &summary, Some(&sid), None);
+                            &summary, Some(&sid), Some(&input.to_string()));
                     }
                     AgentToGateway::TurnComplete { ref message, .. } => {
-                        let tokens = format!("in: {}, out: {}",
+                        let tokens = format!("in: {}, out: {}, thinking: {}, cache read: {}, cache write: {}",
                             message.token_input.unwrap_or(0),
-                            message.token_output.unwrap_or(0));
+                            message.token_output.unwrap_or(0),
+                            message.token_thinking.unwrap_or(0),
+                            message.token_cache_read.unwrap_or(0),
+                            message.token_cache_write.unwrap_or(0));
                         audit(&db, &event_bus, AuditLevel::Info, AuditCategory::Agent, "turn_complete",
                             &format!("Turn complete ({tokens})"), Some(&sid), None);
                     }
@@ -1879,16 +2208,54 @@ async fn run_agent_turn(
                         audit(&db, &event_bus, AuditLevel::Error, AuditCategory::Agent, "agent_error",
                             message, Some(&sid), None);
                     }
+                    // Audit entries raised by the agent itself (e.g. model failover).
+                    // Persisted here; forwarded to the event bus below like any event.
+                    AgentToGateway::AuditLog { level, category, event: name, summary, detail_json } => {
+                        let ts = chrono::Utc::now().to_rfc3339();
+                        let _ = db.insert_audit_log(&ts, Some(&sid),
+                            level.parse().unwrap_or(AuditLevel::Info), category.parse().unwrap_or(AuditCategory::Agent),
+                            name, summary, detail_json.as_deref());
+                    }
                     AgentToGateway::ProcessRequest { ref request_id, ref action } => {
-                        use bat_types::ipc::ProcessAction;
-
+                        use bat_types::ipc::{ProcessAction, ProcessResult};
+
+                        if let ProcessAction::TrackProcessGroup { pgid } | ProcessAction::UntrackProcessGroup { pgid } = *action {
+                            let result = {
+                                let mut groups = agent.process_groups.lock().unwrap();
+                                if matches!(*action, ProcessAction::UntrackProcessGroup { .. }) {
+                                    groups.remove(&pgid);
+                                    ProcessResult::Tracked
+                                } else if ipc::is_child_of(pgid, agent.pid) {
+                                    groups.insert(pgid);
+                                    ProcessResult::Tracked
+                                } else {
+                                    warn!("Agent for session {session_id} asked to track process {pgid}, which it didn't start");
+                                    ProcessResult::Error { message: format!("Process {pgid} was not started by this agent") }
+                                }
+                            };
+                            let _ = agent.pipe.send(&GatewayToAgent::ProcessResponse {
+                                request_id: request_id.clone(),
+                                result,
+                            }).await;
+                            continue;
+                        }
+                        if let ProcessAction::RequestApproval { tool, input } = action {
+                            let (approvals, db, event_bus, approval_tx) =
+                                (approvals.clone(), db.clone(), event_bus.clone(), approval_tx.clone());
+                            let (request_id, tool, input) = (request_id.clone(), tool.clone(), input.clone());
+                            tokio::spawn(async move {
+                                let decision = approvals.request(&db, &event_bus, session_id, &tool, input, approval_timeout).await;
+                                let _ = approval_tx.send((request_id, ProcessResult::Approval { decision }));
+                            });
+                            continue;
+                        }
                         // Handle subagent actions synchronously, process actions async
                         let result = if matches!(action, ProcessAction::SpawnSubagent { .. } | ProcessAction::ListSubagents | ProcessAction::CancelSubagent { .. } | ProcessAction::AskOrchestrator { .. } | ProcessAction::PauseSubagent { .. } | ProcessAction::ResumeSubagent { .. } | ProcessAction::InstructSubagent { .. }) {
-                            handle_subagent_action(action.clone(), session_id, db.clone(), event_bus.clone(), proc_mgr.clone(), gw_config.clone(), telegram_state.clone())
+                            handle_subagent_action(action.clone(), session_id, handles.clone())
                         } else {
-                            handle_process_request(proc_mgr.clone(), action.clone()).await
+                            handle_process_request(proc_mgr.clone(), action.clone(), session_id, &command_policy).await
                         };
-                        let _ = pipe.send(&GatewayToAgent::ProcessResponse {
+                        let _ = agent.pipe.send(&GatewayToAgent::ProcessResponse {
                             request_id: request_id.clone(),
                             result,
                         }).await;
@@ -1899,6 +2266,7 @@ async fn run_agent_turn(
 
                 // Persist TurnComplete message to database
                 if let AgentToGateway::TurnComplete { ref message, .. } = event {
+                    completed = Some(message.id);
                     session_manager
                         .append_message(message)
                         .context("Failed to persist assistant message")?;
@@ -1908,6 +2276,11 @@ async fn run_agent_turn(
                             .update_token_usage(session_id, inp, out)
                             .context("Failed to update token usage")?;
                     }
+                    if let (Some(read), Some(write)) = (message.token_cache_read, message.token_cache_write) {
+                        session_manager
+                            .update_cache_usage(session_id, read, write)
+                            .context("Failed to update cache usage")?;
+                    }
                 }
 
                 // Forward to the dedicated Telegram reply channel if present
@@ -1923,6 +2296,10 @@ async fn run_agent_turn(
                     break;
                 }
             }
+            None if cancelled => {
+                info!("Agent disconnected after Cancel");
+                break;
+            }
             None => {
                 // Pipe closed without TurnComplete
                 audit(&db, &event_bus, AuditLevel::Error, AuditCategory::Ipc, "pipe_disconnected",
@@ -1935,31 +2312,187 @@ async fn run_agent_turn(
         }
     }
 
-    // 7. Wait for the child process to exit and capture stderr
+    // Nobody is waiting for answers to this turn's approvals any more.
+    approvals.withdraw_session(&event_bus, session_id);
+
+    // 7. Keep the agent for the next turn, or wait for it to exit
+    match completed {
+        Some(last_id) if reusable && !cancelled => {
+            // The agent now holds the history it was given plus this user/assistant pair.
+            agent.history_marker = (history_len + 2, Some(last_id));
+            workers.park(session_id, agent, std::time::Duration::from_secs(keep_alive));
+        }
+        _ => finish_agent(agent, cancelled, session_id, &handles).await,
+    }
+    Ok(())
+}
+
+/// Spawn an agent process for a session, sandbox it, wait for it to connect
+/// and send `Init`.
+async fn start_agent(
+    session_id: Uuid,
+    init: workers::AgentInit,
+    history: Vec<Message>,
+    agent_env: &ipc::AgentEnv,
+    handles: &GatewayHandles,
+) -> Result<workers::AgentProcess> {
+    let GatewayHandles { db, event_bus, proc_mgr, config: gw_config, .. } = handles;
+    let egress = proc_mgr.egress();
+    let sid = session_id.to_string();
+    let history_marker = history_marker(&history);
+
+    // 1. Create named pipe server
+    let (server, pipe_name) = ipc::create_pipe_server(session_id)
+        .context("Failed to create agent pipe")?;
+
+    info!("Created pipe: {}", pipe_name);
+
+    // 2. Spawn the agent child process, sandboxed from before it runs and
+    // with its network traffic going through the egress proxy. Its LLM
+    // requests go through the relay on the same port.
+    let route = egress.route(session_id).await?;
+    let llm_relay = egress.relay(session_id).await?;
+    let started = async {
+        let sandbox_cfg = {
+            let cfg = gw_config.read().unwrap();
+            sandbox::SandboxConfig {
+                memory_limit_mb: init.memory_limit_mb.unwrap_or(cfg.sandbox.memory_limit_mb as u64),
+                cpu_shares: cfg.sandbox.cpu_shares,
+                allowed_endpoints: route.iter().map(|route| format!("localhost:{}", route.port)).collect(),
+                path_policies: init.path_policies.clone(),
+                linux_sandbox: cfg.sandbox.linux_sandbox,
+                seccomp_kill: cfg.sandbox.seccomp_kill,
+                ..Default::default()
+            }
+        };
+        let mut pre_spawn = sandbox::pre_spawn_setup(&sandbox_cfg).unwrap_or_else(|e| {
+            warn!("Failed to prepare sandbox: {e}");
+            sandbox::PreSpawnConfig::default()
+        });
+        let child = ipc::spawn_agent(&pipe_name, agent_env, route.as_ref(), &mut pre_spawn)
+            .context("Failed to spawn bat-agent")?;
+
+        let pid = child.id().unwrap_or(0);
+        info!("Spawned bat-agent (pid: {})", pid);
+        audit(db, event_bus, AuditLevel::Info, AuditCategory::Agent, "agent_spawn",
+            &format!("Agent spawned (pid: {pid}, model: {})", init.model), Some(&sid), None);
+
+        // Apply OS-native sandbox, and record what it actually enforces
+        let sandbox_handle = match sandbox::apply_sandbox(pid, &sandbox_cfg, pre_spawn) {
+            Ok(handle) => {
+                let protections = handle.protections();
+                let level = if protections.missing.is_empty() { AuditLevel::Info } else { AuditLevel::Warn };
+                audit(db, event_bus, level, AuditCategory::Agent, "sandbox_applied",
+                    &format!("Sandbox applied (pid: {pid}, mem: {}MB): {protections}", sandbox_cfg.memory_limit_mb),
+                    Some(&sid), None);
+                Some(handle)
+            }
+            Err(e) => {
+                warn!("Failed to apply sandbox: {e}");
+                audit(db, event_bus, AuditLevel::Warn, AuditCategory::Agent, "sandbox_failed",
+                    &format!("Sandbox failed: {e}"), Some(&sid), None);
+                None
+            }
+        };
+
+        // 3. Wait for agent to connect
+        let mut pipe = ipc::wait_for_agent(server)
+            .await
+            .context("Failed while waiting for agent connection")?;
+
+        info!("Agent connected");
+        audit(db, event_bus, AuditLevel::Debug, AuditCategory::Ipc, "pipe_connected",
+            "Agent connected to IPC pipe", Some(&sid), None);
+
+        // 4. Send Init
+        pipe.send(&GatewayToAgent::Init(Box::new(bat_types::ipc::AgentInit {
+            session_id: sid.clone(),
+            model: init.model.clone(),
+            system_prompt: init.system_prompt.clone(),
+            history,
+            path_policies: init.path_policies.clone(),
+            disabled_tools: init.disabled_tools.clone(),
+            session_kind: init.session_kind.clone(),
+            history_tool_result_max_chars: init.history_tool_result_max_chars,
+            thinking_level: init.thinking_level.clone(),
+            fallback_models: init.fallback_models.clone(),
+            max_retries: init.max_retries,
+            model_overrides: init.model_overrides.clone(),
+            shell_env_allowlist: init.shell_env_allowlist.clone(),
+            permissions: init.permissions.clone(),
+            command_policy: init.command_policy.clone(),
+            llm_relay: Some(llm_relay),
+        })))
+        .await
+        .context("Failed to send Init to agent")?;
+
+        Ok(workers::AgentProcess {
+            child,
+            pipe,
+            pid,
+            init,
+            history_marker,
+            sandbox: sandbox_handle,
+            process_groups: workers::ProcessGroups::default(),
+        })
+    }
+    .await;
+    if started.is_err() {
+        // No agent will use the tokens just issued.
+        egress.end_agent(session_id);
+    }
+    started
+}
+
+/// Close the pipe, end the agent's egress session, wait for the process to
+/// exit and audit how it went.
+async fn finish_agent(agent: workers::AgentProcess, cancelled: bool, session_id: Uuid, handles: &GatewayHandles) {
+    let GatewayHandles { db, event_bus, proc_mgr, .. } = handles;
+    let sid = &session_id.to_string();
+    let workers::AgentProcess { child, pipe, pid, process_groups, .. } = agent;
+    // A persistent-capable agent waits for more turns until its pipe closes.
+    drop(pipe);
+    // Before waiting, so a new turn's agent doesn't lose its fresh tokens.
+    proc_mgr.egress().end_agent(session_id);
     let output = child.wait_with_output().await;
+
+    // A cancelled agent may have left tool processes behind; sweep its
+    // process group. Commands it was still running when it exited (e.g. a
+    // blocked `shell_run`) lead groups of their own.
+    if cancelled && pid != 0 {
+        ipc::kill_process_tree(pid);
+    }
+    for pgid in process_groups.lock().unwrap().drain() {
+        ipc::kill_process_tree(pgid);
+    }
+
     match output {
         Ok(out) => {
             let code = out.status.code().unwrap_or(-1);
             if code != 0 {
                 let stderr = String::from_utf8_lossy(&out.stderr);
                 error!("Agent process exited with code {}: {}", code, stderr.trim());
-                audit(&db, &event_bus, AuditLevel::Error, AuditCategory::Agent, "agent_exit",
-                    &format!("Agent exited with code {code}"), Some(&sid),
+                audit(db, event_bus, AuditLevel::Error, AuditCategory::Agent, "agent_exit",
+                    &format!("Agent exited with code {code}"), Some(sid),
                     Some(&format!("{{\"stderr\":\"{}\"}}", stderr.trim().replace('"', "\\\""))));
             } else {
                 info!("Agent process exited cleanly");
-                audit(&db, &event_bus, AuditLevel::Debug, AuditCategory::Agent, "agent_exit",
-                    "Agent exited cleanly", Some(&sid), None);
+                audit(db, event_bus, AuditLevel::Debug, AuditCategory::Agent, "agent_exit",
+                    "Agent exited cleanly", Some(sid), None);
             }
         }
         Err(e) => {
             error!("Failed to wait for agent process: {}", e);
-            audit(&db, &event_bus, AuditLevel::Error, AuditCategory::Agent, "agent_exit",
-                &format!("Failed to wait for agent: {e}"), Some(&sid), None);
+            audit(db, event_bus, AuditLevel::Error, AuditCategory::Agent, "agent_exit",
+                &format!("Failed to wait for agent: {e}"), Some(sid), None);
         }
     }
+}
 
-    Ok(())
+/// Length and last message ID of a history, used to detect whether a
+/// persistent agent's copy is still current.
+fn history_marker(history: &[Message]) -> (usize, Option<Uuid>) {
+    (history.len(), history.last().map(|m| m.id))
 }
 
 /// Build the environment variables for the agent process from config.
@@ -1971,10 +2504,14 @@ fn build_agent_env(cfg: &BatConfig) -> ipc::AgentEnv {
     }
 }
 
-/// Validate that the required API key is available for the model's provider.
-fn validate_provider_key(model: &str, env: &ipc::AgentEnv) -> Result<()> {
-    use bat_types::config::{ApiKeys, LlmProvider};
-    match ApiKeys::provider_for_model(model) {
+/// Validate that the model's provider has an API key and that the model
+/// accepts images, if the message has any.
+fn validate_model(model: &ModelInfo, env: &ipc::AgentEnv, has_images: bool) -> Result<()> {
+    use bat_types::config::LlmProvider;
+    if has_images && !model.vision {
+        anyhow::bail!("{} doesn't accept images. Pick a vision-capable model in Settings → Agent Config.", model.id);
+    }
+    match model.provider {
         LlmProvider::Anthropic => {
             if env.anthropic_key.as_ref().map_or(true, |k| k.is_empty()) {
                 anyhow::bail!(
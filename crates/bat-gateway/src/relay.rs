@@ -0,0 +1,267 @@
+//! The LLM relay: how agents reach Anthropic and OpenAI without holding the
+//! API keys.
+//!
+//! Each session's agent gets a relay token in `Init` and sends provider
+//! requests to the egress proxy's port, with the token where the key would
+//! go. The relay checks the token, picks the upstream from the model
+//! registry — so a key only ever goes where the config sends it — puts the
+//! real key in and streams the response back.
+
+use anyhow::Result;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use bat_types::audit::{AuditCategory, AuditLevel};
+use bat_types::config::{BatConfig, LlmProvider};
+
+use crate::egress::{EgressProxy, Request};
+
+/// Largest request body relayed: a 32 MB PDF, base64-encoded, plus the rest.
+const MAX_BODY: usize = 64 * 1024 * 1024;
+
+/// Request headers passed on to the provider. Everything else, notably the
+/// relay token, stays behind.
+const FORWARDED_HEADERS: &[&str] = &["content-type", "accept", "anthropic-version", "anthropic-beta", "openai-beta"];
+
+/// Response headers that only make sense between the relay and the provider.
+const HOP_BY_HOP: &[&str] = &["connection", "keep-alive", "transfer-encoding", "content-length"];
+
+/// Which provider and API a relay path is for, e.g. `/anthropic/v1/messages`.
+fn route(path: &str) -> Option<(LlmProvider, &'static str)> {
+    match path {
+        "/anthropic/v1/messages" => Some((LlmProvider::Anthropic, "messages")),
+        "/openai/v1/chat/completions" => Some((LlmProvider::OpenAI, "chat/completions")),
+        "/openai/v1/responses" => Some((LlmProvider::OpenAI, "responses")),
+        _ => None,
+    }
+}
+
+/// The API base and key to use for `model` on `provider`: the model's own
+/// endpoint if the registry gives it one, otherwise the provider's.
+fn upstream(cfg: &BatConfig, provider: LlmProvider, model: &str) -> (String, Option<String>) {
+    let registry = cfg.model_registry();
+    let endpoint = registry.lookup(model).filter(|m| m.provider == provider).and_then(|m| m.endpoint.clone());
+    match provider {
+        LlmProvider::Anthropic => {
+            (endpoint.unwrap_or_else(|| "https://api.anthropic.com/v1".to_string()), cfg.api_keys.anthropic_key())
+        }
+        _ => (endpoint.unwrap_or_else(|| "https://api.openai.com/v1".to_string()), cfg.api_keys.openai_key()),
+    }
+}
+
+/// Answer with a JSON error in the shape both providers use, so the
+/// agent's clients report it like any other API error.
+async fn refuse(client: &mut TcpStream, status: &str, kind: &str, message: &str) -> Result<()> {
+    let body = serde_json::json!({ "error": { "type": kind, "message": message } }).to_string();
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    client.write_all(response.as_bytes()).await?;
+    Ok(())
+}
+
+/// Serve one relay request from an agent.
+pub(crate) async fn serve(egress: &EgressProxy, request: Request, mut body: Vec<u8>, client: &mut TcpStream) -> Result<()> {
+    let token = request
+        .header("x-api-key")
+        .or_else(|| request.header("authorization").and_then(|v| v.strip_prefix("Bearer ")))
+        .unwrap_or_default();
+    let Some(session_id) = egress.relay_session(token.trim()) else {
+        return refuse(client, "401 Unauthorized", "authentication_error", "Unknown relay token").await;
+    };
+    let Some((provider, api)) = route(&request.target).filter(|_| request.method == "POST") else {
+        let message = format!("The relay doesn't serve {} {}", request.method, request.target);
+        return refuse(client, "404 Not Found", "not_found_error", &message).await;
+    };
+    let Some(length) = request.header("content-length").and_then(|v| v.parse::<usize>().ok()) else {
+        return refuse(client, "411 Length Required", "invalid_request_error", "Content-Length is required").await;
+    };
+    if length > MAX_BODY {
+        let message = format!("The relay takes at most {MAX_BODY} bytes");
+        return refuse(client, "413 Payload Too Large", "request_too_large", &message).await;
+    }
+    if body.len() < length {
+        let start = body.len();
+        body.resize(length, 0);
+        client.read_exact(&mut body[start..]).await?;
+    }
+    body.truncate(length);
+
+    let model = serde_json::from_slice::<serde_json::Value>(&body)
+        .ok()
+        .and_then(|v| v["model"].as_str().map(str::to_string))
+        .unwrap_or_default();
+    let provider_name = if provider == LlmProvider::Anthropic { "Anthropic" } else { "OpenAI" };
+    let (base, key) = upstream(&egress.config().read().unwrap(), provider, &model);
+    let Some(key) = key else {
+        let message = format!("No {provider_name} API key is configured");
+        return refuse(client, "401 Unauthorized", "authentication_error", &message).await;
+    };
+
+    let mut upstream_request = reqwest::Client::new().post(format!("{}/{api}", base.trim_end_matches('/')));
+    for name in FORWARDED_HEADERS {
+        if let Some(value) = request.header(name) {
+            upstream_request = upstream_request.header(*name, value);
+        }
+    }
+    upstream_request = match provider {
+        LlmProvider::Anthropic => upstream_request.header("x-api-key", key),
+        _ => upstream_request.bearer_auth(key),
+    };
+    let mut response = match upstream_request.body(body).send().await {
+        Ok(response) => response,
+        Err(e) => {
+            let message = format!("The relay couldn't reach {provider_name}: {e}");
+            return refuse(client, "502 Bad Gateway", "api_error", &message).await;
+        }
+    };
+
+    let status = response.status();
+    let sid = session_id.to_string();
+    egress.audit(AuditLevel::Debug, AuditCategory::Network, "llm_relay",
+        &format!("{provider_name} {api} ({model}): {}", status.as_u16()), Some(&sid),
+        Some(&serde_json::json!({ "provider": provider_name, "api": api, "model": model, "status": status.as_u16() }).to_string()));
+
+    let mut head = format!("HTTP/1.1 {} {}\r\n", status.as_u16(), status.canonical_reason().unwrap_or(""));
+    for (name, value) in response.headers() {
+        if let (false, Ok(value)) = (HOP_BY_HOP.contains(&name.as_str()), value.to_str()) {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+    }
+    head.push_str("Connection: close\r\n\r\n");
+    client.write_all(head.as_bytes()).await?;
+    while let Some(chunk) = response.chunk().await? {
+        client.write_all(&chunk).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::events::EventBus;
+    use bat_types::models::ModelInfo;
+    use std::sync::{Arc, RwLock};
+    use uuid::Uuid;
+
+    /// A provider that answers each request with the request it got.
+    async fn echo_provider() -> String {
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut received = Vec::new();
+                    let mut buf = [0u8; 4096];
+                    while !String::from_utf8_lossy(&received).contains("\"model\"") {
+                        match stream.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => received.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    let body = String::from_utf8_lossy(&received);
+                    let response = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nretry-after: 3\r\n\r\n{body}", body.len());
+                    let _ = stream.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        format!("http://127.0.0.1:{port}/v1")
+    }
+
+    async fn post(url: &str, path: &str, token: &str, body: &str) -> String {
+        let authority = url.trim_start_matches("http://");
+        let mut stream = TcpStream::connect(authority).await.unwrap();
+        let request = format!(
+            "POST {path} HTTP/1.1\r\nHost: {authority}\r\nx-api-key: {token}\r\nanthropic-version: 2023-06-01\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = Vec::new();
+        let _ = tokio::time::timeout(std::time::Duration::from_secs(5), stream.read_to_end(&mut response)).await;
+        String::from_utf8_lossy(&response).into_owned()
+    }
+
+    #[tokio::test]
+    async fn test_relay_swaps_the_token_for_the_key() {
+        let mut cfg = BatConfig::default();
+        cfg.api_keys.anthropic = Some("sk-ant-real".to_string());
+        let mut model = ModelInfo::local("claude-relayed");
+        model.provider = LlmProvider::Anthropic;
+        model.endpoint = Some(echo_provider().await);
+        cfg.models.push(model);
+        let key = cfg.api_keys.anthropic_key().unwrap();
+        let db = Arc::new(Database::open_in_memory().unwrap());
+        let egress = EgressProxy::new(Arc::new(RwLock::new(cfg)), db, EventBus::new());
+
+        let session = Uuid::new_v4();
+        let relay = egress.relay(session).await.unwrap();
+        assert!(relay.anthropic);
+        let body = r#"{"model":"claude-relayed","messages":[]}"#;
+
+        let relayed = post(&relay.url, "/anthropic/v1/messages", &relay.token, body).await;
+        assert!(relayed.starts_with("HTTP/1.1 200"), "{relayed}");
+        assert!(relayed.contains("retry-after: 3"));
+        assert!(relayed.contains("POST /v1/messages"));
+        assert!(relayed.contains(&format!("x-api-key: {key}")));
+        assert!(relayed.contains("anthropic-version: 2023-06-01"));
+        assert!(!relayed.contains(&relay.token));
+
+        assert!(post(&relay.url, "/anthropic/v1/messages", "guess", body).await.starts_with("HTTP/1.1 401"));
+        assert!(post(&relay.url, "/openai/v1/files", &relay.token, body).await.starts_with("HTTP/1.1 404"));
+        // The proxy token a session's processes see doesn't open the relay
+        let route = egress.route(session).await.unwrap().unwrap();
+        let proxy_token = route.url.rsplit_once('@').unwrap().0.rsplit_once(':').unwrap().1;
+        assert!(post(&relay.url, "/anthropic/v1/messages", proxy_token, body).await.starts_with("HTTP/1.1 401"));
+    }
+
+    #[tokio::test]
+    async fn test_token_is_rejected_once_its_agent_exits() {
+        let mut cfg = BatConfig::default();
+        cfg.api_keys.anthropic = Some("sk-ant-real".to_string());
+        let mut model = ModelInfo::local("claude-relayed");
+        model.provider = LlmProvider::Anthropic;
+        model.endpoint = Some(echo_provider().await);
+        cfg.models.push(model);
+        let db = Arc::new(Database::open_in_memory().unwrap());
+        let egress = EgressProxy::new(Arc::new(RwLock::new(cfg)), db, EventBus::new());
+        let session = Uuid::new_v4();
+        let relay = egress.relay(session).await.unwrap();
+        let body = r#"{"model":"claude-relayed","messages":[]}"#;
+        assert!(post(&relay.url, "/anthropic/v1/messages", &relay.token, body).await.starts_with("HTTP/1.1 200"));
+
+        // What `finish_agent` and the idle-agent shutdown do when an agent exits
+        egress.end_session(session);
+        let refused = post(&relay.url, "/anthropic/v1/messages", &relay.token, body).await;
+        assert!(refused.starts_with("HTTP/1.1 401") && refused.contains("Unknown relay token"), "{refused}");
+
+        // The session's next agent gets a token of its own
+        let next = egress.relay(session).await.unwrap();
+        assert_ne!(next.token, relay.token);
+        assert!(post(&next.url, "/anthropic/v1/messages", &next.token, body).await.starts_with("HTTP/1.1 200"));
+    }
+
+    #[test]
+    fn test_keys_only_go_where_the_config_sends_them() {
+        let mut cfg = BatConfig::default();
+        cfg.api_keys.anthropic = Some("sk-ant".to_string());
+        let mut proxied = ModelInfo::local("claude-via-gateway");
+        proxied.provider = LlmProvider::Anthropic;
+        proxied.endpoint = Some("https://llm.internal/v1".to_string());
+        cfg.models.push(proxied);
+
+        assert_eq!(route("/anthropic/v1/messages"), Some((LlmProvider::Anthropic, "messages")));
+        assert_eq!(route("/openai/v1/files"), None);
+        assert_eq!(route("/anthropic/v1/messages/../../evil"), None);
+
+        let (base, key) = upstream(&cfg, LlmProvider::Anthropic, "claude-via-gateway");
+        assert_eq!(base, "https://llm.internal/v1");
+        if std::env::var_os("ANTHROPIC_API_KEY").is_none() {
+            assert_eq!(key.as_deref(), Some("sk-ant"));
+        }
+        // A model registered for another provider doesn't move the key
+        assert_eq!(upstream(&cfg, LlmProvider::OpenAI, "claude-via-gateway").0, "https://api.openai.com/v1");
+        assert_eq!(upstream(&cfg, LlmProvider::Anthropic, "claude-sonnet-4-6").0, "https://api.anthropic.com/v1");
+    }
+}
@@ -3,9 +3,11 @@
 //! Applies isolation at agent process spawn time:
 //! - **Windows:** Job Objects with memory/CPU limits
 //! - **macOS:** Seatbelt sandbox profiles
-//! - **Linux:** namespaces + cgroups + seccomp-bpf
+//! - **Linux:** cgroups, user + mount namespaces, Landlock and seccomp-bpf,
+//!   applied in the child between fork and exec
 
 use anyhow::Result;
+use bat_types::policy::PathPolicy;
 #[allow(unused_imports)]
 use tracing::{info, warn};
 
@@ -18,8 +20,18 @@ pub struct SandboxConfig {
     pub cpu_shares: u32,
     /// Allowed filesystem paths (read or read-write).
     pub allowed_paths: Vec<(String, bool)>, // (path, writable)
-    /// Allowed network endpoints (host:port).
+    /// Network endpoints (`host:port`) the agent may connect to; empty
+    /// means any. Linux can only restrict TCP connections by port.
     pub allowed_endpoints: Vec<String>,
+    /// The session's path policies. On Linux they become the agent's
+    /// Landlock rules, and denied paths are masked in its mount namespace.
+    pub path_policies: Vec<PathPolicy>,
+    /// Apply the Linux spawn-time restrictions (namespaces, Landlock,
+    /// seccomp, `no_new_privs`). Resource limits apply either way.
+    pub linux_sandbox: bool,
+    /// Kill the agent on a syscall outside the allowlist instead of
+    /// failing the call with `EPERM`.
+    pub seccomp_kill: bool,
 }
 
 impl Default for SandboxConfig {
@@ -28,17 +40,25 @@ impl Default for SandboxConfig {
             memory_limit_mb: 512,
             cpu_shares: 0,
             allowed_paths: Vec::new(),
-            allowed_endpoints: vec!["api.anthropic.com:443".to_string()],
+            allowed_endpoints: Vec::new(),
+            path_policies: Vec::new(),
+            linux_sandbox: true,
+            seccomp_kill: false,
         }
     }
 }
 
 /// Apply sandbox to a child process by PID.
-/// Call this AFTER spawning but BEFORE the process does real work.
+/// Call this AFTER spawning but BEFORE the process does real work, with the
+/// `PreSpawnConfig` the process was spawned with.
 pub fn apply_sandbox(
     pid: u32,
     config: &SandboxConfig,
+    pre_spawn: PreSpawnConfig,
 ) -> Result<SandboxHandle> {
+    #[cfg(not(target_os = "linux"))]
+    let _ = pre_spawn;
+
     #[cfg(target_os = "windows")]
     return apply_windows_sandbox(pid, config);
 
@@ -46,7 +66,7 @@ pub fn apply_sandbox(
     return apply_macos_sandbox(pid, config);
 
     #[cfg(target_os = "linux")]
-    return apply_linux_sandbox(pid, config);
+    return apply_linux_sandbox(pid, config, pre_spawn);
 
     #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
     {
@@ -58,7 +78,8 @@ pub fn apply_sandbox(
 
 /// Generate sandbox arguments to pass to the child process at spawn time.
 /// On macOS, this generates a seatbelt profile file.
-/// On Linux, this sets up namespace flags.
+/// On Linux, this prepares everything the child applies to itself before
+/// exec (see `PreSpawnConfig::apply_to`).
 /// On Windows, sandbox is applied post-spawn via Job Objects.
 pub fn pre_spawn_setup(_config: &SandboxConfig) -> Result<PreSpawnConfig> {
     #[cfg(target_os = "macos")]
@@ -67,7 +88,10 @@ pub fn pre_spawn_setup(_config: &SandboxConfig) -> Result<PreSpawnConfig> {
         Ok(PreSpawnConfig { seatbelt_profile: Some(profile), ..Default::default() })
     }
 
-    #[cfg(not(target_os = "macos"))]
+    #[cfg(target_os = "linux")]
+    return Ok(PreSpawnConfig { linux: Some(linux::Plan::new(_config)?) });
+
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
     Ok(PreSpawnConfig::default())
 }
 
@@ -75,6 +99,21 @@ pub fn pre_spawn_setup(_config: &SandboxConfig) -> Result<PreSpawnConfig> {
 pub struct PreSpawnConfig {
     #[cfg(target_os = "macos")]
     pub seatbelt_profile: Option<String>,
+    #[cfg(target_os = "linux")]
+    linux: Option<linux::Plan>,
+}
+
+impl PreSpawnConfig {
+    /// Hook the spawn-time restrictions into `cmd`, which runs `program`.
+    pub fn apply_to(&mut self, cmd: &mut tokio::process::Command, program: &std::path::Path) {
+        #[cfg(target_os = "linux")]
+        if let Some(plan) = &mut self.linux {
+            plan.apply_to(cmd, program);
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        let _ = (cmd, program);
+    }
 }
 
 /// Handle to sandbox resources. Drop to clean up.
@@ -82,6 +121,22 @@ pub enum SandboxHandle {
     None,
     #[cfg(target_os = "windows")]
     WindowsJob(WindowsJobHandle),
+    #[cfg(target_os = "linux")]
+    Linux(linux::Applied),
+}
+
+impl SandboxHandle {
+    /// The protections actually in force for the process, and those that
+    /// were asked for but couldn't be applied.
+    pub fn protections(&self) -> Protections {
+        match self {
+            SandboxHandle::None => Protections::default(),
+            #[cfg(target_os = "windows")]
+            SandboxHandle::WindowsJob(_) => Protections { active: vec!["job object".to_string()], missing: Vec::new() },
+            #[cfg(target_os = "linux")]
+            SandboxHandle::Linux(applied) => applied.protections.clone(),
+        }
+    }
 }
 
 impl Drop for SandboxHandle {
@@ -92,7 +147,30 @@ impl Drop for SandboxHandle {
             SandboxHandle::WindowsJob(handle) => {
                 handle.close();
             }
+            #[cfg(target_os = "linux")]
+            SandboxHandle::Linux(applied) => applied.cleanup(),
+        }
+    }
+}
+
+/// What a sandbox ended up enforcing, for the audit log.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Protections {
+    pub active: Vec<String>,
+    pub missing: Vec<String>,
+}
+
+impl std::fmt::Display for Protections {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.active.is_empty() {
+            write!(f, "no protections active")?;
+        } else {
+            write!(f, "active: {}", self.active.join(", "))?;
+        }
+        if !self.missing.is_empty() {
+            write!(f, "; unavailable: {}", self.missing.join(", "))?;
         }
+        Ok(())
     }
 }
 
@@ -194,6 +272,9 @@ fn generate_seatbelt_profile(config: &SandboxConfig) -> Result<String> {
     for endpoint in &config.allowed_endpoints {
         profile.push_str(&format!("(allow network-outbound (remote tcp \"{endpoint}\"))\n"));
     }
+    if config.allowed_endpoints.is_empty() {
+        profile.push_str("(allow network-outbound)\n");
+    }
 
     // Allow DNS resolution
     profile.push_str("(allow network-outbound (remote udp \"*:53\"))\n");
@@ -217,26 +298,794 @@ fn apply_macos_sandbox(
     Ok(SandboxHandle::None)
 }
 
-// ── Linux: namespaces + cgroups ─────────────────────────────────────────
+
+// ── Linux: cgroups + namespaces + Landlock + seccomp ────────────────────
 
 #[cfg(target_os = "linux")]
 fn apply_linux_sandbox(
     pid: u32,
     config: &SandboxConfig,
+    pre_spawn: PreSpawnConfig,
 ) -> Result<SandboxHandle> {
+    // The child restricted itself before exec; all that's left is its report.
+    let Some(plan) = pre_spawn.linux else {
+        return Ok(SandboxHandle::None);
+    };
+    let applied = plan.finish();
+    info!("Linux sandbox applied: pid={pid}, memory_limit={}MB, {}", config.memory_limit_mb, applied.protections);
+    Ok(SandboxHandle::Linux(applied))
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    //! The child applies these to itself between fork and exec, in order:
+    //! join its cgroup, enter new user and mount namespaces and mask denied
+    //! paths, set `no_new_privs`, restrict its files and TCP connections with
+    //! Landlock, install the seccomp filter, and write what took effect to a
+    //! pipe the gateway reads once the spawn returns. Each step is best
+    //! effort, so an old kernel or a locked-down container gives a weaker
+    //! sandbox rather than no agent, and the report makes the difference
+    //! visible.
+
+    use super::{Protections, SandboxConfig};
+    use anyhow::{Context, Result};
+    use bat_types::policy::{expand_tilde, AccessLevel, PathPolicy};
+    use std::ffi::{CStr, CString};
+    use std::io::Read;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::{Path, PathBuf};
+    use std::ptr;
+
+    const CGROUP_ROOT: &str = "/sys/fs/cgroup/batchismo";
+    const CGROUP2_SUPER_MAGIC: libc::c_long = 0x6367_7270;
+
+    // Steps that took effect, as bits in the child's report.
+    const CGROUP: u32 = 1;
+    const USER_NS: u32 = 1 << 1;
+    const MOUNT_NS: u32 = 1 << 2;
+    const NO_NEW_PRIVS: u32 = 1 << 3;
+    const LANDLOCK: u32 = 1 << 4;
+    const SECCOMP: u32 = 1 << 5;
+    const NETWORK: u32 = 1 << 6;
+
+    // Landlock filesystem rights (linux/landlock.h).
+    const FS_EXECUTE: u64 = 1;
+    const FS_WRITE_FILE: u64 = 1 << 1;
+    const FS_READ_FILE: u64 = 1 << 2;
+    const FS_READ_DIR: u64 = 1 << 3;
+    const FS_REMOVE_DIR: u64 = 1 << 4;
+    const FS_REMOVE_FILE: u64 = 1 << 5;
+    const FS_MAKE_DIR: u64 = 1 << 7;
+    const FS_MAKE_REG: u64 = 1 << 8;
+    const FS_MAKE_SOCK: u64 = 1 << 9;
+    const FS_MAKE_FIFO: u64 = 1 << 10;
+    const FS_MAKE_SYM: u64 = 1 << 12;
+    const FS_REFER: u64 = 1 << 13;
+    const FS_TRUNCATE: u64 = 1 << 14;
+    /// Everything ABI 1 handles, `FS_EXECUTE` through `FS_MAKE_SYM`.
+    const FS_ABI_1: u64 = (1 << 13) - 1;
+    /// The rights that apply to a file rather than a directory.
+    const FS_FILE: u64 = FS_EXECUTE | FS_WRITE_FILE | FS_READ_FILE | FS_TRUNCATE;
+
+    const READ: u64 = FS_EXECUTE | FS_READ_FILE | FS_READ_DIR;
+    const WRITE: u64 = FS_WRITE_FILE | FS_REMOVE_DIR | FS_REMOVE_FILE | FS_MAKE_DIR | FS_MAKE_REG
+        | FS_MAKE_SOCK | FS_MAKE_FIFO | FS_MAKE_SYM | FS_REFER | FS_TRUNCATE;
+
+    // Landlock network rights (ABI 4).
+    const NET_CONNECT_TCP: u64 = 1 << 1;
+
+    const LANDLOCK_CREATE_RULESET_VERSION: libc::c_uint = 1;
+    const LANDLOCK_RULE_PATH_BENEATH: libc::c_int = 1;
+    const LANDLOCK_RULE_NET_PORT: libc::c_int = 2;
+
+    #[repr(C)]
+    struct RulesetAttr {
+        handled_access_fs: u64,
+        /// Only understood from ABI 4, and left out of the size before it.
+        handled_access_net: u64,
+    }
+
+    #[repr(C)]
+    struct NetPortAttr {
+        allowed_access: u64,
+        port: u64,
+    }
+
+    #[repr(C, packed)]
+    struct PathBeneathAttr {
+        allowed_access: u64,
+        parent_fd: i32,
+    }
+
+    /// System directories every agent and the commands it runs need to read.
+    const SYSTEM_DIRS: &[&str] = &[
+        "/usr", "/bin", "/sbin", "/lib", "/lib32", "/lib64", "/libx32", "/opt", "/etc", "/nix", "/snap",
+        "/proc", "/sys", "/run",
+    ];
+
+    /// Prepared by the gateway before the fork.
+    pub struct Plan {
+        /// Moved into the `pre_exec` hook by `apply_to`.
+        steps: Option<Steps>,
+        report: Option<OwnedFd>,
+        cgroup: Option<PathBuf>,
+        limited: bool,
+        sandboxed: bool,
+        seccomp_kill: bool,
+        masks: usize,
+        ports: Vec<u16>,
+    }
+
+    impl Plan {
+        pub fn new(config: &SandboxConfig) -> Result<Self> {
+            let (report, report_writer) = pipe()?;
+            let cgroup = create_cgroup(config.memory_limit_mb, config.cpu_shares);
+            let restrictions = config.linux_sandbox.then(|| Restrictions::new(config));
+            Ok(Self {
+                masks: restrictions.as_ref().map_or(0, |r| r.masks.len()),
+                ports: restrictions.as_ref().map_or(Vec::new(), |r| r.ports.clone()),
+                steps: Some(Steps {
+                    cgroup_procs: cgroup.as_ref().and_then(|dir| cstring(&dir.join("cgroup.procs"))),
+                    restrictions,
+                    report: report_writer,
+                }),
+                report: Some(report),
+                cgroup,
+                limited: config.memory_limit_mb > 0 || config.cpu_shares > 0,
+                sandboxed: config.linux_sandbox,
+                seccomp_kill: config.seccomp_kill,
+            })
+        }
+
+        pub fn apply_to(&mut self, cmd: &mut tokio::process::Command, program: &Path) {
+            let Some(mut steps) = self.steps.take() else { return };
+            if let (Some(restrictions), Some(dir)) = (&mut steps.restrictions, program.parent()) {
+                restrictions.rules.extend(cstring(dir).map(|dir| (dir, READ)));
+            }
+            // SAFETY: the hook only makes raw system calls on memory that was
+            // allocated before the fork (see `Steps::run`).
+            unsafe {
+                cmd.pre_exec(move || steps.run());
+            }
+        }
+
+        /// Read the child's report. Call once the spawn has returned: the
+        /// child writes it just before exec.
+        pub fn finish(mut self) -> Applied {
+            let mut buf = [0u8; 12];
+            let read = match (self.steps.is_none(), self.report.take()) {
+                (true, Some(report)) => std::fs::File::from(report).read_exact(&mut buf).is_ok(),
+                _ => false,
+            };
+            let word = |i: usize| if read { u32::from_ne_bytes(buf[i * 4..i * 4 + 4].try_into().unwrap()) } else { 0 };
+            let (done, landlock_abi, masked) = (word(0), word(1), word(2) as usize);
+
+            let mut protections = Protections::default();
+            let mut note = |ok: bool, name: String| {
+                if ok { protections.active.push(name) } else { protections.missing.push(name) }
+            };
+            if self.limited {
+                note(done & CGROUP != 0, "cgroup limits".to_string());
+            }
+            if self.sandboxed {
+                note(done & USER_NS != 0, "user namespace".to_string());
+                note(done & MOUNT_NS != 0, "mount namespace".to_string());
+                if self.masks > 0 {
+                    note(masked == self.masks, format!("{masked} of {} denied paths masked", self.masks));
+                }
+                note(done & NO_NEW_PRIVS != 0, "no_new_privs".to_string());
+                note(done & LANDLOCK != 0, match landlock_abi {
+                    0 => "landlock".to_string(),
+                    abi => format!("landlock (ABI {abi})"),
+                });
+                note(done & SECCOMP != 0, if self.seccomp_kill { "seccomp (kill)" } else { "seccomp" }.to_string());
+                if !self.ports.is_empty() {
+                    let ports: Vec<String> = self.ports.iter().map(u16::to_string).collect();
+                    note(done & NETWORK != 0, format!("tcp connect to port {} only", ports.join(", ")));
+                }
+            }
+            Applied { protections, cgroup: self.cgroup.take() }
+        }
+    }
+
+    impl Drop for Plan {
+        fn drop(&mut self) {
+            // Only still set if the agent never got to use it
+            if let Some(dir) = self.cgroup.take() {
+                let _ = std::fs::remove_dir(dir);
+            }
+        }
+    }
+
+    /// What the sandbox ended up enforcing for a running agent.
+    pub struct Applied {
+        pub protections: Protections,
+        cgroup: Option<PathBuf>,
+    }
+
+    impl Applied {
+        /// Remove the agent's cgroup, which the kernel allows once it has exited.
+        pub fn cleanup(&mut self) {
+            if let Some(dir) = self.cgroup.take() {
+                let _ = std::fs::remove_dir(dir);
+            }
+        }
+    }
+
+    /// What the child does. Everything is allocated up front: nothing may
+    /// allocate between fork and exec.
+    struct Steps {
+        cgroup_procs: Option<CString>,
+        restrictions: Option<Restrictions>,
+        report: OwnedFd,
+    }
+
+    impl Steps {
+        /// Runs in the child between fork and exec: raw system calls only.
+        fn run(&self) -> std::io::Result<()> {
+            let mut report = [0u32; 3];
+            // SAFETY: every pointer passed below is to memory owned by `self`
+            // or to a local, and outlives the call.
+            unsafe {
+                if let Some(procs) = &self.cgroup_procs {
+                    // Writing 0 moves the writer itself
+                    if write_file(procs, b"0") {
+                        report[0] |= CGROUP;
+                    }
+                }
+                if let Some(restrictions) = &self.restrictions {
+                    restrictions.run(&mut report)?;
+                }
+                let mut buf = [0u8; 12];
+                for (chunk, word) in buf.chunks_exact_mut(4).zip(report) {
+                    chunk.copy_from_slice(&word.to_ne_bytes());
+                }
+                libc::write(self.report.as_raw_fd(), buf.as_ptr().cast(), buf.len());
+            }
+            Ok(())
+        }
+    }
+
+    struct Restrictions {
+        uid_map: Vec<u8>,
+        gid_map: Vec<u8>,
+        /// Denied paths to hide in the mount namespace, and whether each is a
+        /// directory.
+        masks: Vec<(CString, bool)>,
+        /// Landlock rules: a path and the rights granted beneath it.
+        rules: Vec<(CString, u64)>,
+        /// The only TCP ports the agent may connect to, if restricted.
+        ports: Vec<u16>,
+        filter: Option<Vec<libc::sock_filter>>,
+    }
+
+    impl Restrictions {
+        fn new(config: &SandboxConfig) -> Self {
+            // SAFETY: geteuid/getegid have no preconditions and can't fail.
+            let (uid, gid) = unsafe { (libc::geteuid(), libc::getegid()) };
+            Self {
+                uid_map: format!("{uid} {uid} 1\n").into_bytes(),
+                gid_map: format!("{gid} {gid} 1\n").into_bytes(),
+                masks: masks(&config.path_policies),
+                rules: landlock_rules(&config.path_policies),
+                ports: config
+                    .allowed_endpoints
+                    .iter()
+                    .filter_map(|endpoint| endpoint.rsplit_once(':')?.1.parse().ok())
+                    .collect(),
+                filter: seccomp::filter(config.seccomp_kill),
+            }
+        }
+
+        unsafe fn run(&self, report: &mut [u32; 3]) -> std::io::Result<()> {
+            // A user namespace where the agent keeps its own IDs, so that it
+            // holds no capabilities outside it, and a private mount namespace
+            // to hide denied paths in.
+            if libc::unshare(libc::CLONE_NEWUSER) == 0 {
+                if !(write_file(c"/proc/self/setgroups", b"deny")
+                    && write_file(c"/proc/self/uid_map", &self.uid_map)
+                    && write_file(c"/proc/self/gid_map", &self.gid_map))
+                {
+                    // Unmapped, the agent couldn't own any file it created
+                    return Err(std::io::Error::other("Failed to map IDs into the agent's user namespace"));
+                }
+                report[0] |= USER_NS;
+            }
+            if libc::unshare(libc::CLONE_NEWNS) == 0
+                && libc::mount(c"none".as_ptr(), c"/".as_ptr(), ptr::null(), libc::MS_REC | libc::MS_PRIVATE, ptr::null()) == 0
+            {
+                report[0] |= MOUNT_NS;
+                report[2] = self.masks.iter().filter(|(path, dir)| mask(path, *dir)).count() as u32;
+            }
+
+            if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) == 0 {
+                report[0] |= NO_NEW_PRIVS;
+            }
+
+            let abi = libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                ptr::null::<RulesetAttr>(),
+                0usize,
+                LANDLOCK_CREATE_RULESET_VERSION,
+            );
+            if abi >= 1 && self.landlock(abi) {
+                report[0] |= LANDLOCK;
+                report[1] = abi as u32;
+                if abi >= 4 && !self.ports.is_empty() {
+                    report[0] |= NETWORK;
+                }
+            }
+
+            // Last, as the filter doesn't allow the calls above
+            if let Some(filter) = &self.filter {
+                let prog = libc::sock_fprog { len: filter.len() as u16, filter: filter.as_ptr().cast_mut() };
+                if libc::prctl(libc::PR_SET_SECCOMP, libc::SECCOMP_MODE_FILTER, &prog as *const libc::sock_fprog) == 0 {
+                    report[0] |= SECCOMP;
+                }
+            }
+            Ok(())
+        }
 
-    // Apply cgroup memory limit
-    if config.memory_limit_mb > 0 {
-        let cgroup_path = format!("/sys/fs/cgroup/batchismo/agent-{pid}");
-        if let Err(e) = std::fs::create_dir_all(&cgroup_path) {
-            warn!("Failed to create cgroup dir (may need root): {e}");
+        unsafe fn landlock(&self, abi: libc::c_long) -> bool {
+            let mut handled = FS_ABI_1;
+            if abi >= 2 {
+                handled |= FS_REFER;
+            }
+            if abi >= 3 {
+                handled |= FS_TRUNCATE;
+            }
+            let net = abi >= 4 && !self.ports.is_empty();
+            let attr = RulesetAttr { handled_access_fs: handled, handled_access_net: if net { NET_CONNECT_TCP } else { 0 } };
+            let size = if abi >= 4 { std::mem::size_of::<RulesetAttr>() } else { std::mem::size_of::<u64>() };
+            let ruleset = libc::syscall(libc::SYS_landlock_create_ruleset, &attr as *const RulesetAttr, size, 0 as libc::c_uint);
+            if ruleset < 0 {
+                return false;
+            }
+            let ruleset = ruleset as libc::c_int;
+            for (path, access) in &self.rules {
+                let fd = libc::open(path.as_ptr(), libc::O_PATH | libc::O_CLOEXEC);
+                if fd < 0 {
+                    continue;
+                }
+                let mut stat: libc::stat = std::mem::zeroed();
+                let is_dir = libc::fstat(fd, &mut stat) == 0 && stat.st_mode & libc::S_IFMT == libc::S_IFDIR;
+                let allowed_access = if is_dir { access & handled } else { access & handled & FS_FILE };
+                let rule = PathBeneathAttr { allowed_access, parent_fd: fd };
+                libc::syscall(
+                    libc::SYS_landlock_add_rule,
+                    ruleset,
+                    LANDLOCK_RULE_PATH_BENEATH,
+                    &rule as *const PathBeneathAttr,
+                    0 as libc::c_uint,
+                );
+                libc::close(fd);
+            }
+            if net {
+                for port in &self.ports {
+                    let rule = NetPortAttr { allowed_access: NET_CONNECT_TCP, port: u64::from(*port) };
+                    libc::syscall(
+                        libc::SYS_landlock_add_rule,
+                        ruleset,
+                        LANDLOCK_RULE_NET_PORT,
+                        &rule as *const NetPortAttr,
+                        0 as libc::c_uint,
+                    );
+                }
+            }
+            let restricted = libc::syscall(libc::SYS_landlock_restrict_self, ruleset, 0 as libc::c_uint) == 0;
+            libc::close(ruleset);
+            restricted
+        }
+    }
+
+    /// Hide `path`: an empty read-only tmpfs over a directory, `/dev/null`
+    /// over a file.
+    unsafe fn mask(path: &CStr, dir: bool) -> bool {
+        if dir {
+            let flags = libc::MS_RDONLY | libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC;
+            libc::mount(c"tmpfs".as_ptr(), path.as_ptr(), c"tmpfs".as_ptr(), flags, c"size=4k,mode=555".as_ptr().cast()) == 0
         } else {
-            let mem_bytes = config.memory_limit_mb * 1024 * 1024;
-            let _ = std::fs::write(format!("{cgroup_path}/memory.max"), mem_bytes.to_string());
-            let _ = std::fs::write(format!("{cgroup_path}/cgroup.procs"), pid.to_string());
-            info!("Linux cgroup sandbox applied: pid={pid}, memory_limit={}MB", config.memory_limit_mb);
+            libc::mount(c"/dev/null".as_ptr(), path.as_ptr(), ptr::null(), libc::MS_BIND, ptr::null()) == 0
         }
     }
 
-    Ok(SandboxHandle::None)
+    /// Write `data` to the file at `path` with raw system calls.
+    unsafe fn write_file(path: &CStr, data: &[u8]) -> bool {
+        let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+        if fd < 0 {
+            return false;
+        }
+        let written = libc::write(fd, data.as_ptr().cast(), data.len());
+        libc::close(fd);
+        written == data.len() as isize
+    }
+
+    fn pipe() -> Result<(OwnedFd, OwnedFd)> {
+        let mut fds = [0; 2];
+        // SAFETY: pipe2 writes two new descriptors into `fds` on success.
+        if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+            return Err(std::io::Error::last_os_error()).context("Failed to create the sandbox report pipe");
+        }
+        // SAFETY: both descriptors are new and owned by nothing else.
+        Ok(unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) })
+    }
+
+    fn cstring(path: &Path) -> Option<CString> {
+        CString::new(path.as_os_str().as_bytes()).ok()
+    }
+
+    /// A cgroup with the agent's memory and CPU limits, if the gateway may
+    /// create one: on a unified (v2) hierarchy, as root or with
+    /// `/sys/fs/cgroup/batchismo` delegated to it.
+    fn create_cgroup(memory_limit_mb: u64, cpu_shares: u32) -> Option<PathBuf> {
+        if memory_limit_mb == 0 && cpu_shares == 0 {
+            return None;
+        }
+        // SAFETY: statfs only writes into `fs`.
+        let mut fs: libc::statfs = unsafe { std::mem::zeroed() };
+        if unsafe { libc::statfs(c"/sys/fs/cgroup".as_ptr(), &mut fs) } != 0 || fs.f_type as libc::c_long != CGROUP2_SUPER_MAGIC {
+            tracing::debug!("No cgroup for agents: /sys/fs/cgroup isn't a cgroup v2 hierarchy");
+            return None;
+        }
+        let root = Path::new(CGROUP_ROOT);
+        if let Err(e) = std::fs::create_dir_all(root) {
+            tracing::debug!("No cgroup for agents (may need root): {e}");
+            return None;
+        }
+        // Hand the controllers down to the agents' cgroups
+        for controller in ["+memory", "+cpu"] {
+            let _ = std::fs::write(root.join("cgroup.subtree_control"), controller);
+        }
+        let dir = root.join(format!("agent-{}", uuid::Uuid::new_v4()));
+        let limits = std::fs::create_dir(&dir).and_then(|_| {
+            if memory_limit_mb > 0 {
+                std::fs::write(dir.join("memory.max"), (memory_limit_mb * 1024 * 1024).to_string())?;
+            }
+            if cpu_shares > 0 {
+                std::fs::write(dir.join("cpu.weight"), cpu_weight(cpu_shares).to_string())?;
+            }
+            Ok(())
+        });
+        match limits {
+            Ok(()) => Some(dir),
+            Err(e) => {
+                tracing::debug!("Failed to set up agent cgroup {}: {e}", dir.display());
+                let _ = std::fs::remove_dir(&dir);
+                None
+            }
+        }
+    }
+
+    /// cgroup v1 CPU shares as a cgroup v2 weight, converted the way runc does.
+    fn cpu_weight(shares: u32) -> u64 {
+        1 + (u64::from(shares.clamp(2, 262_144)) - 2) * 9999 / 262_142
+    }
+
+    /// The part of a policy path before its first glob.
+    fn literal_prefix(path: &Path) -> PathBuf {
+        expand_tilde(path)
+            .components()
+            .take_while(|c| !c.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+            .collect()
+    }
+
+    /// Landlock rules for an agent: the system directories, its scratch
+    /// space, and what the path policies grant. Landlock can only grant
+    /// whole directory trees, so a glob or non-recursive policy is widened to
+    /// the literal directory it starts from, and denies are left to the mount
+    /// namespace and the tools' own checks.
+    fn landlock_rules(policies: &[PathPolicy]) -> Vec<(CString, u64)> {
+        let mut rules: Vec<(PathBuf, u64)> = SYSTEM_DIRS.iter().map(|dir| (PathBuf::from(dir), READ)).collect();
+        rules.push(("/dev".into(), READ | FS_WRITE_FILE | FS_TRUNCATE));
+        for tmp in ["/tmp".into(), "/var/tmp".into(), std::env::temp_dir()] {
+            rules.push((tmp, READ | WRITE));
+        }
+        // Where fs_delete and screenshot keep their files; created here, as
+        // the agent may not be allowed to create them itself
+        let own_dirs = [
+            dirs::home_dir().map(|home| home.join(".batchismo").join("trash")),
+            dirs::data_dir().map(|data| data.join("batchismo").join("screenshots")),
+        ];
+        for dir in own_dirs.into_iter().flatten() {
+            if std::fs::create_dir_all(&dir).is_ok() {
+                rules.push((dir, READ | WRITE));
+            }
+        }
+
+        // Every layer has to allow an access, so the topmost bounds them all
+        let top = policies.iter().map(|p| p.layer).max().unwrap_or(0);
+        for policy in policies.iter().filter(|p| p.layer == top) {
+            let access = match policy.access {
+                AccessLevel::ReadOnly => READ,
+                AccessLevel::ReadWrite => READ | WRITE,
+                AccessLevel::WriteOnly => WRITE,
+                AccessLevel::Deny => continue,
+            };
+            rules.push((literal_prefix(&policy.path), access));
+        }
+        rules
+            .into_iter()
+            .filter(|(path, _)| path.exists())
+            .filter_map(|(path, access)| Some((cstring(&path)?, access)))
+            .collect()
+    }
+
+    /// Deny rules the mount namespace can enforce: literal, recursive, and
+    /// not partly re-opened by a more specific allow.
+    fn masks(policies: &[PathPolicy]) -> Vec<(CString, bool)> {
+        let allowed: Vec<PathBuf> = policies
+            .iter()
+            .filter(|p| p.access != AccessLevel::Deny)
+            .map(|p| literal_prefix(&p.path))
+            .collect();
+        policies
+            .iter()
+            .filter(|p| p.access == AccessLevel::Deny && p.recursive)
+            .map(|p| expand_tilde(&p.path))
+            .filter(|path| literal_prefix(path) == *path)
+            .filter(|path| !allowed.iter().any(|a| a.starts_with(path) && a != path))
+            .filter_map(|path| {
+                let meta = std::fs::symlink_metadata(&path).ok()?;
+                if meta.file_type().is_symlink() {
+                    return None;
+                }
+                Some((cstring(&path)?, meta.is_dir()))
+            })
+            .collect()
+    }
+
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    mod seccomp {
+        //! The syscall allowlist. It starts from PRD §9.2 and adds what a
+        //! modern libc, the async runtime and the commands agents run use
+        //! (`openat`, `statx`, `epoll_*`, `getrandom`, ...). Left out are the
+        //! calls that reach into other processes or the kernel's
+        //! configuration: `ptrace`, `process_vm_*`, `mount`, `unshare`,
+        //! `setns`, `bpf`, `perf_event_open`, `keyctl`, `io_uring_*`,
+        //! `userfaultfd`, module loading, `reboot` and the like.
+
+        use libc::*;
+
+        #[cfg(target_arch = "x86_64")]
+        const AUDIT_ARCH: u32 = 0xC000_003E;
+        #[cfg(target_arch = "aarch64")]
+        const AUDIT_ARCH: u32 = 0xC000_00B7;
+
+        const NAMESPACE_FLAGS: u32 = (CLONE_NEWNS | CLONE_NEWUTS | CLONE_NEWIPC | CLONE_NEWUSER | CLONE_NEWPID
+            | CLONE_NEWNET | CLONE_NEWCGROUP) as u32;
+
+        const ALLOWED: &[c_long] = &[
+            // Files and directories
+            SYS_read, SYS_write, SYS_openat, SYS_openat2, SYS_close, SYS_close_range, SYS_fstat, SYS_newfstatat,
+            SYS_statx, SYS_lseek, SYS_pread64, SYS_pwrite64, SYS_readv, SYS_writev, SYS_preadv, SYS_pwritev,
+            SYS_preadv2, SYS_pwritev2, SYS_faccessat, SYS_faccessat2, SYS_getdents64, SYS_getcwd, SYS_chdir,
+            SYS_fchdir, SYS_mkdirat, SYS_mknodat, SYS_unlinkat, SYS_renameat2, SYS_linkat, SYS_symlinkat,
+            SYS_readlinkat, SYS_fchmod, SYS_fchmodat, SYS_fchown, SYS_fchownat, SYS_umask, SYS_utimensat,
+            SYS_truncate, SYS_ftruncate, SYS_fallocate, SYS_fsync, SYS_fdatasync, SYS_sync, SYS_syncfs,
+            SYS_flock, SYS_fcntl, SYS_ioctl, SYS_statfs, SYS_fstatfs, SYS_readahead,
+            SYS_splice, SYS_tee, SYS_copy_file_range, SYS_getxattr, SYS_lgetxattr, SYS_fgetxattr,
+            SYS_listxattr, SYS_llistxattr, SYS_flistxattr, SYS_setxattr, SYS_lsetxattr, SYS_fsetxattr,
+            SYS_removexattr, SYS_lremovexattr, SYS_fremovexattr, SYS_inotify_init1, SYS_inotify_add_watch,
+            SYS_inotify_rm_watch, SYS_dup, SYS_dup3, SYS_pipe2, SYS_memfd_create,
+            // Memory
+            SYS_mmap, SYS_mprotect, SYS_munmap, SYS_brk, SYS_mremap, SYS_msync, SYS_mincore, SYS_madvise,
+            SYS_membarrier, SYS_shmget, SYS_shmat, SYS_shmdt, SYS_shmctl, SYS_semget, SYS_semop, SYS_semctl,
+            SYS_semtimedop,
+            // Processes, threads and signals
+            SYS_execve, SYS_execveat, SYS_exit, SYS_exit_group, SYS_wait4, SYS_waitid, SYS_kill, SYS_tkill,
+            SYS_tgkill, SYS_pidfd_open, SYS_pidfd_send_signal, SYS_getpid, SYS_getppid, SYS_gettid, SYS_setpgid,
+            SYS_getpgid, SYS_getsid, SYS_setsid, SYS_set_tid_address, SYS_set_robust_list, SYS_get_robust_list,
+            SYS_rseq, SYS_futex, SYS_prctl, SYS_prlimit64, SYS_getrusage, SYS_times, SYS_sched_yield,
+            SYS_sched_getaffinity, SYS_sched_setaffinity, SYS_sched_getparam, SYS_sched_getscheduler,
+            SYS_sched_get_priority_max, SYS_sched_get_priority_min, SYS_getpriority, SYS_setpriority,
+            SYS_capget, SYS_capset, SYS_personality, SYS_rt_sigaction, SYS_rt_sigprocmask, SYS_rt_sigreturn,
+            SYS_rt_sigsuspend, SYS_rt_sigtimedwait, SYS_rt_sigpending, SYS_sigaltstack, SYS_restart_syscall,
+            SYS_seccomp, SYS_landlock_create_ruleset, SYS_landlock_add_rule, SYS_landlock_restrict_self,
+            // Identity and system information
+            SYS_getuid, SYS_getgid, SYS_geteuid, SYS_getegid, SYS_getresuid, SYS_getresgid, SYS_getgroups,
+            SYS_uname, SYS_sysinfo, SYS_getcpu, SYS_getrandom,
+            // Time and waiting
+            SYS_nanosleep, SYS_clock_nanosleep, SYS_clock_gettime, SYS_clock_getres, SYS_gettimeofday,
+            SYS_getitimer, SYS_setitimer, SYS_timer_create, SYS_timer_settime, SYS_timer_gettime,
+            SYS_timer_getoverrun, SYS_timer_delete, SYS_timerfd_create, SYS_timerfd_settime, SYS_timerfd_gettime,
+            SYS_ppoll, SYS_pselect6, SYS_epoll_create1, SYS_epoll_ctl, SYS_epoll_pwait, SYS_epoll_pwait2,
+            SYS_eventfd2, SYS_signalfd4,
+            // Sockets
+            SYS_socket, SYS_socketpair, SYS_connect, SYS_bind, SYS_listen, SYS_accept, SYS_accept4,
+            SYS_getsockname, SYS_getpeername, SYS_sendto, SYS_recvfrom, SYS_sendmsg, SYS_recvmsg, SYS_sendmmsg,
+            SYS_recvmmsg, SYS_shutdown, SYS_setsockopt, SYS_getsockopt,
+        ];
+
+        /// The older calls x86_64 still has alongside their `*at` and
+        /// `*2` successors, and the two libc only names there.
+        #[cfg(target_arch = "x86_64")]
+        const ALLOWED_ARCH: &[c_long] = &[
+            SYS_sendfile, SYS_sync_file_range,
+            SYS_open, SYS_creat, SYS_stat, SYS_lstat, SYS_access, SYS_rename, SYS_renameat, SYS_mkdir, SYS_rmdir,
+            SYS_unlink, SYS_link, SYS_symlink, SYS_readlink, SYS_chmod, SYS_chown, SYS_lchown, SYS_mknod,
+            SYS_utime, SYS_utimes, SYS_futimesat, SYS_getdents, SYS_pipe, SYS_dup2, SYS_poll, SYS_select,
+            SYS_epoll_create, SYS_epoll_wait, SYS_inotify_init, SYS_eventfd, SYS_signalfd, SYS_fadvise64,
+            SYS_alarm, SYS_pause, SYS_time, SYS_getpgrp, SYS_fork, SYS_vfork, SYS_arch_prctl, SYS_getrlimit,
+            SYS_setrlimit,
+        ];
+        #[cfg(target_arch = "aarch64")]
+        const ALLOWED_ARCH: &[c_long] = &[71 /* sendfile */, 84 /* sync_file_range */];
+
+        fn stmt(code: u32, k: u32) -> sock_filter {
+            sock_filter { code: code as u16, jt: 0, jf: 0, k }
+        }
+
+        fn jump(code: u32, k: u32, jt: u8, jf: u8) -> sock_filter {
+            sock_filter { code: code as u16, jt, jf, k }
+        }
+
+        pub fn filter(kill: bool) -> Option<Vec<sock_filter>> {
+            let deny = if kill { SECCOMP_RET_KILL_PROCESS } else { SECCOMP_RET_ERRNO | EPERM as u32 };
+            let allow = stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW);
+            let mut filter = vec![
+                // Only the native ABI
+                stmt(BPF_LD | BPF_W | BPF_ABS, 4),
+                jump(BPF_JMP | BPF_JEQ | BPF_K, AUDIT_ARCH, 1, 0),
+                stmt(BPF_RET | BPF_K, SECCOMP_RET_KILL_PROCESS),
+                stmt(BPF_LD | BPF_W | BPF_ABS, 0),
+            ];
+            #[cfg(target_arch = "x86_64")]
+            filter.extend([
+                // x32 calls share the architecture but set this bit
+                jump(BPF_JMP | BPF_JGE | BPF_K, 0x4000_0000, 0, 1),
+                stmt(BPF_RET | BPF_K, deny),
+            ]);
+            filter.extend([
+                // clone3 takes its flags in memory the filter can't read; libc
+                // falls back to clone when it's missing
+                jump(BPF_JMP | BPF_JEQ | BPF_K, SYS_clone3 as u32, 0, 1),
+                stmt(BPF_RET | BPF_K, SECCOMP_RET_ERRNO | ENOSYS as u32),
+                // clone, as long as it creates no namespaces
+                jump(BPF_JMP | BPF_JEQ | BPF_K, SYS_clone as u32, 0, 4),
+                stmt(BPF_LD | BPF_W | BPF_ABS, 16),
+                jump(BPF_JMP | BPF_JSET | BPF_K, NAMESPACE_FLAGS, 0, 1),
+                stmt(BPF_RET | BPF_K, deny),
+                allow,
+            ]);
+            for &nr in ALLOWED.iter().chain(ALLOWED_ARCH) {
+                filter.extend([jump(BPF_JMP | BPF_JEQ | BPF_K, nr as u32, 0, 1), allow]);
+            }
+            filter.push(stmt(BPF_RET | BPF_K, deny));
+            Some(filter)
+        }
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    mod seccomp {
+        pub fn filter(_kill: bool) -> Option<Vec<libc::sock_filter>> {
+            None
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::super::{apply_sandbox, pre_spawn_setup};
+        use super::*;
+        use std::process::Stdio;
+
+        fn policy(path: &Path, access: AccessLevel) -> PathPolicy {
+            PathPolicy { id: None, path: path.to_path_buf(), access, recursive: true, description: None, layer: 0 }
+        }
+
+        fn scratch_dir() -> PathBuf {
+            let dir = std::env::temp_dir().join(format!("bat-sandbox-{}", uuid::Uuid::new_v4()));
+            std::fs::create_dir_all(dir.join("private")).unwrap();
+            std::fs::write(dir.join("notes.txt"), "visible").unwrap();
+            std::fs::write(dir.join("private").join("secret.txt"), "hidden").unwrap();
+            dir
+        }
+
+        /// Run `script` in `sh` under the sandbox; returns what the sandbox
+        /// reported and what the script printed.
+        async fn run_sandboxed(config: &SandboxConfig, script: &str) -> (Protections, String) {
+            let mut pre_spawn = pre_spawn_setup(config).unwrap();
+            let child = {
+                let mut cmd = tokio::process::Command::new("/bin/sh");
+                cmd.arg("-c").arg(script).stdout(Stdio::piped()).stderr(Stdio::null());
+                pre_spawn.apply_to(&mut cmd, Path::new("/bin/sh"));
+                cmd.spawn().unwrap()
+            };
+            let handle = apply_sandbox(child.id().unwrap(), config, pre_spawn).unwrap();
+            let output = child.wait_with_output().await.unwrap();
+            (handle.protections(), String::from_utf8_lossy(&output.stdout).into_owned())
+        }
+
+        #[test]
+        fn test_only_literal_denies_nothing_reopens_get_masked() {
+            let dir = scratch_dir();
+            std::fs::create_dir_all(dir.join("keys")).unwrap();
+            let policies = vec![
+                policy(&dir, AccessLevel::ReadWrite),
+                policy(&dir.join("private"), AccessLevel::Deny),
+                policy(&dir.join("keys"), AccessLevel::Deny),
+                policy(&dir.join("keys").join("public.pem"), AccessLevel::ReadOnly),
+                policy(&dir.join("**").join("*.env"), AccessLevel::Deny),
+                policy(&dir.join("missing"), AccessLevel::Deny),
+            ];
+            assert_eq!(masks(&policies), vec![(cstring(&dir.join("private")).unwrap(), true)]);
+
+            // A subagent's grants bound its Landlock rules, not the parent's
+            let mut grant = policy(&dir.join("private"), AccessLevel::ReadOnly);
+            grant.layer = 1;
+            let rules = landlock_rules(&[policy(&dir, AccessLevel::ReadWrite), grant]);
+            assert!(rules.contains(&(cstring(&dir.join("private")).unwrap(), READ)));
+            assert!(!rules.iter().any(|(path, _)| path.as_bytes() == dir.as_os_str().as_bytes()));
+            let _ = std::fs::remove_dir_all(&dir);
+        }
+
+        #[tokio::test]
+        async fn test_report_matches_what_the_agent_can_do() {
+            let dir = scratch_dir();
+            let config = SandboxConfig {
+                memory_limit_mb: 0,
+                cpu_shares: 0,
+                path_policies: vec![policy(&dir, AccessLevel::ReadWrite), policy(&dir.join("private"), AccessLevel::Deny)],
+                ..Default::default()
+            };
+            // Outside every rule, unless the checkout itself is somewhere they cover
+            let manifest = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+            let script = format!(
+                "cat {dir}/notes.txt; echo; cat {dir}/private/secret.txt; echo; head -c 9 {manifest}; echo; \
+                 grep -E '^(NoNewPrivs|Seccomp):' /proc/self/status",
+                dir = dir.display(),
+                manifest = manifest.display(),
+            );
+            let (protections, output) = run_sandboxed(&config, &script).await;
+            let active = |name: &str| protections.active.iter().any(|p| p.starts_with(name));
+            let context = format!("{protections}\n{output}");
+
+            assert!(output.contains("visible"), "{context}");
+            assert_eq!(active("1 of 1 denied paths masked"), !output.contains("hidden"), "{context}");
+            assert_eq!(active("no_new_privs"), output.contains("NoNewPrivs:\t1"), "{context}");
+            assert_eq!(active("seccomp"), output.contains("Seccomp:\t2"), "{context}");
+            let covered = SYSTEM_DIRS.iter().chain(&["/tmp", "/var/tmp"]).any(|dir| manifest.starts_with(dir));
+            if !covered {
+                assert_eq!(active("landlock"), !output.contains("[package]"), "{context}");
+            }
+            assert_eq!(protections.active.len() + protections.missing.len(), 6, "{context}");
+            let _ = std::fs::remove_dir_all(&dir);
+        }
+
+        #[tokio::test]
+        async fn test_tcp_connects_are_limited_to_allowed_ports() {
+            // Uses bash's /dev/tcp
+            if !Path::new("/bin/bash").exists() {
+                return;
+            }
+            let allowed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let other = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let port = |listener: &std::net::TcpListener| listener.local_addr().unwrap().port();
+            let config = SandboxConfig {
+                memory_limit_mb: 0,
+                allowed_endpoints: vec![format!("localhost:{}", port(&allowed))],
+                ..Default::default()
+            };
+            let script = format!(
+                "for port in {} {}; do (echo > /dev/tcp/127.0.0.1/$port) 2>/dev/null && echo open || echo refused; done",
+                port(&allowed),
+                port(&other),
+            );
+            let (protections, output) = run_sandboxed(&config, &format!("exec bash -c '{script}'")).await;
+            let limited = protections.active.iter().any(|p| p.starts_with("tcp connect"));
+            assert_eq!(output, if limited { "open\nrefused\n" } else { "open\nopen\n" }, "{protections}");
+        }
+
+        #[tokio::test]
+        async fn test_resource_limits_apply_without_the_sandbox() {
+            let config = SandboxConfig { linux_sandbox: false, ..Default::default() };
+            let (protections, output) = run_sandboxed(&config, "cat /proc/self/cgroup").await;
+            assert_eq!(protections.active.len() + protections.missing.len(), 1);
+            if protections.active.is_empty() {
+                assert!(!output.contains("batchismo"));
+            } else {
+                assert!(output.contains("/batchismo/agent-"));
+            }
+        }
+    }
 }
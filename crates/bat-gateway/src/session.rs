@@ -43,4 +43,8 @@ impl SessionManager {
     pub fn update_token_usage(&self, session_id: Uuid, input: i64, output: i64) -> Result<()> {
         self.db.update_token_usage(session_id, input, output)
     }
+
+    pub fn update_cache_usage(&self, session_id: Uuid, cache_read: i64, cache_write: i64) -> Result<()> {
+        self.db.update_cache_usage(session_id, cache_read, cache_write)
+    }
 }
@@ -0,0 +1,150 @@
+//! Narrowing what a subagent inherits.
+//!
+//! A subagent runs with its parent's path policies, worker tools, model,
+//! memory limit and network access unless the spawning agent narrows them.
+//! Requests are checked here before anything is spawned: they may narrow,
+//! never widen.
+
+use bat_types::config::BatConfig;
+use bat_types::network::SessionHosts;
+use bat_types::policy::{narrow, sensitive_path_denies, PathPolicy};
+use bat_types::session::SubagentScope;
+
+/// Tools a worker agent can have. `ask_orchestrator` is always kept, so a
+/// narrowed worker can still ask for help.
+pub const WORKER_TOOLS: &[&str] = &[
+    "fs_read", "fs_write", "fs_edit", "fs_list", "fs_search", "fs_move", "fs_copy", "fs_delete", "fs_stat",
+    "fs_read_pdf", "web_fetch", "web_search", "shell_run", "app_open", "system_info", "clipboard", "screenshot",
+    "exec_run", "exec_output", "exec_write", "exec_kill", "exec_list", "ask_orchestrator",
+];
+
+/// What a subagent runs with once its scope is checked.
+#[derive(Debug)]
+pub struct Narrowed {
+    /// The parent's policies, plus the subagent's grants as a layer on top.
+    pub path_policies: Vec<PathPolicy>,
+    /// Only the subagent's grants, for its system prompt; `None` if it
+    /// inherited the parent's paths.
+    pub grants: Option<Vec<PathPolicy>>,
+    pub disabled_tools: Vec<String>,
+    pub model: String,
+    pub memory_limit_mb: Option<u64>,
+    /// Checked by the egress proxy on top of the network policy.
+    pub hosts: SessionHosts,
+}
+
+/// Check `scope` against what the parent has and work out what the
+/// subagent gets. `parent_policies` are the user's path policies.
+pub fn narrow_scope(config: &BatConfig, parent_policies: Vec<PathPolicy>, scope: &SubagentScope) -> Result<Narrowed, String> {
+    let grants = match &scope.paths {
+        Some(paths) => {
+            let mut parent = parent_policies.clone();
+            if config.sandbox.deny_sensitive_paths {
+                parent.extend(sensitive_path_denies());
+            }
+            Some(narrow(&parent, paths)?)
+        }
+        None => None,
+    };
+    let mut path_policies = parent_policies;
+    path_policies.extend(grants.iter().flatten().cloned());
+
+    let mut disabled_tools = config.agent.disabled_tools.clone();
+    disabled_tools.push("session_spawn".to_string());
+    if let Some(tools) = &scope.tools {
+        for tool in tools {
+            if !WORKER_TOOLS.contains(&tool.as_str()) {
+                return Err(format!("Unknown tool '{tool}'. Workers can have: {}", WORKER_TOOLS.join(", ")));
+            }
+            if disabled_tools.contains(tool) {
+                return Err(format!("'{tool}' is disabled, so a subagent can't have it"));
+            }
+        }
+        disabled_tools.extend(
+            WORKER_TOOLS
+                .iter()
+                .filter(|t| **t != "ask_orchestrator" && !tools.iter().any(|tool| tool == *t))
+                .map(|t| t.to_string()),
+        );
+    }
+
+    let model = match &scope.model {
+        Some(model) if !config.agent.is_model_enabled(model) => {
+            return Err(format!("Model '{model}' isn't enabled"));
+        }
+        Some(model) => model.clone(),
+        None => config.agent.model.clone(),
+    };
+
+    let parent_limit = u64::from(config.sandbox.memory_limit_mb);
+    match scope.memory_limit_mb {
+        Some(0) => return Err("'memory_limit_mb' must be above 0".to_string()),
+        Some(mb) if parent_limit > 0 && mb > parent_limit => {
+            return Err(format!("'memory_limit_mb' of {mb} is above the parent's limit of {parent_limit} MB"));
+        }
+        _ => {}
+    }
+
+    let hosts = SessionHosts { allow: scope.hosts.clone(), deny: scope.blocked_hosts.clone().unwrap_or_default() };
+
+    Ok(Narrowed { path_policies, grants, disabled_tools, model, memory_limit_mb: scope.memory_limit_mb, hosts })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bat_types::policy::{check_access, AccessLevel, PathGrant};
+    use std::path::Path;
+
+    fn policy(path: &str, access: AccessLevel) -> PathPolicy {
+        PathPolicy { id: None, path: path.into(), access, recursive: true, description: None, layer: 0 }
+    }
+
+    #[test]
+    fn test_summarising_docs_gets_no_write_access_to_code() {
+        let config = BatConfig::default();
+        let parent = vec![policy("/home/u/Docs", AccessLevel::ReadWrite), policy("/home/u/Code", AccessLevel::ReadWrite)];
+        let scope = SubagentScope {
+            paths: Some(vec![PathGrant { path: "/home/u/Docs/Acme".into(), access: AccessLevel::ReadOnly }]),
+            tools: Some(vec!["fs_read".to_string(), "fs_list".to_string()]),
+            ..Default::default()
+        };
+        let narrowed = narrow_scope(&config, parent, &scope).unwrap();
+        assert!(check_access(&narrowed.path_policies, Path::new("/home/u/Docs/Acme/notes.md"), false));
+        assert!(!check_access(&narrowed.path_policies, Path::new("/home/u/Docs/Acme/notes.md"), true));
+        assert!(!check_access(&narrowed.path_policies, Path::new("/home/u/Code/main.rs"), true));
+        assert_eq!(narrowed.grants.unwrap().len(), 1);
+        for tool in ["fs_write", "shell_run", "exec_run", "session_spawn"] {
+            assert!(narrowed.disabled_tools.iter().any(|t| t == tool), "{tool} should be disabled");
+        }
+        for tool in ["fs_read", "fs_list", "ask_orchestrator"] {
+            assert!(!narrowed.disabled_tools.iter().any(|t| t == tool), "{tool} should be kept");
+        }
+        assert_eq!(narrowed.model, config.agent.model);
+    }
+
+    #[test]
+    fn test_requests_cannot_widen_the_parent() {
+        let mut config = BatConfig::default();
+        config.agent.disabled_tools = vec!["shell_run".to_string()];
+        config.agent.enabled_models = vec!["claude-haiku-4-5".to_string()];
+        config.sandbox.memory_limit_mb = 512;
+        let parent = vec![policy("/home/u/Docs", AccessLevel::ReadOnly)];
+        let narrow_to = |scope: SubagentScope| narrow_scope(&config, parent.clone(), &scope).map(|_| ()).unwrap_err();
+
+        let write_docs = PathGrant { path: "/home/u/Docs".into(), access: AccessLevel::ReadWrite };
+        assert!(narrow_to(SubagentScope { paths: Some(vec![write_docs]), ..Default::default() }).contains("doesn't allow writing"));
+        let elsewhere = PathGrant { path: "/home/u/Code".into(), access: AccessLevel::ReadOnly };
+        assert!(narrow_to(SubagentScope { paths: Some(vec![elsewhere]), ..Default::default() }).contains("not in any allowed policy"));
+        let tools = |tool: &str| SubagentScope { tools: Some(vec![tool.to_string()]), ..Default::default() };
+        assert!(narrow_to(tools("shell_run")).contains("disabled"));
+        assert!(narrow_to(tools("session_spawn")).contains("Unknown tool"));
+        assert!(narrow_to(SubagentScope { model: Some("gpt-5".to_string()), ..Default::default() }).contains("isn't enabled"));
+        assert!(narrow_to(SubagentScope { memory_limit_mb: Some(4096), ..Default::default() }).contains("above the parent's"));
+
+        let scope = SubagentScope { model: Some("claude-haiku-4-5".to_string()), memory_limit_mb: Some(256), ..Default::default() };
+        let narrowed = narrow_scope(&config, parent.clone(), &scope).unwrap();
+        assert_eq!((narrowed.model.as_str(), narrowed.memory_limit_mb), ("claude-haiku-4-5", Some(256)));
+        assert_eq!(narrowed.path_policies, parent);
+    }
+}
@@ -14,23 +14,27 @@ fn read_md(path: &Path) -> String {
 }
 
 /// Format path policies for inclusion in the system prompt.
-fn format_policies(policies: &[PathPolicy]) -> String {
+fn format_policies(config: &BatConfig, policies: &[PathPolicy]) -> String {
     if policies.is_empty() {
         return "  (none configured - all file access will be denied)".to_string();
     }
-    policies
+    let mut lines: Vec<String> = policies
         .iter()
         .map(|p| {
             let access = match p.access {
                 bat_types::policy::AccessLevel::ReadOnly => "read-only",
                 bat_types::policy::AccessLevel::ReadWrite => "read-write",
                 bat_types::policy::AccessLevel::WriteOnly => "write-only",
+                bat_types::policy::AccessLevel::Deny => "denied",
             };
             let scope = if p.recursive { "recursive" } else { "top-level only" };
             format!("  - {} [{}] ({})", p.path.display(), access, scope)
         })
-        .collect::<Vec<_>>()
-        .join("\n")
+        .collect();
+    if config.sandbox.deny_sensitive_paths {
+        lines.push("  - credential stores, browser profiles and .env files, e.g. ~/.ssh and ~/.aws [denied] (built-in)".to_string());
+    }
+    lines.join("\n")
 }
 
 /// Build the orchestrator system prompt for main/user sessions.
@@ -43,7 +47,7 @@ pub fn build_orchestrator_prompt(config: &BatConfig, path_policies: &[PathPolicy
     let skills = read_md(&workspace.join("SKILLS.md"));
 
     let agent_name = &config.agent.name;
-    let policies_str = format_policies(path_policies);
+    let policies_str = format_policies(config, path_policies);
 
     let prompt = format!(
         r#"You are {agent_name}, an orchestrator AI assistant running locally on the user's computer via Batchismo.
@@ -78,7 +82,7 @@ Always delegate to sub-agents. Your tools are:
 You have these session management tools available:
 
 ### Session Tools
-- **session_spawn** - Spawn a background subagent for a task. Input: `{{ "task": "...", "label": "..." }}`. Returns immediately; subagent announces results when done.
+- **session_spawn** - Spawn a background subagent for a task. Input: `{{ "task": "...", "label": "..." }}`. Returns immediately; subagent announces results when done. Give it only what the task needs with optional `paths` (`[{{ "path": "...", "access": "read-only" }}]`), `tools`, `model`, `memory_limit_mb`, `hosts` and `blocked_hosts`; it can't get more than you have.
 - **session_status** - Get status of all spawned subagents. No input required.
 - **session_pause** - Pause a running sub-agent. Input: `{{ "session_key": "..." }}`. Sub-agent stops after current step.
 - **session_resume** - Resume a paused sub-agent. Input: `{{ "session_key": "...", "instructions": "optional new instructions" }}`.
@@ -132,7 +136,7 @@ pub fn build_worker_prompt(config: &BatConfig, path_policies: &[PathPolicy], tas
     let skills = read_md(&workspace.join("SKILLS.md"));
 
     let agent_name = &config.agent.name;
-    let policies_str = format_policies(path_policies);
+    let policies_str = format_policies(config, path_policies);
 
     let prompt = format!(
         r#"You are {agent_name}, a worker AI sub-agent running locally on the user's computer via Batchismo.
@@ -150,13 +154,23 @@ This is your primary objective. Focus on completing this task efficiently and th
 You have these tools available:
 
 ### File Tools
-- **fs_read** - Read the contents of a text file. Input: {{"path": "..."}}
+- **fs_read** - Read the contents of a text file. Input: {{"path": "...", "offset": 1, "limit": 2000}}
   - Text files only (txt, md, rs, json, toml, csv, etc.). Does NOT work on binary files like PDFs.
+  - Lines come back numbered; the numbers are not part of the file. Page through long files with offset/limit.
 - **fs_read_pdf** - Read a PDF file and extract its text content. Input: {{"path": "..."}}
   - Uses Claude to extract text from PDFs. Handles scanned documents and complex layouts.
   - Max file size: 32MB. Enforces the same path policies as fs_read.
 - **fs_write** - Write or create a file. Input: {{"path": "...", "content": "..."}}
+- **fs_edit** - Change part of an existing file. Input: {{"path": "...", "edits": [{{"old_text": "...", "new_text": "..."}}]}} or {{"path": "...", "diff": "<unified diff>"}}
+  - Prefer this over fs_write for changes to existing files. Each old_text must match exactly once. Returns the diff.
 - **fs_list** - List directory contents. Input: {{"path": "..."}}
+- **fs_search** - Find files by name and/or content under a directory. Input: {{"path": "...", "glob": "*.md", "query": "regex", "context": 2}}
+  - Use this instead of listing directories one by one or running grep. Skips .gitignored files.
+- **fs_move** - Move or rename a file or folder. Input: {{"source": "...", "destination": "..."}}
+- **fs_copy** - Copy a file or folder. Input: {{"source": "...", "destination": "..."}}
+- **fs_delete** - Delete a file or folder by moving it to the recoverable trash. Input: {{"path": "..."}}
+- **fs_stat** - Get a file's type, size and timestamps. Input: {{"path": "..."}}
+  - Use these file tools rather than shell commands (mv, cp, rm) to organise files.
 
 File tools enforce path policies - you can only access files within the permitted paths below.
 
@@ -164,7 +178,8 @@ File tools enforce path policies - you can only access files within the permitte
 - **web_fetch** - Fetch the contents of a URL (HTTP/HTTPS). Input: {{"url": "https://..."}}
 
 ### Shell Tools (simple)
-- **shell_run** - Execute a quick shell command. Input: {{"command": "..."}}
+- **shell_run** - Execute a quick shell command. Input: {{"command": "...", "cwd": "...", "timeout": 30}}
+  - Returns exit_code, stdout and stderr. Runs with a minimal environment (no API keys); pass extra variables via "env".
   - Synchronous, 30-second timeout, 50KB output limit
 
 ### Process Tools (advanced)
@@ -215,6 +230,8 @@ File operations outside these paths will be denied.
 - Be proactive - use your tools to investigate, analyze, and take action.
 - For file operations, go ahead and act. Explain briefly what you did after.
 - If an operation fails, report the error clearly and try alternatives.
+- Some tools need the user's approval before each call. If the user denies one, don't retry it or work around it with another tool; take their reason into account or report that you couldn't proceed.
+- Commands are also checked against the user's command rules. A command refused by a rule will be refused every time, however it is rephrased; find another way or report which rule blocked you.
 - When using shell_run, prefer simple commands. For complex multi-step tasks, break them into individual commands.
 - Use web_fetch to look up information when you're not sure about something.
 - You're running locally on the user's machine - you have real access to their files and system. Use it responsibly.
@@ -261,7 +278,7 @@ pub fn build_system_prompt(config: &BatConfig, path_policies: &[PathPolicy]) ->
     let skills = read_md(&workspace.join("SKILLS.md"));
 
     let agent_name = &config.agent.name;
-    let policies_str = format_policies(path_policies);
+    let policies_str = format_policies(config, path_policies);
 
     let prompt = format!(
         r#"You are {agent_name}, a personal AI assistant running locally on the user's computer via Batchismo.
@@ -273,11 +290,20 @@ pub fn build_system_prompt(config: &BatConfig, path_policies: &[PathPolicy]) ->
 You have these tools available:
 
 ### File Tools
-- **fs_read** - Read the contents of a text file. Input: {{"path": "..."}}
+- **fs_read** - Read the contents of a text file, with numbered lines. Input: {{"path": "...", "offset": 1, "limit": 2000}}
 - **fs_read_pdf** - Read a PDF file and extract its text content. Input: {{"path": "..."}}
   - Uses Claude to extract text from PDFs. Max 32MB.
 - **fs_write** - Write or create a file. Input: {{"path": "...", "content": "..."}}
+- **fs_edit** - Change part of an existing file. Input: {{"path": "...", "edits": [{{"old_text": "...", "new_text": "..."}}]}} or {{"path": "...", "diff": "<unified diff>"}}
+  - Prefer this over fs_write for changes to existing files. Each old_text must match exactly once. Returns the diff.
 - **fs_list** - List directory contents. Input: {{"path": "..."}}
+- **fs_search** - Find files by name and/or content under a directory. Input: {{"path": "...", "glob": "*.md", "query": "regex", "context": 2}}
+  - Use this instead of listing directories one by one or running grep. Skips .gitignored files.
+- **fs_move** - Move or rename a file or folder. Input: {{"source": "...", "destination": "..."}}
+- **fs_copy** - Copy a file or folder. Input: {{"source": "...", "destination": "..."}}
+- **fs_delete** - Delete a file or folder by moving it to the recoverable trash. Input: {{"path": "..."}}
+- **fs_stat** - Get a file's type, size and timestamps. Input: {{"path": "..."}}
+  - Use these file tools rather than shell commands (mv, cp, rm) to organise files.
 
 File tools enforce path policies - you can only access files within the permitted paths below.
 
@@ -285,7 +311,8 @@ File tools enforce path policies - you can only access files within the permitte
 - **web_fetch** - Fetch the contents of a URL (HTTP/HTTPS). Input: {{"url": "https://..."}}
 
 ### Shell Tools (simple)
-- **shell_run** - Execute a quick shell command. Input: {{"command": "..."}}
+- **shell_run** - Execute a quick shell command. Input: {{"command": "...", "cwd": "...", "timeout": 30}}
+  - Returns exit_code, stdout and stderr. Runs with a minimal environment (no API keys); pass extra variables via "env".
   - Synchronous, 30-second timeout, 50KB output limit
 
 ### Process Tools (advanced)
@@ -304,7 +331,7 @@ Use shell_run for quick one-off commands.
 - **app_open** - Open a file, URL, or application. Input: {{"target": "..."}}
   - Like double-clicking a file or opening a URL in the browser
 - **system_info** - Get OS, hostname, CPU, memory, and disk info. No input required.
-- **session_spawn** - Spawn a background subagent for a task. Input: `{{ "task": "...", "label": "..." }}`. Returns immediately; subagent announces results when done.
+- **session_spawn** - Spawn a background subagent for a task. Input: `{{ "task": "...", "label": "..." }}`. Returns immediately; subagent announces results when done. Give it only what the task needs with optional `paths` (`[{{ "path": "...", "access": "read-only" }}]`), `tools`, `model`, `memory_limit_mb`, `hosts` and `blocked_hosts`; it can't get more than you have.
 - **session_status** - Get status of all spawned subagents. No input required.
 - **clipboard** - Read or write the system clipboard. Input: `{{ "action": "read" }}` or `{{ "action": "write", "text": "..." }}`.
 - **screenshot** - Take a screenshot of the current screen. Input: `{{ "filename": "optional_name" }}`. Returns path to saved PNG.
@@ -0,0 +1,336 @@
+//! Registry of live agent connections.
+//!
+//! `run_agent_turn` registers every agent it spawns for the lifetime of the
+//! turn. Other parts of the gateway (e.g. an orchestrator calling
+//! `session_instruct`) use the registry to push control messages down that
+//! agent's pipe while it is mid-turn, or to cancel it outright.
+//!
+//! When persistent agents are enabled, the registry also holds agent
+//! processes that are idle between turns, so the next turn for the same
+//! session can skip the spawn/connect/Init round trip.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use bat_types::command_policy::CommandPolicy;
+use bat_types::config::PermissionsConfig;
+use bat_types::ipc::GatewayToAgent;
+use bat_types::models::ModelInfo;
+use bat_types::policy::PathPolicy;
+
+use crate::egress::EgressProxy;
+use crate::{ipc, sandbox};
+
+/// How long a cancelled agent gets to exit on its own before it is killed.
+pub const CANCEL_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Process groups of the commands an agent is running. They lead groups of
+/// their own, so killing the agent's group doesn't reach them.
+pub type ProcessGroups = Arc<Mutex<HashSet<u32>>>;
+
+struct WorkerHandle {
+    tx: mpsc::UnboundedSender<GatewayToAgent>,
+    /// OS process ID of the agent and its commands' groups, once spawned.
+    process: Option<(u32, ProcessGroups)>,
+}
+
+/// Settings an agent process was initialised with. An idle agent is only
+/// reused for a turn that would have sent the same `Init`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgentInit {
+    pub model: String,
+    pub system_prompt: String,
+    pub path_policies: Vec<PathPolicy>,
+    pub disabled_tools: Vec<String>,
+    pub session_kind: String,
+    pub history_tool_result_max_chars: usize,
+    pub thinking_level: String,
+    pub fallback_models: Vec<String>,
+    pub max_retries: u32,
+    pub model_overrides: Vec<ModelInfo>,
+    pub shell_env_allowlist: Vec<String>,
+    pub permissions: PermissionsConfig,
+    pub command_policy: CommandPolicy,
+    /// Overrides `sandbox.memory_limit_mb` for this agent's process.
+    pub memory_limit_mb: Option<u64>,
+}
+
+/// A spawned, connected and initialised agent process.
+pub struct AgentProcess {
+    pub child: tokio::process::Child,
+    pub pipe: ipc::AgentPipe,
+    pub pid: u32,
+    pub init: AgentInit,
+    /// Length and last message ID of the history the agent holds, so the
+    /// gateway can tell whether the session changed behind its back.
+    pub history_marker: (usize, Option<Uuid>),
+    pub sandbox: Option<sandbox::SandboxHandle>,
+    pub process_groups: ProcessGroups,
+}
+
+impl AgentProcess {
+    /// Kill the agent's process tree and its commands' process groups.
+    pub fn kill(&self) {
+        kill_agent(self.pid, &self.process_groups);
+    }
+}
+
+fn kill_agent(pid: u32, process_groups: &ProcessGroups) {
+    ipc::kill_process_tree(pid);
+    for pgid in process_groups.lock().unwrap().drain() {
+        ipc::kill_process_tree(pgid);
+    }
+}
+
+/// Shared map of session ID → channel into that session's pipe loop.
+#[derive(Clone)]
+pub struct WorkerRegistry {
+    workers: Arc<Mutex<HashMap<Uuid, WorkerHandle>>>,
+    /// Persistent agents waiting for their next turn, tagged with a park generation.
+    idle: Arc<Mutex<HashMap<Uuid, (u64, AgentProcess)>>>,
+    next_generation: Arc<AtomicU64>,
+    /// Ends an idle agent's part of its egress session when it is shut down.
+    egress: EgressProxy,
+}
+
+impl WorkerRegistry {
+    pub fn new(egress: EgressProxy) -> Self {
+        Self {
+            workers: Arc::default(),
+            idle: Arc::default(),
+            next_generation: Arc::default(),
+            egress,
+        }
+    }
+
+    /// Register a running agent. Messages passed to `send` for this session
+    /// arrive on the returned receiver until the guard is dropped.
+    pub fn register(&self, session_id: Uuid) -> (WorkerGuard, mpsc::UnboundedReceiver<GatewayToAgent>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.workers
+            .lock()
+            .unwrap()
+            .insert(session_id, WorkerHandle { tx: tx.clone(), process: None });
+        let guard = WorkerGuard {
+            registry: self.clone(),
+            session_id,
+            tx,
+        };
+        (guard, rx)
+    }
+
+    /// Queue a message for a running agent.
+    pub fn send(&self, session_id: Uuid, msg: GatewayToAgent) -> Result<()> {
+        let workers = self.workers.lock().unwrap();
+        let worker = workers
+            .get(&session_id)
+            .ok_or_else(|| anyhow::anyhow!("No running agent for session {session_id}"))?;
+        worker
+            .tx
+            .send(msg)
+            .map_err(|_| anyhow::anyhow!("Agent for session {session_id} is shutting down"))
+    }
+
+    /// Whether an agent is currently connected for this session.
+    pub fn is_running(&self, session_id: Uuid) -> bool {
+        self.workers.lock().unwrap().contains_key(&session_id)
+    }
+
+    /// Ask a running agent to cancel its turn. If the same agent is still
+    /// registered after `grace`, its whole process tree is killed.
+    pub fn cancel(&self, session_id: Uuid, grace: Duration) -> Result<()> {
+        let tx = {
+            let workers = self.workers.lock().unwrap();
+            let worker = workers
+                .get(&session_id)
+                .ok_or_else(|| anyhow::anyhow!("No running agent for session {session_id}"))?;
+            worker.tx.clone()
+        };
+        // The pipe loop may be busy (e.g. blocked on an orchestrator answer),
+        // so the kill below doesn't depend on this being delivered.
+        let _ = tx.send(GatewayToAgent::Cancel);
+
+        let registry = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(grace).await;
+            let process = {
+                let workers = registry.workers.lock().unwrap();
+                workers
+                    .get(&session_id)
+                    .filter(|w| w.tx.same_channel(&tx))
+                    .and_then(|w| w.process.clone())
+            };
+            if let Some((pid, process_groups)) = process {
+                warn!("Agent for session {session_id} ignored Cancel — killing pid {pid}");
+                kill_agent(pid, &process_groups);
+            }
+        });
+        Ok(())
+    }
+
+    /// Take the idle agent for this session if it was started with `init`
+    /// and is still alive. An idle agent with different settings is shut
+    /// down instead, and one that died while parked is cleaned up.
+    pub fn take_idle(&self, session_id: Uuid, init: &AgentInit) -> Option<AgentProcess> {
+        let (_, mut agent) = self.idle.lock().unwrap().remove(&session_id)?;
+        if !matches!(agent.child.try_wait(), Ok(None)) {
+            warn!("Idle agent for session {session_id} (pid: {}) has exited — starting a new one", agent.pid);
+            self.shutdown(session_id, agent);
+            None
+        } else if agent.init == *init {
+            Some(agent)
+        } else {
+            info!("Settings changed for session {session_id} — replacing idle agent");
+            self.shutdown(session_id, agent);
+            None
+        }
+    }
+
+    /// Keep a finished agent around for the next turn. It is shut down if no
+    /// turn claims it within `keep_alive`.
+    pub fn park(&self, session_id: Uuid, agent: AgentProcess, keep_alive: Duration) {
+        let generation = self.next_generation.fetch_add(1, Ordering::Relaxed);
+        if let Some((_, displaced)) = self.idle.lock().unwrap().insert(session_id, (generation, agent)) {
+            // Its egress session is the parked agent's too, so it stays.
+            tokio::spawn(close(displaced));
+        }
+
+        let registry = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(keep_alive).await;
+            let expired = {
+                let mut idle = registry.idle.lock().unwrap();
+                match idle.get(&session_id) {
+                    Some((g, _)) if *g == generation => idle.remove(&session_id),
+                    _ => None,
+                }
+            };
+            if let Some((_, agent)) = expired {
+                info!("Idle agent for session {session_id} reached keep-alive — shutting down");
+                registry.shutdown(session_id, agent);
+            }
+        });
+    }
+
+    /// Shut down the idle agent for this session, if any.
+    pub fn retire_idle(&self, session_id: Uuid) {
+        if let Some((_, agent)) = self.idle.lock().unwrap().remove(&session_id) {
+            self.shutdown(session_id, agent);
+        }
+    }
+
+    /// End the idle agent's egress session (see `EgressProxy::end_agent`),
+    /// then close it in the background. The session ends first so it can't
+    /// outlast a new agent's start.
+    fn shutdown(&self, session_id: Uuid, agent: AgentProcess) {
+        self.egress.end_agent(session_id);
+        tokio::spawn(close(agent));
+    }
+}
+
+/// Close an idle agent's pipe so it exits, killing it if it doesn't.
+async fn close(mut agent: AgentProcess) {
+    let pid = agent.pid;
+    drop(agent.pipe);
+    match tokio::time::timeout(CANCEL_GRACE_PERIOD, agent.child.wait()).await {
+        Ok(_) => info!("Idle agent exited (pid: {pid})"),
+        Err(_) => {
+            warn!("Idle agent did not exit after its pipe closed — killing pid {pid}");
+            kill_agent(pid, &agent.process_groups);
+            let _ = agent.child.wait().await;
+        }
+    }
+}
+
+/// Removes the session from the registry when the agent turn ends.
+pub struct WorkerGuard {
+    registry: WorkerRegistry,
+    session_id: Uuid,
+    tx: mpsc::UnboundedSender<GatewayToAgent>,
+}
+
+impl WorkerGuard {
+    /// Record the agent's process so `cancel` can kill it.
+    pub fn set_process(&self, agent: &AgentProcess) {
+        let mut workers = self.registry.workers.lock().unwrap();
+        if let Some(worker) = workers
+            .get_mut(&self.session_id)
+            .filter(|w| w.tx.same_channel(&self.tx))
+        {
+            worker.process = Some((agent.pid, agent.process_groups.clone()));
+        }
+    }
+}
+
+impl Drop for WorkerGuard {
+    fn drop(&mut self) {
+        let mut workers = self.registry.workers.lock().unwrap();
+        // A newer turn for the same session may have replaced our entry.
+        if workers
+            .get(&self.session_id)
+            .is_some_and(|w| w.tx.same_channel(&self.tx))
+        {
+            workers.remove(&self.session_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::RwLock;
+
+    fn registry() -> WorkerRegistry {
+        let db = Arc::new(crate::db::Database::open_in_memory().unwrap());
+        let config = Arc::new(RwLock::new(bat_types::config::BatConfig::default()));
+        WorkerRegistry::new(EgressProxy::new(config, db, crate::EventBus::new()))
+    }
+
+    #[test]
+    fn test_send_reaches_registered_worker() {
+        let registry = registry();
+        let id = Uuid::new_v4();
+        let (_guard, mut rx) = registry.register(id);
+
+        registry.send(id, GatewayToAgent::Pause).unwrap();
+        assert!(matches!(rx.try_recv(), Ok(GatewayToAgent::Pause)));
+    }
+
+    #[test]
+    fn test_send_to_unknown_session_fails() {
+        let registry = registry();
+        assert!(registry.send(Uuid::new_v4(), GatewayToAgent::Pause).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_sends_cancel_message() {
+        let registry = registry();
+        let id = Uuid::new_v4();
+        let (_guard, mut rx) = registry.register(id);
+
+        registry.cancel(id, Duration::from_secs(60)).unwrap();
+        assert!(matches!(rx.try_recv(), Ok(GatewayToAgent::Cancel)));
+        assert!(registry.cancel(Uuid::new_v4(), Duration::from_secs(60)).is_err());
+    }
+
+    #[test]
+    fn test_guard_unregisters_only_its_own_entry() {
+        let registry = registry();
+        let id = Uuid::new_v4();
+        let (old_guard, _old_rx) = registry.register(id);
+        let (new_guard, _new_rx) = registry.register(id);
+
+        drop(old_guard);
+        assert!(registry.is_running(id));
+
+        drop(new_guard);
+        assert!(!registry.is_running(id));
+    }
+}
@@ -4,6 +4,7 @@ use tauri::State;
 
 use bat_gateway::ToolInfo;
 use bat_types::{
+    approval::{ApprovalDecision, ApprovalRequest},
     audit::{AuditEntry, AuditFilter, AuditStats},
     config::BatConfig,
     memory::{MemoryFileInfo, Observation, ObservationFilter, ObservationSummary},
@@ -126,6 +127,27 @@ pub async fn get_subagents(
         .map_err(|e| e.to_string())
 }
 
+/// Get tool calls waiting for the user's approval.
+#[tauri::command]
+pub async fn get_pending_approvals(
+    state: State<'_, AppState>,
+) -> Result<Vec<ApprovalRequest>, String> {
+    Ok(state.gateway.pending_approvals())
+}
+
+/// Approve or deny a pending tool call.
+#[tauri::command]
+pub async fn resolve_approval(
+    approval_id: String,
+    decision: ApprovalDecision,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state
+        .gateway
+        .resolve_approval(&approval_id, decision)
+        .map_err(|e| e.to_string())
+}
+
 /// Get all configured path policies.
 #[tauri::command]
 pub async fn get_path_policies(
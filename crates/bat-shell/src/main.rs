@@ -121,6 +121,8 @@ fn main() {
             commands::get_audit_logs,
             commands::get_audit_stats,
             commands::get_subagents,
+            commands::get_pending_approvals,
+            commands::resolve_approval,
             commands::list_sessions,
             commands::create_session,
             commands::switch_session,
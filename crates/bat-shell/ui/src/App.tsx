@@ -13,6 +13,7 @@ import { ActivityPanel } from './components/ActivityPanel'
 import { SessionSwitcher } from './components/SessionSwitcher'
 import { UsagePanel } from './components/UsagePanel'
 import { OnboardingWizard } from './components/onboarding/OnboardingWizard'
+import { ApprovalPrompt } from './components/ApprovalPrompt'
 
 export default function App() {
   const { messages, streamingText, agentStatus, error, send, reload } = useChat()
@@ -112,6 +113,9 @@ export default function App() {
           </div>
         )}
       </div>
+
+      {/* Tool calls waiting for approval, over whichever view is open */}
+      <ApprovalPrompt />
     </div>
   )
 }
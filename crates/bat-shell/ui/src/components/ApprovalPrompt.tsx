@@ -0,0 +1,119 @@
+import { useCallback, useEffect, useState } from 'react'
+import type { ApprovalDecision, ApprovalRequest, BatEvent } from '../types'
+import { getPendingApprovals, resolveApproval } from '../lib/tauri'
+import { useBatEvents } from '../hooks/useBatEvents'
+
+/** Modal for tool calls that need the user's approval. Shows the oldest pending one. */
+export function ApprovalPrompt() {
+  const [pending, setPending] = useState<ApprovalRequest[]>([])
+  const [denying, setDenying] = useState(false)
+  const [reason, setReason] = useState('')
+
+  useEffect(() => {
+    getPendingApprovals().then(setPending).catch(console.error)
+  }, [])
+
+  const handleEvent = useCallback((event: BatEvent) => {
+    if (event.type === 'ApprovalRequested') {
+      setPending(prev => [...prev, event.request])
+    } else if (event.type === 'ApprovalResolved') {
+      // Answered here, in another front end, or expired
+      setPending(prev => prev.filter(r => r.id !== event.approval_id))
+    }
+  }, [])
+
+  useBatEvents(handleEvent)
+
+  const request = pending[0]
+
+  // A new request starts with a clean deny form
+  useEffect(() => {
+    setDenying(false)
+    setReason('')
+  }, [request?.id])
+
+  if (!request) return null
+
+  const answer = async (decision: ApprovalDecision) => {
+    setPending(prev => prev.filter(r => r.id !== request.id))
+    try {
+      await resolveApproval(request.id, decision)
+    } catch (e) {
+      console.error('Failed to resolve approval:', e)
+    }
+  }
+
+  return (
+    <div className="fixed inset-0 z-50 flex items-center justify-center bg-black/60">
+      <div className="w-[32rem] max-w-[90vw] rounded-lg border border-amber-500/40 bg-zinc-900 shadow-xl">
+        <div className="flex items-center justify-between border-b border-zinc-800 px-4 py-2.5">
+          <span className="text-sm font-semibold text-amber-300">Approval needed</span>
+          {pending.length > 1 && (
+            <span className="text-xs text-zinc-500">{pending.length - 1} more waiting</span>
+          )}
+        </div>
+        <div className="space-y-3 px-4 py-3">
+          <p className="text-sm text-zinc-300">
+            The agent wants to run <span className="font-mono text-white">{request.tool}</span>
+          </p>
+          <pre className="max-h-64 overflow-auto rounded bg-zinc-950 p-2 text-xs text-zinc-400 whitespace-pre-wrap break-all">
+            {JSON.stringify(request.input, null, 2)}
+          </pre>
+          {denying && (
+            <input
+              autoFocus
+              value={reason}
+              onChange={e => setReason(e.target.value)}
+              onKeyDown={e => {
+                if (e.key === 'Enter') answer({ decision: 'deny', reason: reason.trim() || null })
+                if (e.key === 'Escape') setDenying(false)
+              }}
+              placeholder="Reason (optional) — the agent sees this"
+              className="w-full rounded-md border border-zinc-700 bg-zinc-800 px-2 py-1.5 text-sm text-zinc-200
+                         placeholder-zinc-500 focus:border-zinc-500 focus:outline-none"
+            />
+          )}
+        </div>
+        <div className="flex justify-end gap-2 border-t border-zinc-800 px-4 py-2.5">
+          {denying ? (
+            <>
+              <button
+                onClick={() => setDenying(false)}
+                className="rounded-md px-3 py-1.5 text-xs text-zinc-400 hover:text-zinc-200"
+              >
+                Back
+              </button>
+              <button
+                onClick={() => answer({ decision: 'deny', reason: reason.trim() || null })}
+                className="rounded-md bg-red-600 px-3 py-1.5 text-xs font-medium text-white hover:bg-red-500"
+              >
+                Deny
+              </button>
+            </>
+          ) : (
+            <>
+              <button
+                onClick={() => setDenying(true)}
+                className="rounded-md px-3 py-1.5 text-xs text-red-400 hover:bg-red-400/10"
+              >
+                Deny…
+              </button>
+              <button
+                onClick={() => answer({ decision: 'approve_session' })}
+                className="rounded-md border border-zinc-700 px-3 py-1.5 text-xs text-zinc-200 hover:bg-zinc-800"
+              >
+                Allow for this session
+              </button>
+              <button
+                onClick={() => answer({ decision: 'approve_once' })}
+                className="rounded-md bg-emerald-600 px-3 py-1.5 text-xs font-medium text-white hover:bg-emerald-500"
+              >
+                Approve once
+              </button>
+            </>
+          )}
+        </div>
+      </div>
+    </div>
+  )
+}
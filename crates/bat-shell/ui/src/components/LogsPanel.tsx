@@ -16,6 +16,7 @@ const CATEGORY_COLORS: Record<AuditCategory, string> = {
   gateway: 'text-cyan-400',
   ipc: 'text-orange-400',
   config: 'text-pink-400',
+  network: 'text-sky-400',
 }
 
 export function LogsPanel() {
@@ -111,6 +112,7 @@ export function LogsPanel() {
           <option value="gateway">Gateway</option>
           <option value="ipc">IPC</option>
           <option value="config">Config</option>
+          <option value="network">Network</option>
         </select>
 
         <input
@@ -21,7 +21,9 @@ const MODEL_PRICING: Record<string, { input: number; output: number }> = {
   'anthropic/claude-haiku-3-20240307': { input: 0.25, output: 1.25 },
 }
 
-function estimateCost(model: string, input: number, output: number): number | null {
+function estimateCost(model: string, uncachedInput: number, output: number, cacheRead = 0, cacheWrite = 0): number | null {
+  // Cache reads are billed at 10% of the input price, cache writes at 125%
+  const input = uncachedInput + cacheRead * 0.1 + cacheWrite * 1.25
   // Try exact match first
   const pricing = MODEL_PRICING[model]
   if (pricing) {
@@ -75,7 +77,7 @@ function displayModelName(model: string): string {
 }
 
 function ModelCard({ m, colorName }: { m: ModelUsage; colorName: string }) {
-  const cost = estimateCost(m.model, m.tokenInput, m.tokenOutput)
+  const cost = estimateCost(m.model, m.tokenInput, m.tokenOutput, m.tokenCacheRead, m.tokenCacheWrite)
   const totalTokens = m.tokenInput + m.tokenOutput
   const colors = COLOR_CLASSES[colorName] || COLOR_CLASSES.emerald
 
@@ -199,7 +201,7 @@ export function UsagePanel() {
   const modelCosts = stats.byModel
     .map((m, i) => ({
       model: m.model,
-      cost: estimateCost(m.model, m.tokenInput, m.tokenOutput) ?? 0,
+      cost: estimateCost(m.model, m.tokenInput, m.tokenOutput, m.tokenCacheRead, m.tokenCacheWrite) ?? 0,
       colorName: getModelColor(i),
     }))
     .sort((a, b) => b.cost - a.cost)
@@ -211,6 +213,11 @@ export function UsagePanel() {
         <div className="text-[10px] uppercase tracking-widest text-zinc-500 font-semibold mb-1">Estimated Cost</div>
         <div className="text-4xl font-bold text-emerald-400">{formatCost(stats.estimatedCostUsd)}</div>
         <div className="text-xs text-zinc-500 mt-1">Based on Anthropic pricing · all time</div>
+        {stats.cacheSavingsUsd > 0 && (
+          <div className="text-xs text-emerald-600 mt-1">
+            {formatCost(stats.cacheSavingsUsd)} saved by prompt caching · {formatNum(stats.totalCacheRead)} cached tokens
+          </div>
+        )}
       </div>
 
       {/* Summary Cards */}
@@ -240,8 +247,8 @@ export function UsagePanel() {
             {stats.byModel
               .map((m, i) => ({ m, colorName: getModelColor(i) }))
               .sort((a, b) => {
-                const costA = estimateCost(a.m.model, a.m.tokenInput, a.m.tokenOutput) ?? 0
-                const costB = estimateCost(b.m.model, b.m.tokenInput, b.m.tokenOutput) ?? 0
+                const costA = estimateCost(a.m.model, a.m.tokenInput, a.m.tokenOutput, a.m.tokenCacheRead, a.m.tokenCacheWrite) ?? 0
+                const costB = estimateCost(b.m.model, b.m.tokenInput, b.m.tokenOutput, b.m.tokenCacheRead, b.m.tokenCacheWrite) ?? 0
                 return costB - costA
               })
               .map(({ m, colorName }) => (
@@ -268,7 +275,7 @@ export function UsagePanel() {
               </thead>
               <tbody>
                 {stats.sessions.map((s: SessionUsage) => {
-                  const cost = estimateCost(s.model, s.tokenInput, s.tokenOutput)
+                  const cost = estimateCost(s.model, s.tokenInput, s.tokenOutput, s.tokenCacheRead, s.tokenCacheWrite)
                   return (
                     <tr key={s.key} className="border-b border-zinc-800/50 hover:bg-zinc-900/50">
                       <td className="px-3 py-2 text-zinc-200 font-medium">{s.key}</td>
@@ -1,12 +1,13 @@
 import { useState, useEffect } from 'react'
 import { open } from '@tauri-apps/plugin-dialog'
-import type { PathPolicy } from '../../types'
+import type { AccessLevel, PathPolicy } from '../../types'
 import { getPathPolicies, addPathPolicy, deletePathPolicy } from '../../lib/tauri'
 
 const ACCESS_OPTIONS = [
   { value: 'read-only', label: 'Read Only' },
   { value: 'read-write', label: 'Read & Write' },
   { value: 'write-only', label: 'Write Only' },
+  { value: 'deny', label: 'Deny' },
 ]
 
 export function PathPoliciesPage() {
@@ -16,7 +17,7 @@ export function PathPoliciesPage() {
 
   // Form state
   const [newPath, setNewPath] = useState('')
-  const [newAccess, setNewAccess] = useState<'read-only' | 'read-write' | 'write-only'>('read-write')
+  const [newAccess, setNewAccess] = useState<AccessLevel>('read-write')
   const [newRecursive, setNewRecursive] = useState(true)
   const [adding, setAdding] = useState(false)
 
@@ -76,6 +77,7 @@ export function PathPoliciesPage() {
       'read-only': 'bg-blue-900/50 text-blue-300 border border-blue-700',
       'read-write': 'bg-green-900/50 text-green-300 border border-green-700',
       'write-only': 'bg-orange-900/50 text-orange-300 border border-orange-700',
+      'deny': 'bg-red-900/50 text-red-300 border border-red-700',
     }
     return colors[access] ?? 'bg-zinc-800 text-zinc-300'
   }
@@ -85,7 +87,9 @@ export function PathPoliciesPage() {
       <div>
         <h2 className="text-lg font-semibold text-white">Path Policies</h2>
         <p className="text-sm text-zinc-400 mt-1">
-          Control which directories the agent can access. Changes take effect on the next message.
+          Control which directories the agent can access. Paths may be globs such as <code>~/Projects/*/src/**</code> or
+          {' '}<code>**/*.env</code>; the most specific matching rule wins, and a deny rule blocks whatever a broader
+          rule grants. Changes take effect on the next message.
         </p>
       </div>
 
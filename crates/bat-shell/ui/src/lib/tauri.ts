@@ -1,5 +1,5 @@
 import { invoke } from '@tauri-apps/api/core'
-import type { Message, SessionMeta, PathPolicy, ToolInfo, BatConfig, AuditEntry, AuditFilter, AuditStats, MemoryFileInfo, Observation, ObservationFilter, ObservationSummary, SubagentInfo, UsageStats, ElevenLabsVoice, ImageAttachment, DiffLine, LocalLlmModel, LocalLlmProvider } from '../types'
+import type { Message, SessionMeta, PathPolicy, ToolInfo, BatConfig, AuditEntry, AuditFilter, AuditStats, MemoryFileInfo, Observation, ObservationFilter, ObservationSummary, SubagentInfo, ApprovalRequest, ApprovalDecision, UsageStats, ElevenLabsVoice, ImageAttachment, DiffLine, LocalLlmModel, LocalLlmProvider } from '../types'
 
 export const sendMessage = (content: string, images?: ImageAttachment[]): Promise<void> =>
   invoke('send_message', { content, images: images?.length ? images : null })
@@ -89,6 +89,13 @@ export const getUsageStats = (): Promise<UsageStats> =>
 export const getSubagents = (): Promise<SubagentInfo[]> =>
   invoke('get_subagents')
 
+// Tool-call approvals
+export const getPendingApprovals = (): Promise<ApprovalRequest[]> =>
+  invoke('get_pending_approvals')
+
+export const resolveApproval = (approvalId: string, decision: ApprovalDecision): Promise<void> =>
+  invoke('resolve_approval', { approvalId, decision })
+
 // Onboarding
 export const isOnboardingComplete = (): Promise<boolean> =>
   invoke('is_onboarding_complete')
@@ -30,6 +30,7 @@ export interface Message {
   created_at: string
   token_input: number | null
   token_output: number | null
+  token_thinking?: number | null
 }
 
 export interface SessionMeta {
@@ -43,22 +44,73 @@ export interface SessionMeta {
   token_output: number
 }
 
+export type AccessLevel = 'read-only' | 'read-write' | 'write-only' | 'deny'
+
 export interface PathPolicy {
   id?: number
   path: string
-  access: 'read-only' | 'read-write' | 'write-only'
+  access: AccessLevel
   recursive: boolean
   description: string | null
+  layer?: number
+}
+
+export interface PathGrant {
+  path: string
+  access: AccessLevel
 }
 
 // Tauri bat-event payload types
 export type BatEvent =
   | { type: 'TextDelta'; session_id: string; session_kind: string; content: string }
+  | { type: 'ThinkingDelta'; session_id: string; session_kind: string; content: string }
   | { type: 'ToolCallStart'; session_id: string; session_kind: string; tool_call: ToolCall }
+  | { type: 'ToolCallInputDelta'; session_id: string; session_kind: string; tool_call_id: string; partial_json: string }
+  | { type: 'ToolCallReady'; session_id: string; session_kind: string; tool_call: ToolCall }
   | { type: 'ToolCallResult'; session_id: string; session_kind: string; result: ToolResult }
   | { type: 'TurnComplete'; session_id: string; session_kind: string; message: Message }
   | { type: 'Error'; message: string }
   | { type: 'AuditLog'; level: string; category: string; event: string; summary: string; detail_json: string | null }
+  | { type: 'ApprovalRequested'; request: ApprovalRequest }
+  | { type: 'ApprovalResolved'; approval_id: string; decision: ApprovalDecision }
+
+/** A tool call waiting for the user's go-ahead. */
+export interface ApprovalRequest {
+  id: string
+  session_id: string
+  tool: string
+  input: unknown
+  summary: string
+  requested_at: string
+}
+
+export type ApprovalDecision =
+  | { decision: 'approve_once' }
+  | { decision: 'approve_session' }
+  | { decision: 'deny'; reason?: string | null }
+
+export type PermissionMode = 'allow' | 'ask' | 'deny'
+
+export interface PermissionsConfig {
+  default: PermissionMode
+  tools: Record<string, PermissionMode>
+  approval_timeout_secs: number
+}
+
+export interface CommandPolicy {
+  default: 'allow' | 'deny'
+  allow: string[]
+  deny: string[]
+  protected_paths: string[]
+}
+
+export interface NetworkPolicy {
+  enabled: boolean
+  default: 'allow' | 'deny'
+  allow: string[]
+  deny: string[]
+  block_private_ranges: boolean
+}
 
 // Settings types
 export interface ToolInfo {
@@ -73,7 +125,13 @@ export interface ToolInfo {
 export const TOOL_DISPLAY: Record<string, { name: string; icon: string }> = {
   fs_read: { name: 'Read File', icon: '📄' },
   fs_write: { name: 'Write File', icon: '✏️' },
+  fs_edit: { name: 'Edit File', icon: '📝' },
   fs_list: { name: 'List Directory', icon: '📁' },
+  fs_search: { name: 'Search Files', icon: '🔍' },
+  fs_move: { name: 'Move File', icon: '📦' },
+  fs_copy: { name: 'Copy File', icon: '📑' },
+  fs_delete: { name: 'Delete File', icon: '🗑️' },
+  fs_stat: { name: 'File Info', icon: 'ℹ️' },
   web_fetch: { name: 'Fetch URL', icon: '🌐' },
   shell_run: { name: 'Run Command', icon: '⚡' },
   exec_run: { name: 'Execute', icon: '▶️' },
@@ -95,6 +153,10 @@ export interface AgentConfig {
   personality_prompt: string | null
   disabled_tools: string[]
   enabled_models: string[]
+  history_tool_result_max_chars: number
+  fallback_models: string[]
+  llm_max_retries: number
+  shell_env_allowlist?: string[]
 }
 
 export interface TelegramChannelConfig {
@@ -143,16 +205,37 @@ export interface BatConfig {
   agent: AgentConfig
   gateway: { port: number; log_level: string }
   memory: { update_mode: string; consolidation_schedule: string; max_memory_file_size_kb: number }
-  sandbox: { memory_limit_mb: number; cpu_shares: number; max_concurrent_subagents: number; subagent_timeout_minutes: number }
+  sandbox: { memory_limit_mb: number; cpu_shares: number; max_concurrent_subagents: number; subagent_timeout_minutes: number; persistent_agents: boolean; agent_keep_alive_secs: number; deny_sensitive_paths?: boolean; linux_sandbox?: boolean; seccomp_kill?: boolean }
   paths: PathPolicy[]
   channels?: ChannelsConfig
   voice: VoiceConfig
+  context?: { auto_compact: boolean; compact_at_percent: number; keep_recent_turns: number }
+  permissions?: PermissionsConfig
+  commands?: CommandPolicy
+  network?: NetworkPolicy
   api_keys: ApiKeys
+  models?: ModelInfo[]
+}
+
+/** A `[[models]]` entry in config.toml. Prices are USD per million tokens. */
+export interface ModelInfo {
+  id: string
+  provider: 'anthropic' | 'openai' | 'local' | 'ollama'
+  endpoint?: string
+  context_window: number
+  max_output_tokens: number
+  vision: boolean
+  tools: boolean
+  thinking: boolean
+  input_price: number
+  output_price: number
+  cache_read_price: number
+  cache_write_price: number
 }
 
 // Audit log types
 export type AuditLevel = 'debug' | 'info' | 'warn' | 'error'
-export type AuditCategory = 'agent' | 'tool' | 'gateway' | 'ipc' | 'config'
+export type AuditCategory = 'agent' | 'tool' | 'gateway' | 'ipc' | 'config' | 'network'
 
 export interface AuditEntry {
   id: number
@@ -227,9 +310,12 @@ export interface DiffLine {
 export interface UsageStats {
   totalInput: number
   totalOutput: number
+  totalCacheRead: number
+  totalCacheWrite: number
   sessions: SessionUsage[]
   byModel: ModelUsage[]
   estimatedCostUsd: number
+  cacheSavingsUsd: number
 }
 
 export interface SessionUsage {
@@ -237,6 +323,8 @@ export interface SessionUsage {
   model: string
   tokenInput: number
   tokenOutput: number
+  tokenCacheRead: number
+  tokenCacheWrite: number
   messageCount: number
   lastActive: string
 }
@@ -245,6 +333,8 @@ export interface ModelUsage {
   model: string
   tokenInput: number
   tokenOutput: number
+  tokenCacheRead: number
+  tokenCacheWrite: number
   sessionCount: number
 }
 
@@ -262,6 +352,16 @@ export interface SubagentInfo {
   summary: string | null
   tokenInput: number
   tokenOutput: number
+  scope?: SubagentScope
+}
+
+export interface SubagentScope {
+  paths?: PathGrant[]
+  tools?: string[]
+  model?: string
+  memory_limit_mb?: number
+  hosts?: string[]
+  blocked_hosts?: string[]
 }
 
 // ElevenLabs voice (fetched from API)
@@ -1,6 +1,7 @@
 use std::sync::Arc;
 
 use bat_gateway::Gateway;
+use bat_types::approval::{ApprovalDecision, ApprovalRequest};
 use bat_types::ipc::AgentToGateway;
 use bat_types::memory::{MemoryFileInfo, ObservationSummary};
 use bat_types::message::Message;
@@ -133,6 +134,11 @@ pub struct App {
     // Usage
     pub usage_stats: Option<UsageStats>,
 
+    // Tool calls waiting for approval; the first one is shown
+    pub pending_approvals: Vec<ApprovalRequest>,
+    pub approval_denying: bool,       // true while typing a deny reason
+    pub approval_reason: String,
+
     // Onboarding
     pub onboarding_step: u8,          // 0=welcome, 1=apikey, 2=name, 3=access, 4=ready
     pub onboarding_api_key: String,
@@ -149,6 +155,7 @@ impl App {
             .unwrap_or_default();
 
         let needs_onboarding = !gateway.is_onboarding_complete();
+        let pending_approvals = gateway.pending_approvals();
 
         Self {
             gateway,
@@ -201,6 +208,10 @@ impl App {
 
             usage_stats: None,
 
+            pending_approvals,
+            approval_denying: false,
+            approval_reason: String::new(),
+
             onboarding_step: 0,
             onboarding_api_key: String::new(),
             onboarding_name: String::new(),
@@ -234,6 +245,12 @@ impl App {
                 ));
                 self.tool_calls_expanded.push(false);
             }
+            AgentToGateway::ThinkingDelta { .. } => {
+                // Thinking isn't shown in the TUI
+            }
+            AgentToGateway::ToolCallInputDelta { .. } | AgentToGateway::ToolCallReady { .. } => {
+                // Tool input isn't shown in the TUI
+            }
             AgentToGateway::ToolCallResult { result, session_kind, .. } => {
                 if session_kind != "main" { return; }
                 let status = if result.is_error { "❌" } else { "✅" };
@@ -294,7 +311,32 @@ impl App {
                 );
                 self.messages.push(msg);
             }
+            AgentToGateway::ApprovalRequested { request } => {
+                self.pending_approvals.push(request);
+            }
+            AgentToGateway::ApprovalResolved { approval_id, .. } => {
+                // Answered here, elsewhere, or expired
+                if self.pending_approvals.first().is_some_and(|r| r.id == approval_id) {
+                    self.approval_denying = false;
+                    self.approval_reason.clear();
+                }
+                self.pending_approvals.retain(|r| r.id != approval_id);
+            }
+        }
+    }
+
+    /// Answer the approval request on screen.
+    pub fn resolve_approval(&mut self, decision: ApprovalDecision) {
+        let Some(request) = self.pending_approvals.first() else {
+            return;
+        };
+        let id = request.id.clone();
+        if let Err(e) = self.gateway.resolve_approval(&id, decision) {
+            tracing::warn!("Failed to resolve approval {id}: {e}");
         }
+        self.pending_approvals.retain(|r| r.id != id);
+        self.approval_denying = false;
+        self.approval_reason.clear();
     }
 
     /// Send the current input as a user message.
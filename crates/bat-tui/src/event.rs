@@ -5,6 +5,7 @@ use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifier
 use tokio::sync::broadcast;
 use tracing::warn;
 
+use bat_types::approval::ApprovalDecision;
 use bat_types::ipc::AgentToGateway;
 
 use bat_gateway::Gateway;
@@ -62,6 +63,12 @@ async fn handle_key(app: &mut App, key: KeyEvent) -> Result<()> {
         return Ok(());
     }
 
+    // An approval prompt takes every key until it is answered
+    if !app.pending_approvals.is_empty() {
+        handle_approval_key(app, key);
+        return Ok(());
+    }
+
     // Help overlay toggle
     if key.code == KeyCode::Char('?') && app.input_mode == InputMode::Normal && app.screen == Screen::Chat && app.input.is_empty() {
         app.show_help = !app.show_help;
@@ -90,6 +97,34 @@ async fn handle_key(app: &mut App, key: KeyEvent) -> Result<()> {
     }
 }
 
+fn handle_approval_key(app: &mut App, key: KeyEvent) {
+    if app.approval_denying {
+        match key.code {
+            KeyCode::Enter => {
+                let reason = app.approval_reason.trim().to_string();
+                let reason = (!reason.is_empty()).then_some(reason);
+                app.resolve_approval(ApprovalDecision::Deny { reason });
+            }
+            KeyCode::Esc => {
+                app.approval_denying = false;
+                app.approval_reason.clear();
+            }
+            KeyCode::Backspace => {
+                app.approval_reason.pop();
+            }
+            KeyCode::Char(c) => app.approval_reason.push(c),
+            _ => {}
+        }
+        return;
+    }
+    match key.code {
+        KeyCode::Char('y') => app.resolve_approval(ApprovalDecision::ApproveOnce),
+        KeyCode::Char('a') => app.resolve_approval(ApprovalDecision::ApproveSession),
+        KeyCode::Char('n') => app.approval_denying = true,
+        _ => {}
+    }
+}
+
 async fn handle_chat_key(app: &mut App, key: KeyEvent) -> Result<()> {
     // Session switcher overlay
     if app.show_session_switcher {
@@ -297,7 +332,8 @@ async fn handle_settings_key(app: &mut App, key: KeyEvent) -> Result<()> {
                     let new_access = match policy.access {
                         AccessLevel::ReadOnly => "read-write",
                         AccessLevel::ReadWrite => "write-only",
-                        AccessLevel::WriteOnly => "read-only",
+                        AccessLevel::WriteOnly => "deny",
+                        AccessLevel::Deny => "read-only",
                     };
                     let path_str = policy.path.to_string_lossy().to_string();
                     let recursive = policy.recursive;
@@ -752,6 +788,7 @@ async fn handle_activity_key(app: &mut App, key: KeyEvent) -> anyhow::Result<()>
             // Cancel the selected running subagent
             if let Some(agent) = app.subagents.get(app.activity_cursor) {
                 if agent.status == bat_types::session::SubagentStatus::Running
+                    || agent.status == bat_types::session::SubagentStatus::Paused
                     || agent.status == bat_types::session::SubagentStatus::WaitingForAnswer
                 {
                     let _ = app.gateway.cancel_subagent(agent.session_id).await;
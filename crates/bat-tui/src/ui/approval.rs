@@ -0,0 +1,50 @@
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
+};
+
+use bat_types::approval::ApprovalRequest;
+
+use super::help::centered_rect;
+use crate::app::App;
+
+pub fn render(f: &mut Frame, app: &App, request: &ApprovalRequest) {
+    let area = centered_rect(70, 50, f.area());
+    f.render_widget(Clear, area);
+
+    let input = format!("{:#}", request.input);
+    let mut text = vec![
+        Line::from(""),
+        Line::from(Span::styled(
+            format!("  The agent wants to run {}", request.tool),
+            Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+    ];
+    text.extend(input.lines().map(|line| Line::from(format!("  {line}"))));
+    text.push(Line::from(""));
+
+    if app.approval_denying {
+        text.push(Line::from(format!("  Reason (optional): {}▏", app.approval_reason)));
+        text.push(Line::from(Span::styled(
+            "  [Enter] deny  [Esc] back",
+            Style::default().fg(Color::DarkGray),
+        )));
+    } else {
+        text.push(Line::from(Span::styled(
+            "  [y] approve once  [a] approve for this session  [n] deny",
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+
+    let waiting = app.pending_approvals.len() - 1;
+    let title = if waiting > 0 {
+        format!(" Approval needed ({waiting} more waiting) ")
+    } else {
+        " Approval needed ".to_string()
+    };
+    let prompt = Paragraph::new(text)
+        .wrap(Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL).title(title).style(Style::default().fg(Color::Yellow)));
+    f.render_widget(prompt, area);
+}
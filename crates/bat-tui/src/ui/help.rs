@@ -44,6 +44,11 @@ pub fn render(f: &mut Frame) {
         Line::from("  r              Refresh"),
         Line::from("  Esc            Back to Chat"),
         Line::from(""),
+        Line::from("  ── Approval prompt ──"),
+        Line::from("  y              Approve this call"),
+        Line::from("  a              Approve tool for the session"),
+        Line::from("  n              Deny (type a reason, Enter)"),
+        Line::from(""),
         Line::from("  ── Usage ──"),
         Line::from("  r              Refresh stats"),
         Line::from("  Esc            Back to Chat"),
@@ -71,7 +76,7 @@ pub fn render(f: &mut Frame) {
     f.render_widget(help, area);
 }
 
-fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+pub(super) fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
     let popup_layout = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
@@ -1,4 +1,5 @@
 mod activity;
+mod approval;
 mod chat;
 mod help;
 mod logs;
@@ -27,4 +28,9 @@ pub fn render(f: &mut Frame, app: &App) {
     if app.show_help {
         help::render(f);
     }
+
+    // A pending approval blocks the agent, so it goes above even help
+    if let Some(request) = app.pending_approvals.first() {
+        approval::render(f, app, request);
+    }
 }
@@ -146,7 +146,7 @@ fn render_path_policies(f: &mut Frame, app: &App, area: Rect) {
         .iter()
         .enumerate()
         .map(|(i, policy)| {
-            let access = format!("{:?}", policy.access).to_lowercase().replace("only", "-only");
+            let access = policy.access.to_string();
             let recursive = if policy.recursive { " (recursive)" } else { "" };
             let style = if i == app.path_cursor {
                 Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)
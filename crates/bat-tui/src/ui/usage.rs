@@ -61,7 +61,14 @@ fn render_summary(f: &mut Frame, stats: &bat_types::usage::UsageStats, area: Rec
             format!("${:.4}", stats.estimated_cost_usd),
             Style::default().fg(Color::Green).add_modifier(Modifier::BOLD),
         )),
-        Line::from(Span::styled("Anthropic pricing", Style::default().fg(Color::DarkGray))),
+        Line::from(Span::styled(
+            if stats.cache_savings_usd > 0.0 {
+                format!("${:.4} saved by caching", stats.cache_savings_usd)
+            } else {
+                "Anthropic pricing".to_string()
+            },
+            Style::default().fg(Color::DarkGray),
+        )),
     ])
     .block(Block::default().borders(Borders::ALL))
     .alignment(Alignment::Center);
@@ -0,0 +1,94 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// What happens when the model calls a tool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PermissionMode {
+    /// Run straight away.
+    #[default]
+    Allow,
+    /// Ask the user first.
+    Ask,
+    /// Refuse every call.
+    Deny,
+}
+
+impl std::fmt::Display for PermissionMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Allow => write!(f, "allow"),
+            Self::Ask => write!(f, "ask"),
+            Self::Deny => write!(f, "deny"),
+        }
+    }
+}
+
+/// A tool call waiting for the user's go-ahead.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApprovalRequest {
+    pub id: String,
+    pub session_id: Uuid,
+    pub tool: String,
+    pub input: serde_json::Value,
+    /// One line describing the call, e.g. `shell_run: cargo build`.
+    pub summary: String,
+    pub requested_at: DateTime<Utc>,
+}
+
+/// The user's answer to an `ApprovalRequest`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum ApprovalDecision {
+    /// Run this call only.
+    ApproveOnce,
+    /// Run this call and any later call to the same tool in this session.
+    ApproveSession,
+    Deny {
+        #[serde(default)]
+        reason: Option<String>,
+    },
+}
+
+impl ApprovalDecision {
+    pub fn is_approved(&self) -> bool {
+        !matches!(self, Self::Deny { .. })
+    }
+}
+
+/// One-line description of a tool call for approval prompts, using the
+/// argument that says the most about what the call will do.
+pub fn describe_tool_call(tool: &str, input: &serde_json::Value) -> String {
+    let main_arg = ["command", "path", "target", "url", "source", "query"]
+        .iter()
+        .find_map(|key| input.get(*key).and_then(|v| v.as_str()));
+    match main_arg {
+        Some(arg) => {
+            let arg: String = arg.chars().take(200).collect();
+            format!("{tool}: {arg}")
+        }
+        None => tool.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decision_wire_format() {
+        let json = serde_json::to_value(ApprovalDecision::Deny { reason: Some("too risky".into()) }).unwrap();
+        assert_eq!(json, serde_json::json!({ "decision": "deny", "reason": "too risky" }));
+        let parsed: ApprovalDecision = serde_json::from_str(r#"{"decision":"approve_session"}"#).unwrap();
+        assert_eq!(parsed, ApprovalDecision::ApproveSession);
+        assert!(parsed.is_approved());
+    }
+
+    #[test]
+    fn test_describe_tool_call() {
+        let input = serde_json::json!({ "command": "rm -rf build", "timeout": 5 });
+        assert_eq!(describe_tool_call("shell_run", &input), "shell_run: rm -rf build");
+        assert_eq!(describe_tool_call("screenshot", &serde_json::json!({})), "screenshot");
+    }
+}
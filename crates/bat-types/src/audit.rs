@@ -57,6 +57,7 @@ pub enum AuditCategory {
     Gateway,
     Ipc,
     Config,
+    Network,
 }
 
 impl std::fmt::Display for AuditCategory {
@@ -67,6 +68,7 @@ impl std::fmt::Display for AuditCategory {
             Self::Gateway => write!(f, "gateway"),
             Self::Ipc => write!(f, "ipc"),
             Self::Config => write!(f, "config"),
+            Self::Network => write!(f, "network"),
         }
     }
 }
@@ -80,6 +82,7 @@ impl std::str::FromStr for AuditCategory {
             "gateway" => Ok(Self::Gateway),
             "ipc" => Ok(Self::Ipc),
             "config" => Ok(Self::Config),
+            "network" => Ok(Self::Network),
             _ => Err(format!("unknown audit category: {s}")),
         }
     }
@@ -1,5 +1,11 @@
 use serde::{Deserialize, Serialize};
 
+use std::collections::BTreeMap;
+
+use crate::approval::PermissionMode;
+use crate::command_policy::CommandPolicy;
+use crate::models::{ModelInfo, ModelRegistry};
+use crate::network::NetworkPolicy;
 use crate::policy::PathPolicy;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,6 +21,26 @@ pub struct BatConfig {
     pub voice: VoiceConfig,
     #[serde(default)]
     pub api_keys: ApiKeys,
+    #[serde(default)]
+    pub context: ContextConfig,
+    #[serde(default)]
+    pub permissions: PermissionsConfig,
+    /// Which commands `shell_run` and `exec_run` may start.
+    #[serde(default)]
+    pub commands: CommandPolicy,
+    /// Which hosts agents and the processes they start may connect to.
+    #[serde(default)]
+    pub network: NetworkPolicy,
+    /// Model entries added to, or replacing, the built-in registry.
+    #[serde(default)]
+    pub models: Vec<ModelInfo>,
+}
+
+impl BatConfig {
+    /// The built-in model registry with this config's `models` applied.
+    pub fn model_registry(&self) -> ModelRegistry {
+        ModelRegistry::with_overrides(&self.models)
+    }
 }
 
 /// Named API keys for external providers.
@@ -37,6 +63,10 @@ pub struct ApiKeys {
 }
 
 impl ApiKeys {
+    /// Environment variables the keys can come from. They are kept out of
+    /// agent processes and everything those start.
+    pub const ENV_VARS: &'static [&'static str] = &["ANTHROPIC_API_KEY", "OPENAI_API_KEY", "ELEVENLABS_API_KEY"];
+
     /// Get the Anthropic key, checking env var first.
     pub fn anthropic_key(&self) -> Option<String> {
         std::env::var("ANTHROPIC_API_KEY").ok().or_else(|| self.anthropic.clone())
@@ -65,28 +95,20 @@ impl ApiKeys {
     pub fn ollama_endpoint(&self) -> String {
         self.local_llm_endpoint()
     }
-
-    /// Determine the LLM provider from a model name.
-    pub fn provider_for_model(model: &str) -> LlmProvider {
-        if model.starts_with("claude-") {
-            LlmProvider::Anthropic
-        } else if model.starts_with("gpt-") || model.starts_with("o3-") || model.starts_with("o1-") {
-            LlmProvider::OpenAI
-        } else {
-            // Everything else routes to Ollama (local models like llama3, mistral, phi3, etc.)
-            LlmProvider::Ollama
-        }
-    }
 }
 
 /// LLM provider enum for routing inference requests.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum LlmProvider {
+    #[serde(rename = "anthropic")]
     Anthropic,
+    #[serde(rename = "openai")]
     OpenAI,
     /// Any local LLM (Ollama, LM Studio, etc.)
+    #[serde(rename = "local")]
     LocalLlm,
     /// Kept for backward compatibility — maps to LocalLlm.
+    #[serde(rename = "ollama")]
     Ollama,
 }
 
@@ -129,8 +151,40 @@ pub struct AgentConfig {
     /// Model IDs enabled for multi-LLM routing (v0.4.0).
     #[serde(default)]
     pub enabled_models: Vec<String>,
+    /// Tool results replayed from earlier turns are cut to this many characters.
+    #[serde(default = "default_history_tool_result_max_chars")]
+    pub history_tool_result_max_chars: usize,
+    /// Models tried in order when `model` fails after its retries,
+    /// e.g. `["gpt-4o", "llama3.1"]`.
+    #[serde(default)]
+    pub fallback_models: Vec<String>,
+    /// Retries per model for rate limits, overload and network errors.
+    #[serde(default = "default_llm_max_retries")]
+    pub llm_max_retries: u32,
+    /// Environment variables `shell_run` commands may inherit, on top of
+    /// basics like `PATH` and `HOME`. Everything else, including provider
+    /// API keys, is removed.
+    #[serde(default)]
+    pub shell_env_allowlist: Vec<String>,
+}
+
+impl AgentConfig {
+    /// Whether `model` may be used. An empty `enabled_models` allows every
+    /// model; the default model is always allowed.
+    pub fn is_model_enabled(&self, model: &str) -> bool {
+        self.enabled_models.is_empty() || model == self.model || self.enabled_models.iter().any(|m| m == model)
+    }
+
+    /// `fallback_models` without the ones that aren't enabled.
+    pub fn enabled_fallbacks(&self) -> Vec<String> {
+        self.fallback_models.iter().filter(|m| self.is_model_enabled(m)).cloned().collect()
+    }
 }
 
+pub fn default_history_tool_result_max_chars() -> usize { 4000 }
+
+pub fn default_llm_max_retries() -> u32 { 3 }
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GatewayConfig {
     pub port: u16,
@@ -164,9 +218,100 @@ pub struct SandboxConfig {
     pub max_concurrent_subagents: u32,
     #[serde(default = "default_subagent_timeout")]
     pub subagent_timeout_minutes: u32,
+    /// Keep each session's agent process running between turns instead of
+    /// spawning a fresh one for every message.
+    #[serde(default)]
+    pub persistent_agents: bool,
+    /// Seconds an idle persistent agent is kept before it is shut down.
+    #[serde(default = "default_agent_keep_alive")]
+    pub agent_keep_alive_secs: u64,
+    /// Deny the built-in list of credential and browser-profile locations
+    /// (`policy::sensitive_path_denies`) on top of the path policies.
+    #[serde(default = "default_true")]
+    pub deny_sensitive_paths: bool,
+    /// On Linux, restrict agent processes as they're spawned: user and mount
+    /// namespaces, Landlock rules from the path policies, a seccomp syscall
+    /// allowlist and `no_new_privs`, as far as the kernel allows.
+    #[serde(default = "default_true")]
+    pub linux_sandbox: bool,
+    /// Kill an agent that makes a syscall outside the allowlist, as PRD §9.2
+    /// specifies, instead of failing the call with `EPERM`.
+    #[serde(default)]
+    pub seccomp_kill: bool,
 }
 
 fn default_subagent_timeout() -> u32 { 60 }
+fn default_agent_keep_alive() -> u64 { 300 }
+
+/// Context-window management for long sessions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextConfig {
+    /// Fold older turns into a summary when a session nears the model's limit.
+    #[serde(default = "default_true")]
+    pub auto_compact: bool,
+    /// Compact once the estimated prompt reaches this share of the context window.
+    #[serde(default = "default_compact_at_percent")]
+    pub compact_at_percent: u8,
+    /// Most recent user turns always kept verbatim.
+    #[serde(default = "default_keep_recent_turns")]
+    pub keep_recent_turns: usize,
+}
+
+fn default_compact_at_percent() -> u8 { 80 }
+fn default_keep_recent_turns() -> usize { 4 }
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            auto_compact: true,
+            compact_at_percent: default_compact_at_percent(),
+            keep_recent_turns: default_keep_recent_turns(),
+        }
+    }
+}
+
+/// Which tool calls need the user's approval.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PermissionsConfig {
+    /// Mode for tools not listed in `tools`.
+    #[serde(default)]
+    pub default: PermissionMode,
+    /// Per-tool modes, keyed by tool name.
+    #[serde(default = "default_tool_permissions")]
+    pub tools: BTreeMap<String, PermissionMode>,
+    /// Seconds to wait for an answer before an approval request is denied.
+    #[serde(default = "default_approval_timeout")]
+    pub approval_timeout_secs: u64,
+}
+
+fn default_tool_permissions() -> BTreeMap<String, PermissionMode> {
+    ["shell_run", "exec_run", "app_open"]
+        .into_iter()
+        .map(|tool| (tool.to_string(), PermissionMode::Ask))
+        .collect()
+}
+fn default_approval_timeout() -> u64 { 300 }
+
+impl PermissionsConfig {
+    /// Everything runs without asking.
+    pub fn allow_all() -> Self {
+        Self { default: PermissionMode::Allow, tools: BTreeMap::new(), approval_timeout_secs: default_approval_timeout() }
+    }
+
+    pub fn mode_for(&self, tool: &str) -> PermissionMode {
+        self.tools.get(tool).copied().unwrap_or(self.default)
+    }
+}
+
+impl Default for PermissionsConfig {
+    fn default() -> Self {
+        Self {
+            default: PermissionMode::Allow,
+            tools: default_tool_permissions(),
+            approval_timeout_secs: default_approval_timeout(),
+        }
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ChannelsConfig {
@@ -247,6 +392,10 @@ impl Default for BatConfig {
                 disabled_tools: vec![],
                 onboarding_complete: false,
                 enabled_models: vec![],
+                history_tool_result_max_chars: 4000,
+                fallback_models: vec![],
+                llm_max_retries: 3,
+                shell_env_allowlist: vec![],
             },
             gateway: GatewayConfig {
                 port: 19000,
@@ -265,11 +414,21 @@ impl Default for BatConfig {
                 cpu_shares: 512,
                 max_concurrent_subagents: 5,
                 subagent_timeout_minutes: 60,
+                persistent_agents: false,
+                agent_keep_alive_secs: 300,
+                deny_sensitive_paths: true,
+                linux_sandbox: true,
+                seccomp_kill: false,
             },
             paths: vec![],
             channels: ChannelsConfig::default(),
             voice: VoiceConfig::default(),
             api_keys: ApiKeys::default(),
+            context: ContextConfig::default(),
+            permissions: PermissionsConfig::default(),
+            commands: CommandPolicy::default(),
+            network: NetworkPolicy::default(),
+            models: vec![],
         }
     }
 }
@@ -1,28 +1,27 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::approval::{ApprovalDecision, ApprovalRequest};
+use crate::command_policy::CommandPolicy;
+use crate::config::PermissionsConfig;
+use crate::models::ModelInfo;
 use crate::message::{ImageAttachment, Message, ToolCall, ToolResult};
-use crate::policy::PathPolicy;
+use crate::policy::{PathGrant, PathPolicy};
 
 /// Gateway → Agent
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum GatewayToAgent {
-    Init {
-        session_id: String,
-        model: String,
-        system_prompt: String,
-        history: Vec<Message>,
-        path_policies: Vec<PathPolicy>,
-        #[serde(default)]
-        disabled_tools: Vec<String>,
-        #[serde(default)]
-        session_kind: String,  // "main" or "subagent" - used to decide tool registry
-    },
+    Init(Box<AgentInit>),
+    /// Start a turn. A persistent agent accepts any number of these.
     UserMessage {
         content: String,
         #[serde(default)]
         images: Vec<ImageAttachment>,
+        /// Replacement history, sent when the session changed since the
+        /// agent last saw it (persistent agents only).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        history: Option<Vec<Message>>,
     },
     Cancel,
     /// Response to a process management request from the agent.
@@ -40,6 +39,56 @@ pub enum GatewayToAgent {
         instruction_id: String,
         content: String,
     },
+    /// Suspend a running sub-agent at its next checkpoint (before an LLM call or tool run).
+    Pause,
+    /// Resume a paused sub-agent, optionally with new instructions.
+    Resume {
+        #[serde(default)]
+        instructions: Option<String>,
+    },
+}
+
+/// The first message an agent gets: everything it needs to run its session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentInit {
+    pub session_id: String,
+    pub model: String,
+    pub system_prompt: String,
+    pub history: Vec<Message>,
+    pub path_policies: Vec<PathPolicy>,
+    #[serde(default)]
+    pub disabled_tools: Vec<String>,
+    #[serde(default)]
+    pub session_kind: String,  // "main" or "subagent" - used to decide tool registry
+    /// Cap on the size of tool results replayed from history.
+    #[serde(default = "crate::config::default_history_tool_result_max_chars")]
+    pub history_tool_result_max_chars: usize,
+    /// `AgentConfig::thinking_level`; empty means no thinking.
+    #[serde(default)]
+    pub thinking_level: String,
+    /// Models to fall back to, in order, when `model` keeps failing.
+    #[serde(default)]
+    pub fallback_models: Vec<String>,
+    /// Retries per model for rate limits, overload and network errors.
+    #[serde(default = "crate::config::default_llm_max_retries")]
+    pub max_retries: u32,
+    /// `BatConfig::models` — the user's additions to the model registry.
+    #[serde(default)]
+    pub model_overrides: Vec<ModelInfo>,
+    /// `AgentConfig::shell_env_allowlist`.
+    #[serde(default)]
+    pub shell_env_allowlist: Vec<String>,
+    /// `BatConfig::permissions`. Agents from before permission modes
+    /// existed ran everything, so a missing field means allow-all.
+    #[serde(default = "PermissionsConfig::allow_all")]
+    pub permissions: PermissionsConfig,
+    /// `BatConfig::commands`. A missing field means no rules.
+    #[serde(default = "CommandPolicy::allow_all")]
+    pub command_policy: CommandPolicy,
+    /// Where to send Anthropic and OpenAI requests. The agent never
+    /// gets the API keys themselves.
+    #[serde(default)]
+    pub llm_relay: Option<LlmRelay>,
 }
 
 /// Agent → Gateway
@@ -51,11 +100,31 @@ pub enum AgentToGateway {
         session_kind: String,
         content: String,
     },
+    /// Chunk of the model's extended thinking / reasoning.
+    ThinkingDelta {
+        session_id: Uuid,
+        session_kind: String,
+        content: String,
+    },
     ToolCallStart {
         session_id: Uuid,
         session_kind: String,
         tool_call: ToolCall,
     },
+    /// Fragment of a tool call's JSON input, streamed after its `ToolCallStart`.
+    ToolCallInputDelta {
+        session_id: Uuid,
+        session_kind: String,
+        tool_call_id: String,
+        partial_json: String,
+    },
+    /// The tool call with its fully assembled input, sent once its `tool_use`
+    /// block closes and before the tool runs.
+    ToolCallReady {
+        session_id: Uuid,
+        session_kind: String,
+        tool_call: ToolCall,
+    },
     ToolCallResult {
         session_id: Uuid,
         session_kind: String,
@@ -93,6 +162,15 @@ pub enum AgentToGateway {
         summary: String,
         percent: Option<f32>,
     },
+    /// A tool call is waiting for the user's approval (sent by the gateway).
+    ApprovalRequested {
+        request: ApprovalRequest,
+    },
+    /// A pending approval was answered or expired (sent by the gateway).
+    ApprovalResolved {
+        approval_id: String,
+        decision: ApprovalDecision,
+    },
 }
 
 /// Process management actions the agent can request.
@@ -122,10 +200,25 @@ pub enum ProcessAction {
     },
     /// List all managed processes.
     List,
-    /// Spawn a subagent.
+    /// Spawn a subagent, optionally narrowing what it inherits.
     SpawnSubagent {
         task: String,
         label: Option<String>,
+        /// Paths the subagent may use, each within the parent's access.
+        #[serde(default)]
+        paths: Option<Vec<PathGrant>>,
+        /// Tools the subagent gets instead of the full worker set.
+        #[serde(default)]
+        tools: Option<Vec<String>>,
+        #[serde(default)]
+        model: Option<String>,
+        #[serde(default)]
+        memory_limit_mb: Option<u64>,
+        /// Hosts the subagent may connect to.
+        #[serde(default)]
+        hosts: Option<Vec<String>>,
+        #[serde(default)]
+        blocked_hosts: Option<Vec<String>>,
     },
     /// Get status of subagents.
     ListSubagents,
@@ -153,6 +246,20 @@ pub enum ProcessAction {
         session_key: String,
         instruction: String,
     },
+    /// Ask the user whether a tool call in "ask" mode may run.
+    RequestApproval {
+        tool: String,
+        input: serde_json::Value,
+    },
+    /// A command the agent runs leads process group `pgid`, which the
+    /// gateway kills along with the agent if it has to.
+    TrackProcessGroup {
+        pgid: u32,
+    },
+    /// The group from `TrackProcessGroup` is gone.
+    UntrackProcessGroup {
+        pgid: u32,
+    },
 }
 
 /// Result of a process management request.
@@ -195,6 +302,25 @@ pub enum ProcessResult {
     SubagentResumed,
     /// Instruction sent to sub-agent.
     SubagentInstructed,
+    /// The user's answer to `RequestApproval`.
+    Approval {
+        decision: ApprovalDecision,
+    },
+    /// Answer to `TrackProcessGroup` and `UntrackProcessGroup`.
+    Tracked,
+}
+
+/// The gateway's LLM relay, as a session's agent sees it. Requests to
+/// `{url}/anthropic/v1/...` and `{url}/openai/v1/...` carry `token` in
+/// place of an API key; the gateway swaps in the real key.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LlmRelay {
+    pub url: String,
+    pub token: String,
+    /// Whether the gateway has an Anthropic key to relay with.
+    pub anthropic: bool,
+    /// Whether the gateway has an OpenAI key to relay with.
+    pub openai: bool,
 }
 
 /// Info about a managed process.
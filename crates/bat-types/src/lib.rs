@@ -1,8 +1,13 @@
+pub mod approval;
 pub mod audit;
 pub mod memory;
 pub mod message;
 pub mod session;
 pub mod policy;
+pub mod command_policy;
+pub mod network;
 pub mod ipc;
 pub mod config;
 pub mod usage;
+pub mod models;
+pub mod process;
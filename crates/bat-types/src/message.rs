@@ -25,6 +25,15 @@ pub struct Message {
     pub created_at: DateTime<Utc>,
     pub token_input: Option<i64>,
     pub token_output: Option<i64>,
+    /// Part of `token_output` spent on thinking / reasoning.
+    #[serde(default)]
+    pub token_thinking: Option<i64>,
+    /// Input tokens read from the prompt cache (not part of `token_input`).
+    #[serde(default)]
+    pub token_cache_read: Option<i64>,
+    /// Input tokens written to the prompt cache (not part of `token_input`).
+    #[serde(default)]
+    pub token_cache_write: Option<i64>,
 }
 
 impl Message {
@@ -40,6 +49,9 @@ impl Message {
             created_at: Utc::now(),
             token_input: None,
             token_output: None,
+            token_thinking: None,
+            token_cache_read: None,
+            token_cache_write: None,
         }
     }
 
@@ -60,6 +72,9 @@ impl Message {
             created_at: Utc::now(),
             token_input: None,
             token_output: None,
+            token_thinking: None,
+            token_cache_read: None,
+            token_cache_write: None,
         }
     }
 
@@ -75,6 +90,9 @@ impl Message {
             created_at: Utc::now(),
             token_input: None,
             token_output: None,
+            token_thinking: None,
+            token_cache_read: None,
+            token_cache_write: None,
         }
     }
 
@@ -90,6 +108,9 @@ impl Message {
             created_at: Utc::now(),
             token_input: None,
             token_output: None,
+            token_thinking: None,
+            token_cache_read: None,
+            token_cache_write: None,
         }
     }
 }
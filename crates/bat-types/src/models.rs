@@ -0,0 +1,326 @@
+//! Model capability registry — what each LLM can do, where it is served
+//! from and what it costs.
+//!
+//! Built-in entries cover the hosted Anthropic and OpenAI models. Users add or
+//! override entries with `[[models]]` tables in `config.toml`:
+//!
+//! ```toml
+//! [[models]]
+//! id = "qwen2.5-coder:32b"
+//! provider = "local"
+//! context_window = 32768
+//! max_output_tokens = 8192
+//! ```
+//!
+//! An entry's `id` matches that exact model name and any name that extends
+//! it after a `-`, `:` or `@` (`claude-sonnet-4` matches
+//! `claude-sonnet-4-20250514`). An `id` ending in `-` is a pure prefix. The
+//! longest matching entry wins, and user entries replace built-ins with the
+//! same `id`.
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::LlmProvider;
+
+/// Prompt-cache reads cost a tenth of the input price on Anthropic…
+const ANTHROPIC_CACHE_READ_RATE: f64 = 0.1;
+/// …and cache writes a quarter more than it.
+const ANTHROPIC_CACHE_WRITE_RATE: f64 = 1.25;
+
+/// Rough cost of one image attachment.
+pub const IMAGE_TOKENS: usize = 1600;
+
+/// What the registry knows about a model. Prices are USD per million tokens.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelInfo {
+    /// Model name, or a family prefix (see the module docs).
+    pub id: String,
+    pub provider: LlmProvider,
+    /// API base URL overriding the provider's default, e.g.
+    /// `https://api.openai.com/v1`. For local models this is the server root
+    /// (`http://localhost:11434`) and replaces the configured local endpoint.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    #[serde(default = "default_context_window")]
+    pub context_window: usize,
+    #[serde(default = "default_max_output_tokens")]
+    pub max_output_tokens: u32,
+    /// Accepts image input.
+    #[serde(default)]
+    pub vision: bool,
+    /// Accepts tool definitions.
+    #[serde(default = "default_true")]
+    pub tools: bool,
+    /// Accepts a thinking budget / reasoning effort.
+    #[serde(default)]
+    pub thinking: bool,
+    #[serde(default)]
+    pub input_price: f64,
+    #[serde(default)]
+    pub output_price: f64,
+    #[serde(default)]
+    pub cache_read_price: f64,
+    #[serde(default)]
+    pub cache_write_price: f64,
+}
+
+fn default_context_window() -> usize { 8_192 }
+fn default_max_output_tokens() -> u32 { 4_096 }
+fn default_true() -> bool { true }
+
+impl ModelInfo {
+    fn new(id: &str, provider: LlmProvider, context_window: usize, max_output_tokens: u32) -> Self {
+        Self {
+            id: id.to_string(),
+            provider,
+            endpoint: None,
+            context_window,
+            max_output_tokens,
+            vision: false,
+            tools: true,
+            thinking: false,
+            input_price: 0.0,
+            output_price: 0.0,
+            cache_read_price: 0.0,
+            cache_write_price: 0.0,
+        }
+    }
+
+    /// A model served by the local LLM runtime, with conservative defaults.
+    pub fn local(id: &str) -> Self {
+        Self::new(id, LlmProvider::LocalLlm, default_context_window(), default_max_output_tokens())
+    }
+
+    fn anthropic(id: &str, max_output_tokens: u32, input_price: f64, output_price: f64) -> Self {
+        Self {
+            vision: true,
+            input_price,
+            output_price,
+            cache_read_price: input_price * ANTHROPIC_CACHE_READ_RATE,
+            cache_write_price: input_price * ANTHROPIC_CACHE_WRITE_RATE,
+            ..Self::new(id, LlmProvider::Anthropic, 200_000, max_output_tokens)
+        }
+    }
+
+    /// OpenAI bills cached input at `cache_read_price` and charges nothing
+    /// extra for writing the cache.
+    fn openai(id: &str, context_window: usize, max_output_tokens: u32, prices: (f64, f64, f64)) -> Self {
+        let (input_price, output_price, cache_read_price) = prices;
+        Self {
+            vision: true,
+            input_price,
+            output_price,
+            cache_read_price,
+            cache_write_price: input_price,
+            ..Self::new(id, LlmProvider::OpenAI, context_window, max_output_tokens)
+        }
+    }
+
+    fn with_thinking(mut self) -> Self {
+        self.thinking = true;
+        self
+    }
+
+    fn without_vision(mut self) -> Self {
+        self.vision = false;
+        self
+    }
+
+    /// Reject entries that could never be used.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.id.trim().is_empty() {
+            anyhow::bail!("Model entry is missing an id");
+        }
+        if self.context_window == 0 || self.max_output_tokens == 0 {
+            anyhow::bail!("Model {}: context_window and max_output_tokens must be positive", self.id);
+        }
+        if self.endpoint.as_deref().is_some_and(|e| !e.starts_with("http://") && !e.starts_with("https://")) {
+            anyhow::bail!("Model {}: endpoint must be an http(s) URL", self.id);
+        }
+        Ok(())
+    }
+
+    /// Whether this entry applies to the (bare) model name `model`.
+    fn matches(&self, model: &str) -> bool {
+        match model.strip_prefix(self.id.as_str()) {
+            Some("") => true,
+            Some(rest) => self.id.ends_with('-') || rest.starts_with(['-', ':', '@']),
+            None => false,
+        }
+    }
+
+    /// Estimated cost in USD. `input_tokens` excludes the cached tokens,
+    /// which are billed at their own rates.
+    pub fn estimate_cost(&self, input_tokens: i64, output_tokens: i64, cache_read: i64, cache_write: i64) -> f64 {
+        (input_tokens as f64 * self.input_price
+            + output_tokens as f64 * self.output_price
+            + cache_read as f64 * self.cache_read_price
+            + cache_write as f64 * self.cache_write_price)
+            / 1_000_000.0
+    }
+
+    /// How much cheaper the cached tokens were than sending them uncached.
+    pub fn cache_savings(&self, cache_read: i64, cache_write: i64) -> f64 {
+        let saved = cache_read as f64 * (self.input_price - self.cache_read_price)
+            - cache_write as f64 * (self.cache_write_price - self.input_price);
+        saved / 1_000_000.0
+    }
+
+    /// Average characters per token for the model's tokenizer.
+    pub fn chars_per_token(&self) -> f64 {
+        if self.provider == LlmProvider::Anthropic {
+            3.5
+        } else {
+            4.0
+        }
+    }
+}
+
+/// Model name without an optional `provider/` prefix.
+pub fn bare_model(model: &str) -> &str {
+    model.rsplit('/').next().unwrap_or(model)
+}
+
+/// Built-in and user-configured model entries.
+#[derive(Debug, Clone)]
+pub struct ModelRegistry {
+    models: Vec<ModelInfo>,
+}
+
+impl Default for ModelRegistry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl ModelRegistry {
+    /// The built-in entries only.
+    pub fn builtin() -> Self {
+        Self {
+            models: vec![
+                ModelInfo::anthropic("claude-opus-4-6", 128_000, 5.0, 25.0).with_thinking(),
+                ModelInfo::anthropic("claude-opus-4-5", 64_000, 5.0, 25.0).with_thinking(),
+                ModelInfo::anthropic("claude-opus-4", 32_000, 15.0, 75.0).with_thinking(),
+                ModelInfo::anthropic("claude-sonnet-4", 64_000, 3.0, 15.0).with_thinking(),
+                ModelInfo::anthropic("claude-haiku-4-5", 64_000, 1.0, 5.0).with_thinking(),
+                ModelInfo::anthropic("claude-3-7-sonnet", 64_000, 3.0, 15.0).with_thinking(),
+                ModelInfo::anthropic("claude-3-5-sonnet", 8_192, 3.0, 15.0),
+                ModelInfo::anthropic("claude-3-5-haiku", 8_192, 0.8, 4.0),
+                ModelInfo::anthropic("claude-3-opus", 4_096, 15.0, 75.0),
+                ModelInfo::anthropic("claude-3-haiku", 4_096, 0.25, 1.25),
+                // Newer Claude models not listed above.
+                ModelInfo::anthropic("claude-", 32_000, 3.0, 15.0).with_thinking(),
+                ModelInfo::openai("gpt-5", 400_000, 128_000, (1.25, 10.0, 0.125)).with_thinking(),
+                ModelInfo::openai("gpt-5-mini", 400_000, 128_000, (0.25, 2.0, 0.025)).with_thinking(),
+                ModelInfo::openai("gpt-5-nano", 400_000, 128_000, (0.05, 0.4, 0.005)).with_thinking(),
+                ModelInfo::openai("gpt-4.1", 1_047_576, 32_768, (2.0, 8.0, 0.5)),
+                ModelInfo::openai("gpt-4.1-mini", 1_047_576, 32_768, (0.4, 1.6, 0.1)),
+                ModelInfo::openai("gpt-4.1-nano", 1_047_576, 32_768, (0.1, 0.4, 0.025)),
+                ModelInfo::openai("gpt-4o", 128_000, 16_384, (2.5, 10.0, 1.25)),
+                ModelInfo::openai("gpt-4o-mini", 128_000, 16_384, (0.15, 0.6, 0.075)),
+                ModelInfo::openai("gpt-4-turbo", 128_000, 4_096, (10.0, 30.0, 10.0)),
+                ModelInfo::openai("gpt-3.5-turbo", 16_385, 4_096, (0.5, 1.5, 0.5)).without_vision(),
+                ModelInfo::openai("o1", 200_000, 100_000, (15.0, 60.0, 7.5)).with_thinking(),
+                ModelInfo::openai("o1-mini", 128_000, 65_536, (1.1, 4.4, 0.55)).without_vision(),
+                ModelInfo::openai("o3", 200_000, 100_000, (2.0, 8.0, 0.5)).with_thinking(),
+                ModelInfo::openai("o3-mini", 200_000, 100_000, (1.1, 4.4, 0.55)).with_thinking().without_vision(),
+                ModelInfo::openai("o4-mini", 200_000, 100_000, (1.1, 4.4, 0.275)).with_thinking(),
+                // Newer GPT models not listed above.
+                ModelInfo::openai("gpt-", 128_000, 16_384, (2.5, 10.0, 1.25)),
+            ],
+        }
+    }
+
+    /// Built-in entries plus `overrides`, which replace built-ins with the same `id`.
+    pub fn with_overrides(overrides: &[ModelInfo]) -> Self {
+        let mut registry = Self::builtin();
+        registry.models.retain(|m| !overrides.iter().any(|o| o.id == m.id));
+        registry.models.extend(overrides.iter().cloned());
+        registry
+    }
+
+    /// The entry that applies to `model`, if any. A `provider/` prefix is ignored.
+    pub fn lookup(&self, model: &str) -> Option<&ModelInfo> {
+        let bare = bare_model(model);
+        self.models
+            .iter()
+            .filter(|m| m.matches(bare))
+            .max_by_key(|m| m.id.len())
+    }
+
+    /// Everything known about `model`, with `id` set to its bare name.
+    /// Models without an entry are assumed to be served by the local LLM
+    /// runtime (Ollama and LM Studio accept arbitrary model names).
+    pub fn resolve(&self, model: &str) -> ModelInfo {
+        let bare = bare_model(model);
+        match self.lookup(bare) {
+            Some(info) => ModelInfo { id: bare.to_string(), ..info.clone() },
+            None => ModelInfo::local(bare),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_longest_match_wins() {
+        let registry = ModelRegistry::builtin();
+        assert_eq!(registry.resolve("gpt-4o-mini").input_price, 0.15);
+        assert_eq!(registry.resolve("gpt-4o-2024-08-06").input_price, 2.5);
+        assert_eq!(registry.resolve("claude-opus-4-1-20250805").max_output_tokens, 32_000);
+        assert_eq!(registry.resolve("claude-opus-4-6").max_output_tokens, 128_000);
+        // `gpt-4o` doesn't match `gpt-4o1`; the GPT catch-all does.
+        assert_eq!(registry.lookup("gpt-4o1").unwrap().id, "gpt-");
+    }
+
+    #[test]
+    fn test_resolve_unknown_and_prefixed() {
+        let registry = ModelRegistry::builtin();
+        let claude = registry.resolve("anthropic/claude-sonnet-4-6");
+        assert_eq!(claude.id, "claude-sonnet-4-6");
+        assert_eq!(claude.provider, LlmProvider::Anthropic);
+
+        // A Claude model newer than the table still goes to Anthropic.
+        assert_eq!(registry.resolve("claude-sonnet-9").provider, LlmProvider::Anthropic);
+
+        let local = registry.resolve("llama3.1:8b");
+        assert_eq!(local.provider, LlmProvider::LocalLlm);
+        assert_eq!(local.context_window, 8_192);
+    }
+
+    #[test]
+    fn test_overrides_replace_builtins() {
+        let custom: ModelInfo = serde_json::from_value(serde_json::json!({
+            "id": "gpt-4o",
+            "provider": "openai",
+            "endpoint": "https://proxy.example.com/v1",
+            "context_window": 64000,
+            "input_price": 1.0,
+        }))
+        .unwrap();
+        assert!(custom.tools && !custom.vision);
+
+        let registry = ModelRegistry::with_overrides(&[custom, ModelInfo::local("qwen2.5-coder")]);
+        let gpt = registry.resolve("gpt-4o");
+        assert_eq!(gpt.context_window, 64_000);
+        assert_eq!(gpt.endpoint.as_deref(), Some("https://proxy.example.com/v1"));
+        // Other built-ins are untouched.
+        assert_eq!(registry.resolve("gpt-4o-mini").context_window, 128_000);
+        assert_eq!(registry.lookup("qwen2.5-coder:32b").unwrap().provider, LlmProvider::LocalLlm);
+    }
+
+    #[test]
+    fn test_cached_tokens_cost_less() {
+        let sonnet = ModelRegistry::builtin().resolve("claude-sonnet-4-6");
+        assert!((sonnet.estimate_cost(1_000_000, 0, 0, 0) - 3.0).abs() < 1e-9);
+        assert!((sonnet.estimate_cost(0, 0, 1_000_000, 0) - 0.3).abs() < 1e-9);
+        assert!((sonnet.cache_savings(1_000_000, 0) - 2.7).abs() < 1e-9);
+        assert!(sonnet.cache_savings(0, 1_000_000) < 0.0);
+
+        // OpenAI doesn't charge for cache writes.
+        let gpt = ModelRegistry::builtin().resolve("gpt-4o");
+        assert_eq!(gpt.cache_savings(0, 1_000_000), 0.0);
+    }
+}
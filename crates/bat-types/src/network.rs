@@ -0,0 +1,302 @@
+//! Which hosts agents, and the processes they start, may connect to.
+//!
+//! The gateway's egress proxy checks every connection against these rules
+//! once the host is resolved, so a rule can name the host or the address
+//! it resolved to.
+
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::policy::wildcard_match;
+
+/// What happens to a connection no `allow` rule matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkDefault {
+    #[default]
+    Allow,
+    Deny,
+}
+
+/// Rules for outbound connections.
+///
+/// A rule is a host name, where `*.example.com` matches any subdomain, an
+/// IP address, or a CIDR range such as `10.0.0.0/8`. A host or address can
+/// end in `:port` to match only that port, e.g. `*:22` or `[::1]:8080`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetworkPolicy {
+    /// Route agents and their processes through the gateway's egress proxy.
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Applies to connections no `allow` rule matches.
+    #[serde(default)]
+    pub default: NetworkDefault,
+    /// Hosts that may be reached when `default` is `deny`. A rule without
+    /// wildcards also lifts `block_private_ranges` for what it names.
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// Hosts that are never reached, whatever `allow` says.
+    #[serde(default)]
+    pub deny: Vec<String>,
+    /// Refuse loopback, private, link-local (cloud metadata), CGNAT and
+    /// other non-public addresses.
+    #[serde(default = "default_true")]
+    pub block_private_ranges: bool,
+}
+
+fn default_true() -> bool { true }
+
+impl Default for NetworkPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            default: NetworkDefault::Allow,
+            allow: vec![],
+            deny: vec![],
+            block_private_ranges: true,
+        }
+    }
+}
+
+/// A session's own host rules, which can only narrow the `NetworkPolicy`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionHosts {
+    /// If set, only these hosts may be reached.
+    #[serde(default)]
+    pub allow: Option<Vec<String>>,
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+/// Why a connection was refused.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EgressDenial {
+    /// The rule responsible: a deny pattern, `session deny: <pattern>`,
+    /// `block_private_ranges`, `default: deny` or `session hosts`.
+    pub rule: String,
+    pub reason: String,
+}
+
+impl NetworkPolicy {
+    /// Check a connection to `host:port` that resolved to `addr`.
+    pub fn check(&self, session: &SessionHosts, host: &str, port: u16, addr: IpAddr) -> Result<(), EgressDenial> {
+        let host = normalize_host(host);
+        let target = Target { host: &host, port, addr };
+        let destination = format!("{host}:{port}");
+        if let Some(rule) = self.deny.iter().find(|rule| target.matches(rule)) {
+            return Err(EgressDenial { rule: rule.clone(), reason: format!("{destination} is denied by '{rule}'") });
+        }
+        if let Some(rule) = session.deny.iter().find(|rule| target.matches(rule)) {
+            return Err(EgressDenial {
+                rule: format!("session deny: {rule}"),
+                reason: format!("{destination} is denied to this session by '{rule}'"),
+            });
+        }
+        if self.block_private_ranges
+            && !is_public(addr)
+            && !self.allow.iter().any(|rule| is_literal(rule) && target.matches(rule))
+        {
+            return Err(EgressDenial {
+                rule: "block_private_ranges".to_string(),
+                reason: format!("{destination} resolves to {addr}, a private or reserved address"),
+            });
+        }
+        if self.default == NetworkDefault::Deny && !self.allow.iter().any(|rule| target.matches(rule)) {
+            return Err(EgressDenial { rule: "default: deny".to_string(), reason: format!("{destination} is not in the allowed hosts") });
+        }
+        if let Some(allow) = &session.allow {
+            if !allow.iter().any(|rule| target.matches(rule)) {
+                return Err(EgressDenial {
+                    rule: "session hosts".to_string(),
+                    reason: format!("{destination} is not among the hosts this session may reach"),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Lower-case, without brackets around an IPv6 literal or a trailing dot.
+fn normalize_host(host: &str) -> String {
+    let host = host.strip_prefix('[').and_then(|h| h.strip_suffix(']')).unwrap_or(host);
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+fn is_literal(rule: &str) -> bool {
+    !rule.contains(['*', '?', '['])
+}
+
+struct Target<'a> {
+    host: &'a str,
+    port: u16,
+    addr: IpAddr,
+}
+
+impl Target<'_> {
+    fn matches(&self, rule: &str) -> bool {
+        let rule = rule.trim();
+        let (pattern, port) = split_port(rule);
+        if port.is_some_and(|port| port != self.port) {
+            return false;
+        }
+        let pattern = normalize_host(pattern);
+        if let Some((net, bits)) = pattern.split_once('/') {
+            return match (net.parse::<IpAddr>(), bits.parse::<u8>()) {
+                (Ok(net), Ok(bits)) => in_cidr(self.addr, net, bits),
+                _ => false,
+            };
+        }
+        if let Ok(ip) = pattern.parse::<IpAddr>() {
+            return canonical(ip) == canonical(self.addr);
+        }
+        wildcard_match(&pattern, self.host)
+    }
+}
+
+/// Split a trailing `:port` off a rule. A bare IPv6 address has no port.
+fn split_port(rule: &str) -> (&str, Option<u16>) {
+    if let Some(rest) = rule.strip_prefix('[') {
+        if let Some((addr, after)) = rest.split_once(']') {
+            return (addr, after.strip_prefix(':').and_then(|p| p.parse().ok()));
+        }
+    }
+    match rule.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') => match port.parse() {
+            Ok(port) => (host, Some(port)),
+            Err(_) => (rule, None),
+        },
+        _ => (rule, None),
+    }
+}
+
+/// IPv4 addresses written as IPv6 (`::ffff:a.b.c.d`) compare as IPv4.
+fn canonical(addr: IpAddr) -> IpAddr {
+    match addr {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(addr, IpAddr::V4),
+        v4 => v4,
+    }
+}
+
+fn in_cidr(addr: IpAddr, net: IpAddr, bits: u8) -> bool {
+    match (canonical(addr), canonical(net)) {
+        (IpAddr::V4(addr), IpAddr::V4(net)) if bits <= 32 => {
+            let mask = u32::MAX.checked_shl(32 - u32::from(bits)).unwrap_or(0);
+            u32::from(addr) & mask == u32::from(net) & mask
+        }
+        (IpAddr::V6(addr), IpAddr::V6(net)) if bits <= 128 => {
+            let mask = u128::MAX.checked_shl(128 - u32::from(bits)).unwrap_or(0);
+            u128::from(addr) & mask == u128::from(net) & mask
+        }
+        _ => false,
+    }
+}
+
+/// Whether `addr` is on the public internet, as opposed to this machine,
+/// a private network, a link-local range such as the cloud metadata
+/// service at 169.254.169.254, or another reserved block.
+pub fn is_public(addr: IpAddr) -> bool {
+    match canonical(addr) {
+        IpAddr::V4(v4) => is_public_v4(v4),
+        IpAddr::V6(v6) => is_public_v6(v6),
+    }
+}
+
+fn is_public_v4(addr: Ipv4Addr) -> bool {
+    const RESERVED: &[(Ipv4Addr, u8)] = &[
+        (Ipv4Addr::new(0, 0, 0, 0), 8),
+        (Ipv4Addr::new(10, 0, 0, 0), 8),
+        (Ipv4Addr::new(100, 64, 0, 0), 10),
+        (Ipv4Addr::new(127, 0, 0, 0), 8),
+        (Ipv4Addr::new(169, 254, 0, 0), 16),
+        (Ipv4Addr::new(172, 16, 0, 0), 12),
+        (Ipv4Addr::new(192, 0, 0, 0), 24),
+        (Ipv4Addr::new(192, 0, 2, 0), 24),
+        (Ipv4Addr::new(192, 168, 0, 0), 16),
+        (Ipv4Addr::new(198, 18, 0, 0), 15),
+        (Ipv4Addr::new(198, 51, 100, 0), 24),
+        (Ipv4Addr::new(203, 0, 113, 0), 24),
+        (Ipv4Addr::new(224, 0, 0, 0), 3),
+    ];
+    !RESERVED.iter().any(|&(net, bits)| in_cidr(IpAddr::V4(addr), IpAddr::V4(net), bits))
+}
+
+fn is_public_v6(addr: Ipv6Addr) -> bool {
+    // NAT64 addresses carry an IPv4 address in their last 32 bits
+    if in_cidr(IpAddr::V6(addr), "64:ff9b::".parse().unwrap(), 96) {
+        let [.., a, b, c, d] = addr.octets();
+        return is_public_v4(Ipv4Addr::new(a, b, c, d));
+    }
+    const RESERVED: &[(&str, u8)] = &[
+        ("::", 127), // unspecified and loopback
+        ("100::", 64),
+        ("2001:db8::", 32),
+        ("fc00::", 7),
+        ("fe80::", 10),
+        ("fec0::", 10),
+        ("ff00::", 8),
+    ];
+    !RESERVED.iter().any(|&(net, bits)| in_cidr(IpAddr::V6(addr), net.parse().unwrap(), bits))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_private_and_metadata_ranges_are_blocked_by_default() {
+        let policy = NetworkPolicy::default();
+        let none = SessionHosts::default();
+        for addr in ["127.0.0.1", "10.1.2.3", "172.20.0.1", "192.168.1.1", "169.254.169.254", "100.100.1.1", "0.0.0.0",
+            "::1", "::", "fd00:ec2::254", "fe80::1", "::ffff:169.254.169.254", "64:ff9b::a9fe:a9fe"]
+        {
+            let denial = policy.check(&none, "evil.example", 80, ip(addr)).unwrap_err();
+            assert_eq!(denial.rule, "block_private_ranges", "{addr}");
+        }
+        for addr in ["93.184.216.34", "2606:2800:220:1:248:1893:25c8:1946", "64:ff9b::5db8:d822"] {
+            assert!(policy.check(&none, "example.com", 443, ip(addr)).is_ok(), "{addr}");
+        }
+
+        // Only a rule naming the address lifts the block
+        let nas = NetworkPolicy { allow: vec!["192.168.1.0/24".to_string(), "*.lan".to_string()], ..Default::default() };
+        assert!(nas.check(&none, "nas.lan", 445, ip("192.168.1.20")).is_ok());
+        assert!(nas.check(&none, "router.lan", 80, ip("10.0.0.1")).is_err());
+        let off = NetworkPolicy { block_private_ranges: false, ..Default::default() };
+        assert!(off.check(&none, "localhost", 11434, ip("127.0.0.1")).is_ok());
+    }
+
+    #[test]
+    fn test_rules_match_hosts_ports_and_addresses() {
+        let policy = NetworkPolicy {
+            default: NetworkDefault::Deny,
+            allow: vec!["*.github.com".to_string(), "crates.io".to_string(), "pypi.org:443".to_string()],
+            deny: vec!["gist.github.com".to_string(), "*:22".to_string(), "203.0.113.0/24".to_string()],
+            block_private_ranges: false,
+            ..Default::default()
+        };
+        let none = SessionHosts::default();
+        let check = |host: &str, port: u16| policy.check(&none, host, port, ip("140.82.112.3")).map_err(|d| d.rule);
+
+        assert!(check("api.github.com", 443).is_ok());
+        assert!(check("CRATES.IO.", 443).is_ok());
+        assert_eq!(check("github.com", 443).unwrap_err(), "default: deny");
+        assert_eq!(check("pypi.org", 80).unwrap_err(), "default: deny");
+        assert_eq!(check("gist.github.com", 443).unwrap_err(), "gist.github.com");
+        assert_eq!(check("api.github.com", 22).unwrap_err(), "*:22");
+        let denied = policy.check(&none, "api.github.com", 443, ip("203.0.113.9")).unwrap_err();
+        assert_eq!(denied.rule, "203.0.113.0/24");
+
+        // A session can narrow the policy, never widen it
+        let session = SessionHosts { allow: Some(vec!["crates.io".to_string(), "example.com".to_string()]), deny: vec![] };
+        assert!(policy.check(&session, "crates.io", 443, ip("140.82.112.3")).is_ok());
+        assert_eq!(policy.check(&session, "api.github.com", 443, ip("140.82.112.3")).unwrap_err().rule, "session hosts");
+        assert_eq!(policy.check(&session, "example.com", 443, ip("140.82.112.3")).unwrap_err().rule, "default: deny");
+        let session = SessionHosts { allow: None, deny: vec!["[::1]:8080".to_string(), "crates.io".to_string()] };
+        assert_eq!(policy.check(&session, "crates.io", 443, ip("140.82.112.3")).unwrap_err().rule, "session deny: crates.io");
+        assert!(Target { host: "localhost", port: 8080, addr: ip("::1") }.matches("[::1]:8080"));
+        assert!(!Target { host: "localhost", port: 8081, addr: ip("::1") }.matches("[::1]:8080"));
+    }
+}
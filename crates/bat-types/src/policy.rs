@@ -7,9 +7,39 @@ pub enum AccessLevel {
     ReadOnly,
     ReadWrite,
     WriteOnly,
+    /// Blocks everything the rule covers, overriding less specific grants.
+    Deny,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl AccessLevel {
+    /// Whether a rule with this level grants writing (`write`) or reading.
+    pub fn permits(self, write: bool) -> bool {
+        match self {
+            AccessLevel::ReadWrite => true,
+            AccessLevel::ReadOnly => !write,
+            AccessLevel::WriteOnly => write,
+            AccessLevel::Deny => false,
+        }
+    }
+}
+
+impl std::fmt::Display for AccessLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AccessLevel::ReadOnly => write!(f, "read-only"),
+            AccessLevel::ReadWrite => write!(f, "read-write"),
+            AccessLevel::WriteOnly => write!(f, "write-only"),
+            AccessLevel::Deny => write!(f, "deny"),
+        }
+    }
+}
+
+/// A rule granting or denying access to a path.
+///
+/// `path` may start with `~` and may be a glob: `*`, `?` and `[...]` match
+/// within one component and `**` matches any number of components, as in
+/// `~/Projects/*/src/**` or `**/*.env`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PathPolicy {
     /// Database row id (None for newly created, not yet persisted).
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -18,6 +48,22 @@ pub struct PathPolicy {
     pub access: AccessLevel,
     pub recursive: bool,
     pub description: Option<String>,
+    /// Rules are grouped into layers and a path must be allowed by every
+    /// layer. The user's rules are layer 0; a subagent's narrower grants
+    /// sit on top of its parent's (see [`narrow`]).
+    #[serde(default, skip_serializing_if = "is_base_layer")]
+    pub layer: u8,
+}
+
+fn is_base_layer(layer: &u8) -> bool {
+    *layer == 0
+}
+
+/// A path a subagent asks for, which must lie within its parent's access.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PathGrant {
+    pub path: PathBuf,
+    pub access: AccessLevel,
 }
 
 /// Strip the Windows extended-length path prefix (`\\?\`) so that
@@ -40,30 +86,262 @@ fn normalize(p: &Path) -> PathBuf {
     }
 }
 
+pub(crate) fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")).map(PathBuf::from)
+}
+
+/// Expand a leading `~` to the home directory.
+pub fn expand_tilde(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), home_dir()) {
+        (Ok(rest), Some(home)) if rest.as_os_str().is_empty() => home,
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn components(p: &Path) -> Vec<String> {
+    p.components().map(|c| c.as_os_str().to_string_lossy().into_owned()).collect()
+}
+
+/// Match path components against glob components, where `**` stands for
+/// any number of components.
+fn glob_match(pattern: &[String], path: &[String]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first == "**" => (0..=path.len()).any(|skip| glob_match(rest, &path[skip..])),
+        Some((first, rest)) => path
+            .split_first()
+            .is_some_and(|(head, tail)| wildcard_match(first, head) && glob_match(rest, tail)),
+    }
+}
+
+/// Match `text` against a pattern where `*` is any run of characters, `?`
+/// any one character and `[...]` one of a set (`[!...]` for none of it).
+pub(crate) fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let (p, t): (Vec<char>, Vec<char>) = (pattern.chars().collect(), text.chars().collect());
+    let (mut pi, mut ti) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if p.get(pi) == Some(&'*') {
+            backtrack = Some((pi, ti));
+            pi += 1;
+        } else if let Some(next) = (pi < p.len()).then(|| match_one(&p, pi, t[ti])).flatten() {
+            pi = next;
+            ti += 1;
+        } else if let Some((star, matched)) = backtrack {
+            pi = star + 1;
+            ti = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+/// If the pattern token at `p[pi]` matches `c`, the index after the token.
+fn match_one(p: &[char], pi: usize, c: char) -> Option<usize> {
+    match p[pi] {
+        '?' => Some(pi + 1),
+        '[' => {
+            let Some(end) = p[pi + 1..].iter().position(|&x| x == ']').map(|i| pi + 1 + i) else {
+                // No closing bracket: a literal `[`.
+                return (c == '[').then_some(pi + 1);
+            };
+            let negated = matches!(p.get(pi + 1), Some('!' | '^'));
+            let class = &p[pi + 1 + usize::from(negated)..end];
+            let mut hit = false;
+            let mut i = 0;
+            while i < class.len() {
+                if i + 2 < class.len() && class[i + 1] == '-' {
+                    hit |= class[i] <= c && c <= class[i + 2];
+                    i += 3;
+                } else {
+                    hit |= class[i] == c;
+                    i += 1;
+                }
+            }
+            (hit != negated).then_some(end + 1)
+        }
+        literal => (literal == c).then_some(pi + 1),
+    }
+}
+
 impl PathPolicy {
-    pub fn allows(&self, target: &Path, write: bool) -> bool {
-        let target = normalize(target);
-        let policy_path = normalize(&self.path);
-        let matches = if self.recursive {
-            target.starts_with(&policy_path)
+    /// How specifically this rule covers `target`, or `None` if it doesn't:
+    /// the depth of the path it matched (the target or one of its
+    /// ancestors), then how many literal characters the rule has.
+    fn specificity(&self, target: &Path) -> Option<(usize, usize)> {
+        let mut pattern = components(&normalize(&expand_tilde(&self.path)));
+        // `dir/**` covers what a recursive `dir` does, and is as specific.
+        let trailing_globstar = pattern.len() > 1 && pattern.last().is_some_and(|c| c == "**");
+        if trailing_globstar {
+            pattern.pop();
+        }
+        let target = components(&normalize(target));
+        let depth = if self.recursive || trailing_globstar {
+            (1..=target.len()).rev().find(|&depth| glob_match(&pattern, &target[..depth]))
         } else {
-            target.parent() == Some(policy_path.as_path())
+            target.len().checked_sub(1).filter(|&depth| glob_match(&pattern, &target[..depth]))
+        }?;
+        let literal = pattern.iter().flat_map(|c| c.chars()).filter(|c| !matches!(c, '*' | '?' | '[' | ']')).count();
+        Some((depth, literal))
+    }
+
+    /// Whether this rule, on its own, grants the access.
+    pub fn allows(&self, target: &Path, write: bool) -> bool {
+        self.access.permits(write) && self.specificity(target).is_some()
+    }
+}
+
+/// The outcome of `explain`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessDecision<'a> {
+    pub allowed: bool,
+    /// The rule that decided; `None` if no rule covers the path.
+    pub rule: Option<&'a PathPolicy>,
+    pub target: PathBuf,
+    pub write: bool,
+}
+
+impl std::fmt::Display for AccessDecision<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let target = strip_win_prefix(&self.target).display();
+        let action = if self.write { "writing" } else { "reading" };
+        let Some(rule) = self.rule else {
+            return write!(f, "'{target}' is not in any allowed policy");
         };
-        if !matches {
-            return false;
+        let path = rule.path.display();
+        let about = rule.description.as_deref().map(|d| format!(" ({d})")).unwrap_or_default();
+        match (rule.access, self.allowed) {
+            (AccessLevel::Deny, _) => write!(f, "'{target}' is blocked by the deny rule '{path}'{about}"),
+            (access, true) => write!(f, "{action} '{target}' is allowed by the {access} rule '{path}'{about}"),
+            (access, false) => write!(f, "'{target}' falls under the {access} rule '{path}'{about}, which doesn't allow {action}"),
         }
-        match (self.access, write) {
-            (AccessLevel::ReadWrite, _) => true,
-            (AccessLevel::ReadOnly, false) => true,
-            (AccessLevel::WriteOnly, true) => true,
-            _ => false,
+    }
+}
+
+/// Decide access to `target` and say which rule decided it. The most
+/// specific rule covering the path wins; between equally specific rules a
+/// deny wins over a grant. With several layers, the first layer that
+/// refuses decides, otherwise the topmost one.
+pub fn explain<'a>(policies: &'a [PathPolicy], target: &Path, write: bool) -> AccessDecision<'a> {
+    let mut layers: Vec<u8> = policies.iter().map(|p| p.layer).collect();
+    layers.sort_unstable();
+    layers.dedup();
+    let mut decision = None;
+    for layer in layers {
+        let layer_decision = explain_layer(policies.iter().filter(|p| p.layer == layer), target, write);
+        let refused = !layer_decision.allowed;
+        decision = Some(layer_decision);
+        if refused {
+            break;
         }
     }
+    decision.unwrap_or_else(|| explain_layer(std::iter::empty(), target, write))
 }
 
-/// Check if any policy allows the given path for the given operation.
+fn explain_layer<'a>(policies: impl Iterator<Item = &'a PathPolicy>, target: &Path, write: bool) -> AccessDecision<'a> {
+    let mut best = None;
+    let mut candidates: Vec<&PathPolicy> = Vec::new();
+    for policy in policies {
+        let Some(specificity) = policy.specificity(target) else { continue };
+        match best.cmp(&Some(specificity)) {
+            std::cmp::Ordering::Less => {
+                best = Some(specificity);
+                candidates = vec![policy];
+            }
+            std::cmp::Ordering::Equal => candidates.push(policy),
+            std::cmp::Ordering::Greater => {}
+        }
+    }
+    let rule = candidates
+        .iter()
+        .find(|p| p.access == AccessLevel::Deny)
+        .or_else(|| candidates.iter().find(|p| p.access.permits(write)))
+        .or(candidates.first())
+        .copied();
+    AccessDecision {
+        allowed: rule.is_some_and(|r| r.access.permits(write)),
+        rule,
+        target: target.to_path_buf(),
+        write,
+    }
+}
+
+/// Check whether the policies allow the given path for the given operation.
 pub fn check_access(policies: &[PathPolicy], target: &Path, write: bool) -> bool {
-    policies.iter().any(|p| p.allows(target, write))
+    explain(policies, target, write).allowed
+}
+
+/// Narrow `parent` for a subagent to `grants`. Each grant must be allowed
+/// by `parent` where it is; the returned rules go on top of `parent` as a new
+/// layer, so the subagent can't reach anything `parent` can't, including
+/// what `parent` denies beneath a grant.
+pub fn narrow(parent: &[PathPolicy], grants: &[PathGrant]) -> Result<Vec<PathPolicy>, String> {
+    let layer = parent.iter().map(|p| p.layer).max().map_or(0, |l| l + 1);
+    grants
+        .iter()
+        .map(|grant| {
+            let path = expand_tilde(&grant.path);
+            if !path.is_absolute() {
+                return Err(format!("'{}' is not an absolute path", grant.path.display()));
+            }
+            if grant.access == AccessLevel::Deny {
+                return Err(format!("'{}': a grant can't be 'deny'; leave the path out instead", grant.path.display()));
+            }
+            for write in [false, true].into_iter().filter(|&w| grant.access.permits(w)) {
+                let decision = explain(parent, &path, write);
+                if !decision.allowed {
+                    return Err(format!("Can't grant {} access to '{}': {decision}", grant.access, grant.path.display()));
+                }
+            }
+            Ok(PathPolicy {
+                id: None,
+                path,
+                access: grant.access,
+                recursive: true,
+                description: Some("subagent grant".to_string()),
+                layer,
+            })
+        })
+        .collect()
+}
+
+/// Places holding credentials and browser data. They are denied unless the
+/// user grants one with a more specific rule.
+pub fn sensitive_path_denies() -> Vec<PathPolicy> {
+    [
+        ("~/.ssh", "SSH keys"),
+        ("~/.gnupg", "GPG keys"),
+        ("~/.aws", "AWS credentials"),
+        ("~/.azure", "Azure credentials"),
+        ("~/.config/gcloud", "Google Cloud credentials"),
+        ("~/.kube", "Kubernetes credentials"),
+        ("~/.docker/config.json", "Docker registry credentials"),
+        ("~/.netrc", "network credentials"),
+        ("~/.git-credentials", "Git credentials"),
+        ("~/.password-store", "password store"),
+        ("~/.mozilla", "Firefox profiles"),
+        ("~/.config/google-chrome", "Chrome profiles"),
+        ("~/.config/chromium", "Chromium profiles"),
+        ("~/Library/Application Support/Google/Chrome", "Chrome profiles"),
+        ("~/Library/Application Support/Firefox", "Firefox profiles"),
+        ("~/Library/Keychains", "macOS keychains"),
+        ("~/AppData/Local/Google/Chrome/User Data", "Chrome profiles"),
+        ("~/AppData/Roaming/Mozilla/Firefox", "Firefox profiles"),
+        ("**/.env", "environment files"),
+    ]
+    .into_iter()
+    .map(|(path, about)| PathPolicy {
+        id: None,
+        path: PathBuf::from(path),
+        access: AccessLevel::Deny,
+        recursive: true,
+        description: Some(format!("built-in: {about}")),
+        layer: 0,
+    })
+    .collect()
 }
 
 #[cfg(test)]
@@ -77,6 +355,7 @@ mod tests {
             access,
             recursive,
             description: None,
+            layer: 0,
         }
     }
 
@@ -149,4 +428,109 @@ mod tests {
         assert!(check_access(&policies, Path::new("/tmp/write/file.txt"), true));
         assert!(!check_access(&policies, Path::new("/tmp/other/file.txt"), false));
     }
+
+    #[test]
+    fn deny_overrides_broader_grant() {
+        let policies = vec![
+            policy("/home/u", AccessLevel::ReadWrite, true),
+            policy("/home/u/.ssh", AccessLevel::Deny, true),
+        ];
+        assert!(check_access(&policies, Path::new("/home/u/notes.txt"), true));
+        assert!(!check_access(&policies, Path::new("/home/u/.ssh/id_rsa"), false));
+        assert!(!check_access(&policies, Path::new("/home/u/.ssh"), false));
+    }
+
+    #[test]
+    fn most_specific_rule_wins() {
+        let policies = vec![
+            policy("/home/u", AccessLevel::ReadWrite, true),
+            policy("/home/u/.ssh", AccessLevel::Deny, true),
+            policy("/home/u/.ssh/config", AccessLevel::ReadOnly, true),
+            policy("/home/u/docs", AccessLevel::ReadOnly, true),
+        ];
+        // A grant deeper than the deny re-opens one file, read-only.
+        assert!(check_access(&policies, Path::new("/home/u/.ssh/config"), false));
+        assert!(!check_access(&policies, Path::new("/home/u/.ssh/config"), true));
+        // A narrower read-only rule takes writing away.
+        assert!(!check_access(&policies, Path::new("/home/u/docs/a.md"), true));
+        // At the same depth a deny beats a grant.
+        let tied = vec![policy("/tmp/x", AccessLevel::ReadWrite, true), policy("/tmp/x", AccessLevel::Deny, true)];
+        assert!(!check_access(&tied, Path::new("/tmp/x/file"), false));
+    }
+
+    #[test]
+    fn glob_patterns() {
+        let policies = vec![
+            policy("/home/u/Projects/*/src/**", AccessLevel::ReadWrite, true),
+            policy("**/*.env", AccessLevel::Deny, true),
+        ];
+        assert!(check_access(&policies, Path::new("/home/u/Projects/app/src/main.rs"), true));
+        assert!(check_access(&policies, Path::new("/home/u/Projects/app/src/deep/mod.rs"), true));
+        assert!(!check_access(&policies, Path::new("/home/u/Projects/app/README.md"), false));
+        assert!(!check_access(&policies, Path::new("/home/u/Projects/app/src/prod.env"), false));
+
+        assert!(wildcard_match("file[0-9].t?t", "file7.txt"));
+        assert!(!wildcard_match("file[!0-9].txt", "file7.txt"));
+        assert!(wildcard_match("*.tar.*", "a.tar.gz"));
+    }
+
+    #[test]
+    fn explain_names_the_deciding_rule() {
+        let mut deny = policy("/home/u/.aws", AccessLevel::Deny, true);
+        deny.description = Some("AWS credentials".to_string());
+        let policies = vec![policy("/home/u", AccessLevel::ReadOnly, true), deny];
+
+        let decision = explain(&policies, Path::new("/home/u/.aws/credentials"), false);
+        assert!(!decision.allowed);
+        assert_eq!(decision.rule, Some(&policies[1]));
+        assert_eq!(
+            decision.to_string(),
+            "'/home/u/.aws/credentials' is blocked by the deny rule '/home/u/.aws' (AWS credentials)"
+        );
+        assert_eq!(
+            explain(&policies, Path::new("/home/u/a.txt"), true).to_string(),
+            "'/home/u/a.txt' falls under the read-only rule '/home/u', which doesn't allow writing"
+        );
+        assert_eq!(explain(&policies, Path::new("/etc/hosts"), false).rule, None);
+    }
+
+    #[test]
+    fn sensitive_paths_are_denied_under_home() {
+        let Some(home) = home_dir() else { return };
+        let mut policies = vec![PathPolicy { path: home.clone(), ..policy("", AccessLevel::ReadWrite, true) }];
+        policies.extend(sensitive_path_denies());
+        assert!(check_access(&policies, &home.join("code/main.rs"), true));
+        assert!(!check_access(&policies, &home.join(".ssh/id_ed25519"), false));
+        assert!(!check_access(&policies, &home.join(".aws/credentials"), false));
+        assert!(!check_access(&policies, &home.join("code/app/.env"), false));
+    }
+
+    #[test]
+    fn narrowed_grants_stay_within_the_parent() {
+        let mut parent = vec![
+            policy("/home/u", AccessLevel::ReadWrite, true),
+            policy("/home/u/Docs", AccessLevel::ReadOnly, true),
+            policy("/home/u/Docs/Acme/private", AccessLevel::Deny, true),
+        ];
+        parent.push(policy("**/.env", AccessLevel::Deny, true));
+
+        let err = narrow(&parent, &[PathGrant { path: "/home/u/Docs/Acme".into(), access: AccessLevel::ReadWrite }])
+            .unwrap_err();
+        assert!(err.contains("doesn't allow writing"), "{err}");
+        assert!(narrow(&parent, &[PathGrant { path: "/etc".into(), access: AccessLevel::ReadOnly }]).is_err());
+
+        let grants = narrow(&parent, &[PathGrant { path: "/home/u/Docs/Acme".into(), access: AccessLevel::ReadOnly }])
+            .unwrap();
+        assert_eq!(grants[0].layer, 1);
+        let mut child = parent.clone();
+        child.extend(grants);
+        assert!(check_access(&child, Path::new("/home/u/Docs/Acme/plan.md"), false));
+        assert!(!check_access(&child, Path::new("/home/u/Docs/Acme/plan.md"), true));
+        // Outside the grant, and under the parent's own denies, nothing is allowed.
+        assert!(!check_access(&child, Path::new("/home/u/Code/main.rs"), true));
+        assert!(!check_access(&child, Path::new("/home/u/Docs/Acme/private/a.txt"), false));
+        assert!(!check_access(&child, Path::new("/home/u/Docs/Acme/.env"), false));
+        let decision = explain(&child, Path::new("/home/u/Docs/Acme/private/a.txt"), false);
+        assert_eq!(decision.rule.map(|r| r.layer), Some(0));
+    }
 }
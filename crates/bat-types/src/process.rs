@@ -0,0 +1,37 @@
+//! Killing the processes agents and their tools start.
+
+/// Forcefully kill the process group led by `pid` (on Windows, the process
+/// tree rooted at it). Agents and the commands they run each lead their own
+/// group, so this takes down everything they started.
+pub fn kill_process_tree(pid: u32) {
+    // Process group 0 is the caller's own and -1 means every process.
+    if pid <= 1 {
+        tracing::warn!("Refusing to kill process group {pid}");
+        return;
+    }
+
+    #[cfg(unix)]
+    {
+        // SAFETY: kill(2) has no memory-safety preconditions.
+        let rc = unsafe { libc::kill(-(pid as libc::pid_t), libc::SIGKILL) };
+        if rc != 0 {
+            let err = std::io::Error::last_os_error();
+            if err.raw_os_error() != Some(libc::ESRCH) {
+                tracing::warn!("Failed to kill process group {pid}: {err}");
+            }
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        if let Err(e) = std::process::Command::new("taskkill")
+            .args(["/T", "/F", "/PID", &pid.to_string()])
+            .creation_flags(CREATE_NO_WINDOW)
+            .status()
+        {
+            tracing::warn!("Failed to kill process tree {pid}: {e}");
+        }
+    }
+}
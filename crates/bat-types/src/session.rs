@@ -2,6 +2,8 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::policy::PathGrant;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SessionMeta {
     pub id: Uuid,
@@ -44,6 +46,39 @@ pub struct SubagentInfo {
     pub summary: Option<String>,
     pub token_input: i64,
     pub token_output: i64,
+    /// What the subagent was narrowed to, if its parent narrowed anything.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<SubagentScope>,
+}
+
+/// Limits a subagent was spawned with. Each one left out is inherited from
+/// the parent unchanged.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SubagentScope {
+    /// Paths the subagent may use, each within the parent's access.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paths: Option<Vec<PathGrant>>,
+    /// Tools the subagent gets, from those the parent's workers have.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Memory limit for the subagent's process, at most the parent's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_limit_mb: Option<u64>,
+    /// Hosts the subagent may connect to, within the network policy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hosts: Option<Vec<String>>,
+    /// Hosts the subagent may not connect to, on top of the network policy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocked_hosts: Option<Vec<String>>,
+}
+
+impl SubagentScope {
+    /// Whether anything is narrowed at all.
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
 }
 
 /// Status of a subagent.
@@ -7,12 +7,20 @@ pub struct UsageStats {
     /// Total tokens across all sessions.
     pub total_input: i64,
     pub total_output: i64,
+    /// Input tokens served from / written to the prompt cache.
+    #[serde(default)]
+    pub total_cache_read: i64,
+    #[serde(default)]
+    pub total_cache_write: i64,
     /// Per-session breakdown.
     pub sessions: Vec<SessionUsage>,
     /// Per-model breakdown.
     pub by_model: Vec<ModelUsage>,
-    /// Estimated cost in USD (based on Anthropic pricing).
+    /// Estimated cost in USD, from the model registry's prices.
     pub estimated_cost_usd: f64,
+    /// What prompt caching saved compared to sending every prefix uncached.
+    #[serde(default)]
+    pub cache_savings_usd: f64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,6 +30,10 @@ pub struct SessionUsage {
     pub model: String,
     pub token_input: i64,
     pub token_output: i64,
+    #[serde(default)]
+    pub token_cache_read: i64,
+    #[serde(default)]
+    pub token_cache_write: i64,
     pub message_count: i64,
     pub last_active: String,
 }
@@ -32,17 +44,9 @@ pub struct ModelUsage {
     pub model: String,
     pub token_input: i64,
     pub token_output: i64,
+    #[serde(default)]
+    pub token_cache_read: i64,
+    #[serde(default)]
+    pub token_cache_write: i64,
     pub session_count: i64,
 }
-
-/// Estimate cost for Anthropic Claude models.
-pub fn estimate_cost(model: &str, input_tokens: i64, output_tokens: i64) -> f64 {
-    let (input_per_m, output_per_m) = match model {
-        m if m.contains("opus") => (15.0, 75.0),
-        m if m.contains("sonnet") => (3.0, 15.0),
-        m if m.contains("haiku") => (0.25, 1.25),
-        _ => (3.0, 15.0), // default to sonnet pricing
-    };
-    (input_tokens as f64 / 1_000_000.0 * input_per_m)
-        + (output_tokens as f64 / 1_000_000.0 * output_per_m)
-}